@@ -0,0 +1,279 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use tokio::sync::{mpsc, oneshot};
+
+// The actor model: instead of sharing state behind a Mutex, one task owns the state
+// and everybody else sends it messages. The task handles them one at a time, so the
+// state never needs a lock and can't be seen half updated.
+//
+// - an actor is the state plus how it reacts to each message
+// - a handle is the sending end of its channel, cheap to clone and hand out
+// - a question ("what's the count?") carries a oneshot sender for the answer
+//
+// The actor stops when every handle is dropped: its channel closes and `recv` returns
+// None.
+
+// How many messages can wait for an actor before `send` waits too
+// A bounded channel pushes back on senders faster than the actor
+const MAILBOX_SIZE: usize = 32;
+
+pub trait Actor: Send + 'static {
+    type Msg: Send + 'static;
+
+    // `async fn` in a trait can't say its future is Send, and tokio::spawn needs that,
+    // so the trait spells it out. Implementations can still write `async fn handle`.
+    fn handle(&mut self, msg: Self::Msg) -> impl Future<Output = ()> + Send;
+}
+
+pub struct Handle<Msg> {
+    tx: mpsc::Sender<Msg>,
+}
+
+// Derive would require Msg: Clone, but cloning a handle only clones the Sender
+impl<Msg> Clone for Handle<Msg> {
+    fn clone(&self) -> Handle<Msg> {
+        Handle {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+// The actor isn't running anymore: it panicked, or it stopped on its own
+#[derive(Debug, PartialEq)]
+pub struct ActorGone;
+
+impl fmt::Display for ActorGone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the actor is not running anymore")
+    }
+}
+
+impl std::error::Error for ActorGone {}
+
+impl<Msg> Handle<Msg> {
+    pub async fn send(&self, msg: Msg) -> Result<(), ActorGone> {
+        self.tx.send(msg).await.map_err(|_| ActorGone)
+    }
+}
+
+pub fn spawn_actor<A: Actor>(mut actor: A) -> Handle<A::Msg> {
+    let (tx, mut rx) = mpsc::channel(MAILBOX_SIZE);
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            actor.handle(msg).await;
+        }
+    });
+    Handle { tx }
+}
+
+pub enum CounterMsg {
+    Increment,
+    Get(oneshot::Sender<u64>),
+    // Makes the actor panic, to show the supervisor at work
+    Crash,
+}
+
+#[derive(Default)]
+pub struct CounterActor {
+    count: u64,
+}
+
+impl Actor for CounterActor {
+    type Msg = CounterMsg;
+
+    async fn handle(&mut self, msg: CounterMsg) {
+        match msg {
+            CounterMsg::Increment => self.count += 1,
+            // The asker may have given up waiting, that's not our problem
+            CounterMsg::Get(reply) => {
+                let _ = reply.send(self.count);
+            }
+            CounterMsg::Crash => panic!("the counter crashed at {}", self.count),
+        }
+    }
+}
+
+// Asks the counter behind `handle` for its count
+pub async fn get_count(handle: &Handle<CounterMsg>) -> Result<u64, ActorGone> {
+    let (reply, answer) = oneshot::channel();
+    handle.send(CounterMsg::Get(reply)).await?;
+    // If the actor dies before answering, the reply sender is dropped and we get an error
+    answer.await.map_err(|_| ActorGone)
+}
+
+// An actor that runs another one and starts a new one when it panics ("let it crash",
+// as Erlang puts it): a panic costs the state of the child and the message it was
+// handling, but the handle keeps working and the next messages go to a fresh child.
+//
+// It's an actor itself, taking the child's messages. Each message is handled in a task
+// of its own, which gets the child and gives it back when it's done: tokio catches the
+// panic of a task, and then we have no child to get back and make a new one. (A task
+// per message isn't free, but spawning one is cheap in tokio.)
+pub struct SupervisorActor<A: Actor> {
+    make_child: Box<dyn Fn() -> A + Send>,
+    child: Option<A>,
+    restarts: Arc<AtomicU32>,
+}
+
+impl<A: Actor> SupervisorActor<A> {
+    // `restarts` counts the restarts, for whoever wants to know
+    pub fn new(
+        make_child: impl Fn() -> A + Send + 'static,
+        restarts: Arc<AtomicU32>,
+    ) -> SupervisorActor<A> {
+        SupervisorActor {
+            child: Some(make_child()),
+            make_child: Box::new(make_child),
+            restarts,
+        }
+    }
+}
+
+impl<A: Actor> Actor for SupervisorActor<A> {
+    type Msg = A::Msg;
+
+    async fn handle(&mut self, msg: A::Msg) {
+        let mut child = self.child.take().unwrap_or_else(|| (self.make_child)());
+        let handled = tokio::spawn(async move {
+            child.handle(msg).await;
+            child
+        })
+        .await;
+
+        self.child = match handled {
+            Ok(child) => Some(child),
+            Err(e) if e.is_panic() => {
+                self.restarts.fetch_add(1, Ordering::Relaxed);
+                Some((self.make_child)())
+            }
+            // Cancelled, the runtime is shutting down: a new child is made if there is
+            // another message after all
+            Err(_) => None,
+        };
+    }
+}
+
+pub fn run() {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        // Many tasks, each with its own handle, incrementing the same counter
+        let counter = spawn_actor(CounterActor::default());
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        counter.send(CounterMsg::Increment).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // No lock anywhere, and no increment lost
+        let count = get_count(&counter).await.unwrap();
+        println!("10 tasks x 1000 increments: {}", count);
+
+        // Without a supervisor a panic is the end of the actor
+        counter.send(CounterMsg::Crash).await.unwrap();
+        println!(
+            "the unsupervised counter after its panic: {:?}",
+            get_count(&counter).await
+        );
+
+        // With a supervisor it's a fresh start
+        let restarts = Arc::new(AtomicU32::new(0));
+        let supervised = spawn_actor(SupervisorActor::new(
+            CounterActor::default,
+            restarts.clone(),
+        ));
+        for _ in 0..5 {
+            supervised.send(CounterMsg::Increment).await.unwrap();
+        }
+        supervised.send(CounterMsg::Crash).await.unwrap();
+        // The state is lost with the crashed child, the new one starts at 0
+        // (the panic message above comes from that crash, tokio prints it)
+        supervised.send(CounterMsg::Increment).await.unwrap();
+        let count = get_count(&supervised).await.unwrap();
+        println!(
+            "the supervised counter after its panic: {} ({} restart)",
+            count,
+            restarts.load(Ordering::Relaxed)
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_new_counter_is_at_zero() {
+        let counter = spawn_actor(CounterActor::default());
+        assert_eq!(get_count(&counter).await, Ok(0));
+    }
+
+    // The messages of one handle are handled in the order they were sent
+    #[tokio::test]
+    async fn get_sees_every_increment_sent_before_it() {
+        let counter = spawn_actor(CounterActor::default());
+        for expected in 1..=50 {
+            counter.send(CounterMsg::Increment).await.unwrap();
+            assert_eq!(get_count(&counter).await, Ok(expected));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_increments_are_not_lost() {
+        let counter = spawn_actor(CounterActor::default());
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        counter.send(CounterMsg::Increment).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(get_count(&counter).await, Ok(10_000));
+    }
+
+    #[tokio::test]
+    async fn a_crashed_actor_is_gone() {
+        let counter = spawn_actor(CounterActor::default());
+        counter.send(CounterMsg::Crash).await.unwrap();
+        assert_eq!(get_count(&counter).await, Err(ActorGone));
+        assert_eq!(counter.send(CounterMsg::Increment).await, Err(ActorGone));
+    }
+
+    #[tokio::test]
+    async fn the_supervisor_restarts_a_crashed_child() {
+        let restarts = Arc::new(AtomicU32::new(0));
+        let supervised = spawn_actor(SupervisorActor::new(
+            CounterActor::default,
+            restarts.clone(),
+        ));
+        for _ in 0..5 {
+            supervised.send(CounterMsg::Increment).await.unwrap();
+        }
+        assert_eq!(get_count(&supervised).await, Ok(5));
+
+        supervised.send(CounterMsg::Crash).await.unwrap();
+        supervised.send(CounterMsg::Increment).await.unwrap();
+        // A fresh child, the count starts over
+        assert_eq!(get_count(&supervised).await, Ok(1));
+        assert_eq!(restarts.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_stream::{
+    wrappers::{IntervalStream, ReceiverStream},
+    Stream, StreamExt,
+};
+
+// A Stream is the async version of an Iterator: instead of `next()` returning
+// `Option<Item>` right away, it returns a future that resolves to `Option<Item>`
+// whenever the next item is ready.
+// `tokio_stream::StreamExt` gives streams the adapters we know from iterators
+// (map, filter, take...), and each of them returns a new stream.
+
+// An endless stream of Fibonacci numbers
+// `unfold` builds a stream from a state and an async closure: the closure gets the
+// current state and returns the next item together with the next state
+// (or None to end the stream)
+// `unfold` comes from the `futures` crate, tokio-stream doesn't have one
+pub fn fibonacci_stream() -> impl Stream<Item = u64> {
+    futures::stream::unfold((0_u64, 1_u64), |(current, next)| async move {
+        Some((current, (next, current + next)))
+    })
+}
+
+// Counts from 1 to `n`, one number every `interval`
+// `tokio::time::interval` ticks forever, IntervalStream turns those ticks into a
+// stream of Instants which we then number and cut off after `n` items
+pub fn throttled_counter(n: u64, interval: Duration) -> impl Stream<Item = u64> {
+    IntervalStream::new(tokio::time::interval(interval))
+        .take(n as usize)
+        .map({
+            let mut count = 0;
+            move |_tick| {
+                count += 1;
+                count
+            }
+        })
+}
+
+async fn fibonacci_demo() {
+    // Like iterators, streams are lazy: nothing is computed until we poll them,
+    // so the infinite stream is fine as long as we `take` from it
+    let first_ten: Vec<u64> = fibonacci_stream().take(10).collect().await;
+    println!("first 10 fibonacci numbers: {:?}", first_ten);
+    assert_eq!(first_ten, vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+
+    // map + filter + take, just like with an iterator
+    let even_squares: Vec<u64> = fibonacci_stream()
+        .filter(|n| n % 2 == 0)
+        .map(|n| n * n)
+        .take(5)
+        .collect()
+        .await;
+    println!(
+        "squares of the first 5 even fibonacci numbers: {:?}",
+        even_squares
+    );
+
+    // fold reduces the whole stream to one value
+    let sum = fibonacci_stream().take(10).fold(0, |acc, n| acc + n).await;
+    println!("sum of the first 10: {}", sum);
+    assert_eq!(sum, 88);
+
+    // chunks groups the items in vectors of (at most) 4 items
+    // tokio-stream only has `chunks_timeout`, so this one comes from `futures::StreamExt`
+    // We call it with the full path because importing both StreamExt traits would make
+    // `map`, `filter`... ambiguous
+    let chunks: Vec<Vec<u64>> =
+        futures::StreamExt::collect(futures::StreamExt::chunks(fibonacci_stream().take(10), 4))
+            .await;
+    println!("in chunks of 4: {:?}", chunks);
+}
+
+async fn throttled_demo() {
+    let start = tokio::time::Instant::now();
+    let counts: Vec<u64> = throttled_counter(5, Duration::from_millis(50))
+        .collect()
+        .await;
+
+    // The first tick of an interval fires right away, so 5 ticks take about 4 intervals
+    println!("counted {:?} in {:?}", counts, start.elapsed());
+    assert_eq!(counts, vec![1, 2, 3, 4, 5]);
+}
+
+// Any tokio mpsc receiver can be turned into a stream and use the same adapters
+async fn channel_demo() {
+    let (tx, rx) = mpsc::channel(8);
+
+    tokio::spawn(async move {
+        for i in 1..=10 {
+            if tx.send(i).await.is_err() {
+                break;
+            }
+        }
+        // The stream ends when every sender is dropped, which happens here
+    });
+
+    let odd_doubled: Vec<u32> = ReceiverStream::new(rx)
+        .filter(|n| n % 2 == 1)
+        .map(|n| n * 2)
+        .collect()
+        .await;
+    println!("odd numbers from the channel, doubled: {:?}", odd_doubled);
+    assert_eq!(odd_doubled, vec![2, 6, 10, 14, 18]);
+}
+
+pub fn run() {
+    // The rest of the playground is synchronous, so we start a runtime just for this demo
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        fibonacci_demo().await;
+        throttled_demo().await;
+        channel_demo().await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fibonacci_starts_with_the_known_numbers() {
+        let first_ten: Vec<u64> = fibonacci_stream().take(10).collect().await;
+        assert_eq!(first_ten, [0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+    }
+
+    #[tokio::test]
+    async fn adapters_compose_like_on_iterators() {
+        let even_squares: Vec<u64> = fibonacci_stream()
+            .filter(|n| n % 2 == 0)
+            .map(|n| n * n)
+            .take(5)
+            .collect()
+            .await;
+        assert_eq!(even_squares, [0, 4, 64, 1156, 20736]);
+    }
+
+    // With the clock paused, tokio jumps straight to the next timer instead of
+    // sleeping, so the test takes no time and the elapsed time is exact
+    #[tokio::test(start_paused = true)]
+    async fn the_counter_ticks_once_per_interval() {
+        let start = tokio::time::Instant::now();
+        let counts: Vec<u64> = throttled_counter(5, Duration::from_millis(50))
+            .collect()
+            .await;
+        assert_eq!(counts, [1, 2, 3, 4, 5]);
+        // The first tick is right away
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn a_channel_stream_ends_with_its_senders() {
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            for i in 0..3 {
+                tx.send(i).await.unwrap();
+            }
+        });
+        let received: Vec<u32> = ReceiverStream::new(rx).collect().await;
+        assert_eq!(received, [0, 1, 2]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Path, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+// A tiny REST API with axum: a list of items to read, add to and remove from
+//
+//   GET    /items       200 and the items, as a JSON array of strings
+//   POST   /items       {"name": "..."}, 201 and the new item with its id
+//   DELETE /items/{id}  204, or 404 if there is no item at that index
+//
+// A handler is a plain async function. Its arguments are "extractors": axum looks at
+// their types to know what to take from the request (the state, a part of the path,
+// the body parsed as JSON...), and a request that doesn't fit is answered with a 4xx
+// before the handler even runs. Whatever it returns becomes the response, as long as
+// it implements IntoResponse: a status code, Json, a tuple of both...
+
+// Shared by every request, handlers run on many threads at once
+// The lock is never held across an `.await`, so the one of std is fine
+#[derive(Clone, Default)]
+pub struct AppState {
+    items: Arc<RwLock<Vec<String>>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewItem {
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: usize,
+    pub name: String,
+}
+
+async fn list_items(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(state.items.read().unwrap().clone())
+}
+
+async fn add_item(
+    State(state): State<AppState>,
+    Json(item): Json<NewItem>,
+) -> Result<(StatusCode, Json<Item>), (StatusCode, String)> {
+    let name = item.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "the name is empty".to_string()));
+    }
+    let mut items = state.items.write().unwrap();
+    items.push(name.to_string());
+    let item = Item {
+        id: items.len() - 1,
+        name: name.to_string(),
+    };
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+// The ids are indices: removing an item moves the ones after it down by one
+async fn delete_item(State(state): State<AppState>, Path(id): Path<usize>) -> StatusCode {
+    let mut items = state.items.write().unwrap();
+    if id >= items.len() {
+        return StatusCode::NOT_FOUND;
+    }
+    items.remove(id);
+    StatusCode::NO_CONTENT
+}
+
+// The routes, apart from the server so they can be served on any listener
+pub fn build_router(state: AppState) -> Router {
+    Router::new()
+        .route("/items", get(list_items).post(add_item))
+        .route("/items/{id}", delete(delete_item))
+        .with_state(state)
+}
+
+// Serve the API on localhost until the process exits
+pub async fn run_server(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    println!("listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, build_router(AppState::default())).await
+}
+
+// Send one request through the router and print what comes back
+async fn show(router: &Router, method: &str, uri: &str, json: Option<&str>) {
+    let request = Request::builder().method(method).uri(uri);
+    let request = match json {
+        Some(json) => request
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    println!(
+        "{} {}: {} {}",
+        method,
+        uri,
+        status,
+        String::from_utf8_lossy(&body)
+    );
+}
+
+pub fn run() {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        // A Router is a tower Service, requests can go through it without a socket
+        // (the tests in tests/ serve it on a real port). Its clones share the state
+        let router = build_router(AppState::default());
+        show(&router, "GET", "/items", None).await;
+        for name in ["milk", "bread", "eggs"] {
+            let json = format!(r#"{{"name": "{}"}}"#, name);
+            show(&router, "POST", "/items", Some(&json)).await;
+        }
+        // Remove "bread", "eggs" moves down to 1
+        show(&router, "DELETE", "/items/1", None).await;
+        show(&router, "GET", "/items", None).await;
+
+        // What doesn't fit gets a 4xx
+        show(&router, "DELETE", "/items/2", None).await;
+        // Not a number: the Path extractor refuses it
+        show(&router, "DELETE", "/items/two", None).await;
+        show(&router, "POST", "/items", Some(r#"{"name": "  "}"#)).await;
+        // JSON, but without a name: the Json extractor refuses it
+        show(&router, "POST", "/items", Some(r#"{"title": "milk"}"#)).await;
+        show(&router, "GET", "/nothing", None).await;
+    });
+}
@@ -0,0 +1,121 @@
+// The API served on a real port, a fresh server with no items for every test
+use axum_server_demo::{build_router, AppState, Item};
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+// Port 0: the OS picks a free one, we ask the listener which
+// Returns the base URL of the server
+async fn serve() -> String {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, build_router(AppState::default()))
+            .await
+            .unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+async fn items(client: &Client, base: &str) -> Vec<String> {
+    let response = client.get(format!("{}/items", base)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+async fn post(client: &Client, base: &str, body: serde_json::Value) -> reqwest::Response {
+    client
+        .post(format!("{}/items", base))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn delete(client: &Client, base: &str, id: &str) -> StatusCode {
+    client
+        .delete(format!("{}/items/{}", base, id))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn no_items_at_first() {
+    let base = serve().await;
+    assert!(items(&Client::new(), &base).await.is_empty());
+}
+
+#[tokio::test]
+async fn posted_items_are_listed() {
+    let (base, client) = (serve().await, Client::new());
+    for (id, name) in ["milk", "bread", "eggs"].into_iter().enumerate() {
+        let response = post(&client, &base, json!({ "name": name })).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let item: Item = response.json().await.unwrap();
+        assert_eq!(
+            item,
+            Item {
+                id,
+                name: name.to_string()
+            }
+        );
+    }
+    assert_eq!(items(&client, &base).await, ["milk", "bread", "eggs"]);
+}
+
+#[tokio::test]
+async fn names_are_trimmed() {
+    let (base, client) = (serve().await, Client::new());
+    post(&client, &base, json!({ "name": "  milk " })).await;
+    assert_eq!(items(&client, &base).await, ["milk"]);
+}
+
+#[tokio::test]
+async fn delete_moves_the_next_items_down() {
+    let (base, client) = (serve().await, Client::new());
+    for name in ["milk", "bread", "eggs"] {
+        post(&client, &base, json!({ "name": name })).await;
+    }
+    assert_eq!(delete(&client, &base, "1").await, StatusCode::NO_CONTENT);
+    assert_eq!(items(&client, &base).await, ["milk", "eggs"]);
+    assert_eq!(delete(&client, &base, "1").await, StatusCode::NO_CONTENT);
+    assert_eq!(items(&client, &base).await, ["milk"]);
+}
+
+#[tokio::test]
+async fn delete_past_the_end_is_not_found() {
+    let (base, client) = (serve().await, Client::new());
+    assert_eq!(delete(&client, &base, "0").await, StatusCode::NOT_FOUND);
+    post(&client, &base, json!({ "name": "milk" })).await;
+    assert_eq!(delete(&client, &base, "1").await, StatusCode::NOT_FOUND);
+    assert_eq!(items(&client, &base).await, ["milk"]);
+}
+
+#[tokio::test]
+async fn bad_requests_are_refused() {
+    let (base, client) = (serve().await, Client::new());
+    // Not a number: the Path extractor refuses it
+    assert_eq!(delete(&client, &base, "two").await, StatusCode::BAD_REQUEST);
+    let blank = post(&client, &base, json!({ "name": "  " })).await;
+    assert_eq!(blank.status(), StatusCode::BAD_REQUEST);
+    // JSON, but without a name: the Json extractor refuses it
+    let nameless = post(&client, &base, json!({ "title": "milk" })).await;
+    assert_eq!(nameless.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // Not JSON at all
+    let text = client
+        .post(format!("{}/items", base))
+        .body("milk")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(text.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let nothing = client
+        .get(format!("{}/nothing", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(nothing.status(), StatusCode::NOT_FOUND);
+    assert!(items(&client, &base).await.is_empty());
+}
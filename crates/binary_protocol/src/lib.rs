@@ -0,0 +1,358 @@
+use std::fmt;
+
+// A small binary protocol, and reading it without copying the payloads around
+//
+// Every message is a frame:
+//
+//   +----------------+------+-------------------+
+//   | length (4, LE) | type | payload (length)  |
+//   +----------------+------+-------------------+
+//
+// The length comes first so the reader knows how much to wait for: over TCP the bytes
+// come in whatever pieces the network likes, half a frame, three frames and a bit...
+// The decoder keeps what it got until a whole frame is there.
+//
+// "Zero-copy": `decode_frame` and `FrameDecoder::next_frame` hand out the payload as a
+// slice of the bytes they were given, never as a Vec of its own.
+
+pub const HEADER_LEN: usize = 5;
+// Longer frames are refused, or a broken length could make us wait for (and buffer)
+// 4GB that never come
+pub const MAX_PAYLOAD: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    Ping = 0,
+    Data = 1,
+    Ack = 2,
+}
+
+impl MessageType {
+    pub fn from_u8(byte: u8) -> Option<MessageType> {
+        match byte {
+            0 => Some(MessageType::Ping),
+            1 => Some(MessageType::Data),
+            2 => Some(MessageType::Ack),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    // Not even a whole header
+    TooShort(usize),
+    // The header says `expected` payload bytes, there are `found`
+    LengthMismatch { expected: usize, found: usize },
+    UnknownType(u8),
+    // The length is above MAX_PAYLOAD
+    TooLarge(usize),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooShort(len) => {
+                write!(f, "{} bytes, a frame has at least {}", len, HEADER_LEN)
+            }
+            FrameError::LengthMismatch { expected, found } => write!(
+                f,
+                "the frame says {} bytes of payload, there are {}",
+                expected, found
+            ),
+            FrameError::UnknownType(byte) => write!(f, "unknown message type {}", byte),
+            FrameError::TooLarge(len) => {
+                write!(f, "a payload of {} bytes, the most is {}", len, MAX_PAYLOAD)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+pub fn encode_frame(msg_type: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(
+        payload.len() <= MAX_PAYLOAD,
+        "payload too large for a frame"
+    );
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.push(msg_type);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// The payload length and the type of the frame starting at `buf`, if the header is
+// all there
+fn header(buf: &[u8]) -> Result<(usize, u8), FrameError> {
+    if buf.len() < HEADER_LEN {
+        return Err(FrameError::TooShort(buf.len()));
+    }
+    let len = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if len > MAX_PAYLOAD {
+        return Err(FrameError::TooLarge(len));
+    }
+    Ok((len, buf[4]))
+}
+
+// Exactly one frame, not a byte more or less
+pub fn decode_frame(buf: &[u8]) -> Result<(u8, &[u8]), FrameError> {
+    let (len, msg_type) = header(buf)?;
+    let payload = &buf[HEADER_LEN..];
+    if payload.len() != len {
+        return Err(FrameError::LengthMismatch {
+            expected: len,
+            found: payload.len(),
+        });
+    }
+    if MessageType::from_u8(msg_type).is_none() {
+        return Err(FrameError::UnknownType(msg_type));
+    }
+    Ok((msg_type, payload))
+}
+
+// Frames out of a stream that comes in pieces
+//
+//   decoder.feed(&bytes_from_the_socket);
+//   while let Some((msg_type, payload)) = decoder.next_frame()? { ... }
+//
+// The frames read are only dropped from the buffer on the next `feed`, that's what
+// lets `next_frame` lend out the payloads instead of copying them.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    // Where the next frame starts in the buffer, what's before was read already
+    start: usize,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        // Move what's left to the front once, instead of after every frame
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        self.buffer.extend_from_slice(data);
+    }
+
+    // The next whole frame, None until more bytes are fed
+    // A frame of an unknown type is skipped, after the error: its length is good, the
+    // next frame starts after it. A length that is too large leaves nothing to go on,
+    // every next call returns that error again
+    pub fn next_frame(&mut self) -> Result<Option<(u8, &[u8])>, FrameError> {
+        let rest = &self.buffer[self.start..];
+        let len = match header(rest) {
+            Ok((len, _)) => len,
+            Err(FrameError::TooShort(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if rest.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+        let frame = self.start..self.start + HEADER_LEN + len;
+        self.start = frame.end;
+        decode_frame(&self.buffer[frame]).map(Some)
+    }
+
+    // The bytes of frames that aren't complete yet
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+}
+
+pub fn run() {
+    let messages: Vec<(u8, Vec<u8>)> = vec![
+        (MessageType::Ping as u8, Vec::new()),
+        (MessageType::Data as u8, b"hello".to_vec()),
+        (MessageType::Data as u8, (0..=255).collect()),
+        (MessageType::Ack as u8, 42_u32.to_le_bytes().to_vec()),
+        (MessageType::Data as u8, "zero-copy ✓".as_bytes().to_vec()),
+    ];
+    let stream: Vec<u8> = messages
+        .iter()
+        .flat_map(|(msg_type, payload)| encode_frame(*msg_type, payload))
+        .collect();
+    println!("{} frames, {} bytes", messages.len(), stream.len());
+    println!("the first two: {:02x?}", &stream[..15]);
+
+    // Cut at a few places that don't care where the frames start or end
+    let cuts = [3, 4, 9, 10, 40, 41, 42, 200, 290];
+    let mut decoder = FrameDecoder::new();
+    let mut from = 0;
+    for to in cuts.into_iter().chain([stream.len()]) {
+        decoder.feed(&stream[from..to]);
+        println!("fed {} bytes", to - from);
+        from = to;
+        while let Some((msg_type, payload)) = decoder.next_frame().unwrap() {
+            let name = MessageType::from_u8(msg_type).unwrap();
+            println!("  {:?} with {} bytes", name, payload.len());
+        }
+    }
+
+    // What decode_frame refuses
+    let frame = encode_frame(MessageType::Data as u8, b"abc");
+    println!("{:?}", decode_frame(&frame[..4]));
+    println!("{:?}", decode_frame(&frame[..6]));
+    println!("{:?}", decode_frame(&encode_frame(7, b"?")));
+    println!("{:?}", decode_frame(&[0xff, 0xff, 0xff, 0xff, 1]));
+
+    // In a stream an unknown type is skipped, the frames around it still come through
+    let mut mixed = encode_frame(0, b"");
+    mixed.extend(encode_frame(9, b"skip me"));
+    mixed.extend(encode_frame(2, b"ok"));
+    let mut decoder = FrameDecoder::new();
+    decoder.feed(&mixed);
+    for _ in 0..3 {
+        println!("{:?}", decoder.next_frame());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tiny xorshift pseudo random generator, so the splits are all over the place but
+    // the same on every run
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    // Feed `stream` to a decoder in pieces cut at `cuts`, and collect what comes out
+    fn decode_in_pieces(stream: &[u8], cuts: &[usize]) -> Vec<Result<(u8, Vec<u8>), FrameError>> {
+        let mut decoder = FrameDecoder::new();
+        let mut frames = Vec::new();
+        let mut from = 0;
+        for &to in cuts.iter().chain([&stream.len()]) {
+            decoder.feed(&stream[from..to]);
+            from = to;
+            loop {
+                match decoder.next_frame() {
+                    Ok(Some((msg_type, payload))) => frames.push(Ok((msg_type, payload.to_vec()))),
+                    Ok(None) => break,
+                    Err(e) => frames.push(Err(e)),
+                }
+            }
+        }
+        assert_eq!(decoder.buffered(), 0);
+        frames
+    }
+
+    fn messages() -> Vec<(u8, Vec<u8>)> {
+        vec![
+            (MessageType::Ping as u8, Vec::new()),
+            (MessageType::Data as u8, b"hello".to_vec()),
+            (MessageType::Data as u8, (0..=255).collect()),
+            (MessageType::Ack as u8, 42_u32.to_le_bytes().to_vec()),
+            (MessageType::Data as u8, "zero-copy ✓".as_bytes().to_vec()),
+        ]
+    }
+
+    fn stream_of(messages: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        messages
+            .iter()
+            .flat_map(|(msg_type, payload)| encode_frame(*msg_type, payload))
+            .collect()
+    }
+
+    #[test]
+    fn frames_come_back_in_order() {
+        let messages = messages();
+        let stream = stream_of(&messages);
+        let expected: Vec<_> = messages.into_iter().map(Ok).collect();
+        // Cut at a few places that don't care where the frames start or end
+        let cuts = [3, 4, 9, 10, 40, 41, 42, 200, 290];
+        assert_eq!(decode_in_pieces(&stream, &cuts), expected);
+        assert_eq!(decode_in_pieces(&stream, &[]), expected);
+        let every_byte: Vec<usize> = (1..stream.len()).collect();
+        assert_eq!(decode_in_pieces(&stream, &every_byte), expected);
+    }
+
+    #[test]
+    fn random_splits() {
+        let messages = messages();
+        let stream = stream_of(&messages);
+        let expected: Vec<_> = messages.into_iter().map(Ok).collect();
+        // Random cuts, from every byte on its own to the whole stream at once
+        let mut state = 0x9e37_79b9_7f4a_7c15;
+        for round in 0..1000 {
+            let pieces = (next_random(&mut state) % 20) as usize;
+            let mut cuts: Vec<usize> = (0..pieces)
+                .map(|_| (next_random(&mut state) % (stream.len() as u64 + 1)) as usize)
+                .collect();
+            cuts.sort_unstable();
+            assert_eq!(
+                decode_in_pieces(&stream, &cuts),
+                expected,
+                "round {}",
+                round
+            );
+        }
+    }
+
+    #[test]
+    fn the_payload_is_borrowed() {
+        let frame = encode_frame(MessageType::Data as u8, b"abc");
+        let (msg_type, payload) = decode_frame(&frame).unwrap();
+        assert_eq!((msg_type, payload), (1, &b"abc"[..]));
+        assert!(std::ptr::eq(payload.as_ptr(), frame[HEADER_LEN..].as_ptr()));
+    }
+
+    #[test]
+    fn bad_frames() {
+        let frame = encode_frame(MessageType::Data as u8, b"abc");
+        assert_eq!(decode_frame(&frame[..4]), Err(FrameError::TooShort(4)));
+        assert_eq!(
+            decode_frame(&frame[..6]),
+            Err(FrameError::LengthMismatch {
+                expected: 3,
+                found: 1
+            })
+        );
+        let mut longer = frame.clone();
+        longer.push(0);
+        assert!(matches!(
+            decode_frame(&longer),
+            Err(FrameError::LengthMismatch { .. })
+        ));
+        let unknown = encode_frame(7, b"?");
+        assert_eq!(decode_frame(&unknown), Err(FrameError::UnknownType(7)));
+        let huge = [0xff, 0xff, 0xff, 0xff, 1];
+        assert_eq!(
+            decode_frame(&huge),
+            Err(FrameError::TooLarge(u32::MAX as usize))
+        );
+    }
+
+    #[test]
+    fn unknown_types_are_skipped_in_a_stream() {
+        let mut mixed = encode_frame(0, b"");
+        mixed.extend(encode_frame(9, b"skip me"));
+        mixed.extend(encode_frame(2, b"ok"));
+        assert_eq!(
+            decode_in_pieces(&mixed, &[6, 7]),
+            [
+                Ok((0, Vec::new())),
+                Err(FrameError::UnknownType(9)),
+                Ok((2, b"ok".to_vec()))
+            ]
+        );
+    }
+
+    #[test]
+    fn a_broken_length_stops_the_stream() {
+        let mut decoder = FrameDecoder::new();
+        decoder.feed(&[0xff, 0xff, 0xff, 0xff, 1]);
+        for _ in 0..2 {
+            assert_eq!(
+                decoder.next_frame(),
+                Err(FrameError::TooLarge(u32::MAX as usize))
+            );
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    f64::consts::LN_2,
+    hash::{Hash, Hasher},
+};
+
+// A Bloom filter answers "have I seen this item?" using very little memory.
+// It never stores the items: inserting one sets `k` bits (picked by hashing the item)
+// in a big bit array, and checking an item looks at those same `k` bits.
+// - if one of the bits is 0, the item was definitely never inserted (no false negatives)
+// - if all of them are 1, the item was *probably* inserted: other items may have set
+//   those bits by chance (false positives)
+// The more bits per item, the rarer the false positives.
+#[derive(Debug, PartialEq)]
+pub struct BloomFilter {
+    // The bit array, 64 bits per word
+    bits: Vec<u64>,
+    // How many bits each item sets
+    k: usize,
+    n_bits: usize,
+}
+
+impl BloomFilter {
+    // Size the filter so that after inserting `capacity` items, checking an item that
+    // was never inserted says "probably yes" with a probability of `false_positive_rate`
+    //
+    // The standard formulas, with n = capacity and p = false_positive_rate:
+    //   bits needed:    m = -n * ln(p) / ln(2)^2
+    //   best k:         k = m / n * ln(2)
+    // e.g. 1000 items at 1% need about 9586 bits (1.2 KB) and 7 hashes per item
+    pub fn new_with_fp(capacity: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "the false positive rate must be between 0 and 1"
+        );
+        let capacity = capacity.max(1) as f64;
+
+        let n_bits = (-capacity * false_positive_rate.ln() / (LN_2 * LN_2)).ceil() as usize;
+        let k = ((n_bits as f64 / capacity) * LN_2).round().max(1.0) as usize;
+
+        BloomFilter {
+            bits: vec![0; n_bits.div_ceil(64)],
+            k,
+            n_bits,
+        }
+    }
+
+    pub fn insert(&mut self, item: impl Hash) {
+        for bit in self.bit_indexes(&item) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, item: impl Hash) -> bool {
+        self.bit_indexes(&item)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn n_bits(&self) -> usize {
+        self.n_bits
+    }
+
+    // Double hashing: instead of `k` different hash functions, we hash the item twice
+    // and combine the two hashes: index_i = h1 + i * h2 (mod n_bits)
+    // This is proven to work as well as `k` independent hashes (Kirsch and Mitzenmacher)
+    // and it means hashing the item only twice, whatever `k` is
+    fn bit_indexes(&self, item: &impl Hash) -> impl Iterator<Item = usize> {
+        let h1 = hash_with_seed(item, 0);
+        // An even h2 would only ever reach half of the bits when n_bits is even
+        let h2 = hash_with_seed(item, 1) | 1;
+        let n_bits = self.n_bits as u64;
+
+        (0..self.k as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % n_bits) as usize)
+    }
+
+    // Save the filter as bytes: n_bits and k as u64, then the bit array,
+    // all little endian, so it can be written to a file or sent over the network
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + 8 * self.bits.len());
+        bytes.extend_from_slice(&(self.n_bits as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.k as u64).to_le_bytes());
+        for word in &self.bits {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    // Load a filter saved with `to_bytes`, None if the bytes don't describe a filter
+    pub fn from_bytes(bytes: &[u8]) -> Option<BloomFilter> {
+        let n_bits = u64::from_le_bytes(bytes.get(0..8)?.try_into().ok()?) as usize;
+        let k = u64::from_le_bytes(bytes.get(8..16)?.try_into().ok()?) as usize;
+
+        let words = &bytes[16..];
+        if n_bits == 0 || k == 0 || words.len() != n_bits.div_ceil(64) * 8 {
+            return None;
+        }
+
+        let bits = words
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Some(BloomFilter { bits, k, n_bits })
+    }
+}
+
+// DefaultHasher::new() always starts from the same keys, so hashing the seed first
+// gives us two different (and reproducible) hash functions
+fn hash_with_seed(item: &impl Hash, seed: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn run() {
+    let false_positive_rate = 0.01;
+    let mut filter = BloomFilter::new_with_fp(1000, false_positive_rate);
+    println!(
+        "filter for 1000 items at {}%: {} bits ({} bytes), k = {}",
+        false_positive_rate * 100.0,
+        filter.n_bits(),
+        filter.n_bits().div_ceil(8),
+        filter.k()
+    );
+
+    let seen: Vec<String> = (0..1000).map(|i| format!("user-{}", i)).collect();
+    for item in &seen {
+        filter.insert(item);
+    }
+
+    // Every inserted item must be found
+    let false_negatives = seen.iter().filter(|item| !filter.contains(item)).count();
+    assert_eq!(false_negatives, 0);
+
+    // Items we never inserted should only be found by accident, about 1% of the time
+    let false_positives = (0..1000)
+        .map(|i| format!("visitor-{}", i))
+        .filter(|item| filter.contains(item))
+        .count();
+    let measured_rate = false_positives as f64 / 1000.0;
+    println!(
+        "false negatives: {}, false positives: {} out of 1000 ({}%)",
+        false_negatives,
+        false_positives,
+        measured_rate * 100.0
+    );
+    assert!(measured_rate <= 2.0 * false_positive_rate);
+
+    // Saving and loading gives back the exact same filter
+    let bytes = filter.to_bytes();
+    let loaded = BloomFilter::from_bytes(&bytes).expect("Failed to load the filter");
+    assert_eq!(loaded, filter);
+    assert!(loaded.contains("user-42"));
+    println!("saved and loaded the filter ({} bytes)", bytes.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1000 items, the hashes are the same on every run so the results are too
+    fn filled(false_positive_rate: f64) -> BloomFilter {
+        let mut filter = BloomFilter::new_with_fp(1000, false_positive_rate);
+        for i in 0..1000 {
+            filter.insert(format!("user-{}", i));
+        }
+        filter
+    }
+
+    #[test]
+    fn the_size_follows_the_formulas() {
+        let filter = BloomFilter::new_with_fp(1000, 0.01);
+        assert_eq!((filter.n_bits(), filter.k()), (9586, 7));
+        assert_eq!(filter.bits.len(), 150);
+    }
+
+    #[test]
+    fn no_false_negatives() {
+        for rate in [0.1, 0.01, 0.001] {
+            let filter = filled(rate);
+            assert!((0..1000).all(|i| filter.contains(format!("user-{}", i))));
+        }
+    }
+
+    #[test]
+    fn false_positives_stay_within_twice_the_rate() {
+        for rate in [0.1, 0.01] {
+            let filter = filled(rate);
+            let false_positives = (0..10_000)
+                .filter(|i| filter.contains(format!("visitor-{}", i)))
+                .count();
+            assert!(
+                false_positives as f64 / 10_000.0 <= 2.0 * rate,
+                "{} false positives at {}",
+                false_positives,
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn save_and_load_give_the_same_bits() {
+        let filter = filled(0.01);
+        let bytes = filter.to_bytes();
+        assert_eq!(bytes.len(), 16 + 8 * filter.bits.len());
+
+        let loaded = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.bits, filter.bits);
+        assert_eq!((loaded.n_bits, loaded.k), (filter.n_bits, filter.k));
+        assert!(loaded.contains("user-42"));
+    }
+
+    #[test]
+    fn bytes_that_are_not_a_filter_are_rejected() {
+        let bytes = filled(0.01).to_bytes();
+        assert!(BloomFilter::from_bytes(&bytes[..10]).is_none());
+        // One word short, one byte too many
+        assert!(BloomFilter::from_bytes(&bytes[..bytes.len() - 8]).is_none());
+        assert!(BloomFilter::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_none());
+        // No bits, no hashes
+        let mut empty = vec![0; 16];
+        empty[8] = 1;
+        assert!(BloomFilter::from_bytes(&empty).is_none());
+        let mut no_hashes = bytes.clone();
+        no_hashes[8..16].fill(0);
+        assert!(BloomFilter::from_bytes(&no_hashes).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "between 0 and 1")]
+    fn a_rate_of_1_is_refused() {
+        BloomFilter::new_with_fp(1000, 1.0);
+    }
+}
@@ -14,11 +14,11 @@ pub enum ChannelMsg<'a> {
     Decrement { thread: &'a str, count: u32 },
 }
 
-pub fn increment(thread: &str, count: u32) -> ChannelMsg {
+pub fn increment(thread: &str, count: u32) -> ChannelMsg<'_> {
     ChannelMsg::Increment { thread, count }
 }
 
-pub fn decrement(thread: &str, count: u32) -> ChannelMsg {
+pub fn decrement(thread: &str, count: u32) -> ChannelMsg<'_> {
     ChannelMsg::Decrement { thread, count }
 }
 
@@ -74,7 +74,7 @@ pub fn run(buffer_size: usize) {
     drop(tx);
 
     let mut counter = 0;
-    while let Some(msg) = rx.recv().ok() {
+    while let Ok(msg) = rx.recv() {
         match msg {
             ChannelMsg::Increment { thread, count } => {
                 println!("[{thread:?}] incremented {count:?}");
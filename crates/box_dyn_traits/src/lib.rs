@@ -28,7 +28,7 @@ pub struct WttrIn;
 
 impl WeatherService for WttrIn {
     fn get_weather(&self) -> String {
-        format!("Loading weather from WttrIn")
+        "Loading weather from WttrIn".to_string()
     }
 }
 
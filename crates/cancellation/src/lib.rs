@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+// Stopping tasks from the outside, and cleanly
+//
+// Dropping a future cancels it too, but then it stops at whatever `.await` it was
+// waiting on, with no chance to say so or clean up. A CancellationToken is a flag the
+// task watches: it decides where it stops, and what it returns when it does.
+//
+// Tokens form a tree: `child_token()` makes a token that is cancelled when its parent
+// is, but cancelling the child leaves the parent (and the other children) alone. A
+// server can give every request a child of its shutdown token: shutting down stops
+// them all, one request timing out stops only itself.
+
+// How much work a task does: STEPS steps of STEP each, half a second in total
+const STEPS: u32 = 10;
+const STEP: Duration = Duration::from_millis(50);
+
+// Do the work, unless the token is cancelled first
+// `select!` waits for whichever comes first, the next step or the cancellation, so
+// the task reacts at once, even in the middle of a step
+pub async fn long_running_task(token: CancellationToken, id: u32) -> String {
+    for _ in 0..STEPS {
+        tokio::select! {
+            _ = token.cancelled() => return "cancelled".to_string(),
+            _ = sleep(STEP) => {}
+        }
+    }
+    format!("task {} done", id)
+}
+
+// The same, but looking at the token between the steps
+// Simpler (no select!), but a step that started always finishes first: the task can
+// be up to a STEP late to notice
+pub async fn polling_task(token: CancellationToken, id: u32) -> String {
+    for _ in 0..STEPS {
+        if token.is_cancelled() {
+            return "cancelled".to_string();
+        }
+        sleep(STEP).await;
+    }
+    format!("task {} done", id)
+}
+
+// Cancel the token after `duration`
+pub async fn run_with_timeout(token: CancellationToken, duration: Duration) {
+    sleep(duration).await;
+    token.cancel();
+}
+
+pub fn run() {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        // Three tasks with a child of the same parent, the parent is cancelled after 200ms
+        let parent = CancellationToken::new();
+        let started = Instant::now();
+        let tasks: Vec<_> = (1..=3)
+            .map(|id| tokio::spawn(long_running_task(parent.child_token(), id)))
+            .collect();
+        tokio::spawn(run_with_timeout(parent.clone(), Duration::from_millis(200)));
+        for task in tasks {
+            let outcome = task.await.unwrap();
+            println!("{:.0?}: {}", started.elapsed(), outcome);
+        }
+
+        // Without a cancellation the tasks do all their work
+        let outcome = long_running_task(CancellationToken::new(), 4).await;
+        println!("not cancelled: {}", outcome);
+
+        // The polling task only notices at the end of its step
+        let token = CancellationToken::new();
+        let started = Instant::now();
+        let task = tokio::spawn(polling_task(token.clone(), 8));
+        tokio::spawn(run_with_timeout(token, STEP / 5));
+        let outcome = task.await.unwrap();
+        println!(
+            "the polling task noticed after {:.0?}: {}",
+            started.elapsed(),
+            outcome
+        );
+    });
+}
+
+// With the clock paused, tokio moves it forward whenever every task is waiting for a
+// timer: the half second of work takes no time, and the elapsed times are exact
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn a_task_that_is_not_cancelled_does_all_its_work() {
+        let started = Instant::now();
+        assert_eq!(
+            long_running_task(CancellationToken::new(), 4).await,
+            "task 4 done"
+        );
+        assert_eq!(started.elapsed(), STEP * STEPS);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_polling_task_that_is_not_cancelled_does_all_its_work() {
+        let token = CancellationToken::new();
+        assert_eq!(polling_task(token.clone(), 5).await, "task 5 done");
+        assert!(!token.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelling_the_parent_cancels_the_children() {
+        let parent = CancellationToken::new();
+        let started = Instant::now();
+        let tasks: Vec<_> = (1..=3)
+            .map(|id| tokio::spawn(long_running_task(parent.child_token(), id)))
+            .collect();
+        tokio::spawn(run_with_timeout(parent.clone(), Duration::from_millis(200)));
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), "cancelled");
+        }
+        // At once, not at the end of a step or after their half second
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+        // A child made after the parent was cancelled is cancelled from the start
+        assert!(parent.child_token().is_cancelled());
+    }
+
+    // Its sibling and the parent go on
+    #[tokio::test(start_paused = true)]
+    async fn cancelling_a_child_only_cancels_that_child() {
+        let parent = CancellationToken::new();
+        let (first, second) = (parent.child_token(), parent.child_token());
+        let first_task = tokio::spawn(long_running_task(first.clone(), 6));
+        let second_task = tokio::spawn(long_running_task(second, 7));
+
+        first.cancel();
+        assert_eq!(first_task.await.unwrap(), "cancelled");
+        assert_eq!(second_task.await.unwrap(), "task 7 done");
+        assert!(!parent.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_polling_task_notices_at_the_end_of_its_step() {
+        let token = CancellationToken::new();
+        let started = Instant::now();
+        let task = tokio::spawn(polling_task(token.clone(), 8));
+        tokio::spawn(run_with_timeout(token, STEP / 5));
+
+        assert_eq!(task.await.unwrap(), "cancelled");
+        assert_eq!(started.elapsed(), STEP);
+    }
+}
@@ -0,0 +1,245 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, never, select, Receiver};
+
+// Waiting on several channels at once with crossbeam's `select!`
+//
+// A thread that reads from two sensors and a shutdown switch can't block on one of
+// them, a reading would wait behind the other sensor being slow. `select!` waits on
+// all of them and runs the arm of the one that's ready first (a random one when
+// several are, so no channel starves). A `default(timeout)` arm runs when nothing came
+// in for that long, here to notice sensors that went quiet.
+//
+// Every message type has its own channel: the types say what came in, there is no
+// enum to match on afterwards.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureReading(pub f32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PressureReading(pub f32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownSignal;
+
+// How long the monitor waits for a message before it says the sensors are quiet
+pub const QUIET_AFTER: Duration = Duration::from_millis(100);
+
+// How many messages a channel holds before the sensor has to wait to send
+const CAPACITY: usize = 16;
+
+// The average of every value added so far, without keeping them
+// The sum is an f64, adding thousands of f32 readings in an f32 would lose digits
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunningAverage {
+    sum: f64,
+    count: u64,
+}
+
+impl RunningAverage {
+    pub fn add(&mut self, value: f32) {
+        self.sum += value as f64;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    // None before the first value
+    pub fn average(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+// What the monitor saw until it was shut down
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    pub temperature: RunningAverage,
+    pub pressure: RunningAverage,
+    // How many times QUIET_AFTER went by without a message
+    pub timeouts: u32,
+}
+
+// Read the three channels until a ShutdownSignal comes in
+//
+// The readings sent before the signal are all counted: `select!` could pick the signal
+// while readings wait in the other channels, so those are taken before stopping.
+// A sensor that stops (drops its sender) is left out from then on. A disconnected
+// channel is always "ready" (`recv` fails at once), selecting on it would spin, so it
+// is replaced with `never()`, a channel nothing ever comes out of. The shutdown
+// channel closing counts as the signal.
+pub fn monitor(
+    temperatures: &Receiver<TemperatureReading>,
+    pressures: &Receiver<PressureReading>,
+    shutdown: &Receiver<ShutdownSignal>,
+    quiet_after: Duration,
+) -> Summary {
+    let mut summary = Summary::default();
+    let (mut temperatures_open, mut pressures_open) = (temperatures.clone(), pressures.clone());
+    loop {
+        select! {
+            recv(temperatures_open) -> reading => match reading {
+                Ok(TemperatureReading(celsius)) => summary.temperature.add(celsius),
+                Err(_) => temperatures_open = never(),
+            },
+            recv(pressures_open) -> reading => match reading {
+                Ok(PressureReading(hpa)) => summary.pressure.add(hpa),
+                Err(_) => pressures_open = never(),
+            },
+            recv(shutdown) -> _ => break,
+            default(quiet_after) => {
+                summary.timeouts += 1;
+                println!("  nothing for {:?}", quiet_after);
+            }
+        }
+    }
+    for TemperatureReading(celsius) in temperatures.try_iter() {
+        summary.temperature.add(celsius);
+    }
+    for PressureReading(hpa) in pressures.try_iter() {
+        summary.pressure.add(hpa);
+    }
+    summary
+}
+
+// Three sensors on threads of their own, at their own pace, and the monitor reading them
+// The temperature sensor pauses for longer than QUIET_AFTER halfway, while the pressure
+// sensor is done already, so the timeout arm gets to run
+pub fn run_select_demo() -> Summary {
+    let (temperature_tx, temperatures) = bounded(CAPACITY);
+    let (pressure_tx, pressures) = bounded(CAPACITY);
+    let (shutdown_tx, shutdown) = bounded(1);
+    let start = Instant::now();
+
+    let temperature_sensor = thread::spawn(move || {
+        for i in 0..10 {
+            if i == 5 {
+                thread::sleep(QUIET_AFTER * 5 / 2);
+            }
+            let celsius = 20.0 + i as f32 * 0.5;
+            println!(
+                "  {:>4}ms temperature {}",
+                start.elapsed().as_millis(),
+                celsius
+            );
+            temperature_tx.send(TemperatureReading(celsius)).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+    let pressure_sensor = thread::spawn(move || {
+        for i in 0..4 {
+            let hpa = 1000.0 + i as f32 * 4.0;
+            println!("  {:>4}ms pressure {}", start.elapsed().as_millis(), hpa);
+            pressure_tx.send(PressureReading(hpa)).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+    });
+    let switch = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(600));
+        println!("  {:>4}ms shutdown", start.elapsed().as_millis());
+        shutdown_tx.send(ShutdownSignal).unwrap();
+    });
+
+    let summary = monitor(&temperatures, &pressures, &shutdown, QUIET_AFTER);
+    for sensor in [temperature_sensor, pressure_sensor, switch] {
+        sensor.join().unwrap();
+    }
+    summary
+}
+
+pub fn run() {
+    println!("Three sensors:");
+    let summary = run_select_demo();
+    println!(
+        "{} temperature readings, average {:.2}°C",
+        summary.temperature.count(),
+        summary.temperature.average().unwrap()
+    );
+    println!(
+        "{} pressure readings, average {:.1} hPa",
+        summary.pressure.count(),
+        summary.pressure.average().unwrap()
+    );
+    println!("{} timeout(s)", summary.timeouts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_average() {
+        let mut average = RunningAverage::default();
+        assert_eq!(average.average(), None);
+        for value in [1.0, 2.0, 6.0] {
+            average.add(value);
+        }
+        assert_eq!(average.count(), 3);
+        assert_eq!(average.average(), Some(3.0));
+    }
+
+    #[test]
+    fn averages_of_injected_messages() {
+        // All in the channels before the loop starts, the signal included: whatever
+        // order `select!` takes them in, every reading counts
+        let (temperature_tx, temperatures) = bounded(CAPACITY);
+        let (pressure_tx, pressures) = bounded(CAPACITY);
+        let (shutdown_tx, shutdown) = bounded(1);
+        for celsius in [18.5, 19.0, 21.5, 23.0] {
+            temperature_tx.send(TemperatureReading(celsius)).unwrap();
+        }
+        for hpa in [1013.25, 1009.75] {
+            pressure_tx.send(PressureReading(hpa)).unwrap();
+        }
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        let summary = monitor(&temperatures, &pressures, &shutdown, QUIET_AFTER);
+        assert_eq!(summary.temperature.count(), 4);
+        assert_eq!(summary.temperature.average(), Some(20.5));
+        assert_eq!(summary.pressure.count(), 2);
+        assert_eq!(summary.pressure.average(), Some(1011.5));
+        assert_eq!(summary.timeouts, 0);
+    }
+
+    #[test]
+    fn closed_sensors_wait_for_the_signal() {
+        // Sensors that stop (their senders dropped) don't keep the loop spinning, and
+        // with no reading at all there is no average
+        let (temperature_tx, temperatures) = bounded::<TemperatureReading>(CAPACITY);
+        let (pressure_tx, pressures) = bounded::<PressureReading>(CAPACITY);
+        let (shutdown_tx, shutdown) = bounded(1);
+        drop((temperature_tx, pressure_tx));
+        let switch = thread::spawn(move || {
+            thread::sleep(QUIET_AFTER * 3 / 2);
+            shutdown_tx.send(ShutdownSignal).unwrap();
+        });
+        let summary = monitor(&temperatures, &pressures, &shutdown, QUIET_AFTER);
+        switch.join().unwrap();
+        assert_eq!(summary.temperature.average(), None);
+        assert_eq!(summary.pressure.average(), None);
+        // It waited for the signal, rather than picking the closed channels again and again
+        assert!(summary.timeouts >= 1);
+    }
+
+    #[test]
+    fn closed_shutdown_channel_stops() {
+        let (_temperature_tx, temperatures) = bounded::<TemperatureReading>(CAPACITY);
+        let (_pressure_tx, pressures) = bounded::<PressureReading>(CAPACITY);
+        let (shutdown_tx, shutdown) = bounded::<ShutdownSignal>(1);
+        drop(shutdown_tx);
+        let summary = monitor(&temperatures, &pressures, &shutdown, QUIET_AFTER);
+        assert_eq!(summary, Summary::default());
+    }
+
+    #[test]
+    fn demo() {
+        let summary = run_select_demo();
+        assert_eq!(summary.temperature.count(), 10);
+        assert_eq!(summary.pressure.count(), 4);
+        // 20.0, 20.5 ... 24.5 and 1000, 1004, 1008, 1012
+        assert!((summary.temperature.average().unwrap() - 22.25).abs() < 1e-9);
+        assert!((summary.pressure.average().unwrap() - 1006.0).abs() < 1e-9);
+        assert!(summary.timeouts >= 1);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Four channels moving the same million u64 from one thread (or task) to another.
+// They all do the same job, what differs is what happens when one side is faster:
+//
+// - std::sync::mpsc::sync_channel: bounded, `send` blocks when the buffer is full.
+//   That's backpressure, a fast producer can't pile up messages faster than they are
+//   handled. Good enough for most thread-to-thread work and needs no dependency.
+// - crossbeam bounded: the same idea, usually faster under contention, and both ends
+//   can be cloned (many producers *and* many consumers, std only has one consumer).
+//   `select!` over several channels is built in too.
+// - crossbeam unbounded: `send` never blocks, the queue just grows. The fastest when
+//   the consumer keeps up, but nothing stops a fast producer from eating all the memory.
+// - tokio::sync::mpsc: bounded, but waiting is an `.await` instead of blocking the
+//   thread, so it's the one to use between async tasks. Blocking a runtime thread
+//   with a std or crossbeam `recv` would stall every other task scheduled on it.
+//   Waiting senders are served first come first served, so none of them starves.
+//
+// Numbers only mean something in a release build (`--release`).
+
+// How many messages the bounded channels can hold before `send` has to wait
+const CAPACITY: usize = 1024;
+
+// Send 0..total from another thread with `send` while this thread calls `recv` total
+// times, and return how long it took
+// Both closures only need `&self`, every channel here can send and receive through a
+// shared reference, that's what lets us use them from two threads without a Mutex
+pub fn bench_channel(
+    label: &str,
+    send: impl Fn(u64) + Send,
+    recv: impl Fn() -> u64,
+    total: u64,
+) -> Duration {
+    let start = Instant::now();
+
+    // `send` moves to the other thread, with a scoped thread it doesn't have to be 'static
+    let received = thread::scope(|scope| {
+        scope.spawn(move || {
+            for value in 0..total {
+                send(value);
+            }
+        });
+
+        (0..total).map(|_| recv()).sum::<u64>()
+    });
+
+    let elapsed = start.elapsed();
+    assert_eq!(received, expected_sum(total), "{} lost messages", label);
+    elapsed
+}
+
+// 0 + 1 + ... + (total - 1)
+fn expected_sum(total: u64) -> u64 {
+    total * total.saturating_sub(1) / 2
+}
+
+pub fn run_std_sync_channel(n: u64) -> Duration {
+    let (tx, rx) = mpsc::sync_channel(CAPACITY);
+    bench_channel(
+        "std sync_channel",
+        move |value| tx.send(value).unwrap(),
+        move || rx.recv().unwrap(),
+        n,
+    )
+}
+
+pub fn run_crossbeam_bounded(n: u64) -> Duration {
+    let (tx, rx) = crossbeam_channel::bounded(CAPACITY);
+    bench_channel(
+        "crossbeam bounded",
+        move |value| tx.send(value).unwrap(),
+        move || rx.recv().unwrap(),
+        n,
+    )
+}
+
+pub fn run_crossbeam_unbounded(n: u64) -> Duration {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    bench_channel(
+        "crossbeam unbounded",
+        move |value| tx.send(value).unwrap(),
+        move || rx.recv().unwrap(),
+        n,
+    )
+}
+
+// tokio's `recv` takes `&mut self` and returns a future, so it doesn't fit
+// `bench_channel`: here the two sides are tasks instead of threads
+pub fn run_tokio_mpsc(n: u64) -> Duration {
+    // Two worker threads, so the tasks can really run in parallel like the threads above
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .expect("Failed to start the tokio runtime");
+
+    runtime.block_on(async {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(CAPACITY);
+        let start = Instant::now();
+
+        let producer = tokio::spawn(async move {
+            for value in 0..n {
+                tx.send(value).await.unwrap();
+            }
+        });
+        let consumer = tokio::spawn(async move {
+            let mut received = 0_u64;
+            for _ in 0..n {
+                received += rx.recv().await.unwrap();
+            }
+            received
+        });
+
+        producer.await.expect("producer panicked");
+        let received = consumer.await.expect("consumer panicked");
+
+        let elapsed = start.elapsed();
+        assert_eq!(received, expected_sum(n), "tokio mpsc lost messages");
+        elapsed
+    })
+}
+
+pub fn run() {
+    let n = 1_000_000;
+
+    let results = [
+        ("std sync_channel", run_std_sync_channel(n)),
+        ("crossbeam bounded", run_crossbeam_bounded(n)),
+        ("crossbeam unbounded", run_crossbeam_unbounded(n)),
+        ("tokio mpsc", run_tokio_mpsc(n)),
+    ];
+
+    println!("{} messages, bounded channels hold {}:", n, CAPACITY);
+    println!("  {:<20} | {:>12} | {:>12}", "channel", "time", "msgs/s");
+    for (label, elapsed) in results {
+        println!(
+            "  {:<20} | {:>12?} | {:>12.0}",
+            label,
+            elapsed,
+            n as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// A circuit breaker sits in front of a call to another service. While the service
+// works the calls go through (the circuit is "closed", like an electric circuit).
+// After too many failures in a row it "opens": calls fail right away without even
+// trying, which gives the service time to recover and keeps us from waiting on
+// timeouts. Once the open period is over it lets a few test calls through
+// ("half open") and closes again if they succeed.
+//
+//            N failures in a row               open_duration is over
+//   Closed ----------------------> Open ---------------------------> HalfOpen
+//     ^                             ^                                   |  |
+//     |                             |          any failure              |  |
+//     |                             +-----------------------------------+  |
+//     |                   M successes in a row                             |
+//     +--------------------------------------------------------------------+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { successes: u32 },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BreakerError<E> {
+    // The breaker is open, the function wasn't called at all
+    Open,
+    // The function was called and failed
+    Inner(E),
+}
+
+impl<E: fmt::Display> fmt::Display for BreakerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerError::Open => write!(f, "circuit breaker is open"),
+            BreakerError::Inner(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for BreakerError<E> {}
+
+// Cloning the breaker shares the state, so every clone sees the same failures
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    state: Arc<Mutex<BreakerState>>,
+    failure_threshold: u32,
+    success_threshold: u32,
+    open_duration: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        failure_threshold: u32,
+        success_threshold: u32,
+        open_duration: Duration,
+    ) -> CircuitBreaker {
+        CircuitBreaker {
+            state: Arc::new(Mutex::new(BreakerState::Closed { failures: 0 })),
+            failure_threshold,
+            success_threshold,
+            open_duration,
+        }
+    }
+
+    // The current state, an Open breaker whose time is up already reports HalfOpen
+    pub fn state(&self) -> BreakerState {
+        let mut state = self.state.lock().unwrap();
+        self.expire_open(&mut state);
+        *state
+    }
+
+    pub async fn call<F, Fut, T, E>(&self, f: F) -> Result<T, BreakerError<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        // Decide if the call may go through, then release the lock before awaiting:
+        // holding a std Mutex across an `.await` would block every other caller
+        // (and the future wouldn't be Send anymore)
+        {
+            let mut state = self.state.lock().unwrap();
+            self.expire_open(&mut state);
+            if let BreakerState::Open { .. } = *state {
+                return Err(BreakerError::Open);
+            }
+        }
+
+        let result = f().await;
+
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Ok(value) => {
+                self.on_success(&mut state);
+                Ok(value)
+            }
+            Err(e) => {
+                self.on_failure(&mut state);
+                Err(BreakerError::Inner(e))
+            }
+        }
+    }
+
+    fn expire_open(&self, state: &mut BreakerState) {
+        if let BreakerState::Open { until } = *state {
+            if Instant::now() >= until {
+                *state = BreakerState::HalfOpen { successes: 0 };
+            }
+        }
+    }
+
+    fn on_success(&self, state: &mut BreakerState) {
+        *state = match *state {
+            // A success resets the count, the threshold is about failures in a row
+            BreakerState::Closed { .. } => BreakerState::Closed { failures: 0 },
+            BreakerState::HalfOpen { successes } if successes + 1 >= self.success_threshold => {
+                BreakerState::Closed { failures: 0 }
+            }
+            BreakerState::HalfOpen { successes } => BreakerState::HalfOpen {
+                successes: successes + 1,
+            },
+            // Another caller opened the breaker while our call was running,
+            // one late success isn't enough to close it again
+            open @ BreakerState::Open { .. } => open,
+        };
+    }
+
+    fn on_failure(&self, state: &mut BreakerState) {
+        let open = BreakerState::Open {
+            until: Instant::now() + self.open_duration,
+        };
+
+        *state = match *state {
+            BreakerState::Closed { failures } if failures + 1 >= self.failure_threshold => open,
+            BreakerState::Closed { failures } => BreakerState::Closed {
+                failures: failures + 1,
+            },
+            // The service is still broken, back to waiting
+            BreakerState::HalfOpen { .. } => open,
+            BreakerState::Open { .. } => open,
+        };
+    }
+}
+
+// Pretends to call a service, `healthy` decides if it works
+async fn call_service(healthy: bool) -> Result<&'static str, String> {
+    if healthy {
+        Ok("200 OK")
+    } else {
+        Err("500 Internal Server Error".to_string())
+    }
+}
+
+pub fn run() {
+    // The rest of the playground is synchronous, so we start a runtime just for this demo
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("Failed to start the tokio runtime");
+
+    runtime.block_on(async {
+        let breaker = CircuitBreaker::new(3, 2, Duration::from_millis(100));
+
+        // 3 failures in a row open the breaker
+        for _ in 0..3 {
+            let result = breaker.call(|| call_service(false)).await;
+            println!("{:?} -> {:?}", result, breaker.state());
+        }
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+
+        // While it is open the service isn't even called
+        let result = breaker.call(|| call_service(true)).await;
+        println!("while open: {:?}", result);
+        assert_eq!(result, Err(BreakerError::Open));
+
+        // Once the open period is over, we get to try again
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        println!("after waiting: {:?}", breaker.state());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen { successes: 0 });
+
+        // A failure while half open opens it again right away
+        let result = breaker.call(|| call_service(false)).await;
+        println!("{:?} -> {:?}", result, breaker.state());
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+
+        // And 2 successes while half open close it
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        for _ in 0..2 {
+            let result = breaker.call(|| call_service(true)).await;
+            println!("{:?} -> {:?}", result, breaker.state());
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed { failures: 0 });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN_FOR: Duration = Duration::from_millis(20);
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(3, 2, OPEN_FOR)
+    }
+
+    async fn fail(breaker: &CircuitBreaker, times: usize) {
+        for _ in 0..times {
+            let _ = breaker.call(|| call_service(false)).await;
+        }
+    }
+
+    async fn succeed(breaker: &CircuitBreaker, times: usize) {
+        for _ in 0..times {
+            let _ = breaker.call(|| call_service(true)).await;
+        }
+    }
+
+    // Open, with the open period over
+    async fn half_open() -> CircuitBreaker {
+        let breaker = breaker();
+        fail(&breaker, 3).await;
+        tokio::time::sleep(OPEN_FOR * 2).await;
+        breaker
+    }
+
+    #[tokio::test]
+    async fn closed_counts_the_failures() {
+        let breaker = breaker();
+        assert_eq!(breaker.state(), BreakerState::Closed { failures: 0 });
+        let result = breaker.call(|| call_service(false)).await;
+        assert_eq!(
+            result,
+            Err(BreakerError::Inner("500 Internal Server Error".to_string()))
+        );
+        fail(&breaker, 1).await;
+        assert_eq!(breaker.state(), BreakerState::Closed { failures: 2 });
+    }
+
+    #[tokio::test]
+    async fn closed_a_success_resets_the_count() {
+        let breaker = breaker();
+        fail(&breaker, 2).await;
+        assert_eq!(breaker.call(|| call_service(true)).await, Ok("200 OK"));
+        assert_eq!(breaker.state(), BreakerState::Closed { failures: 0 });
+    }
+
+    #[tokio::test]
+    async fn closed_to_open_after_the_failure_threshold() {
+        let breaker = breaker();
+        fail(&breaker, 3).await;
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+    }
+
+    #[tokio::test]
+    async fn open_does_not_call_the_function() {
+        let breaker = breaker();
+        fail(&breaker, 3).await;
+        let mut called = false;
+        let result = breaker
+            .call(|| {
+                called = true;
+                call_service(true)
+            })
+            .await;
+        assert_eq!(result, Err(BreakerError::Open));
+        assert!(!called);
+    }
+
+    #[tokio::test]
+    async fn open_to_half_open_when_the_time_is_up() {
+        assert_eq!(
+            half_open().await.state(),
+            BreakerState::HalfOpen { successes: 0 }
+        );
+    }
+
+    #[tokio::test]
+    async fn half_open_to_open_on_a_failure() {
+        let breaker = half_open().await;
+        fail(&breaker, 1).await;
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+    }
+
+    #[tokio::test]
+    async fn half_open_to_closed_after_the_success_threshold() {
+        let breaker = half_open().await;
+        succeed(&breaker, 1).await;
+        assert_eq!(breaker.state(), BreakerState::HalfOpen { successes: 1 });
+        succeed(&breaker, 1).await;
+        assert_eq!(breaker.state(), BreakerState::Closed { failures: 0 });
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_state() {
+        let breaker = breaker();
+        let clone = breaker.clone();
+        fail(&clone, 3).await;
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+    }
+
+    #[test]
+    fn errors_display_like_the_inner_error() {
+        assert_eq!(
+            BreakerError::<String>::Open.to_string(),
+            "circuit breaker is open"
+        );
+        assert_eq!(BreakerError::Inner("boom").to_string(), "boom");
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::Path;
+
+use config::{Config, ConfigError, Environment, File};
+use serde::Deserialize;
+
+// Layered configuration: every layer can override what the layers below it say
+//
+//   3. environment variables   APP_PORT=9000
+//   2. config/{env}.toml        development.toml, production.toml...
+//   1. config/default.toml      everything, with sensible values
+//
+// So the defaults live in one file, each environment only lists what it changes, and
+// one value can still be changed for a single run (or by a container platform) without
+// touching any file. The `config` crate merges the layers into one tree of values, and
+// serde turns that into our struct: a missing or mistyped value is an error right at
+// startup, not when the value is first used.
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AppConfig {
+    pub database_url: String,
+    pub port: u16,
+    pub debug: bool,
+    pub max_connections: u32,
+}
+
+// The config directory of this crate, wherever the program is started from
+const CONFIG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config");
+
+pub fn load_config(env: &str) -> Result<AppConfig, ConfigError> {
+    load_config_from(Path::new(CONFIG_DIR), env)
+}
+
+// The same, with the files in `dir`
+pub fn load_config_from(dir: &Path, env: &str) -> Result<AppConfig, ConfigError> {
+    let config: AppConfig = Config::builder()
+        // The later a source is added, the higher it is
+        .add_source(File::from(dir.join("default.toml")))
+        // An environment doesn't need a file of its own
+        .add_source(File::from(dir.join(format!("{}.toml", env))).required(false))
+        // APP_MAX_CONNECTIONS sets max_connections. Environment variables are always
+        // strings, `try_parsing` turns "9000" and "true" into a number and a bool
+        .add_source(Environment::with_prefix("APP").try_parsing(true))
+        .build()?
+        .try_deserialize()?;
+
+    validate(&config)?;
+    Ok(config)
+}
+
+// What the types can't say
+// Errors are reported as a ConfigError too, the caller has a single kind of error
+fn validate(config: &AppConfig) -> Result<(), ConfigError> {
+    if config.port == 0 {
+        return Err(ConfigError::Message("port must be greater than 0".into()));
+    }
+    if config.max_connections < 1 {
+        return Err(ConfigError::Message(
+            "max_connections must be at least 1".into(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn run() {
+    let config = load_config("development").expect("Failed to load the configuration");
+    println!("development:");
+    println!("  database_url    = {}", config.database_url);
+    println!("  port            = {}", config.port);
+    println!("  debug           = {}", config.debug);
+    println!("  max_connections = {}", config.max_connections);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Mutex;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    // Environment variables are shared by the whole process, and the tests run in
+    // parallel: the ones loading a config hold this, so none sees another's APP_PORT
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const DEFAULT: &str =
+        "database_url = \"sqlite://default.db\"\nport = 1000\ndebug = false\nmax_connections = 1\n";
+
+    // A config directory with default.toml and test.toml
+    fn config_dir(test: &str) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("default.toml"), DEFAULT).unwrap();
+        fs::write(dir.path().join("test.toml"), test).unwrap();
+        dir
+    }
+
+    // Loads with these environment variables set, and removes them after
+    fn load_with(dir: &TempDir, vars: &[(&str, &str)]) -> Result<AppConfig, ConfigError> {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, value) in vars {
+            std::env::set_var(key, value);
+        }
+        let config = load_config_from(dir.path(), "test");
+        for (key, _) in vars {
+            std::env::remove_var(key);
+        }
+        config
+    }
+
+    #[test]
+    fn the_crate_config_files() {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let config = load_config("development").unwrap();
+        // Overridden by development.toml, except for the port which comes from the default
+        assert_eq!(config.database_url, "postgres://localhost/app_dev");
+        assert_eq!(config.port, 8080);
+        assert!(config.debug);
+        // An environment without a file of its own gets the defaults
+        assert_eq!(load_config("staging").unwrap().max_connections, 10);
+    }
+
+    #[test]
+    fn the_default_is_used_when_nothing_overrides_it() {
+        let dir = config_dir("");
+        let config = load_with(&dir, &[]).unwrap();
+        assert_eq!(
+            config,
+            AppConfig {
+                database_url: "sqlite://default.db".into(),
+                port: 1000,
+                debug: false,
+                max_connections: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn the_environment_file_overrides_the_default() {
+        let dir = config_dir("port = 2000\n");
+        let config = load_with(&dir, &[]).unwrap();
+        assert_eq!(config.port, 2000);
+        assert_eq!(config.database_url, "sqlite://default.db");
+    }
+
+    #[test]
+    fn an_environment_variable_overrides_the_environment_file() {
+        let dir = config_dir("port = 2000\n");
+        let config = load_with(&dir, &[("APP_PORT", "3000"), ("APP_DEBUG", "true")]).unwrap();
+        assert_eq!(config.port, 3000);
+        assert!(config.debug);
+        assert_eq!(config.max_connections, 1);
+    }
+
+    #[test]
+    fn default_toml_is_required() {
+        let dir = TempDir::new().unwrap();
+        assert!(load_with(&dir, &[]).is_err());
+    }
+
+    #[test]
+    fn the_port_must_be_greater_than_0() {
+        let dir = config_dir("");
+        let error = load_with(&dir, &[("APP_PORT", "0")]).unwrap_err();
+        assert_eq!(error.to_string(), "port must be greater than 0");
+    }
+
+    #[test]
+    fn there_must_be_at_least_one_connection() {
+        let dir = config_dir("max_connections = 0\n");
+        let error = load_with(&dir, &[]).unwrap_err();
+        assert_eq!(error.to_string(), "max_connections must be at least 1");
+    }
+
+    // An error from serde, with the key in it
+    #[test]
+    fn a_value_of_the_wrong_type_is_an_error() {
+        let dir = config_dir("port = \"eighty\"\n");
+        let error = load_with(&dir, &[]).unwrap_err();
+        assert!(error.to_string().contains("port"), "{}", error);
+    }
+}
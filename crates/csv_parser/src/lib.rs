@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+// Reading and writing CSV by hand, one record at a time (RFC 4180)
+//
+// The format looks like "split the lines on commas", but a field can be quoted, and
+// then it can hold commas, quotes (written twice: `""`) and even line breaks. So a
+// record is not always a line: the reader goes on to the next line while a quoted
+// field is still open.
+//
+// The reader is streaming: it only ever holds the record it is reading, whatever the
+// size of the input.
+
+pub struct CsvReader<R: BufRead> {
+    inner: R,
+    delimiter: u8,
+    // Whether the first record names the columns (see `with_header`)
+    has_header: bool,
+    // The line we are at, for the errors
+    line: usize,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    // Comma-separated, with a header
+    pub fn new(inner: R) -> Self {
+        CsvReader {
+            inner,
+            delimiter: b',',
+            has_header: true,
+            line: 0,
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn has_header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    // The next record, or None at the end of the input
+    // Empty lines are skipped, the header (if any) is returned like any other record.
+    // A record ends with LF or CRLF, line breaks inside quotes are kept as they are
+    pub fn next_row(&mut self) -> io::Result<Option<Vec<String>>> {
+        let mut fields = Vec::new();
+        let mut field = Vec::new();
+        // Inside a quoted field, where the delimiter and line breaks are just text
+        let mut in_quotes = false;
+        // Right after the closing quote, only a delimiter or the end can come
+        let mut closed = false;
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            if self.inner.read_until(b'\n', &mut line)? == 0 {
+                // Between two records that's the end, in a quoted field it's too early
+                if in_quotes {
+                    return Err(self.error("the input ends inside a quoted field"));
+                }
+                return Ok(None);
+            }
+            self.line += 1;
+            // An empty line between records
+            if !in_quotes && trim_line_break(&line).is_empty() {
+                continue;
+            }
+
+            let mut bytes = line.iter().copied().peekable();
+            let mut line_break = false;
+            while let Some(byte) = bytes.next() {
+                if in_quotes {
+                    match byte {
+                        // `""` is a quote, a single one closes the field
+                        b'"' if bytes.peek() == Some(&b'"') => {
+                            field.push(b'"');
+                            bytes.next();
+                        }
+                        b'"' => {
+                            in_quotes = false;
+                            closed = true;
+                        }
+                        // Line breaks too are kept as they are
+                        _ => field.push(byte),
+                    }
+                } else if byte == b'\n' || byte == b'\r' && bytes.peek() == Some(&b'\n') {
+                    line_break = true;
+                    break;
+                } else if byte == self.delimiter {
+                    fields.push(self.field(std::mem::take(&mut field))?);
+                    closed = false;
+                } else if closed {
+                    return Err(self.error("text after the closing quote of a field"));
+                } else if byte == b'"' {
+                    // Only a whole field can be quoted: `ab"c` is not valid
+                    if !field.is_empty() {
+                        return Err(self.error("a quote inside a field that isn't quoted"));
+                    }
+                    in_quotes = true;
+                } else {
+                    field.push(byte);
+                }
+            }
+
+            // The record ends with the line, unless a quoted field goes on on the next
+            // one. The last line may have no line break at all
+            if line_break || !in_quotes {
+                break;
+            }
+        }
+
+        fields.push(self.field(field)?);
+        Ok(Some(fields))
+    }
+
+    // A HashMap per record, keyed by the names in the header
+    // Without a header (see `has_header`) the columns are named "1", "2"...
+    pub fn with_header(self) -> HeaderedCsvReader<R> {
+        HeaderedCsvReader {
+            reader: self,
+            header: None,
+        }
+    }
+
+    fn field(&self, bytes: Vec<u8>) -> io::Result<String> {
+        String::from_utf8(bytes).map_err(|_| self.error("a field isn't valid UTF-8"))
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {}: {}", self.line, message),
+        )
+    }
+}
+
+// The line without its LF or CRLF
+fn trim_line_break(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().transpose()
+    }
+}
+
+pub struct HeaderedCsvReader<R: BufRead> {
+    reader: CsvReader<R>,
+    // Read with the first record
+    header: Option<Vec<String>>,
+}
+
+impl<R: BufRead> HeaderedCsvReader<R> {
+    // The names of the columns, None for an empty input
+    pub fn header(&mut self) -> io::Result<Option<&[String]>> {
+        if self.header.is_none() && self.reader.has_header {
+            self.header = self.reader.next_row()?;
+        }
+        Ok(self.header.as_deref())
+    }
+
+    pub fn next_row(&mut self) -> io::Result<Option<HashMap<String, String>>> {
+        self.header()?;
+        let row = match self.reader.next_row()? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        // Without a header the first record says how many columns there are
+        let header = self
+            .header
+            .get_or_insert_with(|| (1..=row.len()).map(|i| i.to_string()).collect());
+        // Every record has as many fields as the header, or we'd have to guess
+        if row.len() != header.len() {
+            return Err(self.reader.error(&format!(
+                "{} fields, the header has {}",
+                row.len(),
+                header.len()
+            )));
+        }
+        Ok(Some(header.iter().cloned().zip(row).collect()))
+    }
+}
+
+impl<R: BufRead> Iterator for HeaderedCsvReader<R> {
+    type Item = io::Result<HashMap<String, String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().transpose()
+    }
+}
+
+// Write the rows as CSV, comma-separated, every record ending with CRLF like RFC 4180
+// says. Only the fields that need it are quoted: the ones with a comma, a quote or a
+// line break in them
+pub fn write_csv<W: Write, I: IntoIterator<Item = Vec<String>>>(
+    mut writer: W,
+    rows: I,
+) -> io::Result<()> {
+    for row in rows {
+        for (i, field) in row.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            // A record with one empty field would be an empty line, which readers skip
+            let needs_quotes =
+                field.contains([',', '"', '\r', '\n']) || row.len() == 1 && field.is_empty();
+            if needs_quotes {
+                write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                writer.write_all(field.as_bytes())?;
+            }
+        }
+        writer.write_all(b"\r\n")?;
+    }
+    writer.flush()
+}
+
+// Every record of `input`, without a header
+fn rows(input: &str) -> io::Result<Vec<Vec<String>>> {
+    CsvReader::new(input.as_bytes()).has_header(false).collect()
+}
+
+pub fn run() {
+    let input = "\
+name,city,quote,price
+Ada,London,\"Analytical, and proud\",12.50
+Grace,\"New York\",\"She said \"\"it's a bug\"\"\",8.00
+Linus,Helsinki,\"two
+lines\",3.25
+";
+    let mut reader = CsvReader::new(input.as_bytes()).with_header();
+    let header = reader.header().unwrap().unwrap().to_vec();
+    println!("columns: {:?}", header);
+    let records: Vec<_> = reader.collect::<io::Result<_>>().unwrap();
+    for record in &records {
+        println!(
+            "{} from {}: {:?}",
+            record["name"], record["city"], record["quote"]
+        );
+    }
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["quote"], "Analytical, and proud");
+    assert_eq!(records[1]["quote"], "She said \"it's a bug\"");
+    assert_eq!(records[2]["quote"], "two\nlines");
+
+    // Prices with VAT, written back in the order of the header
+    let mut rows = vec![header.clone()];
+    for record in &records {
+        let mut row: Vec<String> = header.iter().map(|name| record[name].clone()).collect();
+        let price: f64 = record["price"].parse().unwrap();
+        row[3] = format!("{:.2}", price * 1.2);
+        rows.push(row);
+    }
+    let mut output = Vec::new();
+    write_csv(&mut output, rows.clone()).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    print!("{}", output);
+    assert!(output.contains("Ada,London,\"Analytical, and proud\",15.00\r\n"));
+    assert!(output.contains("\"She said \"\"it's a bug\"\"\""));
+    // And it reads back to the same rows
+    assert_eq!(self::rows(&output).unwrap(), rows);
+}
+
+// The cases RFC 4180 describes, one by one
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    // 1. Records are separated by a line break, CRLF (or just LF)
+    #[test]
+    fn records_end_with_a_line_break() {
+        assert_eq!(
+            rows("a,b\r\nc,d\r\n").unwrap(),
+            [row(&["a", "b"]), row(&["c", "d"])]
+        );
+        assert_eq!(
+            rows("a,b\nc,d\n").unwrap(),
+            [row(&["a", "b"]), row(&["c", "d"])]
+        );
+    }
+
+    // 2. The last record may or may not have a line break
+    #[test]
+    fn the_trailing_crlf_is_optional() {
+        assert_eq!(
+            rows("a,b\r\nc,d").unwrap(),
+            [row(&["a", "b"]), row(&["c", "d"])]
+        );
+        assert_eq!(rows("a,b\r\n").unwrap(), rows("a,b").unwrap());
+    }
+
+    // 3. An optional header with the same format as the records
+    #[test]
+    fn the_header_names_the_fields() {
+        let mut headered = CsvReader::new("x,y\r\n1,2\r\n".as_bytes()).with_header();
+        assert_eq!(headered.header().unwrap().unwrap(), row(&["x", "y"]));
+        let record = headered.next_row().unwrap().unwrap();
+        assert_eq!((record["x"].as_str(), record["y"].as_str()), ("1", "2"));
+        assert!(headered.next_row().unwrap().is_none());
+    }
+
+    #[test]
+    fn without_a_header_the_columns_are_numbered() {
+        let mut numbered = CsvReader::new("a,b".as_bytes())
+            .has_header(false)
+            .with_header();
+        assert_eq!(numbered.next_row().unwrap().unwrap()["2"], "b");
+    }
+
+    // 4. Fields are separated by commas, spaces are part of the field, and the last
+    // field has no comma after it
+    #[test]
+    fn spaces_are_part_of_the_field() {
+        assert_eq!(rows(" a , b,c").unwrap(), [row(&[" a ", " b", "c"])]);
+    }
+
+    // Every record has the same number of fields
+    #[test]
+    fn ragged_rows_are_an_error() {
+        let mut uneven = CsvReader::new("x,y\n1,2,3\n".as_bytes()).with_header();
+        assert!(uneven.next_row().is_err());
+        let mut short = CsvReader::new("x,y\n1\n".as_bytes()).with_header();
+        assert!(short.next_row().is_err());
+    }
+
+    // 5. Fields may be quoted, and then the quotes aren't part of them
+    #[test]
+    fn quotes_around_a_field_are_dropped() {
+        assert_eq!(rows("\"a\",b,\"c\"").unwrap(), [row(&["a", "b", "c"])]);
+    }
+
+    // 6. Quoted fields can hold line breaks and commas
+    #[test]
+    fn a_quoted_field_can_hold_a_newline() {
+        assert_eq!(
+            rows("\"a\r\nb\",\"c,d\"\r\ne,f").unwrap(),
+            [row(&["a\r\nb", "c,d"]), row(&["e", "f"])]
+        );
+    }
+
+    // 7. A quote inside a quoted field is written twice
+    #[test]
+    fn an_escaped_quote_is_a_single_quote() {
+        assert_eq!(
+            rows("\"a \"\"b\"\" c\",\"\"\"\"").unwrap(),
+            [row(&["a \"b\" c", "\""])]
+        );
+    }
+
+    // Empty fields, quoted or not, and empty lines between the records
+    #[test]
+    fn empty_fields_and_lines() {
+        assert_eq!(
+            rows(",\"\",\n\n\r\na").unwrap(),
+            [row(&["", "", ""]), row(&["a"])]
+        );
+        assert_eq!(rows("").unwrap(), Vec::<Vec<String>>::new());
+        assert_eq!(rows("\"\"").unwrap(), [row(&[""])]);
+    }
+
+    // What the RFC doesn't allow
+    #[test]
+    fn malformed_quotes_are_an_error() {
+        assert!(rows("\"open").is_err());
+        assert!(rows("\"open\nstill open").is_err());
+        assert!(rows("\"a\"b").is_err());
+        assert!(rows("a\"b").is_err());
+        let error = rows("ok\n\"a\"b").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: text after the closing quote of a field"
+        );
+    }
+
+    // Another delimiter, the comma is just text then
+    #[test]
+    fn another_delimiter() {
+        let semicolons: Vec<_> = CsvReader::new("a;\"b;c\";d,e".as_bytes())
+            .delimiter(b';')
+            .has_header(false)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(semicolons, [row(&["a", "b;c", "d,e"])]);
+    }
+
+    // Writing quotes what needs it, and reads back to the same fields
+    #[test]
+    fn written_fields_read_back_the_same() {
+        let tricky = vec![
+            row(&["plain", "with,comma", "with \"quotes\"", "two\nlines", ""]),
+            row(&[""]),
+            row(&["cr\r", " spaces "]),
+        ];
+        let mut output = Vec::new();
+        write_csv(&mut output, tricky.clone()).unwrap();
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "plain,\"with,comma\",\"with \"\"quotes\"\"\",\"two\nlines\",\r\n\"\"\r\n\"cr\r\", spaces \r\n"
+        );
+        assert_eq!(rows(std::str::from_utf8(&output).unwrap()).unwrap(), tricky);
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+// chrono has three kinds of dates and times:
+//
+// - Naive*: a date and/or time without any time zone, "2024-07-01 09:00". It's what
+//   people write down, but it isn't a point in time until we know where it was written.
+// - DateTime<Utc>: a point in time. Comparing and subtracting these always works.
+// - DateTime<Tz>: a point in time plus the time zone to show it in.
+//
+// chrono-tz brings the IANA time zone database ("Europe/Paris", "America/New_York"),
+// compiled into the binary. A zone is not a fixed offset: New York is UTC-5 in winter
+// and UTC-4 in summer, and the rules changed over the years. So going from a naive
+// local time to a point in time needs the zone *and* the date.
+//
+// Daylight saving time makes it worse, twice a year:
+// - in spring the clocks jump from 02:00 to 03:00, so 02:30 never happens that day
+// - in autumn they go from 02:00 back to 01:00, so 01:30 happens twice
+
+// The format of the naive times we parse: "2024-07-01 09:00"
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[derive(Debug, PartialEq)]
+pub enum TimeError {
+    UnknownTimeZone(String),
+    InvalidTime(String),
+    // The local time was skipped by a DST change
+    NonExistent(NaiveDateTime, Tz),
+}
+
+impl fmt::Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeError::UnknownTimeZone(name) => write!(f, "unknown time zone `{}`", name),
+            TimeError::InvalidTime(time) => {
+                write!(f, "`{}` is not a time like \"2024-07-01 09:00\"", time)
+            }
+            TimeError::NonExistent(time, tz) => {
+                write!(
+                    f,
+                    "{} never happened in {}, the clocks skipped it",
+                    time, tz
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+pub fn parse_tz(name: &str) -> Result<Tz, TimeError> {
+    name.parse()
+        .map_err(|_| TimeError::UnknownTimeZone(name.to_string()))
+}
+
+// The point in time `time` was in zone `tz`
+pub fn parse_local(tz: &str, time: &str) -> Result<DateTime<Tz>, TimeError> {
+    let tz = parse_tz(tz)?;
+    let naive = NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .map_err(|_| TimeError::InvalidTime(time.to_string()))?;
+
+    // `from_local_datetime` can't just return a DateTime, because of DST
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(time) => Ok(time),
+        // The time happened twice, we take the first one (still on summer time)
+        LocalResult::Ambiguous(earliest, _latest) => Ok(earliest),
+        LocalResult::None => Err(TimeError::NonExistent(naive, tz)),
+    }
+}
+
+// Do a meeting at `a_time` in `a_tz` and one at `b_time` in `b_tz`, both lasting
+// `duration_hours`, overlap? Panics on a time zone or time it can't parse, see
+// `try_meeting_overlap` to get the error instead.
+pub fn meeting_overlap(
+    a_tz: &str,
+    a_time: &str,
+    b_tz: &str,
+    b_time: &str,
+    duration_hours: u32,
+) -> bool {
+    try_meeting_overlap(a_tz, a_time, b_tz, b_time, duration_hours)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_meeting_overlap(
+    a_tz: &str,
+    a_time: &str,
+    b_tz: &str,
+    b_time: &str,
+    duration_hours: u32,
+) -> Result<bool, TimeError> {
+    // Once both are points in time, the zones don't matter anymore
+    let a_start = parse_local(a_tz, a_time)?.with_timezone(&Utc);
+    let b_start = parse_local(b_tz, b_time)?.with_timezone(&Utc);
+    let duration = Duration::hours(duration_hours as i64);
+
+    // Two intervals [start, end) overlap when each one starts before the other ends
+    // Back to back meetings (one ends at 10:00, the next starts at 10:00) don't overlap
+    Ok(a_start < b_start + duration && b_start < a_start + duration)
+}
+
+// A Unix timestamp (seconds since 1970-01-01 00:00:00 UTC) as a local time in `tz`,
+// like "2023-11-15 07:13:20 JST (+09:00)". Panics on an unknown time zone.
+pub fn unix_to_local(ts: i64, tz: &str) -> String {
+    let tz = parse_tz(tz).unwrap_or_else(|e| panic!("{}", e));
+    let utc = DateTime::from_timestamp(ts, 0).expect("timestamp out of range");
+    utc.with_timezone(&tz)
+        .format("%Y-%m-%d %H:%M:%S %Z (%:z)")
+        .to_string()
+}
+
+// How many days from today to `date`, negative if it's in the past
+// "Today" is the local date of this machine
+pub fn days_until(date: NaiveDate) -> i64 {
+    days_between(chrono::Local::now().date_naive(), date)
+}
+
+// Dates have no time zone and no DST, so this is always a whole number of days
+pub fn days_between(from: NaiveDate, to: NaiveDate) -> i64 {
+    (to - from).num_days()
+}
+
+// The offset from UTC of `tz` at `time` (local time), in hours
+fn utc_offset_hours(tz: &str, time: &str) -> f64 {
+    let local = parse_local(tz, time).unwrap();
+    local.offset().fix().local_minus_utc() as f64 / 3600.0
+}
+
+pub fn run() {
+    // DST: the same zone, two different offsets
+    let summer = utc_offset_hours("America/New_York", "2024-07-01 12:00");
+    let winter = utc_offset_hours("America/New_York", "2024-01-15 12:00");
+    println!(
+        "New York is UTC{:+} in summer and UTC{:+} in winter",
+        summer, winter
+    );
+    assert_ne!(summer, winter);
+
+    // Same instant, shown in a few zones
+    let ts = 1_700_000_000;
+    println!("timestamp {}:", ts);
+    for tz in ["UTC", "America/New_York", "Europe/Paris", "Asia/Tokyo"] {
+        println!("  {:<18} {}", tz, unix_to_local(ts, tz));
+    }
+
+    // Meetings
+    // 9:00 in New York is 15:00 in Paris in summer, both one hour long
+    let overlap = meeting_overlap(
+        "America/New_York",
+        "2024-07-01 09:00",
+        "Europe/Paris",
+        "2024-07-01 15:30",
+        1,
+    );
+    println!(
+        "NY 09:00 and Paris 15:30 on 2024-07-01 overlap: {}",
+        overlap
+    );
+
+    // DST transitions
+    // 2024-03-10 in New York: 02:00 becomes 03:00, 02:30 doesn't exist
+    let skipped = parse_local("America/New_York", "2024-03-10 02:30");
+    println!(
+        "2024-03-10 02:30 in New York: {}",
+        skipped.as_ref().unwrap_err()
+    );
+    // 2024-11-03 in New York: 02:00 becomes 01:00 again, 01:30 happens twice
+    let twice = parse_local("America/New_York", "2024-11-03 01:30").unwrap();
+    println!("2024-11-03 01:30 in New York, the first time: {}", twice);
+
+    // Leap seconds
+    // 2016-12-31 ended with 23:59:60 UTC. Unix time pretends leap seconds don't exist
+    // (every day is 86400 seconds), and so does chrono mostly: it can hold 23:59:60 (as
+    // 23:59:59 with more than a second of nanoseconds) but its timestamp is the one of
+    // 23:59:59. The time zone database doesn't know about them at all.
+    let leap = DateTime::parse_from_rfc3339("2016-12-31T23:59:60Z").unwrap();
+    println!(
+        "leap second {} has the timestamp {}",
+        leap.to_rfc3339(),
+        leap.timestamp()
+    );
+
+    // Days
+    let next_new_year = NaiveDate::from_ymd_opt(chrono::Local::now().year() + 1, 1, 1).unwrap();
+    println!(
+        "days until {}: {}",
+        next_new_year,
+        days_until(next_new_year)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn offsets_change_with_dst() {
+        assert_eq!(
+            utc_offset_hours("America/New_York", "2024-07-01 12:00"),
+            -4.0
+        );
+        assert_eq!(
+            utc_offset_hours("America/New_York", "2024-01-15 12:00"),
+            -5.0
+        );
+        // Not everybody does DST
+        assert_eq!(utc_offset_hours("Asia/Tokyo", "2024-07-01 12:00"), 9.0);
+        assert_eq!(utc_offset_hours("Asia/Tokyo", "2024-01-15 12:00"), 9.0);
+        assert_eq!(utc_offset_hours("Asia/Kolkata", "2024-01-15 12:00"), 5.5);
+    }
+
+    #[test]
+    fn unix_timestamps() {
+        assert_eq!(unix_to_local(0, "UTC"), "1970-01-01 00:00:00 UTC (+00:00)");
+        assert_eq!(
+            unix_to_local(1_700_000_000, "Asia/Tokyo"),
+            "2023-11-15 07:13:20 JST (+09:00)"
+        );
+        assert_eq!(unix_to_local(-1, "UTC"), "1969-12-31 23:59:59 UTC (+00:00)");
+    }
+
+    #[test]
+    fn midnight() {
+        // Midnight in Paris is still the day before in UTC
+        assert_eq!(
+            unix_to_local(1_704_063_600, "Europe/Paris"),
+            "2024-01-01 00:00:00 CET (+01:00)"
+        );
+        assert_eq!(
+            unix_to_local(1_704_063_600, "UTC"),
+            "2023-12-31 23:00:00 UTC (+00:00)"
+        );
+        let midnight = parse_local("Europe/Paris", "2024-01-01 00:00").unwrap();
+        assert_eq!(midnight.with_timezone(&Utc).hour(), 23);
+        // A meeting across midnight: 23:30 in Tokyo is 16:30 in Paris (summer)
+        assert!(meeting_overlap(
+            "Asia/Tokyo",
+            "2024-07-01 23:30",
+            "Europe/Paris",
+            "2024-07-01 16:00",
+            1,
+        ));
+        // And one ending at midnight doesn't touch the next day's
+        assert!(!meeting_overlap(
+            "UTC",
+            "2024-01-01 23:00",
+            "UTC",
+            "2024-01-02 00:00",
+            1,
+        ));
+    }
+
+    #[test]
+    fn spring_forward_skips_a_time() {
+        // 2024-03-10 in New York: 02:00 becomes 03:00
+        assert!(matches!(
+            parse_local("America/New_York", "2024-03-10 02:30"),
+            Err(TimeError::NonExistent(..))
+        ));
+        let before = parse_local("America/New_York", "2024-03-10 01:59").unwrap();
+        let after = parse_local("America/New_York", "2024-03-10 03:00").unwrap();
+        assert_eq!(after - before, Duration::minutes(1));
+        assert!(matches!(
+            try_meeting_overlap(
+                "America/New_York",
+                "2024-03-10 02:30",
+                "UTC",
+                "2024-03-10 07:00",
+                1
+            ),
+            Err(TimeError::NonExistent(..))
+        ));
+    }
+
+    #[test]
+    fn fall_back_repeats_a_time() {
+        // 2024-11-03 in New York: 02:00 becomes 01:00 again, we take the first 01:30
+        let twice = parse_local("America/New_York", "2024-11-03 01:30").unwrap();
+        assert_eq!(twice.offset().fix().local_minus_utc(), -4 * 3600);
+        // From 00:00 to 03:00 that night is four hours
+        let start = parse_local("America/New_York", "2024-11-03 00:00").unwrap();
+        let end = parse_local("America/New_York", "2024-11-03 03:00").unwrap();
+        assert_eq!(end - start, Duration::hours(4));
+    }
+
+    #[test]
+    fn meetings_across_zones() {
+        // 9:00 in New York is 15:00 in Paris in summer
+        assert!(meeting_overlap(
+            "America/New_York",
+            "2024-07-01 09:00",
+            "Europe/Paris",
+            "2024-07-01 15:30",
+            1,
+        ));
+        // Back to back, no overlap
+        assert!(!meeting_overlap(
+            "America/New_York",
+            "2024-07-01 09:00",
+            "Europe/Paris",
+            "2024-07-01 16:00",
+            1,
+        ));
+        // The US switches to summer time two weeks before Europe: on 2024-03-20 New
+        // York is already UTC-4 but Paris is still UTC+1, so 9:00 in New York is 14:00
+        // in Paris
+        assert!(meeting_overlap(
+            "America/New_York",
+            "2024-03-20 09:00",
+            "Europe/Paris",
+            "2024-03-20 14:00",
+            1,
+        ));
+        assert!(!meeting_overlap(
+            "America/New_York",
+            "2024-07-20 09:00",
+            "Europe/Paris",
+            "2024-07-20 14:00",
+            1,
+        ));
+    }
+
+    #[test]
+    fn leap_second() {
+        // Unix time and chrono pretend it doesn't exist, its timestamp is the one of
+        // the second before
+        let leap = DateTime::parse_from_rfc3339("2016-12-31T23:59:60Z").unwrap();
+        let before = DateTime::parse_from_rfc3339("2016-12-31T23:59:59Z").unwrap();
+        assert_eq!(leap.to_rfc3339(), "2016-12-31T23:59:60+00:00");
+        assert_eq!(leap.timestamp(), before.timestamp());
+        assert_eq!(leap.nanosecond(), 1_000_000_000);
+        // The next day starts one second after 23:59:59, as if it never happened
+        assert_eq!(
+            unix_to_local(leap.timestamp() + 1, "UTC"),
+            "2017-01-01 00:00:00 UTC (+00:00)"
+        );
+    }
+
+    #[test]
+    fn bad_input() {
+        assert_eq!(
+            parse_tz("Mars/Olympus_Mons"),
+            Err(TimeError::UnknownTimeZone("Mars/Olympus_Mons".to_string()))
+        );
+        assert!(matches!(
+            try_meeting_overlap("UTC", "yesterday", "UTC", "2024-01-01 00:00", 1),
+            Err(TimeError::InvalidTime(_))
+        ));
+        assert!(matches!(
+            try_meeting_overlap("UTC", "2024-01-01 00:00", "Nowhere", "2024-01-01 00:00", 1),
+            Err(TimeError::UnknownTimeZone(_))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown time zone `Nowhere`")]
+    fn meeting_overlap_panics_on_bad_input() {
+        meeting_overlap("Nowhere", "2024-01-01 00:00", "UTC", "2024-01-01 00:00", 1);
+    }
+
+    #[test]
+    fn days() {
+        let next_new_year = date(chrono::Local::now().year() + 1, 1, 1);
+        assert!((1..=366).contains(&days_until(next_new_year)));
+        // 2024 is a leap year
+        assert_eq!(days_between(date(2024, 1, 1), date(2025, 1, 1)), 366);
+        assert_eq!(days_between(date(2025, 1, 1), date(2024, 12, 31)), -1);
+        // The day New York loses an hour still counts as one day
+        assert_eq!(days_between(date(2024, 3, 9), date(2024, 3, 11)), 2);
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// Dependency injection: a service doesn't create the things it depends on (its
+// database, its logger), it's handed them. Whoever builds it decides which ones, a
+// real database in production, an in-memory one in a test.
+//
+// Two ways to hand them over:
+// - a container (service locator): every service is registered once, by type, and
+//   looked up by type when needed. Flexible, but a missing registration is only
+//   noticed at runtime, when the lookup returns None.
+// - constructor injection, nothing else: the dependencies are the parameters of `new`,
+//   and the wiring is plain code. A missing dependency doesn't compile.
+
+pub trait Logger: Send + Sync {
+    fn log(&self, message: &str);
+}
+
+pub trait Database: Send + Sync {
+    fn insert_user(&self, name: &str) -> u32;
+    fn find_user(&self, id: u32) -> Option<String>;
+}
+
+pub trait UserService: Send + Sync {
+    fn register(&self, name: &str) -> u32;
+    fn greeting(&self, id: u32) -> Option<String>;
+}
+
+pub struct ConsoleLogger;
+
+impl Logger for ConsoleLogger {
+    fn log(&self, message: &str) {
+        println!("[log] {}", message);
+    }
+}
+
+// Keeps the messages instead of printing them, so we can check what was logged
+// `log` only gets `&self` (the logger is shared), hence the Mutex
+#[derive(Default)]
+pub struct MemoryLogger {
+    lines: Mutex<Vec<String>>,
+}
+
+impl MemoryLogger {
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+}
+
+impl Logger for MemoryLogger {
+    fn log(&self, message: &str) {
+        self.lines.lock().unwrap().push(message.to_string());
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryDatabase {
+    users: Mutex<Vec<String>>,
+}
+
+impl Database for InMemoryDatabase {
+    // The id is the position in the Vec, starting at 1
+    fn insert_user(&self, name: &str) -> u32 {
+        let mut users = self.users.lock().unwrap();
+        users.push(name.to_string());
+        users.len() as u32
+    }
+
+    fn find_user(&self, id: u32) -> Option<String> {
+        let users = self.users.lock().unwrap();
+        users.get((id as usize).checked_sub(1)?).cloned()
+    }
+}
+
+// The service only knows the traits, not which database or logger it got
+pub struct UserServiceImpl {
+    db: Arc<dyn Database>,
+    logger: Arc<dyn Logger>,
+}
+
+impl UserServiceImpl {
+    // Constructor injection: everything the service needs comes in through `new`
+    pub fn new(db: Arc<dyn Database>, logger: Arc<dyn Logger>) -> UserServiceImpl {
+        UserServiceImpl { db, logger }
+    }
+}
+
+impl UserService for UserServiceImpl {
+    fn register(&self, name: &str) -> u32 {
+        let id = self.db.insert_user(name);
+        self.logger
+            .log(&format!("registered user {} as {}", name, id));
+        id
+    }
+
+    fn greeting(&self, id: u32) -> Option<String> {
+        let greeting = self
+            .db
+            .find_user(id)
+            .map(|name| format!("Hello, {}!", name));
+        if greeting.is_none() {
+            self.logger.log(&format!("no user {}", id));
+        }
+        greeting
+    }
+}
+
+// A service locator: one value per type
+//
+// `Any` is what makes it work: every 'static type has a TypeId, and an `Arc<dyn Any>`
+// can be turned back into an `Arc<T>` if T is the type it was made from
+// (`downcast`). The map goes from the TypeId to the value.
+//
+// `downcast` only works for sized types, so a trait object can't be registered as
+// `Arc<dyn Database>` directly: we register an `Arc<Arc<dyn Database>>`, i.e. T is
+// `Arc<dyn Database>`, and that's also the type we look it up with.
+#[derive(Default)]
+pub struct Container {
+    services: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Container {
+    pub fn new() -> Container {
+        Container::default()
+    }
+
+    // Registering the same type again replaces the previous one
+    pub fn register<T: Any + Send + Sync>(&mut self, svc: Arc<T>) {
+        self.services.insert(TypeId::of::<T>(), svc);
+    }
+
+    // None if nothing of type T was registered, the compiler can't check that for us
+    pub fn resolve<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let service = self.services.get(&TypeId::of::<T>())?.clone();
+        // Can't fail, the value under TypeId::of::<T>() is always a T
+        service.downcast::<T>().ok()
+    }
+}
+
+// Wire up the application with a container
+// The user service itself gets its dependencies through its constructor, the
+// container is only the place they are found
+pub fn build_container() -> Container {
+    let mut container = Container::new();
+    container.register::<Arc<dyn Logger>>(Arc::new(Arc::new(ConsoleLogger)));
+    container.register::<Arc<dyn Database>>(Arc::new(Arc::new(InMemoryDatabase::default())));
+
+    let db = container
+        .resolve::<Arc<dyn Database>>()
+        .expect("The database is registered above");
+    let logger = container
+        .resolve::<Arc<dyn Logger>>()
+        .expect("The logger is registered above");
+    let users: Arc<dyn UserService> =
+        Arc::new(UserServiceImpl::new((*db).clone(), (*logger).clone()));
+    container.register(Arc::new(users));
+    container
+}
+
+// The same application without a container: the wiring is just code, and forgetting
+// a dependency is a compile error instead of a None
+pub fn build_user_service(logger: Arc<dyn Logger>) -> Arc<dyn UserService> {
+    let db = Arc::new(InMemoryDatabase::default());
+    Arc::new(UserServiceImpl::new(db, logger))
+}
+
+pub fn run() {
+    println!("With a container:");
+    let container = build_container();
+    let users = container
+        .resolve::<Arc<dyn UserService>>()
+        .expect("build_container registers the user service");
+    let id = users.register("Ferris");
+    println!("{}", users.greeting(id).unwrap());
+
+    println!("Without a container:");
+    // The test logger goes straight into the constructor
+    let memory = Arc::new(MemoryLogger::default());
+    let users = build_user_service(memory.clone());
+    let id = users.register("Corro");
+    println!("{}", users.greeting(id).unwrap());
+    for line in memory.lines() {
+        println!("[memory] {}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_user_service_resolves() {
+        let container = build_container();
+        let users = container.resolve::<Arc<dyn UserService>>().unwrap();
+        let id = users.register("Ferris");
+        assert_eq!(users.greeting(id).as_deref(), Some("Hello, Ferris!"));
+        assert_eq!(users.greeting(42), None);
+    }
+
+    #[test]
+    fn an_empty_container_resolves_nothing() {
+        let container = Container::new();
+        assert!(container.resolve::<Arc<dyn Logger>>().is_none());
+        assert!(container.resolve::<Arc<dyn UserService>>().is_none());
+    }
+
+    #[test]
+    fn missing_registrations_are_none() {
+        let container = build_container();
+        assert!(container.resolve::<MemoryLogger>().is_none());
+        assert!(container.resolve::<String>().is_none());
+    }
+
+    // The type has to be exactly the registered one: the logger is registered as
+    // `Arc<dyn Logger>`, not as the ConsoleLogger it is
+    #[test]
+    fn the_concrete_type_is_not_registered() {
+        let container = build_container();
+        assert!(container.resolve::<ConsoleLogger>().is_none());
+        assert!(container.resolve::<Arc<ConsoleLogger>>().is_none());
+        assert!(container.resolve::<Arc<dyn Logger>>().is_some());
+    }
+
+    // A test can swap in a logger it can read
+    #[test]
+    fn registering_again_replaces() {
+        let mut container = build_container();
+        let memory = Arc::new(MemoryLogger::default());
+        container.register::<Arc<dyn Logger>>(Arc::new(memory.clone()));
+
+        container.resolve::<Arc<dyn Logger>>().unwrap().log("hello");
+        assert_eq!(memory.lines(), ["hello"]);
+    }
+
+    #[test]
+    fn constructor_injection() {
+        let memory = Arc::new(MemoryLogger::default());
+        let users = build_user_service(memory.clone());
+        let id = users.register("Corro");
+        assert_eq!(users.greeting(id).as_deref(), Some("Hello, Corro!"));
+        assert_eq!(users.greeting(id + 1), None);
+        assert_eq!(memory.lines(), ["registered user Corro as 1", "no user 2"]);
+    }
+
+    #[test]
+    fn user_ids_start_at_1() {
+        let db = InMemoryDatabase::default();
+        assert_eq!(db.find_user(0), None);
+        assert_eq!(db.insert_user("a"), 1);
+        assert_eq!(db.insert_user("b"), 2);
+        assert_eq!(db.find_user(2).as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,224 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+// Three ways to turn bytes into something else, for different reasons:
+//
+// - base64 and hex turn *any* bytes into plain text, to put binary data where only text
+//   fits (JSON, URLs, emails, a terminal). They make it bigger: base64 writes 3 bytes as
+//   4 characters (+33%), hex writes every byte as 2 characters (+100%).
+// - MessagePack turns *structured data* into bytes. It's JSON's data model (numbers,
+//   strings, arrays, maps) in a compact binary form, plus a real type for raw bytes,
+//   which JSON doesn't have.
+
+// The standard alphabet (A-Z a-z 0-9 + /) with `=` padding, the one of RFC 4648
+// The crate also has URL_SAFE (`-` and `_` instead of `+` and `/`) and variants
+// without padding, picked the same way
+pub fn encode_base64(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+pub fn decode_base64(s: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    STANDARD.decode(s)
+}
+
+// Lowercase, `hex::encode_upper` for uppercase
+pub fn to_hex(data: &[u8]) -> String {
+    hex::encode(data)
+}
+
+// Accepts both cases
+pub fn from_hex(s: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(s)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Payload {
+    pub id: u32,
+    // Without `serde_bytes`, serde sees a Vec<u8> as a list of numbers, one element at
+    // a time. With it, MessagePack writes the bytes as one `bin` value: a length and
+    // the bytes as they are. (JSON has no bytes type, so there it's a list of numbers
+    // either way.)
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+    pub tags: Vec<String>,
+}
+
+// `to_vec` writes a struct as an array of its fields, in order: the smallest output,
+// but both sides need the same struct. `to_vec_named` writes a map with the field
+// names, bigger but readable without the struct (and field order can change).
+pub fn encode_msgpack(p: &Payload) -> Vec<u8> {
+    // Serializing a struct like this one into a Vec can't fail
+    rmp_serde::to_vec(p).expect("Failed to encode the payload")
+}
+
+pub fn decode_msgpack(bytes: &[u8]) -> Result<Payload, rmp_serde::decode::Error> {
+    rmp_serde::from_slice(bytes)
+}
+
+// The payload of the demo, with every possible byte in its body
+fn sample_payload() -> Payload {
+    Payload {
+        id: 4_000_000,
+        body: (0..=255).collect(),
+        tags: vec![
+            "audio".to_string(),
+            "raw".to_string(),
+            "ünïcödé".to_string(),
+        ],
+    }
+}
+
+pub fn run() {
+    println!("base64 of \"foobar\": {}", encode_base64(b"foobar"));
+    println!("hex of \"hello\":     {}", to_hex(b"hello"));
+
+    // Round trips: base64 and hex carry the MessagePack bytes as text
+    let payload = sample_payload();
+    let msgpack = encode_msgpack(&payload);
+    let base64 = encode_base64(&msgpack);
+    let hex = to_hex(&msgpack);
+    println!("msgpack of the payload starts with {}...", &hex[..24]);
+    println!("base64 of it starts with          {}...", &base64[..24]);
+    let via_base64 = decode_msgpack(&decode_base64(&base64).unwrap()).unwrap();
+    let via_hex = decode_msgpack(&from_hex(&hex).unwrap()).unwrap();
+    println!(
+        "back from base64: {}, from hex: {}",
+        via_base64 == payload,
+        via_hex == payload
+    );
+
+    // Sizes, compared to JSON
+    // The body alone makes JSON big: every byte is 1 to 3 digits and a comma
+    let json = serde_json::to_string(&payload).unwrap();
+    let named = rmp_serde::to_vec_named(&payload).unwrap();
+    println!(
+        "the same payload ({} bytes of body) as:",
+        payload.body.len()
+    );
+    for (format, size) in [
+        ("JSON", json.len()),
+        ("MessagePack", msgpack.len()),
+        ("MessagePack, named", named.len()),
+        ("MessagePack in base64", base64.len()),
+        ("MessagePack in hex", hex.len()),
+    ] {
+        println!(
+            "  {:<22} {:>5} bytes, {:>3}% of JSON",
+            format,
+            size,
+            size * 100 / json.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_test_vectors() {
+        // The test vectors of RFC 4648: the padding depends on the length modulo 3
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode_base64(plain.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded).unwrap(), plain.as_bytes());
+        }
+        // The standard alphabet, not the URL safe one
+        assert_eq!(encode_base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn bad_base64() {
+        assert!(decode_base64("not base64!").is_err());
+        // Missing padding
+        assert!(decode_base64("Zg").is_err());
+        assert!(decode_base64("-_8=").is_err());
+    }
+
+    #[test]
+    fn hex_known_values() {
+        assert_eq!(to_hex(b""), "");
+        assert_eq!(to_hex(b"hello"), "68656c6c6f");
+        assert_eq!(to_hex(&[0x00, 0xff, 0x10]), "00ff10");
+        assert_eq!(from_hex("DEADbeef").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn bad_hex() {
+        // An odd number of digits, and a letter that isn't a hex digit
+        assert_eq!(from_hex("abc"), Err(hex::FromHexError::OddLength));
+        assert_eq!(
+            from_hex("zz"),
+            Err(hex::FromHexError::InvalidHexCharacter { c: 'z', index: 0 })
+        );
+    }
+
+    #[test]
+    fn msgpack_known_value() {
+        // 0x93 is an array of 3 elements, 0x01 the number 1 (small numbers fit in the
+        // type byte), 0xc4 0x00 a `bin` of length 0 and 0x90 an empty array
+        let empty = Payload {
+            id: 1,
+            body: Vec::new(),
+            tags: Vec::new(),
+        };
+        assert_eq!(encode_msgpack(&empty), [0x93, 0x01, 0xc4, 0x00, 0x90]);
+        // The body as one `bin` (0xc4, length, bytes), a tag as a short string (0xa0 + length)
+        let small = Payload {
+            id: 2,
+            body: vec![0xab, 0xcd],
+            tags: vec!["hi".to_string()],
+        };
+        assert_eq!(
+            encode_msgpack(&small),
+            [0x93, 0x02, 0xc4, 0x02, 0xab, 0xcd, 0x91, 0xa2, b'h', b'i']
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let payload = sample_payload();
+        let msgpack = encode_msgpack(&payload);
+        assert_eq!(decode_msgpack(&msgpack).unwrap(), payload);
+        let base64 = encode_base64(&msgpack);
+        assert_eq!(decode_base64(&base64).unwrap(), msgpack);
+        let hex = to_hex(&msgpack);
+        assert_eq!(from_hex(&hex).unwrap(), msgpack);
+        assert_eq!(from_hex(&hex.to_uppercase()).unwrap(), msgpack);
+        let json = serde_json::to_string(&payload).unwrap();
+        assert_eq!(serde_json::from_str::<Payload>(&json).unwrap(), payload);
+        // Every length modulo 3, and every byte
+        for len in 0..=256 {
+            let data: Vec<u8> = (0..len).map(|i| (255 - i) as u8).collect();
+            assert_eq!(decode_base64(&encode_base64(&data)).unwrap(), data);
+            assert_eq!(from_hex(&to_hex(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn a_cut_message_is_an_error() {
+        let msgpack = encode_msgpack(&sample_payload());
+        // Not a wrong payload
+        assert!(decode_msgpack(&msgpack[..msgpack.len() - 1]).is_err());
+        assert!(decode_msgpack(&[]).is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        let payload = sample_payload();
+        let msgpack = encode_msgpack(&payload);
+        let json = serde_json::to_string(&payload).unwrap();
+        let named = rmp_serde::to_vec_named(&payload).unwrap();
+        assert!(msgpack.len() < json.len());
+        assert!(msgpack.len() < named.len());
+        assert_eq!(to_hex(&msgpack).len(), msgpack.len() * 2);
+        assert_eq!(encode_base64(&msgpack).len(), msgpack.len().div_ceil(3) * 4);
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Expanding ${VAR} in a string, the way a shell does, and reading .env files
+//
+// The subset of the POSIX parameter expansion that config files use:
+//
+//   ${VAR}           the value of VAR, an error if it isn't defined
+//   ${VAR:-default}  the value of VAR, or `default` if it's undefined or empty
+//   ${VAR:?message}  the value of VAR, or an error with `message`
+//   $$               a plain `$`
+//
+// The default is expanded too, so defaults can fall back on other variables:
+// ${DATABASE_URL:-postgres://${DB_HOST:-localhost}/app}. Like in a shell it's only
+// expanded when it's used: ${A:-${B:?}} with A set doesn't care about B.
+//
+// A `$` that isn't followed by `{` or `$` is kept as it is, `$HOME` isn't expanded.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpansionError {
+    // The variable (and the message of `:?`, if it has one)
+    Undefined(String),
+    // What's wrong and where, in bytes from the start of the template
+    MalformedSyntax(String),
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpansionError::Undefined(variable) => write!(f, "undefined variable {}", variable),
+            ExpansionError::MalformedSyntax(reason) => write!(f, "malformed template: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ExpansionError {}
+
+pub fn expand_vars(
+    template: &str,
+    env: &HashMap<String, String>,
+) -> Result<String, ExpansionError> {
+    let mut expander = Expander {
+        template,
+        pos: 0,
+        env,
+    };
+    expander.word(false, true)
+}
+
+struct Expander<'a> {
+    template: &'a str,
+    // Where we are in the template, in bytes
+    pos: usize,
+    env: &'a HashMap<String, String>,
+}
+
+impl<'a> Expander<'a> {
+    // What's left of the template, borrowed from it and not from us
+    fn rest(&self) -> &'a str {
+        &self.template[self.pos..]
+    }
+
+    fn malformed(&self, reason: &str) -> ExpansionError {
+        ExpansionError::MalformedSyntax(format!("{} at {}", reason, self.pos))
+    }
+
+    // The template up to its end, or up to the `}` that ends the word of a `:-` or `:?`
+    // when `nested` (that `}` is left for the caller)
+    // Without `evaluate` the word is only read, to get past it: no variable is looked
+    // up and none can be undefined, it only has to be well-formed
+    fn word(&mut self, nested: bool, evaluate: bool) -> Result<String, ExpansionError> {
+        let mut expanded = String::new();
+        loop {
+            let rest = self.rest();
+            let special = rest.find(|c| c == '$' || (nested && c == '}'));
+            let Some(at) = special else {
+                if nested {
+                    self.pos = self.template.len();
+                    return Err(self.malformed("missing `}`"));
+                }
+                expanded.push_str(rest);
+                self.pos = self.template.len();
+                return Ok(expanded);
+            };
+            expanded.push_str(&rest[..at]);
+            self.pos += at;
+
+            let rest = self.rest();
+            if rest.starts_with('}') {
+                return Ok(expanded);
+            }
+            if rest.starts_with("${") {
+                self.pos += 2;
+                expanded.push_str(&self.braced(evaluate)?);
+            } else {
+                // `$$` is one `$`, a `$` on its own stays a `$`
+                self.pos += if rest.starts_with("$$") { 2 } else { 1 };
+                expanded.push('$');
+            }
+        }
+    }
+
+    // What's after a `${`, up to and with its `}`
+    fn braced(&mut self, evaluate: bool) -> Result<String, ExpansionError> {
+        let rest = self.rest();
+        let name_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.malformed("expected a variable name after `${`"));
+        }
+        self.pos += name_len;
+
+        let rest = self.rest();
+        if rest.starts_with('}') {
+            self.pos += 1;
+            if !evaluate {
+                return Ok(String::new());
+            }
+            // Defined but empty is fine here, only `:-` and `:?` care
+            return self
+                .env
+                .get(name)
+                .cloned()
+                .ok_or_else(|| ExpansionError::Undefined(name.to_string()));
+        }
+
+        let operator = if rest.starts_with(":-") || rest.starts_with(":?") {
+            &rest[..2]
+        } else if rest.is_empty() {
+            return Err(self.malformed("missing `}`"));
+        } else {
+            return Err(self.malformed("expected `}`, `:-` or `:?` after the variable name"));
+        };
+        let operator_is_default = operator == ":-";
+        self.pos += 2;
+
+        let value = self
+            .env
+            .get(name)
+            .filter(|value| !value.is_empty())
+            .cloned();
+        // The word is only expanded when it's needed
+        let word = self.word(true, evaluate && value.is_none())?;
+        // The `}`, `word` stopped on it
+        self.pos += 1;
+        match value {
+            Some(value) => Ok(value),
+            None if !evaluate => Ok(String::new()),
+            None if operator_is_default => Ok(word),
+            None if word.is_empty() => Err(ExpansionError::Undefined(name.to_string())),
+            None => Err(ExpansionError::Undefined(format!("{}: {}", name, word))),
+        }
+    }
+}
+
+// The variables of a .env file
+//
+//   # comments, and empty lines
+//   KEY=value
+//   export KEY=value           the `export` of shell scripts is allowed
+//   KEY="a value with spaces"  with \n, \t, \" and \\ inside
+//   KEY='taken as it is'       no escapes
+//   KEY=value # a comment      after a space, outside of quotes
+//
+// The values are not expanded, see `expand_vars` for that
+pub fn load_dotenv(path: &Path) -> io::Result<HashMap<String, String>> {
+    parse_dotenv(&fs::read_to_string(path)?)
+}
+
+// The same, from the text of the file
+// A line that isn't one of the above is an InvalidData error, with its number
+pub fn parse_dotenv(text: &str) -> io::Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", index + 1, reason),
+            )
+        };
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("expected KEY=value"))?;
+        let key = key.trim();
+        let valid_key = !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(invalid(&format!("`{}` is not a variable name", key)));
+        }
+        let value = parse_value(value.trim()).map_err(invalid)?;
+        vars.insert(key.to_string(), value);
+    }
+    Ok(vars)
+}
+
+// The value after the `=`, already trimmed
+fn parse_value(raw: &str) -> Result<String, &'static str> {
+    let (value, after) = if let Some(quoted) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c @ ('"' | '\\' | '$'))) => value.push(c),
+                    // Not an escape we know, the backslash stays
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => return Err("missing closing `\"`"),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err("missing closing `\"`"),
+            }
+        };
+        (value, &quoted[end + 1..])
+    } else if let Some(quoted) = raw.strip_prefix('\'') {
+        let end = quoted.find('\'').ok_or("missing closing `'`")?;
+        (quoted[..end].to_string(), &quoted[end + 1..])
+    } else {
+        // Unquoted: up to a comment, if there is one
+        let value = match raw.find(" #").or_else(|| raw.find("\t#")) {
+            Some(comment) => &raw[..comment],
+            None => raw,
+        };
+        return Ok(value.trim_end().to_string());
+    };
+    // After the closing quote, only a comment
+    let after = after.trim_start();
+    if !after.is_empty() && !after.starts_with('#') {
+        return Err("unexpected text after the closing quote");
+    }
+    Ok(value)
+}
+
+const SAMPLE_ENV: &str = r#"
+# Where the app runs
+export APP_NAME=playground
+HOST=0.0.0.0
+PORT=8080 # the default port
+GREETING="Hello, \"world\"\tfrom ${APP_NAME}"
+PASSWORD='p@ss#word $not_expanded'
+EMPTY=
+"#;
+
+pub fn run() {
+    let env = parse_dotenv(SAMPLE_ENV).unwrap();
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+    for key in keys {
+        println!("{} = {:?}", key, env[key]);
+    }
+
+    let template = "${APP_NAME} listening on ${HOST}:${PORT}, \
+                    logs at ${LOG_DIR:-/var/log/${APP_NAME}}, \
+                    db ${DATABASE_URL:-postgres://${DB_HOST:-localhost}:${DB_PORT:-5432}/app}";
+    println!("{}", expand_vars(template, &env).unwrap());
+
+    // What goes wrong
+    for template in ["${SECRET:?set SECRET in .env}", "${PORT-80}"] {
+        println!(
+            "{:30} {}",
+            template,
+            expand_vars(template, &env).unwrap_err()
+        );
+    }
+    for bad in ["KEY='x' y", "MY KEY=x"] {
+        println!("{:30} {}", bad, parse_dotenv(bad).unwrap_err());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> HashMap<String, String> {
+        parse_dotenv(SAMPLE_ENV).unwrap()
+    }
+
+    fn expand(template: &str) -> Result<String, ExpansionError> {
+        expand_vars(template, &env())
+    }
+
+    fn undefined(variable: &str) -> Result<String, ExpansionError> {
+        Err(ExpansionError::Undefined(variable.to_string()))
+    }
+
+    #[test]
+    fn the_sample_env_is_parsed() {
+        let env = env();
+        assert_eq!(env.len(), 6);
+        assert_eq!(env["APP_NAME"], "playground");
+        assert_eq!(env["PORT"], "8080");
+        assert_eq!(env["GREETING"], "Hello, \"world\"\tfrom ${APP_NAME}");
+        assert_eq!(env["PASSWORD"], "p@ss#word $not_expanded");
+        assert_eq!(env["EMPTY"], "");
+    }
+
+    #[test]
+    fn load_dotenv_reads_a_file() {
+        let path =
+            std::env::temp_dir().join(format!("env_var_expansion_{}.env", std::process::id()));
+        fs::write(&path, SAMPLE_ENV).unwrap();
+        assert_eq!(load_dotenv(&path).unwrap(), env());
+        fs::remove_file(&path).unwrap();
+        assert!(load_dotenv(&path).is_err());
+    }
+
+    #[test]
+    fn a_template_with_many_variables() {
+        let template = "${APP_NAME} listening on ${HOST}:${PORT}, \
+                        logs at ${LOG_DIR:-/var/log/${APP_NAME}}, \
+                        db ${DATABASE_URL:-postgres://${DB_HOST:-localhost}:${DB_PORT:-5432}/app}";
+        assert_eq!(
+            expand(template).unwrap(),
+            "playground listening on 0.0.0.0:8080, logs at /var/log/playground, \
+             db postgres://localhost:5432/app"
+        );
+    }
+
+    // ${VAR}
+    #[test]
+    fn plain_variables() {
+        assert_eq!(expand("${PORT}").unwrap(), "8080");
+        assert_eq!(expand("${EMPTY}").unwrap(), "");
+        assert_eq!(expand("${NOPE}"), undefined("NOPE"));
+        assert_eq!(
+            expand("price: $5, $$HOME, ${PORT}$").unwrap(),
+            "price: $5, $HOME, 8080$"
+        );
+    }
+
+    // ${VAR:-default}, with empty counting as undefined
+    #[test]
+    fn defaults() {
+        assert_eq!(expand("${PORT:-80}").unwrap(), "8080");
+        assert_eq!(expand("${NOPE:-80}").unwrap(), "80");
+        assert_eq!(expand("${EMPTY:-was empty}").unwrap(), "was empty");
+        assert_eq!(expand("${NOPE:-}").unwrap(), "");
+    }
+
+    // As deep as needed, and the unused ones are never looked at
+    #[test]
+    fn nested_defaults() {
+        assert_eq!(expand("${A:-${B:-${C:-${PORT}}}}").unwrap(), "8080");
+        assert_eq!(expand("${A:-${B:-${C:-deep}}}").unwrap(), "deep");
+        assert_eq!(expand("${PORT:-${NOPE}}").unwrap(), "8080");
+        assert_eq!(expand("${PORT:-${NOPE:?never checked}}").unwrap(), "8080");
+        assert_eq!(expand("${A:-${NOPE}}"), undefined("NOPE"));
+        assert_eq!(expand("${A:-${SECRET:-${PORT:?}}}").unwrap(), "8080");
+    }
+
+    // ${VAR:?message}, the message is expanded too
+    #[test]
+    fn required_variables() {
+        assert_eq!(expand("${HOST:?set HOST}").unwrap(), "0.0.0.0");
+        assert_eq!(
+            expand("${SECRET:?set SECRET in .env}"),
+            undefined("SECRET: set SECRET in .env")
+        );
+        assert_eq!(expand("${EMPTY:?}"), undefined("EMPTY"));
+        assert_eq!(
+            expand("${SECRET:?missing for ${APP_NAME}}"),
+            undefined("SECRET: missing for playground")
+        );
+    }
+
+    #[test]
+    fn malformed_templates() {
+        for (template, reason) in [
+            ("${PORT", "missing `}` at 6"),
+            ("${PORT:-80", "missing `}` at 10"),
+            ("${A:-${PORT}", "missing `}` at 12"),
+            ("${}", "expected a variable name after `${` at 2"),
+            ("${1X}", "expected a variable name after `${` at 2"),
+            ("${ PORT}", "expected a variable name after `${` at 2"),
+            (
+                "${PORT-80}",
+                "expected `}`, `:-` or `:?` after the variable name at 6",
+            ),
+            (
+                "${PORT:=80}",
+                "expected `}`, `:-` or `:?` after the variable name at 6",
+            ),
+        ] {
+            assert_eq!(
+                expand(template),
+                Err(ExpansionError::MalformedSyntax(reason.to_string())),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_dotenv_lines() {
+        for (line, reason) in [
+            ("JUST_A_WORD", "line 1: expected KEY=value"),
+            ("1KEY=x", "line 1: `1KEY` is not a variable name"),
+            ("MY KEY=x", "line 1: `MY KEY` is not a variable name"),
+            ("=x", "line 1: `` is not a variable name"),
+            ("KEY=\"unterminated", "line 1: missing closing `\"`"),
+            ("KEY='unterminated", "line 1: missing closing `'`"),
+            (
+                "KEY='x' y",
+                "line 1: unexpected text after the closing quote",
+            ),
+            (
+                "# ok\nKEY=\"x\"y",
+                "line 2: unexpected text after the closing quote",
+            ),
+        ] {
+            let error = parse_dotenv(line).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), reason);
+        }
+    }
+
+    #[test]
+    fn a_comment_after_a_quoted_value_is_fine() {
+        let env = parse_dotenv("A=\"x\" # comment\nB='y'\t#\nC=z#not a comment").unwrap();
+        assert_eq!((env["A"].as_str(), env["B"].as_str()), ("x", "y"));
+        assert_eq!(env["C"], "z#not a comment");
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+
+// Event sourcing: instead of storing the current state (balance = 70) we store
+// everything that happened (opened, +100, -30), and the state is whatever replaying
+// those events gives. The log is append-only: an event is never changed or deleted, a
+// mistake is fixed by a new event that undoes it.
+//
+// - the history is complete, we can tell how we got to a state, and what the state
+//   was at any point in the past (replay up to that point)
+// - a projection is one way of looking at the events (the balance of an account, the
+//   list of open accounts...), and new ones can be added later and fed the whole past
+//
+// The price: the rules are checked when the events are *replayed*, not when they are
+// appended. This store takes anything, so an invalid history (withdrawing more than
+// there is) is only found when a projection is built. A real system checks a command
+// against the current projection before appending its event.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountEvent {
+    Opened { id: u64, owner: String },
+    Deposited { amount: u64 },
+    Withdrawn { amount: u64 },
+    Closed,
+}
+
+// Every event is stored with the account it happened to: only Opened carries an id
+#[derive(Debug, Default)]
+pub struct EventStore {
+    // (sequence number, account id, event), in the order they were appended
+    events: Vec<(u64, u64, AccountEvent)>,
+    next_seq: u64,
+}
+
+impl EventStore {
+    pub fn new() -> EventStore {
+        EventStore::default()
+    }
+
+    // Returns the sequence number of the event: it orders all the events of the
+    // store, whatever the account
+    pub fn append(&mut self, account_id: u64, event: AccountEvent) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.events.push((seq, account_id, event));
+        seq
+    }
+
+    // The events of one account, oldest first
+    pub fn events_for(&self, account_id: u64) -> impl Iterator<Item = (u64, &AccountEvent)> {
+        self.events
+            .iter()
+            .filter(move |(_, id, _)| *id == account_id)
+            .map(|(seq, _, event)| (*seq, event))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountProjection {
+    pub balance: u64,
+    pub owner: String,
+    pub open: bool,
+}
+
+// What's wrong with a history, and which event made it wrong
+#[derive(Debug, PartialEq)]
+pub enum ProjectionError {
+    // An event for an account before it was opened (or opened twice)
+    NotOpened { seq: u64 },
+    AlreadyOpened { seq: u64 },
+    Overdrawn { seq: u64, balance: u64, amount: u64 },
+    AfterClose { seq: u64 },
+}
+
+impl fmt::Display for ProjectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectionError::NotOpened { seq } => {
+                write!(f, "event #{} is for an account that isn't open yet", seq)
+            }
+            ProjectionError::AlreadyOpened { seq } => {
+                write!(f, "event #{} opens an account that is already open", seq)
+            }
+            ProjectionError::Overdrawn {
+                seq,
+                balance,
+                amount,
+            } => write!(
+                f,
+                "event #{} withdraws {} from a balance of {}",
+                seq, amount, balance
+            ),
+            ProjectionError::AfterClose { seq } => {
+                write!(f, "event #{} happens after the account was closed", seq)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProjectionError {}
+
+// The state of an account, from replaying its events in order
+// None when the account has no events, or its history is invalid (see `try_rebuild`)
+pub fn rebuild(store: &EventStore, account_id: u64) -> Option<AccountProjection> {
+    try_rebuild(store, account_id).ok().flatten()
+}
+
+// The same, saying what's wrong with an invalid history
+pub fn try_rebuild(
+    store: &EventStore,
+    account_id: u64,
+) -> Result<Option<AccountProjection>, ProjectionError> {
+    let mut account: Option<AccountProjection> = None;
+
+    for (seq, event) in store.events_for(account_id) {
+        // Every event but Opened needs an account that is open
+        let open_account = match (&mut account, event) {
+            (None, AccountEvent::Opened { owner, .. }) => {
+                account = Some(AccountProjection {
+                    balance: 0,
+                    owner: owner.clone(),
+                    open: true,
+                });
+                continue;
+            }
+            (Some(_), AccountEvent::Opened { .. }) => {
+                return Err(ProjectionError::AlreadyOpened { seq })
+            }
+            (None, _) => return Err(ProjectionError::NotOpened { seq }),
+            (Some(account), _) if !account.open => return Err(ProjectionError::AfterClose { seq }),
+            (Some(account), _) => account,
+        };
+
+        match *event {
+            AccountEvent::Opened { .. } => unreachable!("handled above"),
+            AccountEvent::Deposited { amount } => open_account.balance += amount,
+            AccountEvent::Withdrawn { amount } => {
+                open_account.balance =
+                    open_account
+                        .balance
+                        .checked_sub(amount)
+                        .ok_or(ProjectionError::Overdrawn {
+                            seq,
+                            balance: open_account.balance,
+                            amount,
+                        })?;
+            }
+            AccountEvent::Closed => open_account.open = false,
+        }
+    }
+    Ok(account)
+}
+
+// Alice's account, opened, used and closed, and Bob's, its events interleaved with hers
+// in the log. Alice's balance ends at 0 and Bob's at 5
+fn sample_store(alice: u64, bob: u64) -> EventStore {
+    let mut store = EventStore::new();
+    store.append(
+        alice,
+        AccountEvent::Opened {
+            id: alice,
+            owner: "Alice".to_string(),
+        },
+    );
+    store.append(alice, AccountEvent::Deposited { amount: 100 });
+    store.append(
+        bob,
+        AccountEvent::Opened {
+            id: bob,
+            owner: "Bob".to_string(),
+        },
+    );
+    store.append(alice, AccountEvent::Withdrawn { amount: 30 });
+    store.append(bob, AccountEvent::Deposited { amount: 5 });
+    store.append(alice, AccountEvent::Deposited { amount: 45 });
+    store.append(alice, AccountEvent::Withdrawn { amount: 115 });
+    store.append(alice, AccountEvent::Closed);
+    store
+}
+
+pub fn run() {
+    let (alice, bob) = (1, 2);
+    let mut store = sample_store(alice, bob);
+
+    println!("the log:");
+    for (seq, account_id, event) in &store.events {
+        println!("  #{} account {}: {:?}", seq, account_id, event);
+    }
+    println!("Alice's account, rebuilt: {:?}", rebuild(&store, alice));
+    println!("Bob's account, rebuilt: {:?}", rebuild(&store, bob));
+
+    // Nothing stops us from appending an invalid event, it's the projection that
+    // finds out
+    store.append(bob, AccountEvent::Withdrawn { amount: 50 });
+    match try_rebuild(&store, bob) {
+        Ok(account) => println!("Bob's account: {:?}", account),
+        Err(e) => println!("Bob's account: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: u64 = 1;
+    const BOB: u64 = 2;
+
+    #[test]
+    fn sequence_numbers_go_up_across_accounts() {
+        let mut store = sample_store(ALICE, BOB);
+        assert_eq!(store.len(), 8);
+        assert_eq!(store.append(BOB, AccountEvent::Closed), 8);
+        let seqs: Vec<_> = store.events_for(BOB).map(|(seq, _)| seq).collect();
+        assert_eq!(seqs, vec![2, 4, 8]);
+    }
+
+    #[test]
+    fn replaying_gives_the_state() {
+        let store = sample_store(ALICE, BOB);
+        assert_eq!(
+            rebuild(&store, ALICE),
+            Some(AccountProjection {
+                balance: 0,
+                owner: "Alice".to_string(),
+                open: false,
+            })
+        );
+        assert_eq!(
+            rebuild(&store, BOB),
+            Some(AccountProjection {
+                balance: 5,
+                owner: "Bob".to_string(),
+                open: true,
+            })
+        );
+    }
+
+    #[test]
+    fn an_account_without_events_is_none() {
+        let store = sample_store(ALICE, BOB);
+        assert_eq!(try_rebuild(&store, 3), Ok(None));
+        assert_eq!(rebuild(&EventStore::new(), ALICE), None);
+    }
+
+    #[test]
+    fn an_overdraft_is_only_found_by_the_projection() {
+        let mut store = sample_store(ALICE, BOB);
+        // Appending takes it
+        let seq = store.append(BOB, AccountEvent::Withdrawn { amount: 50 });
+        assert_eq!(store.len(), 9);
+
+        assert_eq!(
+            try_rebuild(&store, BOB),
+            Err(ProjectionError::Overdrawn {
+                seq,
+                balance: 5,
+                amount: 50,
+            })
+        );
+        assert_eq!(rebuild(&store, BOB), None);
+        // The other account isn't affected
+        assert_eq!(rebuild(&store, ALICE).unwrap().balance, 0);
+    }
+
+    #[test]
+    fn withdrawing_everything_is_not_an_overdraft() {
+        let mut store = sample_store(ALICE, BOB);
+        store.append(BOB, AccountEvent::Withdrawn { amount: 5 });
+        assert_eq!(rebuild(&store, BOB).unwrap().balance, 0);
+    }
+
+    #[test]
+    fn nothing_happens_after_a_close() {
+        let mut store = sample_store(ALICE, BOB);
+        let seq = store.append(ALICE, AccountEvent::Deposited { amount: 1 });
+        assert_eq!(
+            try_rebuild(&store, ALICE),
+            Err(ProjectionError::AfterClose { seq })
+        );
+    }
+
+    #[test]
+    fn an_account_is_opened_once_before_anything_else() {
+        let mut store = EventStore::new();
+        let seq = store.append(4, AccountEvent::Deposited { amount: 1 });
+        assert_eq!(
+            try_rebuild(&store, 4),
+            Err(ProjectionError::NotOpened { seq })
+        );
+
+        let mut store = sample_store(ALICE, BOB);
+        let seq = store.append(
+            BOB,
+            AccountEvent::Opened {
+                id: BOB,
+                owner: "Bob".to_string(),
+            },
+        );
+        assert_eq!(
+            try_rebuild(&store, BOB),
+            Err(ProjectionError::AlreadyOpened { seq })
+        );
+    }
+}
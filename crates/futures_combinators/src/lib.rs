@@ -0,0 +1,410 @@
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+// `map`, `and_then` and `race`, written by hand instead of taken from the futures crate
+//
+// A future is a value with a `poll` method: every poll does as much work as it can
+// without waiting, then returns Poll::Ready(output) or Poll::Pending. Pending comes
+// with a promise: the future arranged for the Waker in the Context to be called once
+// it's worth polling again. Combinators are futures that own other futures and poll
+// them from their own `poll`.
+//
+// `poll` takes `self: Pin<&mut Self>`: once polled, a future may never move again (an
+// async block can hold references into itself). To poll the futures inside ours, we
+// need a Pin<&mut> to them too, which we get from our own Pin<&mut Self>. That is
+// called pin projection, the pin-project crate generates it, here we do it by hand.
+
+// Transform the output of a future with `f`
+pub struct Map<F, G> {
+    fut: F,
+    f: G,
+}
+
+impl<F, G, T> Future for Map<F, G>
+where
+    F: Future,
+    G: FnMut(F::Output) -> T,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        // SAFETY: `fut` is never moved out of `self`, it's pinned as long as the Map
+        // is ("structural pinning"). `f` is not pinned, we only call it.
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.fut) };
+        match fut.poll(cx) {
+            Poll::Ready(output) => Poll::Ready((this.f)(output)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+// Where an AndThen is: waiting for the first future, or for the one `f` made from
+// its output
+pub enum AndThenState<F, Fut2> {
+    First(F),
+    Second(Fut2),
+    Done,
+}
+
+// Run a future, then the future `f` makes from its output
+// This is what `.await` twice in a row does in an async block
+pub struct AndThen<F, G, Fut2> {
+    state: AndThenState<F, Fut2>,
+    // An Option so we can take it out and call it, it's a FnOnce
+    f: Option<G>,
+}
+
+impl<F, G, Fut2> Future for AndThen<F, G, Fut2>
+where
+    F: Future,
+    G: FnOnce(F::Output) -> Fut2,
+    Fut2: Future,
+{
+    type Output = Fut2::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Fut2::Output> {
+        // SAFETY: the futures in `state` are never moved, `Pin::set` below drops the
+        // old state where it is and writes the new one in its place
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut state = unsafe { Pin::new_unchecked(&mut this.state) };
+        loop {
+            // SAFETY: the same, we only hand out pinned references to the futures
+            match unsafe { state.as_mut().get_unchecked_mut() } {
+                AndThenState::First(first) => {
+                    let output = match unsafe { Pin::new_unchecked(first) }.poll(cx) {
+                        Poll::Ready(output) => output,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    let f = this
+                        .f
+                        .take()
+                        .expect("AndThen's function is only called once");
+                    // The second future may be ready at once, so we go round again and
+                    // poll it right away
+                    state.set(AndThenState::Second(f(output)));
+                }
+                AndThenState::Second(second) => {
+                    let output = match unsafe { Pin::new_unchecked(second) }.poll(cx) {
+                        Poll::Ready(output) => output,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    state.set(AndThenState::Done);
+                    return Poll::Ready(output);
+                }
+                AndThenState::Done => panic!("AndThen polled after it completed"),
+            }
+        }
+    }
+}
+
+// Resolve with the output of whichever future finishes first, the other one is
+// dropped with the Race (dropping a future cancels it)
+// If both are ready on the same poll, f1 wins: it's polled first
+pub struct Race<F1, F2> {
+    f1: F1,
+    f2: F2,
+}
+
+impl<F1, F2> Future for Race<F1, F2>
+where
+    F1: Future,
+    F2: Future<Output = F1::Output>,
+{
+    type Output = F1::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F1::Output> {
+        // SAFETY: both futures are pinned as long as the Race is, like Map's
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Poll::Ready(output) = unsafe { Pin::new_unchecked(&mut this.f1) }.poll(cx) {
+            return Poll::Ready(output);
+        }
+        // Both got the waker: whichever makes progress first wakes us up
+        unsafe { Pin::new_unchecked(&mut this.f2) }.poll(cx)
+    }
+}
+
+// So the combinators read like the ones from the futures crate:
+//
+//   ready(2).map(|x| x * 10).and_then(|x| ready(x + 1))
+pub trait FutureExt: Future + Sized {
+    fn map<G, T>(self, f: G) -> Map<Self, G>
+    where
+        G: FnMut(Self::Output) -> T,
+    {
+        Map { fut: self, f }
+    }
+
+    fn and_then<G, Fut2>(self, f: G) -> AndThen<Self, G, Fut2>
+    where
+        G: FnOnce(Self::Output) -> Fut2,
+        Fut2: Future,
+    {
+        AndThen {
+            state: AndThenState::First(self),
+            f: Some(f),
+        }
+    }
+
+    fn race<F2>(self, other: F2) -> Race<Self, F2>
+    where
+        F2: Future<Output = Self::Output>,
+    {
+        Race {
+            f1: self,
+            f2: other,
+        }
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+// Ready after being polled `polls` times, it wakes itself up after every other poll
+// No timer, no thread: which of two CountDowns wins a race never depends on timing
+pub struct CountDown<T> {
+    polls: u32,
+    value: Option<T>,
+}
+
+pub fn count_down<T>(polls: u32, value: T) -> CountDown<T> {
+    CountDown {
+        polls,
+        value: Some(value),
+    }
+}
+
+// CountDown holds nothing that cares about being moved, so it's Unpin and can be
+// used through a plain &mut, no unsafe needed
+impl<T> Unpin for CountDown<T> {}
+
+impl<T> Future for CountDown<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if self.polls <= 1 {
+            return Poll::Ready(
+                self.value
+                    .take()
+                    .expect("CountDown polled after it completed"),
+            );
+        }
+        self.polls -= 1;
+        // Not done yet, but there is nothing to wait for: ask to be polled again
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+// Ready after `duration`, a thread sleeps and then calls the waker
+// (a real runtime has one timer for all its sleeps, this is one thread per sleep)
+pub struct Sleep {
+    deadline: Instant,
+    started: bool,
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::now() + duration,
+        started: false,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+        if !self.started {
+            self.started = true;
+            let waker = cx.waker().clone();
+            let wait = self.deadline - now;
+            thread::spawn(move || {
+                thread::sleep(wait);
+                waker.wake();
+            });
+        }
+        Poll::Pending
+    }
+}
+
+// The waker of our executor: a flag saying "poll again", and the thread to wake up
+// when the flag is set
+struct FlagWaker {
+    woken: Mutex<bool>,
+    thread: Thread,
+}
+
+impl Wake for FlagWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        *self.woken.lock().unwrap() = true;
+        self.thread.unpark();
+    }
+}
+
+// A minimal single-threaded executor: poll the future, and when it's pending, sleep
+// until its waker is called, then poll it again
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    // `pin!` pins the future on the stack: it can't move for as long as we poll it
+    let mut fut = pin!(fut);
+    let flag = Arc::new(FlagWaker {
+        woken: Mutex::new(false),
+        thread: thread::current(),
+    });
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // `park` can also return without an unpark, so we check the flag every time
+        loop {
+            let mut woken = flag.woken.lock().unwrap();
+            if *woken {
+                *woken = false;
+                break;
+            }
+            drop(woken);
+            thread::park();
+        }
+    }
+}
+
+// Counts how many times a future got polled
+fn polls_of<F: Future>(fut: F) -> (F::Output, u32) {
+    let mut polls = 0;
+    let mut fut = pin!(fut);
+    let output = block_on(std::future::poll_fn(|cx| {
+        polls += 1;
+        fut.as_mut().poll(cx)
+    }));
+    (output, polls)
+}
+
+pub fn run() {
+    // Map transforms the value, and the closure runs only once it's ready
+    let doubled = block_on(count_down(3, 21).map(|x| x * 2));
+    println!("map: {}", doubled);
+
+    // AndThen runs the futures one after the other, the second made from the first's output
+    let (chained, polls) = polls_of(count_down(2, 4).and_then(|x| count_down(3, x + 1)));
+    println!("and_then: {} after {} polls", chained, polls);
+
+    // Race: the one that needs fewer polls wins, whichever side it's on
+    let winner = block_on(count_down(5, "slow").race(count_down(2, "fast")));
+    println!("race: {}", winner);
+
+    // With real time: the sleeps wake the executor from other threads
+    let started = Instant::now();
+    let winner = block_on(
+        sleep(Duration::from_millis(200))
+            .map(|_| "200ms")
+            .race(sleep(Duration::from_millis(20)).map(|_| "20ms")),
+    );
+    println!(
+        "race of sleeps: {} won after {:.0?}",
+        winner,
+        started.elapsed()
+    );
+
+    // Everything together: a lookup with a timeout
+    let lookup = sleep(Duration::from_millis(10))
+        .and_then(|_| count_down(2, 1234))
+        .map(Ok::<u32, &str>);
+    let timeout = sleep(Duration::from_millis(500)).map(|_| Err("timed out"));
+    println!(
+        "lookup with a timeout: {:?}",
+        block_on(lookup.race(timeout))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::ready;
+
+    use super::*;
+
+    #[test]
+    fn map_transforms_the_value() {
+        assert_eq!(block_on(count_down(3, 21).map(|x| x * 2)), 42);
+        assert_eq!(block_on(ready(7).map(|x| format!("#{}", x))), "#7");
+    }
+
+    #[test]
+    fn map_runs_only_once_the_value_is_ready() {
+        let mut calls = 0;
+        let (value, polls) = polls_of(count_down(3, 1).map(|x| {
+            calls += 1;
+            x + 1
+        }));
+        assert_eq!((value, polls), (2, 3));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn and_then_runs_the_futures_one_after_the_other() {
+        let (chained, polls) = polls_of(count_down(2, 4).and_then(|x| count_down(3, x + 1)));
+        assert_eq!(chained, 5);
+        // 2 polls for the first, and the second is polled right away when it's made,
+        // so its first poll is the first one's last: 2 + 3 - 1
+        assert_eq!(polls, 4);
+    }
+
+    #[test]
+    fn race_returns_the_faster_result() {
+        assert_eq!(
+            block_on(count_down(5, "slow").race(count_down(2, "fast"))),
+            "fast"
+        );
+        assert_eq!(
+            block_on(count_down(2, "fast").race(count_down(5, "slow"))),
+            "fast"
+        );
+    }
+
+    #[test]
+    fn race_of_two_ready_futures_is_won_by_the_first() {
+        assert_eq!(
+            block_on(count_down(1, "first").race(count_down(1, "second"))),
+            "first"
+        );
+    }
+
+    // The loser is dropped, we don't wait for it
+    #[test]
+    fn race_of_sleeps() {
+        let started = Instant::now();
+        let winner = block_on(
+            sleep(Duration::from_millis(200))
+                .map(|_| "200ms")
+                .race(sleep(Duration::from_millis(20)).map(|_| "20ms")),
+        );
+        assert_eq!(winner, "20ms");
+        assert!(started.elapsed() < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn a_lookup_with_a_timeout() {
+        let lookup = sleep(Duration::from_millis(10))
+            .and_then(|_| count_down(2, 1234))
+            .map(Ok::<u32, &str>);
+        let timeout = sleep(Duration::from_millis(500)).map(|_| Err("timed out"));
+        assert_eq!(block_on(lookup.race(timeout)), Ok(1234));
+
+        let lookup = sleep(Duration::from_millis(500)).map(|_| Ok(1234));
+        let timeout = sleep(Duration::from_millis(10)).map(|_| Err("timed out"));
+        assert_eq!(block_on(lookup.race(timeout)), Err("timed out"));
+    }
+}
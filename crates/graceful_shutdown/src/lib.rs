@@ -0,0 +1,280 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use tokio::time::{sleep, timeout_at};
+use tokio_util::sync::CancellationToken;
+
+// Shutting down a program made of background tasks
+//
+// Stopping is two steps: tell every task to stop (cancel the token they all watch),
+// then wait until they say they are done, so what they had in flight gets written,
+// flushed or closed. A task that takes too long must not keep the program up
+// forever though: after a timeout the ones still running are aborted.
+//
+// Every task gets a TaskHandle: a child of the coordinator's token and a sender to
+// report back with. The coordinator counts the reports as they come in.
+
+// What a task gets when it registers: when to stop, and how to say it did
+pub struct TaskHandle {
+    name: String,
+    token: CancellationToken,
+    completion_tx: mpsc::Sender<String>,
+}
+
+impl TaskHandle {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Completes when the shutdown starts, for `select!`
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    // Tell the coordinator the task is done, after its cleanup
+    // Takes the handle: a task only finishes once
+    pub async fn done(self) {
+        // The coordinator stops listening after the timeout, then nobody cares anymore
+        let _ = self.completion_tx.send(self.name).await;
+    }
+}
+
+// How the shutdown went
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    // The tasks that finished in time, with how long they took after the signal
+    pub finished: Vec<(String, Duration)>,
+    // The tasks still running at the timeout, aborted if they were spawned by the
+    // coordinator
+    pub late: Vec<String>,
+}
+
+pub struct ShutdownCoordinator {
+    token: CancellationToken,
+    completion_tx: mpsc::Sender<String>,
+    completion_rx: mpsc::Receiver<String>,
+    // Every registered task, with a way to abort it when it was spawned by `spawn`
+    tasks: Mutex<Vec<(String, Option<AbortHandle>)>>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        // Room for a few reports at once, a full channel only makes `done` wait a bit
+        let (completion_tx, completion_rx) = mpsc::channel(16);
+        ShutdownCoordinator {
+            token: CancellationToken::new(),
+            completion_tx,
+            completion_rx,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Register a task the caller runs however it wants
+    pub fn register_task(&self, name: &str) -> TaskHandle {
+        self.tasks.lock().unwrap().push((name.to_string(), None));
+        self.handle(name)
+    }
+
+    // Register a task and spawn it on tokio, so it can be aborted if it's late
+    pub fn spawn<F, Fut>(&self, name: &str, task: F)
+    where
+        F: FnOnce(TaskHandle) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let abort = tokio::spawn(task(self.handle(name))).abort_handle();
+        self.tasks
+            .lock()
+            .unwrap()
+            .push((name.to_string(), Some(abort)));
+    }
+
+    fn handle(&self, name: &str) -> TaskHandle {
+        TaskHandle {
+            name: name.to_string(),
+            token: self.token.child_token(),
+            completion_tx: self.completion_tx.clone(),
+        }
+    }
+
+    // Signal every task to stop, and wait for them for at most `timeout`
+    pub async fn shutdown_and_wait(mut self, timeout: Duration) -> ShutdownReport {
+        // tokio's clock, which a test can pause and move forward
+        let started = tokio::time::Instant::now();
+        let deadline = started + timeout;
+        self.token.cancel();
+
+        let mut tasks = self.tasks.into_inner().unwrap();
+        let mut report = ShutdownReport::default();
+        while !tasks.is_empty() {
+            match timeout_at(deadline, self.completion_rx.recv()).await {
+                Ok(Some(name)) => {
+                    if let Some(i) = tasks.iter().position(|(task, _)| *task == name) {
+                        tasks.remove(i);
+                        report.finished.push((name, started.elapsed()));
+                    }
+                }
+                // Can't happen, we hold a sender ourselves
+                Ok(None) => break,
+                Err(_elapsed) => break,
+            }
+        }
+
+        // Whatever is left is late: stop it where it is
+        for (name, abort) in tasks {
+            if let Some(abort) = abort {
+                abort.abort();
+            }
+            report.late.push(name);
+        }
+        report
+    }
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A task doing some work in a loop until the shutdown, then taking `cleanup` to
+// finish what it was doing
+async fn worker(handle: TaskHandle, cleanup: Duration) {
+    loop {
+        tokio::select! {
+            _ = handle.cancelled() => break,
+            _ = sleep(Duration::from_millis(10)) => {}
+        }
+    }
+    sleep(cleanup).await;
+    handle.done().await;
+}
+
+pub fn run() {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        // Five tasks, the slowest needs longer to clean up than the shutdown allows
+        let coordinator = ShutdownCoordinator::new();
+        let cleanups = [0, 50, 150, 300, 2000];
+        for (i, &cleanup) in cleanups.iter().enumerate() {
+            let cleanup = Duration::from_millis(cleanup);
+            coordinator.spawn(&format!("task {}", i + 1), move |handle| {
+                worker(handle, cleanup)
+            });
+        }
+        sleep(Duration::from_millis(50)).await;
+
+        let started = Instant::now();
+        let report = coordinator
+            .shutdown_and_wait(Duration::from_millis(500))
+            .await;
+        let elapsed = started.elapsed();
+        for (name, took) in &report.finished {
+            println!("{} shut down after {:.0?}", name, took);
+        }
+        for name in &report.late {
+            println!("{} was too slow, aborted", name);
+        }
+        println!("the shutdown took {:.0?}", elapsed);
+    });
+}
+
+// With the clock paused, tokio moves it forward whenever every task is waiting for a
+// timer: the timeouts take no time, and the durations are exact
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_tasks_are_terminated_after_the_timeout() {
+        let coordinator = ShutdownCoordinator::new();
+        for (i, cleanup) in [0, 50, 150, 300, 2000].into_iter().enumerate() {
+            coordinator.spawn(&format!("task {}", i + 1), move |handle| {
+                worker(handle, ms(cleanup))
+            });
+        }
+        sleep(ms(50)).await;
+
+        let started = Instant::now();
+        let report = coordinator.shutdown_and_wait(ms(500)).await;
+
+        // In the order of their cleanup, the slowest didn't make it
+        let finished: Vec<_> = report
+            .finished
+            .iter()
+            .map(|(name, took)| (name.as_str(), *took))
+            .collect();
+        assert_eq!(
+            finished,
+            [
+                ("task 1", ms(0)),
+                ("task 2", ms(50)),
+                ("task 3", ms(150)),
+                ("task 4", ms(300))
+            ]
+        );
+        assert_eq!(report.late, ["task 5"]);
+        // The shutdown waited for the timeout, not for the slow task
+        assert_eq!(started.elapsed(), ms(500));
+    }
+
+    // Aborting drops the task, and its sender with it: the channel closes unused
+    #[tokio::test(start_paused = true)]
+    async fn a_late_task_is_really_stopped() {
+        let coordinator = ShutdownCoordinator::new();
+        let (tx, mut rx) = mpsc::channel::<()>(1);
+        coordinator.spawn("stubborn", move |handle| async move {
+            // Ignores the signal altogether
+            sleep(ms(200)).await;
+            let _ = tx.send(()).await;
+            handle.done().await;
+        });
+
+        let report = coordinator.shutdown_and_wait(ms(20)).await;
+        assert_eq!(report.late, ["stubborn"]);
+        assert!(report.finished.is_empty());
+        assert_eq!(rx.recv().await, None);
+    }
+
+    // The coordinator can't stop a task registered by hand, it only reports it
+    #[tokio::test(start_paused = true)]
+    async fn tasks_registered_by_hand() {
+        let coordinator = ShutdownCoordinator::new();
+        let handle = coordinator.register_task("by hand");
+        assert_eq!(handle.name(), "by hand");
+        assert!(!handle.is_cancelled());
+        let task = tokio::spawn(async move {
+            handle.cancelled().await;
+            handle.done().await;
+        });
+        let late = coordinator.register_task("never done");
+
+        let report = coordinator.shutdown_and_wait(ms(100)).await;
+        assert_eq!(report.finished, [("by hand".to_string(), ms(0))]);
+        assert_eq!(report.late, ["never done"]);
+        assert!(late.is_cancelled());
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nothing_registered_is_done_at_once() {
+        let started = Instant::now();
+        let report = ShutdownCoordinator::new()
+            .shutdown_and_wait(Duration::from_secs(10))
+            .await;
+        assert!(report.finished.is_empty() && report.late.is_empty());
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Write as _,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, Read},
+    path::Path,
+    time::Instant,
+};
+
+use sha2::{Digest, Sha256};
+
+// Two families of hash functions, for different jobs:
+//
+// - Hashes for hash tables (std's SipHash). Fast on small keys, 64 bits, and keyed
+//   with a random seed so an attacker can't send keys that all land in the same bucket.
+//   The output changes between runs and Rust versions: never store it or send it.
+// - Cryptographic hashes (SHA-256, BLAKE3). The output is fixed by a spec, the same on
+//   every machine forever, and nobody can find two inputs with the same hash. That's
+//   what checksums, content addressing and signatures need.
+//
+// BLAKE3 is much faster than SHA-256 in software: it uses SIMD and splits big inputs
+// in chunks hashed as a tree (the `rayon` feature even hashes them on several threads).
+// SHA-256 has dedicated instructions on recent CPUs, which closes some of the gap.
+
+// `DefaultHasher::new()` always starts from the same keys, so this is the same for
+// the whole run of the program. A `HashMap` gets random keys instead (RandomState).
+pub fn std_hash<H: Hash>(val: &H) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    val.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+// Lowercase hex, two characters per byte, the way hashes are usually shown
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}
+
+pub fn blake3_hash(data: &[u8]) -> String {
+    // `blake3::Hash` has a `to_hex` of its own
+    blake3::hash(data).to_hex().to_string()
+}
+
+// The BLAKE3 hash of a file, read 64 KiB at a time so the file never has to fit in
+// memory. Hashing the pieces one after the other gives the same result as hashing all
+// of it at once.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// Runs `f` and prints how long it took along with its result
+fn timed<T: std::fmt::Display>(label: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    println!("  {:<12} {:>10.2?}  {}", label, start.elapsed(), result);
+    result
+}
+
+pub fn run() {
+    // A tiny change gives a completely different hash (the avalanche effect)
+    println!("sha256(\"abc\") = {}", to_hex(&sha256(b"abc")));
+    println!("sha256(\"abd\") = {}", to_hex(&sha256(b"abd")));
+
+    // The same 1 MB of zeros through all of them
+    let zeros = vec![0_u8; 1024 * 1024];
+    let path = std::env::temp_dir().join("hashing_demo_zeros.bin");
+    std::fs::write(&path, &zeros).expect("Failed to write the temporary file");
+
+    println!("1 MB of zeros:");
+    timed("SipHash", || format!("{:016x}", std_hash(&zeros)));
+    timed("SHA-256", || to_hex(&sha256(&zeros)));
+    let in_memory = timed("BLAKE3", || blake3_hash(&zeros));
+    let from_file = timed("BLAKE3 file", || {
+        hash_file(&path).expect("Failed to hash the file")
+    });
+    // Streaming doesn't change the hash
+    println!("  same BLAKE3 from the file: {}", in_memory == from_file);
+    std::fs::remove_file(&path).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From FIPS 180-2 (appendix B) and the NIST examples
+    #[test]
+    fn sha256_test_vectors() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            to_hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            to_hex(&sha256(&[b'a'; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    // The inputs of the official vectors (test_vectors.json in the BLAKE3 repository)
+    // are the bytes 0, 1, 2... 250, 0, 1... of the given length
+    fn blake3_input(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn blake3_test_vectors() {
+        assert_eq!(
+            blake3_hash(&blake3_input(0)),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(
+            blake3_hash(&blake3_input(1)),
+            "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"
+        );
+        assert_eq!(
+            blake3_hash(&blake3_input(1024)),
+            "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7"
+        );
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(to_hex(&[]), "");
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xab, 0xff]), "000fabff");
+    }
+
+    #[test]
+    fn std_hash_of_equal_values() {
+        // SipHash has no fixed output to check, only that equal values hash the same
+        assert_eq!(std_hash(&"abc"), std_hash(&String::from("abc")));
+        assert_ne!(std_hash(&"abc"), std_hash(&"abd"));
+    }
+
+    #[test]
+    fn hash_file_streams_the_same_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        // Not a multiple of the 64 KiB buffer, the last read is a short one
+        let data = blake3_input(200_000);
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(hash_file(&path).unwrap(), blake3_hash(&data));
+
+        let empty = dir.path().join("empty.bin");
+        std::fs::write(&empty, b"").unwrap();
+        assert_eq!(hash_file(&empty).unwrap(), blake3_hash(b""));
+    }
+
+    #[test]
+    fn hash_file_of_a_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = hash_file(&dir.path().join("missing")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,161 @@
+use std::{borrow::Cow, fmt};
+
+// Higher-ranked trait bounds (HRTB) are the `for<'a>` in `F: for<'a> Fn(&'a str) -> &'a str`.
+// It reads "for every lifetime 'a, F can be called with a &'a str and returns a &'a str".
+//
+// Without it the lifetime is a generic parameter of the function, so the *caller*
+// picks it, and it has to be a lifetime the caller can name. Strings created inside
+// the function live shorter than anything the caller can name, so the function
+// can't pass them to `f`. With `for<'a>` the function picks a new lifetime on every
+// call, including the ones of its own locals.
+//
+// Most of the time we don't write `for<'a>`: `F: Fn(&str) -> &str` is exactly the
+// same bound, the elided lifetimes turn into a higher-ranked one. Writing it out
+// shows what is going on, and it is needed where elision doesn't apply (see the
+// supertrait of HigherRankedTrait, there is no `&` to elide a lifetime from there).
+
+/// Applies `f` to a word that only exists inside this function, and uppercases
+/// the part of the word `f` returns.
+///
+/// `f` has to work for the lifetime of our local `word`, which is why the bound is
+/// higher-ranked. If the caller chooses the lifetime instead, there is no lifetime
+/// it can choose that is short enough for a local of the function:
+///
+/// ```compile_fail
+/// fn bad_apply<'b, F: Fn(&'b str) -> &'b str>(f: F) -> String {
+///     let word = String::from("playground");
+///     // error[E0597]: `word` does not live long enough
+///     // `'b` comes from outside, so `&word` would have to outlive the function
+///     f(&word).to_uppercase()
+/// }
+///
+/// bad_apply(|s| &s[..1]);
+/// ```
+pub fn apply_to_str<F>(f: F) -> String
+where
+    F: for<'a> Fn(&'a str) -> &'a str,
+{
+    let word = String::from("playground");
+    let head = f(&word);
+    // `head` borrows from `word`, that's what the `&'a str -> &'a str` says,
+    // so it's a prefix we can cut off (assuming `f` returned the start of the word)
+    format!("{}{}", head.to_uppercase(), &word[head.len()..])
+}
+
+// Transforms byte slices, borrowing them when nothing changes
+// `Fn(&[u8]) -> Cow<'_, [u8]>` would be the same bound, here we spell it out: the
+// output borrows from the input, for any slice and not one particular lifetime
+pub struct Mapper<F: for<'a> Fn(&'a [u8]) -> Cow<'a, [u8]>> {
+    func: F,
+}
+
+impl<F: for<'a> Fn(&'a [u8]) -> Cow<'a, [u8]>> Mapper<F> {
+    pub fn new(func: F) -> Mapper<F> {
+        Mapper { func }
+    }
+
+    // One `Mapper` works for slices of any lifetime, here the ones of `inputs`
+    pub fn map<'a>(&self, inputs: &[&'a [u8]]) -> Vec<Cow<'a, [u8]>> {
+        inputs.iter().map(|input| (self.func)(input)).collect()
+    }
+}
+
+// Lowercase ASCII, copying only the slices that have an uppercase letter
+fn ascii_lowercase(bytes: &[u8]) -> Cow<'_, [u8]> {
+    if bytes.iter().any(u8::is_ascii_uppercase) {
+        Cow::Owned(bytes.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(bytes)
+    }
+}
+
+// The same idea in a trait: `for<'a>` can appear in a supertrait (or where clause)
+// A type implementing HigherRankedTrait can transform a reference to another value
+// of the same type, whatever the lifetime of that reference is
+pub trait Transform<T> {
+    type Output;
+    fn transform(&self, input: T) -> Self::Output;
+}
+
+pub trait HigherRankedTrait: for<'a> Transform<&'a Self> {}
+
+// Every type that qualifies gets it for free
+impl<T: for<'a> Transform<&'a T>> HigherRankedTrait for T {}
+
+#[derive(Clone)]
+pub struct Word(pub String);
+
+// The beginning `input` has in common with `self`, borrowed from `input`
+impl<'a> Transform<&'a Word> for Word {
+    type Output = &'a str;
+
+    fn transform(&self, input: &'a Word) -> &'a str {
+        let common = self
+            .0
+            .chars()
+            .zip(input.0.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        &input.0[..common]
+    }
+}
+
+// `local` only lives inside this function, so we can't name its lifetime in the
+// signature. `T: HigherRankedTrait` promises `transform` works with it anyway
+pub fn transform_copy<T>(value: &T) -> String
+where
+    T: HigherRankedTrait + Clone,
+    for<'a> <T as Transform<&'a T>>::Output: fmt::Display,
+{
+    let local = value.clone();
+    // The result borrows `local`, so it must be gone before `local` is,
+    // that's why we don't call `to_string` on a temporary at the end of the block
+    let transformed = value.transform(&local);
+    transformed.to_string()
+}
+
+pub fn run() {
+    // The first character, however many bytes it takes
+    // The closure is written right in the call on purpose: there the compiler sees the
+    // `for<'a>` bound and makes the closure higher-ranked. Stored in a `let` first, its
+    // input and output would get two unrelated lifetimes and `&s[..end]` wouldn't compile
+    let word = apply_to_str(|s| {
+        let end = s.chars().next().map_or(0, char::len_utf8);
+        &s[..end]
+    });
+    println!("apply_to_str(first char): {}", word);
+    assert_eq!(word, "Playground");
+    assert_eq!(apply_to_str(|s| &s[..4]), "PLAYground");
+
+    let mapper = Mapper::new(ascii_lowercase);
+    let inputs: [&[u8]; 3] = [b"already quiet", b"LOUD", b"Mixed Case"];
+    let mapped = mapper.map(&inputs);
+    for (input, output) in inputs.iter().zip(&mapped) {
+        let kind = match output {
+            Cow::Borrowed(_) => "borrowed",
+            Cow::Owned(_) => "owned",
+        };
+        println!(
+            "{:<15} -> {:<15} ({})",
+            String::from_utf8_lossy(input),
+            String::from_utf8_lossy(output),
+            kind
+        );
+    }
+    assert!(matches!(mapped[0], Cow::Borrowed(_)));
+    assert_eq!(&*mapped[1], b"loud");
+
+    // Closures work too, as long as they return something borrowed from their argument
+    let trim = Mapper::new(|bytes: &[u8]| Cow::Borrowed(bytes.trim_ascii()));
+    assert_eq!(&*trim.map(&[b"  padded  "])[0], b"padded");
+
+    let rust = Word("rustacean".to_string());
+    let rusty = Word("rusty".to_string());
+    println!(
+        "common start of rustacean and rusty: {}",
+        rust.transform(&rusty)
+    );
+    assert_eq!(rust.transform(&rusty), "rust");
+    assert_eq!(transform_copy(&rust), "rustacean");
+}
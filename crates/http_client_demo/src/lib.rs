@@ -0,0 +1,247 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use retry_backoff::{retry, RetryPolicy};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+// An HTTP client for a JSON API, with reqwest
+//
+// Not every failure is worth another try: a 503 or a timeout may be gone in a moment,
+// a 404 or a body that isn't the JSON we expect will be the same next time. So only
+// the first kind goes through the retries (the RetryPolicy of retry_backoff), the
+// others come back at once.
+
+#[derive(Debug)]
+pub enum ApiError {
+    // No answer: can't connect, timed out, the connection dropped...
+    Request(reqwest::Error),
+    // An answer, but not a 2xx
+    Status(StatusCode),
+    // A 2xx, but the body isn't what we asked for
+    Decode(serde_json::Error),
+}
+
+impl ApiError {
+    // Whether trying again could help
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::Request(e) => e.is_timeout() || e.is_connect(),
+            ApiError::Status(status) => status.is_server_error(),
+            ApiError::Decode(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Request(e) => write!(f, "request failed: {}", e),
+            ApiError::Status(status) => write!(f, "the server answered {}", status),
+            ApiError::Decode(e) => write!(f, "unexpected body: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+pub struct ApiClient {
+    base_url: String,
+    // A Client holds a pool of connections, it's meant to be made once and reused
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+}
+
+impl ApiClient {
+    // `timeout` is for every attempt on its own, from connecting to the end of the body
+    pub fn new(base_url: &str, retry_policy: RetryPolicy, timeout: Duration) -> ApiClient {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to build the HTTP client");
+        ApiClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+            retry_policy,
+        }
+    }
+
+    // GET `path` and parse the body as a T
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        // `retry` retries every Err: the errors that aren't worth it are wrapped in an
+        // Ok so they get through on the first try
+        let outcome = retry(&self.retry_policy, || async {
+            match self.get_json_once(&url).await {
+                Err(e) if e.is_retryable() => {
+                    println!("  GET {}: {}", url, e);
+                    Err(e)
+                }
+                other => Ok(other),
+            }
+        })
+        .await;
+        outcome.and_then(|result| result)
+    }
+
+    async fn get_json_once<T: DeserializeOwned>(&self, url: &str) -> Result<T, ApiError> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(ApiError::Request)?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ApiError::Status(status));
+        }
+        // The body comes after the headers, it can time out too
+        let body = response.bytes().await.map_err(ApiError::Request)?;
+        serde_json::from_slice(&body).map_err(ApiError::Decode)
+    }
+}
+
+// A post of https://jsonplaceholder.typicode.com, the other fields (userId) are ignored
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Post {
+    pub id: u32,
+    pub title: String,
+    pub body: String,
+}
+
+pub fn run() {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        let client = ApiClient::new(
+            "https://jsonplaceholder.typicode.com",
+            RetryPolicy::builder().build(),
+            Duration::from_secs(10),
+        );
+        match client.get_json::<Post>("/posts/1").await {
+            Ok(post) => println!("post {}: {}", post.id, post.title),
+            Err(e) => {
+                println!("Failed to fetch the post: {}", e);
+                return;
+            }
+        }
+        match client.get_json::<Vec<Post>>("/posts").await {
+            Ok(posts) => println!("{} posts", posts.len()),
+            Err(e) => println!("Failed to fetch the posts: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // Three attempts with short waits, the mock server answers in no time
+    fn quick_policy() -> RetryPolicy {
+        RetryPolicy::builder()
+            .max_attempts(3)
+            .base_delay(Duration::from_millis(10))
+            .jitter(false)
+            .build()
+    }
+
+    fn client(server: &MockServer) -> ApiClient {
+        ApiClient::new(&server.uri(), quick_policy(), Duration::from_millis(100))
+    }
+
+    async fn requests(server: &MockServer, path: &str) -> usize {
+        let received = server.received_requests().await.unwrap_or_default();
+        received.iter().filter(|r| r.url.path() == path).count()
+    }
+
+    #[tokio::test]
+    async fn mock_server() {
+        let server = MockServer::start().await;
+        let post = serde_json::json!({
+            "userId": 1,
+            "id": 1,
+            "title": "mocked",
+            "body": "from wiremock",
+        });
+        // Mocks are matched in the order they were mounted: two 503s, then the post
+        Mock::given(method("GET"))
+            .and(path("/posts/1"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/posts/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&post))
+            .mount(&server)
+            .await;
+
+        // The 503s are retried, the third attempt gets the post
+        let fetched: Post = client(&server).get_json("/posts/1").await.unwrap();
+        assert_eq!(
+            fetched,
+            Post {
+                id: 1,
+                title: "mocked".to_string(),
+                body: "from wiremock".to_string(),
+            }
+        );
+        assert_eq!(requests(&server, "/posts/1").await, 3);
+    }
+
+    #[tokio::test]
+    async fn a_503_every_time_uses_up_the_attempts() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/down"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let error = client(&server).get_json::<Post>("/down").await.unwrap_err();
+        assert!(matches!(
+            error,
+            ApiError::Status(StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert_eq!(requests(&server, "/down").await, 3);
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_retried() {
+        let server = MockServer::start().await;
+        // Too slow for the client's timeout, every time
+        Mock::given(method("GET"))
+            .and(path("/slow"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .mount(&server)
+            .await;
+
+        let error = client(&server).get_json::<Post>("/slow").await.unwrap_err();
+        assert!(matches!(&error, ApiError::Request(e) if e.is_timeout()));
+        assert_eq!(requests(&server, "/slow").await, 3);
+    }
+
+    #[tokio::test]
+    async fn not_found_and_bad_json_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/broken"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{not json"))
+            .mount(&server)
+            .await;
+        let client = client(&server);
+
+        // wiremock answers 404 when no mock matches
+        let error = client.get_json::<Post>("/missing").await.unwrap_err();
+        assert!(matches!(error, ApiError::Status(StatusCode::NOT_FOUND)));
+        assert!(!error.is_retryable());
+        assert_eq!(requests(&server, "/missing").await, 1);
+
+        let error = client.get_json::<Post>("/broken").await.unwrap_err();
+        assert!(matches!(error, ApiError::Decode(_)));
+        assert!(!error.is_retryable());
+        assert_eq!(requests(&server, "/broken").await, 1);
+    }
+}
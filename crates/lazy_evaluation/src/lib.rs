@@ -0,0 +1,191 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+// Lazy evaluation: a value is only computed when someone asks for it, and only once
+//
+// A closure that takes no arguments is a computation put off until later (a "thunk"):
+// building it costs nothing, calling it does the work. The tools here are all built on
+// that:
+// - Lazy holds the thunk and calls it the first time the value is read, the value is
+//   kept for every read after that
+// - LazyMap is `map` that doesn't call its closure for the items that are skipped over
+// - memoize wraps a function with a cache, every argument is computed only once
+//
+// std has Lazy already (LazyCell, and LazyLock for statics, see regex_demo), this is
+// the same idea written out.
+
+pub struct Lazy<T, F = Box<dyn FnOnce() -> T>> {
+    // Empty until the first `get`
+    cell: OnceCell<T>,
+    // The thunk, taken out (and dropped) when it's called
+    init: Option<F>,
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    pub fn new(f: F) -> Self {
+        Lazy {
+            cell: OnceCell::new(),
+            init: Some(f),
+        }
+    }
+
+    // The value, computed now if it wasn't yet
+    // `&mut self`: a Lazy isn't meant to be shared while it's being computed, so there
+    // is no way for `f` to read the Lazy it's computing
+    pub fn get(&mut self) -> &T {
+        // The fields are captured on their own, `cell` borrowed and `init` mutably
+        self.cell.get_or_init(|| {
+            let init = self
+                .init
+                .take()
+                .expect("the cell is filled once init is taken");
+            init()
+        })
+    }
+
+    pub fn is_evaluated(&self) -> bool {
+        self.cell.get().is_some()
+    }
+}
+
+// Like `iter.map(f)`, `f` is called as the items are taken. The difference is in what
+// isn't taken: Map only knows `next`, so `nth(1000)` and `last()` call `f` on every
+// item on the way there. LazyMap passes them on to the iterator underneath, and `f`
+// is called on the one item that comes out. `count()` calls it on none.
+pub struct LazyMap<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<I: Iterator, F> LazyMap<I, F> {
+    pub fn new(iter: I, f: F) -> Self {
+        LazyMap { iter, f }
+    }
+}
+
+impl<I: Iterator, R, F: FnMut(I::Item) -> R> Iterator for LazyMap<I, F> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        self.iter.next().map(&mut self.f)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<R> {
+        self.iter.nth(n).map(&mut self.f)
+    }
+
+    fn last(mut self) -> Option<R> {
+        self.iter.last().map(&mut self.f)
+    }
+
+    fn count(self) -> usize {
+        self.iter.count()
+    }
+}
+
+// `f` with a cache: the first call with an argument calls `f`, the next ones with the
+// same argument get the same result back without calling it
+// The results are shared (Rc) rather than cloned, they can be anything. The argument
+// is cloned once, to be kept as the key.
+// The cache is only borrowed around the lookup and the insert, not while `f` runs, and
+// it only grows: it's for a small set of arguments coming back again and again.
+pub fn memoize<A, R, F>(f: F) -> impl Fn(A) -> Rc<R>
+where
+    A: Hash + Eq + Clone,
+    F: Fn(A) -> R,
+{
+    let cache: RefCell<HashMap<A, Rc<R>>> = RefCell::new(HashMap::new());
+    move |argument| {
+        if let Some(result) = cache.borrow().get(&argument) {
+            return Rc::clone(result);
+        }
+        let result = Rc::new(f(argument.clone()));
+        cache.borrow_mut().insert(argument, Rc::clone(&result));
+        result
+    }
+}
+
+pub fn run() {
+    // Lazy: nothing happens until the first `get`
+    let calls = Cell::new(0);
+    let mut primes = Lazy::new(|| {
+        calls.set(calls.get() + 1);
+        println!("  computing the primes below 10 000...");
+        (2..10_000_u32)
+            .filter(|n| (2..).take_while(|d| d * d <= *n).all(|d| n % d != 0))
+            .count()
+    });
+    println!("Lazy created, evaluated: {}", primes.is_evaluated());
+    assert_eq!(calls.get(), 0);
+    assert!(!primes.is_evaluated());
+
+    println!("first get: {}", primes.get());
+    println!("second get: {}", primes.get());
+    assert_eq!(*primes.get(), 1229);
+    assert_eq!(calls.get(), 1);
+    assert!(primes.is_evaluated());
+
+    // The default type parameter: a boxed thunk, so Lazies of different closures can
+    // go in the same Vec
+    let mut settings: Vec<Lazy<String>> = vec![
+        Lazy::new(Box::new(|| "from the defaults".to_string())),
+        Lazy::new(Box::new(|| format!("from a file of {} bytes", 42))),
+    ];
+    for setting in &mut settings {
+        println!("setting {}", setting.get());
+    }
+
+    // A Lazy that's never read never runs
+    let never = Lazy::new(|| -> u32 { panic!("never read, never computed") });
+    assert!(!never.is_evaluated());
+
+    // LazyMap against map: the 1000th square, the last one, and how many
+    let squared = Cell::new(0);
+    let square = |n: u64| {
+        squared.set(squared.get() + 1);
+        n * n
+    };
+    assert_eq!((0..10_000).map(square).nth(1000), Some(1_000_000));
+    println!("map: nth(1000) squared {} numbers", squared.get());
+    assert_eq!(squared.get(), 1001);
+
+    squared.set(0);
+    let mut lazy = LazyMap::new(0..10_000, square);
+    assert_eq!(lazy.nth(1000), Some(1_000_000));
+    assert_eq!(lazy.next(), Some(1001 * 1001));
+    println!(
+        "LazyMap: nth(1000) and next() squared {} numbers",
+        squared.get()
+    );
+    assert_eq!(squared.get(), 2);
+
+    squared.set(0);
+    assert_eq!(LazyMap::new(0..10_000, square).last(), Some(9999 * 9999));
+    assert_eq!(LazyMap::new(0..10_000, square).count(), 10_000);
+    assert_eq!(squared.get(), 1);
+    // Taken one by one, it's `map`
+    let all: Vec<u64> = LazyMap::new(1..=4_u64, |n| n * 10).collect();
+    assert_eq!(all, [10, 20, 30, 40]);
+
+    // memoize: one call of the function per different argument
+    let calls = Cell::new(0);
+    let slow_length = memoize(|word: String| {
+        calls.set(calls.get() + 1);
+        println!("  measuring {:?}", word);
+        word.chars().count()
+    });
+    for word in ["lazy", "thunk", "lazy", "lazy", "thunk", "once"] {
+        println!("{} -> {}", word, slow_length(word.to_string()));
+    }
+    assert_eq!(calls.get(), 3);
+    // The same result, not a copy of it
+    let first = slow_length("lazy".to_string());
+    assert!(Rc::ptr_eq(&first, &slow_length("lazy".to_string())));
+    assert_eq!(calls.get(), 3);
+}
@@ -0,0 +1,271 @@
+use std::{collections::HashMap, hash::Hash, marker::PhantomData, ptr::NonNull};
+
+// A Least Recently Used cache keeps at most `cap` entries.
+// When it is full and we insert a new key, the entry that was used the longest time ago is evicted.
+//
+// To do that in O(1) we need two data structures working together:
+// - a HashMap to find an entry by key quickly
+// - a doubly linked list ordering the entries by usage: the head is the most
+//   recently used (MRU) entry and the tail the least recently used (LRU) one
+// Moving an entry to the front of a doubly linked list is O(1) as long as we
+// already have a pointer to its node, and that's what the HashMap gives us.
+//
+//   map: { "a" -> ptr_a, "b" -> ptr_b, "c" -> ptr_c }
+//
+//   head -> [c] <-> [a] <-> [b] <- tail
+//           MRU             LRU
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    prev: Option<NonNull<Node<K, V>>>,
+    next: Option<NonNull<Node<K, V>>>,
+}
+
+pub struct LruCache<K, V> {
+    cap: usize,
+    map: HashMap<K, NonNull<Node<K, V>>>,
+    head: Option<NonNull<Node<K, V>>>,
+    tail: Option<NonNull<Node<K, V>>>,
+    // We own the nodes (they are boxes we allocated), PhantomData tells the
+    // compiler so, which matters for the drop check
+    marker: PhantomData<Box<Node<K, V>>>,
+}
+
+// SAFETY (for every `unsafe` block in this file):
+// - every node is allocated with `Box::new` and turned into a pointer with `Box::leak`,
+//   so the pointers are non-null, aligned and point to a live node until we
+//   free it with `Box::from_raw`
+// - a node is freed exactly once: when it is evicted in `put` or in `Drop`,
+//   and it is removed from both the map and the list before that, so no dangling
+//   pointer is left behind
+// - the list and the map only hold raw pointers, no `&`/`&mut` to a node is ever kept
+//   across calls. Inside a method we only create short-lived references and never two
+//   `&mut` to the same node at the same time, so nothing aliases
+// - all methods that touch the nodes take `&mut self` (except `Drop`, which owns self),
+//   so no other code can access the list while we are rewiring it
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    pub fn new(cap: usize) -> LruCache<K, V> {
+        assert!(cap > 0, "an LRU cache needs room for at least one entry");
+
+        LruCache {
+            cap,
+            map: HashMap::with_capacity(cap),
+            head: None,
+            tail: None,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // Looking up a key counts as "using" it, so the entry moves to the front
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let node = *self.map.get(key)?;
+        self.detach(node);
+        self.push_front(node);
+
+        // SAFETY: see the comment above the impl, the node is alive and
+        // the returned reference is tied to `&mut self`
+        unsafe { Some(&(*node.as_ptr()).value) }
+    }
+
+    pub fn put(&mut self, key: K, value: V) {
+        // The key is already cached: replace the value and mark it as used
+        if let Some(&node) = self.map.get(&key) {
+            // SAFETY: the node is alive, and this is the only reference to it
+            unsafe { (*node.as_ptr()).value = value };
+            self.detach(node);
+            self.push_front(node);
+            return;
+        }
+
+        // Make room by evicting the least recently used entry (the tail)
+        if self.map.len() == self.cap {
+            if let Some(lru) = self.tail {
+                self.detach(lru);
+                // SAFETY: the node was just unlinked, we remove it from the map
+                // below, so after this point nothing points to it anymore
+                let lru = unsafe { Box::from_raw(lru.as_ptr()) };
+                self.map.remove(&lru.key);
+            }
+        }
+
+        let node = Box::new(Node {
+            key: key.clone(),
+            value,
+            prev: None,
+            next: None,
+        });
+        let node = NonNull::from(Box::leak(node));
+        self.push_front(node);
+        self.map.insert(key, node);
+    }
+
+    // Unlink a node from the list, connecting its neighbours to each other
+    fn detach(&mut self, node: NonNull<Node<K, V>>) {
+        // SAFETY: see the comment above the impl
+        unsafe {
+            let prev = (*node.as_ptr()).prev;
+            let next = (*node.as_ptr()).next;
+
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = next,
+                None => self.head = next,
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).prev = prev,
+                None => self.tail = prev,
+            }
+
+            (*node.as_ptr()).prev = None;
+            (*node.as_ptr()).next = None;
+        }
+    }
+
+    // Insert a (detached) node as the new head of the list
+    fn push_front(&mut self, node: NonNull<Node<K, V>>) {
+        // SAFETY: see the comment above the impl
+        unsafe {
+            (*node.as_ptr()).next = self.head;
+            (*node.as_ptr()).prev = None;
+            match self.head {
+                Some(head) => (*head.as_ptr()).prev = Some(node),
+                None => self.tail = Some(node),
+            }
+        }
+        self.head = Some(node);
+    }
+}
+
+// The nodes were leaked on purpose, so we have to free them ourselves
+impl<K, V> Drop for LruCache<K, V> {
+    fn drop(&mut self) {
+        let mut current = self.head.take();
+        while let Some(node) = current {
+            // SAFETY: every node in the list is alive and owned by the cache,
+            // we read `next` before freeing the node
+            let node = unsafe { Box::from_raw(node.as_ptr()) };
+            current = node.next;
+        }
+    }
+}
+
+pub fn run() {
+    let mut cache = LruCache::new(2);
+    cache.put("a", 1);
+    cache.put("b", 2);
+
+    // Using "a" makes "b" the least recently used entry...
+    println!("get a: {:?}", cache.get(&"a"));
+
+    // ...so adding "c" evicts "b"
+    cache.put("c", 3);
+    println!("get b: {:?}", cache.get(&"b"));
+    println!("get c: {:?}", cache.get(&"c"));
+    println!("len: {}", cache.len());
+
+    // With a capacity of one, every new key evicts the previous one
+    let mut tiny = LruCache::new(1);
+    tiny.put(1, "one");
+    tiny.put(2, "two");
+    println!(
+        "capacity-1 cache keeps only the last key: {:?}",
+        tiny.get(&2)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn a_hit_becomes_the_most_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        // "a" was the LRU entry, now "b" is
+        assert_eq!(cache.get(&"a"), Some(&1));
+        cache.put("c", 3);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"c"), Some(&3));
+    }
+
+    #[test]
+    fn the_least_recently_used_is_evicted() {
+        let mut cache = LruCache::new(3);
+        for (i, key) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+            cache.put(key, i);
+        }
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(&2));
+        assert_eq!(cache.get(&"d"), Some(&3));
+        assert_eq!(cache.get(&"e"), Some(&4));
+    }
+
+    #[test]
+    fn putting_a_cached_key_replaces_and_uses_it() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("a", 10);
+        assert_eq!(cache.len(), 2);
+        cache.put("c", 3);
+        assert_eq!(cache.get(&"a"), Some(&10));
+        assert_eq!(cache.get(&"b"), None);
+    }
+
+    #[test]
+    fn a_missing_key_is_none() {
+        let mut cache: LruCache<&str, i32> = LruCache::new(2);
+        assert_eq!(cache.get(&"a"), None);
+        assert!(cache.is_empty());
+        cache.put("a", 1);
+        assert_eq!(cache.get(&"z"), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn capacity_one() {
+        let mut tiny = LruCache::new(1);
+        tiny.put(1, "one");
+        assert_eq!(tiny.get(&1), Some(&"one"));
+        tiny.put(2, "two");
+        assert_eq!(tiny.len(), 1);
+        assert_eq!(tiny.get(&1), None);
+        assert_eq!(tiny.get(&2), Some(&"two"));
+        tiny.put(2, "deux");
+        assert_eq!(tiny.get(&2), Some(&"deux"));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one entry")]
+    fn capacity_zero_panics() {
+        LruCache::<u8, u8>::new(0);
+    }
+
+    #[test]
+    fn values_are_dropped_once() {
+        let value = Rc::new(());
+        let mut cache = LruCache::new(2);
+        for key in 0..5 {
+            cache.put(key, value.clone());
+        }
+        // The evicted ones are gone already
+        assert_eq!(Rc::strong_count(&value), 3);
+        cache.put(4, value.clone());
+        assert_eq!(Rc::strong_count(&value), 3);
+        drop(cache);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,169 @@
+// Declarative macros (macro_rules!) work by pattern matching on tokens.
+// Each arm has a matcher on the left and a transcriber on the right, the first
+// arm that matches the input wins and its transcriber is pasted in place of the call.
+// Every macro here is exported with #[macro_export], which places it at the root of
+// the crate, so other crates can use them with `use macro_rules_demo::map;`
+
+/// Builds a `HashMap` from `key => value` pairs.
+///
+/// Forgetting the `=>` between a key and its value doesn't match any arm:
+///
+/// ```compile_fail
+/// use macro_rules_demo::map;
+///
+/// let ages: std::collections::HashMap<&str, u32> = map!("alice", 30);
+/// ```
+#[macro_export]
+macro_rules! map {
+    // An empty call expands to an empty map
+    () => {
+        ::std::collections::HashMap::new()
+    };
+    // `$(...),+` means "one or more repetitions separated by commas"
+    // and `$(,)?` allows an optional trailing comma
+    //
+    // map!("a" => 1, "b" => 2) expands to:
+    // {
+    //     let mut map = ::std::collections::HashMap::new();
+    //     map.insert("a", 1);
+    //     map.insert("b", 2);
+    //     map
+    // }
+    //
+    // We use the full path `::std::collections::HashMap` so the macro works even
+    // if the caller didn't import HashMap (or has a different HashMap in scope)
+    ($($key:expr => $value:expr),+ $(,)?) => {{
+        let mut map = ::std::collections::HashMap::new();
+        $(
+            map.insert($key, $value);
+        )+
+        map
+    }};
+}
+
+/// Panics with the value and the expected pattern when the value doesn't match.
+///
+/// The second argument must be a pattern, an expression like a function call isn't one:
+///
+/// ```compile_fail
+/// use macro_rules_demo::assert_matches;
+///
+/// assert_matches!(Some(1), Some(1 + 1));
+/// ```
+#[macro_export]
+macro_rules! assert_matches {
+    // `$pattern:pat` accepts anything that can appear on the left side of a match arm
+    // We also accept an optional `if` guard like a real match arm would
+    //
+    // assert_matches!(value, Some(x) if x > 2) expands to:
+    // match value {
+    //     Some(x) if x > 2 => {}
+    //     ref other => panic!(...),
+    // }
+    //
+    // `stringify!` turns the pattern tokens back into a string for the panic message
+    ($value:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
+        match $value {
+            $pattern $(if $guard)? => {}
+            ref other => panic!(
+                "assertion failed: `{:?}` does not match `{}`",
+                other,
+                stringify!($pattern $(if $guard)?)
+            ),
+        }
+    };
+}
+
+/// Applies a closure to the elements of two lists pairwise, producing a `Vec`.
+///
+/// Both lists must have the same number of elements, otherwise the recursion
+/// runs out of matching arms:
+///
+/// ```compile_fail
+/// use macro_rules_demo::zip_with;
+///
+/// let sums = zip_with!(|a, b| a + b; [1, 2, 3], [4, 5]);
+/// ```
+///
+/// And the lists must be wrapped in brackets:
+///
+/// ```compile_fail
+/// use macro_rules_demo::zip_with;
+///
+/// let sums = zip_with!(|a, b| a + b; (1, 2), (3, 4));
+/// ```
+#[macro_export]
+macro_rules! zip_with {
+    // This is the public entry point. We bind the closure to a variable once so it
+    // isn't evaluated again for every element, then start the recursion with an
+    // empty accumulator.
+    //
+    // The arms starting with `@step` are "internal rules": the caller is never
+    // supposed to write `@step` themselves, it is just a marker that can't be
+    // confused with a regular expression.
+    //
+    // zip_with!(f; [1, 2], [3, 4]) expands step by step:
+    // zip_with!(@step f; []; [1, 2]; [3, 4])
+    // zip_with!(@step f; [f(1, 3)]; [2]; [4])
+    // zip_with!(@step f; [f(1, 3), f(2, 4)]; []; [])
+    // vec![f(1, 3), f(2, 4)]
+    ($f:expr; [$($a:expr),* $(,)?], [$($b:expr),* $(,)?]) => {{
+        let f = $f;
+        $crate::zip_with!(@step f; []; [$($a),*]; [$($b),*])
+    }};
+    // Take the head of both lists, apply the function and recurse on the tails
+    (@step $f:ident; [$($out:expr),*]; [$a:expr $(, $rest_a:expr)*]; [$b:expr $(, $rest_b:expr)*]) => {
+        $crate::zip_with!(@step $f; [$($out,)* $f($a, $b)]; [$($rest_a),*]; [$($rest_b),*])
+    };
+    // Both lists are empty, so the accumulator holds every result
+    (@step $f:ident; [$($out:expr),*]; []; []) => {
+        vec![$($out),*]
+    };
+}
+
+// Macro hygiene: identifiers created inside a macro live in their own "syntax context".
+// The `x` declared by this macro can never be confused with an `x` written by the caller,
+// even though they have the same name.
+//
+// with_local_x!(x + 1) expands to something like:
+// {
+//     let x#macro = 2;
+//     (x#caller + 1) * x#macro
+// }
+macro_rules! with_local_x {
+    ($e:expr) => {{
+        let x = 2;
+        $e * x
+    }};
+}
+
+pub fn run() {
+    // map! gives us a literal syntax for HashMaps, like vec! does for vectors
+    let ages = map! {
+        "alice" => 30,
+        "bob" => 25,
+    };
+    println!("ages: {:?}", ages.get("alice"));
+
+    let empty: std::collections::HashMap<u8, u8> = map!();
+    println!("empty map has {} entries", empty.len());
+
+    // assert_matches! lets us check the shape of a value without caring about all of it
+    let parsed: Result<u32, String> = "42".parse::<u32>().map_err(|e| e.to_string());
+    assert_matches!(parsed, Ok(n) if n > 40);
+    println!("parsed value matches `Ok(n) if n > 40`");
+
+    // zip_with! applies the closure to each pair of elements
+    let sums = zip_with!(|a, b| a + b; [1, 2, 3], [10, 20, 30]);
+    println!("sums: {:?}", sums);
+    let labels = zip_with!(|name, n| format!("{}={}", name, n); ["a", "b"], [1, 2]);
+    println!("labels: {:?}", labels);
+
+    // The caller's `x` is 10, the macro's own `x` is 2
+    // If the macro captured our `x` the result would be (10 + 1) * 10 = 110,
+    // but thanks to hygiene it is (10 + 1) * 2 = 22
+    let x = 10;
+    let result = with_local_x!(x + 1);
+    assert_eq!(result, 22);
+    println!("hygiene: with_local_x!(x + 1) = {}", result);
+}
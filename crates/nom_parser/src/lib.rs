@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use nom::{
+    branch::alt,
+    bytes::complete::take_while1,
+    character::complete::{char, line_ending, multispace0, not_line_ending, space0},
+    combinator::{eof, map, value, verify},
+    multi::many0,
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    IResult,
+};
+
+// Parser combinators build a big parser out of tiny ones.
+// With nom, a parser is just a function taking the input and returning an IResult:
+// - Ok((rest, output)): it matched, `rest` is what's left of the input
+// - Err(...): it didn't match (and the caller may try something else)
+// Combinators like `alt`, `pair` or `many0` take parsers and return a new parser.
+//
+// We parse a simple configuration format:
+//
+//   # a comment
+//   name = playground
+//   bars = 20
+
+// A key is made of letters, digits and underscores, at least one of them
+pub fn parse_key(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)
+}
+
+// A value is everything up to the end of the line, without the trailing whitespace
+// `verify` rejects empty values, so `key =` is an error instead of an empty string
+pub fn parse_value(input: &str) -> IResult<&str, &str> {
+    verify(map(not_line_ending, str::trim_end), |value: &str| {
+        !value.is_empty()
+    })(input)
+}
+
+// The end of a line, or the end of the input for the last line
+fn end_of_line(input: &str) -> IResult<&str, &str> {
+    alt((line_ending, eof))(input)
+}
+
+// One line of the file, None for the lines that don't define anything
+pub fn parse_line(input: &str) -> IResult<&str, Option<(&str, &str)>> {
+    // `value` replaces the output of a parser, we don't care about the text of
+    // a blank line or a comment, only that it was one
+    let blank = value(None, pair(space0, line_ending));
+    let comment = value(
+        None,
+        tuple((space0, char('#'), not_line_ending, end_of_line)),
+    );
+
+    // `key = value`, with any amount of spaces around the `=`
+    let entry = map(
+        terminated(
+            preceded(
+                space0,
+                separated_pair(parse_key, delimited(space0, char('='), space0), parse_value),
+            ),
+            end_of_line,
+        ),
+        Some,
+    );
+
+    // `alt` tries each parser in order and returns the first that matches
+    alt((blank, comment, entry))(input)
+}
+
+// `many0` runs `parse_line` until it stops matching. If that happens before the
+// end of the input there's a line we couldn't understand, so we require the end
+// of the input (after some optional trailing whitespace) to make that an error
+// A key defined twice keeps its last value
+pub fn parse_config(
+    input: &str,
+) -> Result<HashMap<String, String>, nom::Err<nom::error::Error<&str>>> {
+    let (_, lines) = terminated(many0(parse_line), pair(multispace0, eof))(input)?;
+
+    Ok(lines
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+pub fn run() {
+    let input = "\
+# The visualizer settings
+# (comments and blank lines are skipped)
+
+name = playground
+bars   =   20   
+source=synthetic
+
+  # indented comment
+window_title = Simple Manual
+";
+
+    let config = parse_config(input).expect("Failed to parse the config");
+
+    // A HashMap has no order, sort the keys so the output is always the same
+    let mut keys: Vec<&String> = config.keys().collect();
+    keys.sort();
+    for key in keys {
+        println!("{} = {:?}", key, config[key]);
+    }
+    assert_eq!(config["bars"], "20");
+    assert_eq!(config["window_title"], "Simple Manual");
+
+    // A key without a value is an error, which tells us where parsing stopped
+    match parse_config("name = playground\nbars =\n") {
+        Ok(config) => println!("unexpectedly parsed {:?}", config),
+        Err(e) => println!("`bars =` is rejected: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn keys_and_values() {
+        assert_eq!(parse_key("bars = 20"), Ok((" = 20", "bars")));
+        assert_eq!(parse_key("window_title2="), Ok(("=", "window_title2")));
+        assert!(parse_key("= 20").is_err());
+        assert_eq!(
+            parse_value("Simple Manual\nnext"),
+            Ok(("\nnext", "Simple Manual"))
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_is_not_part_of_the_value() {
+        assert_eq!(parse_value("20   \t"), Ok(("", "20")));
+        assert_eq!(
+            parse_config("bars = 20   \nname = x \t\n").unwrap(),
+            config(&[("bars", "20"), ("name", "x")])
+        );
+        // After the last line too
+        assert_eq!(
+            parse_config("bars = 20\n\n  \n").unwrap(),
+            config(&[("bars", "20")])
+        );
+    }
+
+    #[test]
+    fn a_missing_value_is_an_error() {
+        assert!(parse_value("").is_err());
+        assert!(parse_value("   \n").is_err());
+        assert!(parse_config("bars =\n").is_err());
+        assert!(parse_config("name = playground\nbars =   ").is_err());
+    }
+
+    #[test]
+    fn consecutive_comments_are_skipped() {
+        let input = "# one\n#two\n  # three\n#\nbars = 20\n# four";
+        assert_eq!(parse_config(input).unwrap(), config(&[("bars", "20")]));
+        assert_eq!(parse_line("# one\nrest"), Ok(("rest", None)));
+    }
+
+    #[test]
+    fn lines_that_are_not_entries_are_errors() {
+        assert!(parse_config("just words\n").is_err());
+        assert!(parse_config("bars = 20\n= 30\n").is_err());
+    }
+
+    #[test]
+    fn the_last_definition_wins() {
+        assert_eq!(
+            parse_config("bars = 20\nbars = 30").unwrap(),
+            config(&[("bars", "30")])
+        );
+        assert_eq!(parse_config("").unwrap(), HashMap::new());
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Watching a directory for changes with notify
+//
+// The operating system reports every little step: saving a file in an editor can be
+// a create, a few writes, a close and a rename, all within a few milliseconds. Nobody
+// wants five events for that, so they are "debounced": the changes to a path are
+// held until it was quiet for DEBOUNCE, and then reported as one.
+//
+// notify hands the raw events to a channel, a thread of ours merges them and sends
+// the debounced ones on.
+
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// What happened to a path, all the raw events put together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Modify,
+    Remove,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebouncedEvent {
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+}
+
+// Which paths we care about
+#[derive(Debug, Clone, Copy)]
+pub struct EventFilter {
+    // Skip the dotfiles, and everything in a dot-directory (.git...)
+    pub ignore_hidden: bool,
+}
+
+impl Default for EventFilter {
+    fn default() -> EventFilter {
+        EventFilter {
+            ignore_hidden: true,
+        }
+    }
+}
+
+impl EventFilter {
+    // `relative` is relative to the directory being watched: the directory itself
+    // may well be hidden (tempfile makes `.tmpXXXX` directories), what's in it isn't
+    pub fn allows(&self, relative: &Path) -> bool {
+        let hidden = relative
+            .components()
+            .any(|part| part.as_os_str().to_string_lossy().starts_with('.'));
+        !(self.ignore_hidden && hidden)
+    }
+}
+
+// Two changes to the same path in a row, as one. None when they cancel out: a file
+// created and removed before anyone was told never existed as far as they know
+pub fn merge(previous: Option<ChangeKind>, next: ChangeKind) -> Option<ChangeKind> {
+    use ChangeKind::*;
+    match (previous, next) {
+        (None, next) => Some(next),
+        // Still new, whatever was written in it
+        (Some(Create), Create | Modify) => Some(Create),
+        (Some(Create), Remove) => None,
+        // Removed and made again: for whoever listens it's the same file, changed
+        (Some(Remove), Create | Modify) => Some(Modify),
+        (Some(Modify), Create | Modify) => Some(Modify),
+        (Some(Modify | Remove), Remove) => Some(Remove),
+    }
+}
+
+// The changes a raw event stands for, the accesses and the like are none
+fn changes(event: &Event) -> Vec<(PathBuf, ChangeKind)> {
+    let all = |kind| {
+        event
+            .paths
+            .iter()
+            .map(|path| (path.clone(), kind))
+            .collect()
+    };
+    match event.kind {
+        notify::EventKind::Create(_) => all(ChangeKind::Create),
+        // A rename is the old name going away and the new one appearing
+        notify::EventKind::Modify(ModifyKind::Name(RenameMode::From)) => all(ChangeKind::Remove),
+        notify::EventKind::Modify(ModifyKind::Name(RenameMode::To)) => all(ChangeKind::Create),
+        notify::EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => match &event.paths[..] {
+            [from, to] => vec![
+                (from.clone(), ChangeKind::Remove),
+                (to.clone(), ChangeKind::Create),
+            ],
+            _ => Vec::new(),
+        },
+        notify::EventKind::Modify(_) => all(ChangeKind::Modify),
+        notify::EventKind::Remove(_) => all(ChangeKind::Remove),
+        notify::EventKind::Access(_) | notify::EventKind::Any | notify::EventKind::Other => {
+            Vec::new()
+        }
+    }
+}
+
+// Watch `path` and everything under it, sending a DebouncedEvent for every change
+// (hidden files left out, see EventFilter)
+//
+// Dropping the watcher stops it: notify drops its end of the raw channel with it, our
+// thread sends what it was still holding and ends, and with it its `tx`. Whoever
+// reads the other end of `tx` then sees the channel close.
+pub fn watch_directory(
+    path: &Path,
+    tx: Sender<DebouncedEvent>,
+) -> notify::Result<RecommendedWatcher> {
+    // The events come with absolute paths, with the symlinks resolved
+    let root = path.canonicalize()?;
+    let (raw_tx, raw_rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(raw_tx)?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    thread::spawn(move || debounce(&root, EventFilter::default(), raw_rx, tx));
+    Ok(watcher)
+}
+
+fn debounce(
+    root: &Path,
+    filter: EventFilter,
+    raw: Receiver<notify::Result<Event>>,
+    tx: Sender<DebouncedEvent>,
+) {
+    // What happened to every path that changed lately, and when it last changed
+    let mut pending: HashMap<PathBuf, (ChangeKind, Instant)> = HashMap::new();
+    loop {
+        // Wait for the next raw event, but not past the moment a path becomes quiet
+        let next_due = pending.values().map(|&(_, at)| at + DEBOUNCE).min();
+        let received = match next_due {
+            Some(due) => raw.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => raw.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let stopped = match received {
+            Ok(Ok(event)) => {
+                let now = Instant::now();
+                for (path, kind) in changes(&event) {
+                    let relative = path.strip_prefix(root).unwrap_or(&path);
+                    if !filter.allows(relative) {
+                        continue;
+                    }
+                    let previous = pending.get(&path).map(|&(kind, _)| kind);
+                    match merge(previous, kind) {
+                        Some(kind) => pending.insert(path, (kind, now)),
+                        None => pending.remove(&path),
+                    };
+                }
+                false
+            }
+            Ok(Err(e)) => {
+                eprintln!("watch error: {}", e);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            // The watcher was dropped
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        // Send the paths that were quiet long enough, or all of them when stopping
+        let now = Instant::now();
+        let mut due: Vec<(PathBuf, ChangeKind, Instant)> = Vec::new();
+        pending.retain(|path, &mut (kind, at)| {
+            let quiet = stopped || now >= at + DEBOUNCE;
+            if quiet {
+                due.push((path.clone(), kind, at));
+            }
+            !quiet
+        });
+        // In the order they changed, not the order of the HashMap
+        due.sort_by_key(|&(_, _, at)| at);
+        for (path, kind, _) in due {
+            if tx.send(DebouncedEvent { kind, path }).is_err() {
+                // Nobody listens anymore
+                return;
+            }
+        }
+        if stopped {
+            return;
+        }
+    }
+}
+
+pub fn run(dir: &Path) {
+    use ChangeKind::*;
+    fs::create_dir_all(dir).unwrap();
+    let (tx, rx) = mpsc::channel();
+    let watcher = watch_directory(dir, tx).unwrap();
+    let root = dir.canonicalize().unwrap();
+    let name = |event: &DebouncedEvent| {
+        let relative = event.path.strip_prefix(&root).unwrap_or(&event.path);
+        relative.display().to_string()
+    };
+
+    // The changes, from another thread, with pauses longer than DEBOUNCE between the
+    // ones that should be reported on their own
+    let written = Instant::now();
+    let writer = {
+        let dir = dir.to_path_buf();
+        thread::spawn(move || {
+            let pause = || thread::sleep(DEBOUNCE * 2);
+            // Created and written to: one Create
+            fs::write(dir.join("a.txt"), "hello").unwrap();
+            fs::write(dir.join("a.txt"), "hello again").unwrap();
+            pause();
+            fs::write(dir.join("a.txt"), "changed").unwrap();
+            // Hidden, and created then removed right away: nothing
+            fs::write(dir.join(".hidden"), "psst").unwrap();
+            fs::write(dir.join("b.txt"), "short-lived").unwrap();
+            fs::remove_file(dir.join("b.txt")).unwrap();
+            pause();
+            fs::remove_file(dir.join("a.txt")).unwrap();
+            fs::remove_file(dir.join(".hidden")).unwrap();
+        })
+    };
+
+    let first = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no event within 1s");
+    println!(
+        "{:?} {} after {:?}",
+        first.kind,
+        name(&first),
+        written.elapsed()
+    );
+    assert_eq!((first.kind, name(&first).as_str()), (Create, "a.txt"));
+    assert!(written.elapsed() < Duration::from_secs(1));
+
+    let mut events = vec![first];
+    writer.join().unwrap();
+    while let Ok(event) = rx.recv_timeout(DEBOUNCE * 3) {
+        println!("{:?} {}", event.kind, name(&event));
+        events.push(event);
+    }
+    let seen: Vec<(ChangeKind, String)> = events.iter().map(|e| (e.kind, name(e))).collect();
+    assert_eq!(
+        seen,
+        [
+            (Create, "a.txt".to_string()),
+            (Modify, "a.txt".to_string()),
+            (Remove, "a.txt".to_string()),
+        ]
+    );
+
+    // Stopping: drop the watcher, the channel closes once the thread has sent what it
+    // still had, there is no thread left behind
+    fs::write(dir.join("c.txt"), "just before the end").unwrap();
+    thread::sleep(Duration::from_millis(50));
+    drop(watcher);
+    let rest: Vec<DebouncedEvent> = rx.iter().collect();
+    println!("stopped, {} more event(s) on the way out", rest.len());
+    fs::remove_file(dir.join("c.txt")).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChangeKind::*;
+
+    #[test]
+    fn changes_to_the_same_path_are_merged() {
+        assert_eq!(merge(None, Modify), Some(Modify));
+        assert_eq!(merge(Some(Create), Modify), Some(Create));
+        assert_eq!(merge(Some(Create), Remove), None);
+        assert_eq!(merge(Some(Remove), Create), Some(Modify));
+        assert_eq!(merge(Some(Modify), Modify), Some(Modify));
+        assert_eq!(merge(Some(Modify), Remove), Some(Remove));
+    }
+
+    #[test]
+    fn hidden_paths_are_filtered_out() {
+        let filter = EventFilter::default();
+        assert!(filter.allows(Path::new("notes/today.txt")));
+        assert!(!filter.allows(Path::new(".git/index")));
+        assert!(!filter.allows(Path::new("notes/.today.txt.swp")));
+        let everything = EventFilter {
+            ignore_hidden: false,
+        };
+        assert!(everything.allows(Path::new(".git/index")));
+    }
+}
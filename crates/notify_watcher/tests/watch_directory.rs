@@ -0,0 +1,53 @@
+// The watcher against a real directory, made fresh for every test
+use std::fs;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify_watcher::{watch_directory, ChangeKind, DebouncedEvent, DEBOUNCE};
+use tempfile::TempDir;
+
+#[test]
+fn a_new_file_is_reported_within_a_second() {
+    let dir = TempDir::new().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let _watcher = watch_directory(dir.path(), tx).unwrap();
+
+    fs::write(dir.path().join("new.txt"), "hello").unwrap();
+
+    let event = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no event within 1s");
+    assert_eq!(event.kind, ChangeKind::Create);
+    assert_eq!(event.path.file_name().unwrap(), "new.txt");
+}
+
+#[test]
+fn hidden_files_are_not_reported() {
+    let dir = TempDir::new().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let _watcher = watch_directory(dir.path(), tx).unwrap();
+
+    fs::write(dir.path().join(".hidden"), "psst").unwrap();
+    fs::write(dir.path().join("visible.txt"), "hi").unwrap();
+
+    let event = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(event.path.file_name().unwrap(), "visible.txt");
+    assert!(rx.recv_timeout(DEBOUNCE * 3).is_err());
+}
+
+#[test]
+fn dropping_the_watcher_closes_the_channel() {
+    let dir = TempDir::new().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let watcher = watch_directory(dir.path(), tx).unwrap();
+    fs::write(dir.path().join("last.txt"), "bye").unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+
+    drop(watcher);
+
+    // What was still held back is sent on the way out, then the channel closes
+    let rest: Vec<DebouncedEvent> = rx.iter().collect();
+    assert!(rest
+        .iter()
+        .all(|e| e.path.file_name().unwrap() == "last.txt"));
+}
@@ -0,0 +1,231 @@
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+// An observer is anything that wants to be told when an event happens
+// The trait is generic over the event type, so an Observer<NetworkEvent> can
+// only ever be subscribed to a bus of NetworkEvents: the compiler checks it for us
+pub trait Observer<E>: Send {
+    fn on_event(&self, event: &E);
+}
+
+// What `subscribe` hands back, to unsubscribe later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionId(u64);
+
+// The event bus keeps a list of observers and forwards every published event to all of them
+// Like in `box_dyn_traits`, we need Box<dyn Observer<E>> to store different observer types in the same Vec
+pub struct EventBus<E> {
+    subscribers: Vec<(SubscriptionId, Box<dyn Observer<E>>)>,
+    // Never reused, so an old id can't unsubscribe somebody else
+    next_id: u64,
+}
+
+impl<E> EventBus<E> {
+    pub fn new() -> EventBus<E> {
+        EventBus {
+            subscribers: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn subscribe(&mut self, observer: Box<dyn Observer<E>>) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, observer));
+        id
+    }
+
+    // Stop sending events to that observer (and drop it), false if it was gone already
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|(subscribed, _)| *subscribed != id);
+        self.subscribers.len() < before
+    }
+
+    pub fn publish(&self, event: &E) {
+        for (_, subscriber) in self.subscribers.iter() {
+            subscriber.on_event(event);
+        }
+    }
+}
+
+impl<E> Default for EventBus<E> {
+    fn default() -> EventBus<E> {
+        EventBus::new()
+    }
+}
+
+// The events our example application cares about
+#[derive(Debug)]
+pub enum NetworkEvent {
+    Connected { ssid: String },
+    Disconnected,
+    SignalChanged { strength: u8 },
+}
+
+// Prints every event it receives
+pub struct Logger;
+
+impl Observer<NetworkEvent> for Logger {
+    fn on_event(&self, event: &NetworkEvent) {
+        println!("[logger] {:?}", event);
+    }
+}
+
+// Counts the events it receives
+// The count lives in an Arc<AtomicU32> so whoever created the Counter can keep a
+// clone and read the value, even after the Counter itself was moved into the bus
+// Since `on_event` only gets `&self` we need the atomic to be able to increment it
+pub struct Counter {
+    count: Arc<AtomicU32>,
+}
+
+impl Counter {
+    pub fn new(count: Arc<AtomicU32>) -> Counter {
+        Counter { count }
+    }
+}
+
+// The Counter doesn't care about the event type, so it can observe any bus
+impl<E> Observer<E> for Counter {
+    fn on_event(&self, _event: &E) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// Wraps a closure so it can subscribe without writing a new struct every time
+// `PhantomData<fn(&E)>` tells the compiler that this type "uses" E without storing one,
+// and using a fn pointer type keeps FnObserver Send no matter what E is
+pub struct FnObserver<E, F: Fn(&E) + Send> {
+    f: F,
+    marker: PhantomData<fn(&E)>,
+}
+
+impl<E, F: Fn(&E) + Send> FnObserver<E, F> {
+    pub fn new(f: F) -> FnObserver<E, F> {
+        FnObserver {
+            f,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E, F: Fn(&E) + Send> Observer<E> for FnObserver<E, F> {
+    fn on_event(&self, event: &E) {
+        (self.f)(event);
+    }
+}
+
+pub fn run() {
+    let mut bus = EventBus::new();
+
+    // The counter is shared: one clone goes into the bus, the other stays here
+    let count = Arc::new(AtomicU32::new(0));
+    bus.subscribe(Box::new(Logger));
+    bus.subscribe(Box::new(Counter::new(count.clone())));
+
+    // A closure observer that only cares about weak signals
+    bus.subscribe(Box::new(FnObserver::new(|event: &NetworkEvent| {
+        if let NetworkEvent::SignalChanged { strength } = event {
+            if *strength < 30 {
+                println!("[closure] weak signal: {}%", strength);
+            }
+        }
+    })));
+
+    bus.publish(&NetworkEvent::Connected {
+        ssid: "my_ssid".to_string(),
+    });
+    for strength in [90, 70, 50, 30, 20, 10, 40, 80] {
+        bus.publish(&NetworkEvent::SignalChanged { strength });
+    }
+    bus.publish(&NetworkEvent::Disconnected);
+
+    let total = count.load(Ordering::SeqCst);
+    println!("counter saw {} events", total);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn counter(bus: &mut EventBus<u32>) -> (SubscriptionId, Arc<AtomicU32>) {
+        let count = Arc::new(AtomicU32::new(0));
+        let id = bus.subscribe(Box::new(Counter::new(count.clone())));
+        (id, count)
+    }
+
+    fn seen(count: &AtomicU32) -> u32 {
+        count.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn publish_reaches_every_subscriber() {
+        let mut bus = EventBus::new();
+        let (_, first) = counter(&mut bus);
+        let (_, second) = counter(&mut bus);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = events.clone();
+        bus.subscribe(Box::new(FnObserver::new(move |event: &u32| {
+            received.lock().unwrap().push(*event);
+        })));
+
+        for event in 1..=10 {
+            bus.publish(&event);
+        }
+        assert_eq!((seen(&first), seen(&second)), (10, 10));
+        assert_eq!(*events.lock().unwrap(), (1..=10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn an_unsubscribed_observer_hears_nothing_more() {
+        let mut bus = EventBus::new();
+        let (first_id, first) = counter(&mut bus);
+        let (_, second) = counter(&mut bus);
+        let (_, third) = counter(&mut bus);
+        bus.publish(&1);
+
+        assert!(bus.unsubscribe(first_id));
+        bus.publish(&2);
+        bus.publish(&3);
+        assert_eq!((seen(&first), seen(&second), seen(&third)), (1, 3, 3));
+        // The bus dropped it, only our clone of the count is left
+        assert_eq!(Arc::strong_count(&first), 1);
+    }
+
+    #[test]
+    fn unsubscribing_twice() {
+        let mut bus = EventBus::new();
+        let (id, _) = counter(&mut bus);
+        let (_, other) = counter(&mut bus);
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        bus.publish(&1);
+        assert_eq!(seen(&other), 1);
+    }
+
+    #[test]
+    fn ids_are_not_reused() {
+        let mut bus = EventBus::new();
+        let (first_id, _) = counter(&mut bus);
+        bus.unsubscribe(first_id);
+        let (second_id, second) = counter(&mut bus);
+        assert_ne!(first_id, second_id);
+        // The old id doesn't remove the new subscriber
+        assert!(!bus.unsubscribe(first_id));
+        bus.publish(&1);
+        assert_eq!(seen(&second), 1);
+    }
+
+    #[test]
+    fn publishing_without_subscribers() {
+        let bus: EventBus<NetworkEvent> = EventBus::default();
+        bus.publish(&NetworkEvent::Disconnected);
+    }
+}
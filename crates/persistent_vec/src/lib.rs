@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+// A persistent vector never changes: `push` and `update` return a new vector and
+// the old one stays exactly as it was, so every version can be kept and shared
+// between threads without locks.
+//
+// Copying everything on each change would be O(n), so instead the values live in the
+// leaves of a tree where each node has up to 32 children (like Clojure's vectors).
+// A change only copies the path from the root to the leaf it touches (at most
+// log32(n) nodes, 4 levels are enough for a million values), every other node is
+// shared with the previous version through an Arc.
+//
+//            root (v1)      root (v2 = v1.update(40, x))
+//            /    \        /    \
+//       leaf 0-31  leaf 32-63'   <- only this leaf and the root are new in v2
+//                \
+//                 leaf 32-63 (still used by v1)
+
+// Each level of the tree uses 5 bits of the index (2^5 = 32 children)
+const BITS: u32 = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+enum Node<T> {
+    Branch(Vec<Arc<Node<T>>>),
+    Leaf(Vec<T>),
+}
+
+pub struct PersistentVec<T: Clone> {
+    root: Arc<Node<T>>,
+    len: usize,
+    // How many bits of the index the levels above the leaves use
+    // 0 when the root is a leaf, 5 with one level of branches, 10 with two...
+    shift: u32,
+}
+
+// Cloning a version is O(1), it just bumps the reference count of the root
+impl<T: Clone> Clone for PersistentVec<T> {
+    fn clone(&self) -> Self {
+        PersistentVec {
+            root: self.root.clone(),
+            len: self.len,
+            shift: self.shift,
+        }
+    }
+}
+
+impl<T: Clone> Default for PersistentVec<T> {
+    fn default() -> Self {
+        PersistentVec::new()
+    }
+}
+
+impl<T: Clone> PersistentVec<T> {
+    pub fn new() -> PersistentVec<T> {
+        PersistentVec {
+            root: Arc::new(Node::Leaf(Vec::new())),
+            len: 0,
+            shift: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // A new version with `val` at the end
+    pub fn push(&self, val: T) -> PersistentVec<T> {
+        // The tree is full: add a level on top, the old tree becomes its first child
+        if self.len == 1 << (self.shift + BITS) {
+            let root = Node::Branch(vec![self.root.clone(), new_path(self.shift, val)]);
+            return PersistentVec {
+                root: Arc::new(root),
+                len: self.len + 1,
+                shift: self.shift + BITS,
+            };
+        }
+
+        PersistentVec {
+            root: push_in(&self.root, self.shift, self.len, val),
+            len: self.len + 1,
+            shift: self.shift,
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        if idx >= self.len {
+            return None;
+        }
+
+        match self.leaf_for(idx) {
+            Node::Leaf(values) => values.get(idx & MASK),
+            Node::Branch(_) => unreachable!("the bottom level only has leaves"),
+        }
+    }
+
+    // A new version where the value at `idx` is `val`
+    // Panics if `idx` is out of bounds, like indexing a Vec
+    pub fn update(&self, idx: usize, val: T) -> PersistentVec<T> {
+        assert!(
+            idx < self.len,
+            "index {} out of bounds (len {})",
+            idx,
+            self.len
+        );
+
+        PersistentVec {
+            root: update_in(&self.root, self.shift, idx, val),
+            len: self.len,
+            shift: self.shift,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).filter_map(|idx| self.get(idx))
+    }
+
+    // Walk down to the leaf holding `idx`
+    // At each level, the bits of the index for that level pick the child to follow
+    fn leaf_for(&self, idx: usize) -> &Node<T> {
+        let mut node = &*self.root;
+        let mut shift = self.shift;
+        while let Node::Branch(children) = node {
+            node = &children[(idx >> shift) & MASK];
+            shift -= BITS;
+        }
+        node
+    }
+}
+
+// A branch of single children down to a leaf holding only `val`
+fn new_path<T>(shift: u32, val: T) -> Arc<Node<T>> {
+    if shift == 0 {
+        Arc::new(Node::Leaf(vec![val]))
+    } else {
+        Arc::new(Node::Branch(vec![new_path(shift - BITS, val)]))
+    }
+}
+
+// Copy the nodes on the way to index `idx` and add `val` in the last leaf
+// The children we don't go through are shared (only their Arc is cloned)
+fn push_in<T: Clone>(node: &Arc<Node<T>>, shift: u32, idx: usize, val: T) -> Arc<Node<T>> {
+    match &**node {
+        Node::Leaf(values) => {
+            let mut values = values.clone();
+            values.push(val);
+            Arc::new(Node::Leaf(values))
+        }
+        Node::Branch(children) => {
+            let mut children = children.clone();
+            let child = (idx >> shift) & MASK;
+            if child < children.len() {
+                children[child] = push_in(&children[child], shift - BITS, idx, val);
+            } else {
+                children.push(new_path(shift - BITS, val));
+            }
+            Arc::new(Node::Branch(children))
+        }
+    }
+}
+
+fn update_in<T: Clone>(node: &Arc<Node<T>>, shift: u32, idx: usize, val: T) -> Arc<Node<T>> {
+    match &**node {
+        Node::Leaf(values) => {
+            let mut values = values.clone();
+            values[idx & MASK] = val;
+            Arc::new(Node::Leaf(values))
+        }
+        Node::Branch(children) => {
+            let mut children = children.clone();
+            let child = (idx >> shift) & MASK;
+            children[child] = update_in(&children[child], shift - BITS, idx, val);
+            Arc::new(Node::Branch(children))
+        }
+    }
+}
+
+pub fn run() {
+    let mut v1 = PersistentVec::new();
+    for i in 0..100 {
+        v1 = v1.push(i);
+    }
+
+    // Each push returned a new version, the one we keep has 100 values in 4 leaves
+    let v2 = v1.push(100);
+    let v3 = v2.update(5, 500);
+
+    println!("v1: {} values, v2: {} values", v1.len(), v2.len());
+    println!(
+        "v1[5] = {:?}, v2[5] = {:?}, v3[5] = {:?}",
+        v1.get(5),
+        v2.get(5),
+        v3.get(5)
+    );
+    assert_eq!(v1.get(5), Some(&5));
+    assert_eq!(v3.get(5), Some(&500));
+    assert_eq!(v1.get(100), None);
+    assert_eq!(v2.get(100), Some(&100));
+
+    // The leaves v1 and v2 have in common are the very same allocation
+    // `ptr::eq` compares addresses: equal means shared, not just equal values
+    let shared = |a: &PersistentVec<i32>, b: &PersistentVec<i32>, idx: usize| {
+        std::ptr::eq(a.leaf_for(idx), b.leaf_for(idx))
+    };
+    for idx in [0, 32, 64, 96] {
+        println!(
+            "leaf of index {:>2}: shared by v1 and v2: {}, by v2 and v3: {}",
+            idx,
+            shared(&v1, &v2, idx),
+            shared(&v2, &v3, idx)
+        );
+    }
+    // The push only copied the last leaf, the update only the first one
+    assert!(shared(&v1, &v2, 0) && !shared(&v1, &v2, 96));
+    assert!(!shared(&v2, &v3, 0) && shared(&v2, &v3, 96));
+
+    // Enough values to need a third level
+    let big = (0..2000).fold(PersistentVec::new(), |v, i| v.push(i));
+    assert!(big.iter().copied().eq(0..2000));
+    println!("2000 values in a tree with {} levels", big.shift / BITS + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec_of(len: usize) -> PersistentVec<usize> {
+        (0..len).fold(PersistentVec::new(), |v, i| v.push(i))
+    }
+
+    #[test]
+    fn get_returns_the_values_of_each_version() {
+        let versions: Vec<_> = (0..=70).map(vec_of).collect();
+        for (len, version) in versions.iter().enumerate() {
+            assert_eq!(version.len(), len);
+            assert!(version.iter().copied().eq(0..len));
+            assert_eq!(version.get(len), None);
+        }
+    }
+
+    #[test]
+    fn pushing_to_b_does_not_change_a() {
+        let a = vec_of(32);
+        let b = a.push(32);
+        assert_eq!(a.len(), 32);
+        assert_eq!(a.get(32), None);
+        assert_eq!(b.get(32), Some(&32));
+        // Two versions pushed from the same one don't see each other
+        let c = a.push(99);
+        assert_eq!((b.get(32), c.get(32)), (Some(&32), Some(&99)));
+    }
+
+    #[test]
+    fn updating_b_does_not_change_a() {
+        let a = vec_of(1100);
+        let b = a.update(0, 1000).update(555, 5000).update(1099, 9000);
+        assert!(a.iter().copied().eq(0..1100));
+        assert_eq!(
+            (b.get(0), b.get(555), b.get(1099)),
+            (Some(&1000), Some(&5000), Some(&9000))
+        );
+        assert_eq!(b.get(554), Some(&554));
+        assert_eq!(b.len(), a.len());
+    }
+
+    // Every level boundary: 32 values fill a leaf, 1024 a branch of leaves
+    #[test]
+    fn the_tree_grows_a_level_when_full() {
+        for len in [31, 32, 33, 1023, 1024, 1025, 32 * 1024 + 1] {
+            let v = vec_of(len);
+            assert!(v.iter().copied().eq(0..len), "len {}", len);
+        }
+        assert_eq!(vec_of(32).shift, 0);
+        assert_eq!(vec_of(33).shift, BITS);
+        assert_eq!(vec_of(1025).shift, 2 * BITS);
+    }
+
+    // Only the path to the changed leaf is copied
+    #[test]
+    fn versions_share_the_leaves_they_did_not_change() {
+        let a = vec_of(100);
+        let b = a.update(40, 0);
+        assert!(std::ptr::eq(a.leaf_for(0), b.leaf_for(0)));
+        assert!(!std::ptr::eq(a.leaf_for(40), b.leaf_for(40)));
+        assert!(std::ptr::eq(a.leaf_for(99), b.leaf_for(99)));
+    }
+
+    #[test]
+    #[should_panic(expected = "index 3 out of bounds (len 3)")]
+    fn updating_past_the_end_panics() {
+        vec_of(3).update(3, 0);
+    }
+}
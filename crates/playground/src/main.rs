@@ -10,15 +10,74 @@ fn main() {
 
     let playground = &args[1];
     match playground.as_str() {
+        // The visualizer is a binary of its own (GTK needs its main thread)
         "relm4_cairo_visualizer" => run_playground("relm4_cairo_visualizer"),
-        // Add other playgrounds here
+        // The others are libraries, their `run` is the demo
+        "actor_model" => actor_model::run(),
+        "async_streams" => async_streams::run(),
+        "atomic_counter" => atomic_counter::run(),
+        "axum_server_demo" => axum_server_demo::run(),
+        "binary_protocol" => binary_protocol::run(),
+        "bloom_filter" => bloom_filter::run(),
+        // A buffer of 1 shows the sender waiting for the receiver on every message
+        "bounded_channel" => bounded_channel::run(1),
+        "box_dyn_traits" => box_dyn_traits::run(box_dyn_traits::Config {
+            weather_service: "wttrin".to_string(),
+        }),
+        "cancellation" => cancellation::run(),
+        "channel_select" => channel_select::run(),
+        "channels_comparison" => channels_comparison::run(),
+        "circuit_breaker" => circuit_breaker::run(),
+        "config_management" => config_management::run(),
+        "csv_parser" => csv_parser::run(),
+        "date_time_demo" => date_time_demo::run(),
+        "dependency_injection" => dependency_injection::run(),
+        "encoding_demo" => encoding_demo::run(),
+        "env_var_expansion" => env_var_expansion::run(),
+        "event_sourcing" => event_sourcing::run(),
+        "futures_combinators" => futures_combinators::run(),
+        "graceful_shutdown" => graceful_shutdown::run(),
+        "hashing_demo" => hashing_demo::run(),
+        "hrtb_demo" => hrtb_demo::run(),
+        "http_client_demo" => http_client_demo::run(),
+        "lazy_evaluation" => lazy_evaluation::run(),
+        "lru_cache" => lru_cache::run(),
+        "macro_rules_demo" => macro_rules_demo::run(),
+        "nom_parser" => nom_parser::run(),
+        "notify_watcher" => notify_watcher::run(&env::temp_dir().join("playground-notify-watcher")),
+        "observer_pattern" => observer_pattern::run(),
+        "persistent_vec" => persistent_vec::run(),
+        "proc_macro_derive_usage" => proc_macro_derive_usage::run(),
+        "random_demo" => random_demo::run(),
+        "rate_limiter" => rate_limiter::run(),
+        "rayon_demo" => rayon_demo::run(),
+        "regex_demo" => regex_demo::run(),
+        "retry_backoff" => retry_backoff::run(),
+        "ring_buffer" => ring_buffer::run(),
+        "skip_list" => skip_list::run(),
+        "slab_allocator" => slab_allocator::run(),
+        "sqlite_demo" => sqlite_demo::run(),
+        "state_machine" => state_machine::run(),
+        "strategy_pattern" => strategy_pattern::run(),
+        "structured_logging" => structured_logging::run(),
+        "tcp_async" => tcp_async::run(),
+        "tcp_echo_server" => tcp_echo_server::run(),
+        "trait_bounds" => trait_bounds::run(),
+        "trait_enums" => trait_enums::run(),
+        "trie" => trie::run(),
+        "type_erasure" => type_erasure::run(),
+        "unicode_demo" => unicode_demo::run(),
+        "unsafe_pointers" => unsafe_pointers::run(),
+        "visitor_pattern" => visitor_pattern::run(),
+        "waker_executor" => waker_executor::run(),
+        "zero_cost" => zero_cost::run(),
         _ => println!("Unknown playground: {}", playground),
     }
 }
 
 fn run_playground(crate_name: &str) {
     let status = Command::new("cargo")
-        .args(&["run", "-p", crate_name])
+        .args(["run", "-p", crate_name])
         .status()
         .expect("Failed to execute playground");
 
@@ -0,0 +1,146 @@
+use proc_macro::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, LitStr};
+
+// A derive macro is a function from tokens to tokens: the compiler hands us the
+// tokens of the item `#[derive(Describe)]` is on, and pastes whatever we return
+// right after it. `syn` parses the input tokens into a syntax tree and `quote!`
+// turns Rust-looking code back into tokens.
+//
+// For
+//
+//   #[derive(Describe)]
+//   struct Point { x: i32, #[describe(rename = "y_axis")] y: i32 }
+//
+// we generate
+//
+//   impl Describe for Point {
+//       fn describe() -> String {
+//           String::from("Point { x: i32, y_axis: i32 }")
+//       }
+//   }
+//
+// The description is built while compiling, the generated function just returns it.
+// Proc macro crates can only export macros, so the `Describe` trait itself lives in
+// the crate using the macro and has to be in scope where the derive is used.
+//
+// `attributes(describe)` tells the compiler `#[describe(...)]` belongs to us,
+// otherwise it would complain about an unknown attribute on the fields
+#[proc_macro_derive(Describe, attributes(describe))]
+pub fn derive_describe(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match describe(&input) {
+        Ok(tokens) => tokens.into(),
+        // A syn::Error becomes a `compile_error!` pointing at the tokens it was
+        // created with, so the user sees the message right on their code
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn describe(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    let Data::Struct(data) = &input.data else {
+        let kind = match input.data {
+            Data::Enum(_) => "an enum",
+            _ => "a union",
+        };
+        return Err(syn::Error::new_spanned(
+            name,
+            format!(
+                "#[derive(Describe)] only works on structs, `{}` is {}",
+                name, kind
+            ),
+        ));
+    };
+
+    // Each kind of struct is described the way it's written
+    let description = match &data.fields {
+        // struct Point { x: i32, y: i32 } -> "Point { x: i32, y: i32 }"
+        Fields::Named(fields) => {
+            let fields = fields
+                .named
+                .iter()
+                .map(|field| {
+                    let field_name = match rename(field)? {
+                        Some(renamed) => renamed,
+                        // Named fields always have an ident
+                        None => field.ident.as_ref().unwrap().to_string(),
+                    };
+                    Ok(format!("{}: {}", field_name, type_name(field)))
+                })
+                .collect::<syn::Result<Vec<String>>>()?;
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        // struct Meters(f64) -> "Meters(f64)", a renamed field gets a name: "Meters(value: f64)"
+        Fields::Unnamed(fields) => {
+            let fields = fields
+                .unnamed
+                .iter()
+                .map(|field| {
+                    Ok(match rename(field)? {
+                        Some(renamed) => format!("{}: {}", renamed, type_name(field)),
+                        None => type_name(field),
+                    })
+                })
+                .collect::<syn::Result<Vec<String>>>()?;
+            format!("{}({})", name, fields.join(", "))
+        }
+        // struct Marker; -> "Marker"
+        Fields::Unit => name.to_string(),
+    };
+
+    // Generic structs work too, we just copy their generics into the impl
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics Describe for #name #ty_generics #where_clause {
+            fn describe() -> String {
+                String::from(#description)
+            }
+        }
+    })
+}
+
+// The name from `#[describe(rename = "...")]`, if the field has one
+fn rename(field: &Field) -> syn::Result<Option<String>> {
+    let mut renamed = None;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("describe") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                let value: LitStr = meta.value()?.parse()?;
+                renamed = Some(value.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown describe attribute, expected `rename = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(renamed)
+}
+
+// Printing tokens puts a space between every one of them (`Vec < String >`), so we
+// glue back the ones that are written without spaces
+fn type_name(field: &Field) -> String {
+    let mut name = field.ty.to_token_stream().to_string();
+    for (spaced, glued) in [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ::", "::"),
+        (":: ", "::"),
+        ("& ", "&"),
+        (" ,", ","),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+        (" ;", ";"),
+    ] {
+        name = name.replace(spaced, glued);
+    }
+    name
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+// The derive macro lives in its own crate (proc macros must), this one uses it
+// The trait and the macro have the same name, that's fine: one is a type and the
+// other a macro, they live in different namespaces (serde does the same with Serialize)
+use proc_macro_derive::Describe;
+
+// What `#[derive(Describe)]` implements: a description of the type's fields and their types
+pub trait Describe {
+    fn describe() -> String;
+}
+
+#[derive(Describe)]
+pub struct User {
+    pub name: String,
+    // The description uses the name from the attribute instead of the field's
+    #[describe(rename = "e-mail")]
+    pub email: Option<String>,
+    pub scores: HashMap<String, Vec<u32>>,
+}
+
+#[derive(Describe)]
+pub struct Rgb(pub u8, pub u8, #[describe(rename = "blue")] pub u8);
+
+#[derive(Describe)]
+pub struct Marker;
+
+// Generic structs get a generic impl
+#[derive(Describe)]
+pub struct Labeled<'a, T> {
+    pub label: &'a str,
+    pub value: T,
+}
+
+pub fn run() {
+    let descriptions = [
+        User::describe(),
+        Rgb::describe(),
+        Marker::describe(),
+        Labeled::<f64>::describe(),
+    ];
+    for description in &descriptions {
+        println!("{}", description);
+    }
+
+    assert_eq!(
+        descriptions[0],
+        "User { name: String, e-mail: Option<String>, scores: HashMap<String, Vec<u32>> }"
+    );
+    assert_eq!(descriptions[1], "Rgb(u8, u8, blue: u8)");
+    assert_eq!(descriptions[2], "Marker");
+    // The description is about the definition, so it shows `T`, not the type we picked
+    assert_eq!(descriptions[3], "Labeled { label: &'a str, value: T }");
+}
@@ -0,0 +1,7 @@
+// Every file in tests/ui must fail to compile with exactly the error in the
+// .stderr file next to it. Run with TRYBUILD=overwrite to regenerate them
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
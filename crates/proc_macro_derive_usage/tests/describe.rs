@@ -0,0 +1,49 @@
+// What the derive generates for every kind of struct
+use proc_macro_derive_usage::{Describe, Labeled, Marker, Rgb, User};
+
+#[test]
+fn named_fields() {
+    assert_eq!(
+        User::describe(),
+        "User { name: String, e-mail: Option<String>, scores: HashMap<String, Vec<u32>> }"
+    );
+}
+
+#[test]
+fn tuple_fields() {
+    assert_eq!(Rgb::describe(), "Rgb(u8, u8, blue: u8)");
+}
+
+#[test]
+fn unit_struct() {
+    assert_eq!(Marker::describe(), "Marker");
+}
+
+// The description is about the definition, whatever T is
+#[test]
+fn generics_are_kept() {
+    assert_eq!(
+        Labeled::<f64>::describe(),
+        "Labeled { label: &'a str, value: T }"
+    );
+    assert_eq!(Labeled::<u8>::describe(), Labeled::<String>::describe());
+}
+
+// Arrays, tuples, paths and references come out the way they're written
+#[derive(proc_macro_derive::Describe)]
+#[allow(dead_code)]
+struct Shapes<'a> {
+    grid: [[u8; 3]; 3],
+    pair: (i32, &'a mut String),
+    path: std::path::PathBuf,
+    callback: fn(u32) -> bool,
+}
+
+#[test]
+fn types_are_printed_without_extra_spaces() {
+    assert_eq!(
+        Shapes::describe(),
+        "Shapes { grid: [[u8; 3]; 3], pair: (i32, &'a mut String), \
+         path: std::path::PathBuf, callback: fn(u32) -> bool }"
+    );
+}
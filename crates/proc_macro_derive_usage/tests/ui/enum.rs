@@ -0,0 +1,10 @@
+use proc_macro_derive::Describe;
+
+// The derive refuses enums (and unions) with an error pointing at the name
+#[derive(Describe)]
+enum Direction {
+    Up,
+    Down,
+}
+
+fn main() {}
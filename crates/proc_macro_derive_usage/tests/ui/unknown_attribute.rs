@@ -0,0 +1,10 @@
+use proc_macro_derive::Describe;
+
+// Only `rename` is known, anything else points at the attribute
+#[derive(Describe)]
+struct User {
+    #[describe(skip)]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,128 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rand_distr::{Distribution, Normal, Uniform};
+
+// `rand` splits random numbers in two parts:
+//
+// - a generator (`Rng`) produces random bits. `rand::rng()` is a generator per thread,
+//   seeded from the operating system, different on every run. `StdRng::seed_from_u64`
+//   is one we seed ourselves: the same seed always gives the same numbers, which is what
+//   tests, simulations and replays need.
+// - a distribution (`Distribution`) turns those bits into values with a given shape:
+//   every number of a range equally likely (`Uniform`), a bell curve (`Normal`)...
+//
+// The functions take `&mut impl Rng`, so the caller chooses: seeded or not.
+//
+// `StdRng` is a good generator, but which algorithm it uses may change between
+// versions of rand. For numbers that must stay the same forever, pick one by name
+// (rand_chacha's ChaCha12Rng for instance).
+
+// `count` rolls of a die with `sides` sides, each from 1 to `sides`
+pub fn roll_dice(rng: &mut impl Rng, sides: u32, count: u32) -> Vec<u32> {
+    // `rng.random_range(1..=sides)` works too, but builds the distribution on every call.
+    // `Uniform` does the setup once, and avoids the bias of `random % sides`: when
+    // 2^32 isn't a multiple of `sides`, the lowest values would come up more often.
+    let die = Uniform::new_inclusive(1, sides).expect("a die needs at least one side");
+    die.sample_iter(rng).take(count as usize).collect()
+}
+
+// `n` values from a normal distribution: most of them close to `mean`, about 68% less
+// than `std_dev` away from it and 95% less than two `std_dev` away
+pub fn sample_normal(rng: &mut impl Rng, mean: f64, std_dev: f64, n: usize) -> Vec<f64> {
+    let normal = Normal::new(mean, std_dev).expect("the standard deviation must be finite");
+    normal.sample_iter(rng).take(n).collect()
+}
+
+// The 52 cards, 0 to 51, in a random order
+// `shuffle` is a Fisher-Yates shuffle: every one of the 52! orders is equally likely
+pub fn shuffle_deck(rng: &mut impl Rng) -> Vec<u8> {
+    let mut deck: Vec<u8> = (0..52).collect();
+    deck.shuffle(rng);
+    deck
+}
+
+// The name of a card of `shuffle_deck`: 0 is the ace of spades, 51 the king of clubs
+pub fn card_name(card: u8) -> String {
+    const RANKS: [&str; 13] = [
+        "A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K",
+    ];
+    const SUITS: [char; 4] = ['♠', '♥', '♦', '♣'];
+    format!("{}{}", RANKS[card as usize % 13], SUITS[card as usize / 13])
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    variance.sqrt()
+}
+
+// Is `value` within 5% of `expected`?
+fn close_to(value: f64, expected: f64) -> bool {
+    (value - expected).abs() <= expected.abs() * 0.05
+}
+
+pub fn run() {
+    // Reproducible: two generators with the same seed give the same numbers
+    let mut a = StdRng::seed_from_u64(42);
+    let mut b = StdRng::seed_from_u64(42);
+    let rolls = roll_dice(&mut a, 6, 10);
+    println!("10 dice with seed 42: {:?}", rolls);
+    assert_eq!(rolls, roll_dice(&mut b, 6, 10));
+    assert_eq!(
+        sample_normal(&mut a, 0.0, 1.0, 5),
+        sample_normal(&mut b, 0.0, 1.0, 5)
+    );
+    assert_eq!(shuffle_deck(&mut a), shuffle_deck(&mut b));
+    // Another seed, other numbers
+    let mut c = StdRng::seed_from_u64(7);
+    assert_ne!(
+        roll_dice(&mut c, 6, 10),
+        roll_dice(&mut StdRng::seed_from_u64(42), 6, 10)
+    );
+
+    // Not reproducible: seeded by the OS, different every time the program runs
+    // (it was called `rand::thread_rng()` before rand 0.9)
+    let mut rng = rand::rng();
+    println!("10 dice, any seed:    {:?}", roll_dice(&mut rng, 6, 10));
+
+    // Dice: every value in range, and on average (1 + 6) / 2 = 3.5
+    let rolls = roll_dice(&mut rng, 6, 100_000);
+    assert!(rolls.iter().all(|roll| (1..=6).contains(roll)));
+    let rolls_mean = mean(&rolls.iter().map(|&roll| roll as f64).collect::<Vec<_>>());
+    println!("mean of 100000 d6:  {:.3}", rolls_mean);
+    assert!(close_to(rolls_mean, 3.5));
+    // Every face comes up, about a sixth of the time
+    for face in 1..=6 {
+        let share = rolls.iter().filter(|&&roll| roll == face).count() as f64 / 1e5;
+        assert!(close_to(share, 1.0 / 6.0), "{} came up {:.3}", face, share);
+    }
+    assert_eq!(roll_dice(&mut rng, 1, 3), [1, 1, 1]);
+
+    // Normal: IQ-like scores, mean 100 and standard deviation 15
+    let scores = sample_normal(&mut rng, 100.0, 15.0, 100_000);
+    let (scores_mean, scores_std_dev) = (mean(&scores), std_dev(&scores));
+    println!(
+        "normal(100, 15):    mean {:.2}, std dev {:.2}",
+        scores_mean, scores_std_dev
+    );
+    assert!(close_to(scores_mean, 100.0));
+    assert!(close_to(scores_std_dev, 15.0));
+    let within_one = scores
+        .iter()
+        .filter(|s| (85.0..=115.0).contains(*s))
+        .count();
+    assert!(close_to(within_one as f64 / 1e5, 0.6827));
+
+    // Cards: a shuffled deck still has every card exactly once
+    let deck = shuffle_deck(&mut rng);
+    let hand: Vec<String> = deck[..5].iter().map(|&card| card_name(card)).collect();
+    println!("a hand of five:     {}", hand.join(" "));
+    let mut sorted = deck.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..52).collect::<Vec<u8>>());
+    assert_eq!(card_name(0), "A♠");
+    assert_eq!(card_name(51), "K♣");
+}
@@ -0,0 +1,362 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+// Two ways to say "no more than N requests per second":
+//
+// - token bucket: a bucket holds up to `capacity` tokens and refills at a steady rate,
+//   every request takes a token. A quiet client saves up tokens and can then send a
+//   burst of up to `capacity` requests at once, the long run average stays at the rate.
+// - sliding window: remember when the last requests happened, and allow a new one
+//   only if fewer than `limit` happened during the last `window`. No bursts beyond the
+//   limit, ever, but it keeps a timestamp per allowed request.
+//
+// Both take the current time as a parameter in their `_at` versions: the other
+// methods use Instant::now(), the `_at` ones let us replay made up arrival times and
+// get the same answer every time.
+
+pub struct TokenBucket {
+    capacity: u64,
+    tokens: f64,
+    // Tokens per second
+    refill_rate: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    // The bucket starts full
+    pub fn new(capacity: u64, refill_rate: f64) -> TokenBucket {
+        TokenBucket::new_at(capacity, refill_rate, Instant::now())
+    }
+
+    pub fn new_at(capacity: u64, refill_rate: f64, now: Instant) -> TokenBucket {
+        TokenBucket {
+            capacity,
+            tokens: capacity as f64,
+            refill_rate,
+            last_refill: now,
+        }
+    }
+
+    pub fn try_consume(&mut self, tokens: u64) -> bool {
+        self.try_consume_at(tokens, Instant::now())
+    }
+
+    // All or nothing: if there aren't enough tokens, none are taken
+    pub fn try_consume_at(&mut self, tokens: u64, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= tokens as f64 {
+            self.tokens -= tokens as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    // How long until `tokens` tokens are available, zero if they are now
+    pub fn wait_time_at(&mut self, tokens: u64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = (tokens as f64 - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing / self.refill_rate)
+    }
+
+    // There is no timer adding tokens: we work out how many would have been added
+    // since the last time we looked. Partial tokens count, a request just waits until
+    // they add up to a whole one.
+    fn refill(&mut self, now: Instant) {
+        // `saturating_duration_since`: a `now` older than the last refill adds nothing
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.refill_rate).min(self.capacity as f64);
+        self.last_refill = self.last_refill.max(now);
+    }
+}
+
+pub struct SlidingWindowCounter {
+    window: Duration,
+    // When the allowed requests of the last `window` happened, oldest first
+    requests: VecDeque<Instant>,
+    limit: usize,
+}
+
+impl SlidingWindowCounter {
+    pub fn new(limit: usize, window: Duration) -> SlidingWindowCounter {
+        SlidingWindowCounter {
+            window,
+            requests: VecDeque::with_capacity(limit),
+            limit,
+        }
+    }
+
+    pub fn is_allowed(&mut self) -> bool {
+        self.is_allowed_at(Instant::now())
+    }
+
+    // A rejected request isn't remembered: a client that keeps retrying doesn't push
+    // its next allowed request further away
+    pub fn is_allowed_at(&mut self, now: Instant) -> bool {
+        // Forget the requests that left the window, exactly `window` old is out
+        if let Some(start) = now.checked_sub(self.window) {
+            while self.requests.front().is_some_and(|&time| time <= start) {
+                self.requests.pop_front();
+            }
+        }
+        if self.requests.len() < self.limit {
+            self.requests.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// A token bucket shared by many tasks
+// tokio's Mutex can be held across an `.await`, which `acquire` does while it sleeps
+// (std's Mutex can't: its guard isn't Send, and it would block the executor thread).
+#[derive(Clone)]
+pub struct AsyncRateLimiter {
+    bucket: Arc<Mutex<TokenBucket>>,
+}
+
+impl AsyncRateLimiter {
+    pub fn new(capacity: u64, refill_rate: f64) -> AsyncRateLimiter {
+        AsyncRateLimiter {
+            bucket: Arc::new(Mutex::new(TokenBucket::new(capacity, refill_rate))),
+        }
+    }
+
+    // Take a token if there is one, don't wait
+    pub async fn try_acquire(&self) -> bool {
+        self.bucket.lock().await.try_consume(1)
+    }
+
+    // Wait until there is a token, and take it
+    // Holding the lock while sleeping makes the waiting tasks queue up: tokio's Mutex
+    // is fair, they get their token in the order they asked
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        loop {
+            let wait = bucket.wait_time_at(1, Instant::now());
+            if wait.is_zero() && bucket.try_consume(1) {
+                return;
+            }
+            tokio::time::sleep(wait.max(Duration::from_millis(1))).await;
+        }
+    }
+}
+
+// Made up arrival times: `count` requests with gaps between 0 and 200ms
+// A tiny xorshift generator, so the times are irregular but the same on every run
+fn arrivals(start: Instant, count: usize) -> Vec<Instant> {
+    let mut state: u32 = 0x2545_f491;
+    let mut time = start;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            time += Duration::from_millis((state % 200) as u64);
+            time
+        })
+        .collect()
+}
+
+pub fn run() {
+    let start = Instant::now();
+
+    // 100 requests arriving at irregular times, about 10 per second on average,
+    // against limits of 5 per second
+    let times = arrivals(start, 100);
+    let mut bucket = TokenBucket::new_at(5, 5.0, start);
+    let mut window = SlidingWindowCounter::new(5, Duration::from_secs(1));
+    let by_bucket = times
+        .iter()
+        .filter(|&&time| bucket.try_consume_at(1, time))
+        .count();
+    let by_window = times
+        .iter()
+        .filter(|&&time| window.is_allowed_at(time))
+        .count();
+    let seconds = (*times.last().unwrap() - start).as_secs_f64();
+    println!("100 requests over {:.1}s, 5 per second allowed:", seconds);
+    println!(
+        "  token bucket:   {} allowed, {} rejected",
+        by_bucket,
+        100 - by_bucket
+    );
+    println!(
+        "  sliding window: {} allowed, {} rejected",
+        by_window,
+        100 - by_window
+    );
+
+    // Shared between tasks
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        // 20 tasks want a token, the bucket has 5 and makes 20 per second
+        let limiter = AsyncRateLimiter::new(5, 20.0);
+        let started = Instant::now();
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        println!(
+            "20 tasks waiting for a token: done after {:.2?}",
+            started.elapsed()
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    // The arrivals are the same on every run, so are the counts
+    #[test]
+    fn irregular_arrivals() {
+        let start = Instant::now();
+        let times = arrivals(start, 100);
+        let mut bucket = TokenBucket::new_at(5, 5.0, start);
+        let mut window = SlidingWindowCounter::new(5, Duration::from_secs(1));
+
+        let by_bucket = times
+            .iter()
+            .filter(|&&time| bucket.try_consume_at(1, time))
+            .count();
+        let by_window = times
+            .iter()
+            .filter(|&&time| window.is_allowed_at(time))
+            .count();
+        // The bucket lets more through: it saved up tokens during the quiet stretches
+        assert_eq!(by_bucket, 51);
+        assert_eq!(by_window, 45);
+        // Never more than the initial burst plus what was refilled meanwhile
+        let seconds = (*times.last().unwrap() - start).as_secs_f64();
+        assert!(by_bucket as f64 <= 5.0 + seconds * 5.0);
+    }
+
+    #[test]
+    fn a_full_bucket_lets_a_burst_through() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(5, 1.0, start);
+        let allowed = (0..10).filter(|_| bucket.try_consume_at(1, start)).count();
+        assert_eq!(allowed, 5);
+    }
+
+    #[test]
+    fn partial_tokens_add_up() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(5, 1.0, start);
+        assert!(bucket.try_consume_at(5, start));
+
+        // 2.5s later 2.5 tokens came back: 2 more requests, the half token stays
+        let later = millis(start, 2500);
+        let allowed = (0..3).filter(|_| bucket.try_consume_at(1, later)).count();
+        assert_eq!(allowed, 2);
+        // ...and becomes a whole one 500ms after that
+        assert!(!bucket.try_consume_at(1, millis(start, 2999)));
+        assert!(bucket.try_consume_at(1, millis(start, 3000)));
+    }
+
+    #[test]
+    fn the_bucket_never_holds_more_than_its_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(5, 1.0, start);
+        assert!(!bucket.try_consume_at(6, millis(start, 60_000)));
+        assert!(bucket.try_consume_at(5, millis(start, 60_000)));
+        assert_eq!(
+            bucket.wait_time_at(1, millis(start, 60_000)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            bucket.wait_time_at(1, millis(start, 61_000)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn an_earlier_time_adds_no_tokens() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(1, 1.0, millis(start, 1000));
+        assert!(bucket.try_consume_at(1, millis(start, 1000)));
+        assert!(!bucket.try_consume_at(1, start));
+        assert!(bucket.try_consume_at(1, millis(start, 2000)));
+    }
+
+    // 3 per second: the 4th request within a second is rejected
+    #[test]
+    fn the_sliding_window() {
+        let start = Instant::now();
+        let mut window = SlidingWindowCounter::new(3, Duration::from_secs(1));
+        let results: Vec<bool> = [0, 100, 200, 300, 999, 1000, 1050, 1100, 1200]
+            .iter()
+            .map(|&ms| window.is_allowed_at(millis(start, ms)))
+            .collect();
+        // At 1000 the request of 0 left the window, at 1100 the one of 100, at 1200 the
+        // one of 200 did, but 1000 and 1100 took their places
+        assert_eq!(
+            results,
+            [true, true, true, false, false, true, false, true, true]
+        );
+    }
+
+    #[test]
+    fn rejected_requests_are_not_remembered() {
+        let start = Instant::now();
+        let mut window = SlidingWindowCounter::new(1, Duration::from_secs(1));
+        assert!(window.is_allowed_at(start));
+        for ms in (100..1000).step_by(100) {
+            assert!(!window.is_allowed_at(millis(start, ms)));
+        }
+        assert!(window.is_allowed_at(millis(start, 1000)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn tasks_share_the_tokens() {
+        // 20 tasks want a token, the bucket has 5 and makes 1 per second
+        let limiter = AsyncRateLimiter::new(5, 1.0);
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.try_acquire().await })
+            })
+            .collect();
+        let mut allowed = 0;
+        for task in tasks {
+            if task.await.unwrap() {
+                allowed += 1;
+            }
+        }
+        assert_eq!(allowed, 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn waiting_tasks_get_through_at_the_rate() {
+        // 5 right away, the 10 others at 50 per second
+        let limiter = AsyncRateLimiter::new(5, 50.0);
+        let started = Instant::now();
+        let tasks: Vec<_> = (0..15)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(190));
+    }
+}
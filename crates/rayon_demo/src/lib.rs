@@ -0,0 +1,197 @@
+use std::{collections::HashMap, time::Instant};
+
+use rayon::prelude::*;
+
+// Rayon turns iterator chains into parallel ones: `iter()` becomes `par_iter()` and the
+// work is split between a pool of threads (one per CPU core by default).
+// It uses "work stealing": each thread has a queue of jobs, and a thread that runs
+// out of work steals half of the jobs of a busy one, so the load stays balanced
+// even when some items take longer than others.
+// Parallelism isn't free though, splitting the work and joining the results has a
+// cost, so it only pays off when there is enough work to share.
+
+// Each thread sums a chunk of the slice, then the partial sums are added together
+// Note: floating point addition isn't associative, so the result can differ from
+// the sequential sum in the last digits depending on how the work was split
+pub fn parallel_sum(data: &[f64]) -> f64 {
+    data.par_iter().sum()
+}
+
+// Counts how often each character appears in all the strings
+//
+// - `flat_map` turns the strings into one parallel stream of characters
+// - `fold` gives every thread its own HashMap to count into, so the threads never
+//   fight over a shared map (no locks needed). `HashMap::new` is called to create
+//   the starting map for each piece of work
+// - `reduce` merges those maps two by two until only one is left
+pub fn parallel_map_reduce(data: &[String]) -> HashMap<char, usize> {
+    data.par_iter()
+        .flat_map(|line| line.par_chars())
+        .fold(HashMap::new, |mut counts, c| {
+            *counts.entry(c).or_insert(0) += 1;
+            counts
+        })
+        .reduce(HashMap::new, merge_maps)
+}
+
+// Adds the counts of `b` into `a`
+// We always iterate over the smaller map, that's less work for the same result
+fn merge_maps(a: HashMap<char, usize>, b: HashMap<char, usize>) -> HashMap<char, usize> {
+    let (mut big, small) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    for (c, count) in small {
+        *big.entry(c).or_insert(0) += count;
+    }
+    big
+}
+
+// A tiny xorshift pseudo random generator, good enough to get data to sort
+// without pulling in the `rand` crate
+fn random_numbers(count: usize, mut seed: u64) -> Vec<u64> {
+    (0..count)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        })
+        .collect()
+}
+
+pub fn sort_comparison(n: usize) {
+    let data = random_numbers(n, 0x9e37_79b9_7f4a_7c15);
+
+    let mut sequential = data.clone();
+    let start = Instant::now();
+    sequential.sort_unstable();
+    let sequential_elapsed = start.elapsed();
+
+    // par_sort_unstable is a parallel quicksort: after partitioning, both halves
+    // are sorted at the same time on different threads
+    let mut parallel = data;
+    let start = Instant::now();
+    parallel.par_sort_unstable();
+    let parallel_elapsed = start.elapsed();
+
+    assert_eq!(sequential, parallel);
+    println!("sorting {} numbers:", n);
+    println!("  sort_unstable:     {:?}", sequential_elapsed);
+    println!("  par_sort_unstable: {:?}", parallel_elapsed);
+}
+
+pub fn run() {
+    println!("rayon is using {} threads", rayon::current_num_threads());
+
+    // Summing
+    let data: Vec<f64> = (0..10_000_000).map(|i| (i % 1000) as f64 * 0.001).collect();
+
+    let start = Instant::now();
+    let sequential: f64 = data.iter().sum();
+    let sequential_elapsed = start.elapsed();
+
+    let start = Instant::now();
+    let parallel = parallel_sum(&data);
+    let parallel_elapsed = start.elapsed();
+
+    println!("summing {} numbers:", data.len());
+    println!(
+        "  iter().sum():     {} in {:?}",
+        sequential, sequential_elapsed
+    );
+    println!("  par_iter().sum(): {} in {:?}", parallel, parallel_elapsed);
+
+    // Counting characters
+    let lines: Vec<String> = (0..200_000)
+        .map(|i| format!("line {} of the rayon playground", i))
+        .collect();
+
+    let start = Instant::now();
+    let counts = parallel_map_reduce(&lines);
+    println!(
+        "counted {} distinct characters in {} lines in {:?}",
+        counts.len(),
+        lines.len(),
+        start.elapsed()
+    );
+
+    let mut most_common: Vec<(&char, &usize)> = counts.iter().collect();
+    most_common.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    println!("  most common: {:?}", &most_common[..5]);
+
+    // Sorting
+    sort_comparison(5_000_000);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The plain way, one character after the other on one thread
+    fn sequential_counts(data: &[String]) -> HashMap<char, usize> {
+        let mut counts = HashMap::new();
+        for c in data.iter().flat_map(|line| line.chars()) {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn lines(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| format!("line {} of the rayon playground ✓", i))
+            .collect()
+    }
+
+    #[test]
+    fn map_reduce_matches_the_sequential_count() {
+        for count in [0, 1, 7, 1000, 10_000] {
+            let data = lines(count);
+            assert_eq!(parallel_map_reduce(&data), sequential_counts(&data));
+        }
+    }
+
+    #[test]
+    fn map_reduce_is_the_same_on_every_run() {
+        let data = lines(5_000);
+        let first = parallel_map_reduce(&data);
+        for _ in 0..5 {
+            assert_eq!(parallel_map_reduce(&data), first);
+        }
+        // Every line has two "y", one in "rayon" and one in "playground"
+        assert_eq!(first[&'y'], 2 * data.len());
+        assert_eq!(first[&'✓'], data.len());
+    }
+
+    #[test]
+    fn map_reduce_known_counts() {
+        let data = vec!["abba".to_string(), String::new(), "cab".to_string()];
+        let expected = HashMap::from([('a', 3), ('b', 3), ('c', 1)]);
+        assert_eq!(parallel_map_reduce(&data), expected);
+        assert!(parallel_map_reduce(&[]).is_empty());
+    }
+
+    #[test]
+    fn merge_adds_the_counts() {
+        let a = HashMap::from([('a', 1), ('b', 2)]);
+        let b = HashMap::from([('b', 3), ('c', 4), ('d', 5)]);
+        let expected = HashMap::from([('a', 1), ('b', 5), ('c', 4), ('d', 5)]);
+        assert_eq!(merge_maps(a.clone(), b.clone()), expected);
+        assert_eq!(merge_maps(b, a), expected);
+    }
+
+    #[test]
+    fn parallel_sum_matches_the_sequential_sum() {
+        // Whole numbers are added exactly, however the work is split
+        let data: Vec<f64> = (0..100_000).map(|i| (i % 1000) as f64).collect();
+        assert_eq!(parallel_sum(&data), data.iter().sum::<f64>());
+        assert_eq!(parallel_sum(&[]), 0.0);
+        // Fractions can round differently, but not by much
+        let data: Vec<f64> = (0..100_000).map(|i| (i % 1000) as f64 * 0.001).collect();
+        let sequential: f64 = data.iter().sum();
+        assert!((parallel_sum(&data) - sequential).abs() < 1e-6 * sequential);
+    }
+
+    #[test]
+    fn random_numbers_are_the_same_for_a_seed() {
+        assert_eq!(random_numbers(100, 42), random_numbers(100, 42));
+        assert_ne!(random_numbers(100, 42), random_numbers(100, 43));
+    }
+}
@@ -0,0 +1,297 @@
+use std::{borrow::Cow, sync::LazyLock};
+
+use regex::{Regex, RegexSet};
+
+// Compiling a regex is expensive (it's turned into an automaton), matching with it
+// is cheap. So every regex is compiled once, the first time it's used, and kept in a
+// static: `LazyLock` runs the closure on first access and hands out the same value after.
+// `unwrap` is fine there, the patterns are constants and a typo shows up on first use.
+
+// `(?P<name>...)` is a named capture group, we get its match with `caps.name("name")`
+// instead of counting parentheses to find its index
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?P<email>(?P<user>[A-Za-z0-9._%+-]+)@(?P<domain>[A-Za-z0-9.-]+\.[A-Za-z]{2,}))")
+        .unwrap()
+});
+
+// 2024-05-01T12:34:56Z ERROR message, the level may be in brackets: [ERROR]
+// `(?x)` turns on verbose mode: whitespace is ignored and `#` starts a comment
+static LOG_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+        ^(?P<timestamp>\d{4}-\d{2}-\d{2}[T\ ]\d{2}:\d{2}:\d{2}(?:\.\d+)?Z?)  # date and time
+        \s+
+        \[?(?P<level>TRACE|DEBUG|INFO|WARN|ERROR)\]?                         # level
+        \s+
+        (?P<message>.+)$                                                     # the rest
+        ",
+    )
+    .unwrap()
+});
+
+// 13 to 16 digits, optionally grouped with spaces or dashes: 4111 1111 1111 1111
+// `\b` (word boundary) keeps us from matching the middle of a longer number
+static CREDIT_CARD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:\d[ -]?){12,15}\d\b").unwrap());
+
+// `.+` is greedy, it takes as much as it can, so `<.+>` runs from the first `<` to
+// the *last* `>` of the line. `.+?` is lazy, it takes as little as it can, so `<.+?>`
+// stops at the first `>` and we get one tag per match.
+static TAG_GREEDY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<.+>").unwrap());
+static TAG_LAZY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<.+?>").unwrap());
+// The text of every <b>...</b>, lazy again so two bold words don't become one match
+static BOLD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<b>(?P<text>.*?)</b>").unwrap());
+
+// The names of the patterns in KINDS, in the same order
+const KIND_NAMES: [&str; 5] = ["email", "url", "date", "number", "shouting"];
+
+// A RegexSet matches all its patterns in a single pass over the input and tells us
+// which of them matched. Much faster than trying the patterns one after the other
+// when there are many of them (think routing, or a spam filter), but it only says
+// *which* patterns match, not where: use the individual regexes to get the matches.
+static KINDS: LazyLock<RegexSet> = LazyLock::new(|| {
+    RegexSet::new([
+        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+        r"https?://[^\s]+",
+        r"\d{4}-\d{2}-\d{2}",
+        r"^\d+$",
+        r"^[^a-z]*[A-Z][^a-z]*$",
+    ])
+    .unwrap()
+});
+
+// Every email address in `text`, in order
+pub fn extract_emails(text: &str) -> Vec<&str> {
+    EMAIL
+        .captures_iter(text)
+        .filter_map(|caps| caps.name("email"))
+        .map(|email| email.as_str())
+        .collect()
+}
+
+// The parts borrow from the line, parsing doesn't copy anything
+#[derive(Debug, PartialEq)]
+pub struct LogEntry<'a> {
+    pub timestamp: &'a str,
+    pub level: &'a str,
+    pub message: &'a str,
+}
+
+pub fn parse_log_line(line: &str) -> Option<LogEntry<'_>> {
+    let caps = LOG_LINE.captures(line)?;
+    Some(LogEntry {
+        timestamp: caps.name("timestamp")?.as_str(),
+        level: caps.name("level")?.as_str(),
+        message: caps.name("message")?.as_str(),
+    })
+}
+
+// `replace_all` returns a Cow: when nothing matches it's Cow::Borrowed(text), no
+// allocation at all, and only when something is replaced we get a new String
+pub fn redact_credit_cards(text: &str) -> Cow<'_, str> {
+    CREDIT_CARD.replace_all(text, "****")
+}
+
+// Every tag of `html`: "<p>", "<b>", "</b>"...
+pub fn html_tags(html: &str) -> Vec<&str> {
+    TAG_LAZY.find_iter(html).map(|tag| tag.as_str()).collect()
+}
+
+// The same with the greedy pattern, to show what goes wrong
+pub fn html_tags_greedy(html: &str) -> Vec<&str> {
+    TAG_GREEDY.find_iter(html).map(|tag| tag.as_str()).collect()
+}
+
+pub fn bold_texts(html: &str) -> Vec<&str> {
+    BOLD.captures_iter(html)
+        .filter_map(|caps| caps.name("text"))
+        .map(|text| text.as_str())
+        .collect()
+}
+
+// The names of every pattern of KINDS that `input` matches
+pub fn classify(input: &str) -> Vec<&'static str> {
+    KINDS
+        .matches(input)
+        .into_iter()
+        .map(|index| KIND_NAMES[index])
+        .collect()
+}
+
+pub fn run() {
+    // Emails
+    let text = "Contact alice@example.com or bob.smith+news@mail.example.org, \
+                not me@localhost or @nobody.com";
+    let emails = extract_emails(text);
+    println!("emails in {:?}:\n  {:?}", text, emails);
+
+    // Log lines
+    let lines = [
+        "2024-05-01T12:34:56Z INFO server started on port 8080",
+        "2024-05-01 12:35:02.123 [WARN] disk usage at 91%",
+        "2024-05-01T12:36:10Z ERROR connection reset by peer",
+        "just some text, not a log line",
+        "2024-05-01T12:36:10Z LOUD unknown level",
+    ];
+    println!("log lines:");
+    for line in lines {
+        match parse_log_line(line) {
+            Some(entry) => println!("  {:?}", entry),
+            None => println!("  not a log line: {:?}", line),
+        }
+    }
+
+    // Credit cards
+    let receipt = "Paid with 4111 1111 1111 1111, refund to 5500-0000-0000-0004. Order #12345.";
+    let redacted = redact_credit_cards(receipt);
+    println!("redacted:\n  {}", redacted);
+
+    // Greedy vs lazy
+    let html = "<p>Regexes are <b>fast</b> and <b>handy</b></p>";
+    println!("tags, greedy: {:?}", html_tags_greedy(html));
+    println!("tags, lazy:   {:?}", html_tags(html));
+    println!("bold texts:   {:?}", bold_texts(html));
+
+    // RegexSet
+    println!("classify:");
+    for input in [
+        "see https://example.com/docs",
+        "mail me at carol@example.net on 2024-06-01",
+        "12345",
+        "STOP SHOUTING",
+        "nothing special",
+    ] {
+        println!("  {:<45} {:?}", format!("{:?}", input), classify(input));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emails() {
+        let text = "Contact alice@example.com or bob.smith+news@mail.example.org, \
+                    not me@localhost or @nobody.com";
+        assert_eq!(
+            extract_emails(text),
+            ["alice@example.com", "bob.smith+news@mail.example.org"]
+        );
+        let caps = EMAIL.captures("write to dev@rust-lang.org").unwrap();
+        assert_eq!(&caps["user"], "dev");
+        assert_eq!(&caps["domain"], "rust-lang.org");
+    }
+
+    #[test]
+    fn no_emails() {
+        assert!(extract_emails("").is_empty());
+        assert!(extract_emails("no addresses here").is_empty());
+        assert!(extract_emails("me@localhost, @nobody.com, user@.com, a@b.c").is_empty());
+    }
+
+    #[test]
+    fn log_lines() {
+        assert_eq!(
+            parse_log_line("2024-05-01T12:34:56Z INFO server started on port 8080"),
+            Some(LogEntry {
+                timestamp: "2024-05-01T12:34:56Z",
+                level: "INFO",
+                message: "server started on port 8080",
+            })
+        );
+        assert_eq!(
+            parse_log_line("2024-05-01 12:35:02.123 [WARN] disk usage at 91%"),
+            Some(LogEntry {
+                timestamp: "2024-05-01 12:35:02.123",
+                level: "WARN",
+                message: "disk usage at 91%",
+            })
+        );
+    }
+
+    #[test]
+    fn not_log_lines() {
+        for line in [
+            "",
+            "just some text, not a log line",
+            // An unknown level
+            "2024-05-01T12:36:10Z LOUD unknown level",
+            // Lowercase level
+            "2024-05-01T12:36:10Z error lowercase",
+            // No message
+            "2024-05-01T12:36:10Z ERROR",
+            // Not at the start of the line
+            "at 2024-05-01T12:36:10Z ERROR something",
+            "2024-5-1T12:36:10Z ERROR a short date",
+        ] {
+            assert_eq!(parse_log_line(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn credit_cards() {
+        let receipt = "Paid with 4111 1111 1111 1111, refund to 5500-0000-0000-0004. Order #12345.";
+        assert_eq!(
+            redact_credit_cards(receipt),
+            "Paid with ****, refund to ****. Order #12345."
+        );
+        assert_eq!(redact_credit_cards("4111111111111"), "****");
+    }
+
+    #[test]
+    fn no_credit_cards() {
+        // Nothing to redact, so nothing was allocated
+        for text in [
+            "Order #12345, paid in cash",
+            // 12 digits is too short, 17 is too long
+            "ref 411111111111",
+            "ref 41111111111111111",
+            "",
+        ] {
+            assert!(
+                matches!(redact_credit_cards(text), Cow::Borrowed(t) if t == text),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn greedy_and_lazy_tags() {
+        let html = "<p>Regexes are <b>fast</b> and <b>handy</b></p>";
+        assert_eq!(html_tags_greedy(html), [html]);
+        assert_eq!(
+            html_tags(html),
+            ["<p>", "<b>", "</b>", "<b>", "</b>", "</p>"]
+        );
+        assert_eq!(bold_texts(html), ["fast", "handy"]);
+        assert_eq!(bold_texts("<b></b>"), [""]);
+    }
+
+    #[test]
+    fn no_tags() {
+        assert!(html_tags("no tags, 1 < 2").is_empty());
+        assert!(html_tags_greedy("2 > 1 < 3").is_empty());
+        assert!(html_tags("<>").is_empty());
+        assert!(bold_texts("<p>not bold</p>, <b>not closed").is_empty());
+    }
+
+    #[test]
+    fn classify_inputs() {
+        assert_eq!(
+            classify("mail me at carol@example.net on 2024-06-01"),
+            ["email", "date"]
+        );
+        assert_eq!(classify("see https://example.com/docs"), ["url"]);
+        assert_eq!(classify("12345"), ["number"]);
+        assert_eq!(classify("STOP SHOUTING"), ["shouting"]);
+    }
+
+    #[test]
+    fn classify_nothing() {
+        assert!(classify("nothing special").is_empty());
+        assert!(classify("").is_empty());
+        assert!(classify("12345 apples").is_empty());
+        assert!(classify("ftp://example.com").is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::path::Path;
+
+use relm4::gtk::cairo::{self, Context, Format, ImageSurface};
+use relm4::gtk::gdk::prelude::GdkCairoContextExt;
+use relm4::gtk::gdk_pixbuf::{InterpType, Pixbuf};
+use relm4::gtk::glib;
+
+use crate::render;
+
+// The image behind the bars, when the theme has one (see theme::Background)
+//
+// gdk-pixbuf reads the file once, when the window opens. For every size of the area
+// the image is scaled to cover it (see `render::cover`), darkened, and kept in a
+// surface for the next draws, until the size changes. The new size is always scaled
+// from the image as it was read: scaling the scaled image again and again, resize
+// after resize, would blur it a little more every time.
+
+pub struct BackgroundImage {
+    original: Pixbuf,
+    // How much black goes over it, from 0 to 1
+    dim: f64,
+    // The image for the last size it was drawn at
+    // In a RefCell because drawing only gets a shared reference, like the draw function
+    scaled: RefCell<Option<ImageSurface>>,
+}
+
+impl BackgroundImage {
+    pub fn load(path: &Path, dim: f32) -> Result<BackgroundImage, glib::Error> {
+        Ok(BackgroundImage {
+            original: Pixbuf::from_file(path)?,
+            dim: dim as f64,
+            scaled: RefCell::new(None),
+        })
+    }
+
+    // The image for an area of `width` x `height`, to paint at (0, 0)
+    // A surface is reference counted, the one returned is the one kept
+    pub fn surface(&self, width: i32, height: i32) -> Result<ImageSurface, cairo::Error> {
+        let mut scaled = self.scaled.borrow_mut();
+        if let Some(surface) = scaled.as_ref() {
+            if surface.width() == width && surface.height() == height {
+                return Ok(surface.clone());
+            }
+        }
+        let surface = self.scale(width, height)?;
+        *scaled = Some(surface.clone());
+        Ok(surface)
+    }
+
+    fn scale(&self, width: i32, height: i32) -> Result<ImageSurface, cairo::Error> {
+        // No alpha: a new surface is black, and so is what's under the transparent
+        // parts of the image
+        let surface = ImageSurface::create(Format::Rgb24, width, height)?;
+        let ctx = Context::new(&surface)?;
+        let cover = render::cover(self.original.width(), self.original.height(), width, height);
+        // None when there isn't enough memory for the scaled image, which leaves black
+        if let Some(pixbuf) =
+            self.original
+                .scale_simple(cover.width, cover.height, InterpType::Bilinear)
+        {
+            ctx.set_source_pixbuf(&pixbuf, cover.x as f64, cover.y as f64);
+            ctx.paint()?;
+        }
+        // Darker, so the bars stand out on it
+        ctx.set_source_rgba(0.0, 0.0, 0.0, self.dim);
+        ctx.paint()?;
+        Ok(surface)
+    }
+}
@@ -0,0 +1,393 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+use serde::Deserialize;
+
+use crate::limiter::DEFAULT_MAX_FPS;
+use crate::persist::SavedSettings;
+use crate::render::{ChannelView, RenderStyle, DEFAULT_INNER_RADIUS};
+use crate::settings::{Settings, MAX_BARS, MAX_FRAMERATE, MIN_BARS, MIN_FRAMERATE};
+
+// Where the bar values come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    // The real thing, needs cava installed
+    #[default]
+    Cava,
+    // A made up signal, for machines without audio
+    Synthetic,
+}
+
+// The largest screenshot side, a 8192x8192 screenshot takes 256MB to render
+pub const MAX_SCREENSHOT_SIDE: i32 = 8192;
+
+// A size in pixels, written "1920x1080"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Resolution {
+    pub width: i32,
+    pub height: i32,
+}
+
+// clap uses this for `--screenshot-size`
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Resolution, String> {
+        let error = || {
+            format!(
+                "`{}` is not a size, expected WIDTHxHEIGHT like 1920x1080",
+                text
+            )
+        };
+        let (width, height) = text.trim().split_once('x').ok_or_else(error)?;
+        let side = |side: &str| side.parse::<i32>().map_err(|_| error());
+        let (width, height) = (side(width)?, side(height)?);
+        if !(1..=MAX_SCREENSHOT_SIDE).contains(&width)
+            || !(1..=MAX_SCREENSHOT_SIDE).contains(&height)
+        {
+            return Err(format!(
+                "a screenshot is 1 to {} pixels wide and high, not {}x{}",
+                MAX_SCREENSHOT_SIDE, width, height
+            ));
+        }
+        Ok(Resolution { width, height })
+    }
+}
+
+// And serde for the config file, the message ends up in the TOML error
+impl TryFrom<String> for Resolution {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Resolution, String> {
+        text.parse()
+    }
+}
+
+// The command line of the visualizer
+//
+// Everything is optional: what isn't given on the command line comes from the
+// `--config` file, and what isn't there either gets the default. That's why the
+// values are Options here, we need to know what the user actually typed.
+//
+// The arguments after `--` are handed to GTK untouched, e.g.
+//   relm4_cairo_visualizer --bars 40 -- --gapplication-service
+#[derive(Debug, Parser)]
+#[command(about = "Draws the audio spectrum from cava with cairo")]
+pub struct Cli {
+    #[arg(long, value_parser = parse_bars, help = "Number of bars [default: 20]")]
+    pub bars: Option<usize>,
+
+    #[arg(long, value_parser = parse_framerate, help = "Frames per second [default: 60]")]
+    pub framerate: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "FPS",
+        value_parser = parse_max_fps,
+        help = "Draw the bars at most this often, frames in between are skipped [default: 60]"
+    )]
+    pub max_fps: Option<u32>,
+
+    #[arg(long, help = "Draw the y/h labels above the bars (d toggles them)")]
+    pub debug_labels: bool,
+
+    #[arg(long, help = "Show the draw and data rates in the top-right corner")]
+    pub show_fps: bool,
+
+    #[arg(
+        long,
+        help = "See-through background, needs a compositor (falls back to the theme's background)"
+    )]
+    pub transparent: bool,
+
+    #[arg(
+        long,
+        help = "Draw the bars in the terminal instead of a window (q or Ctrl-C quits)"
+    )]
+    pub tui: bool,
+
+    #[arg(long, value_enum, help = "How to draw the values [default: bars]")]
+    pub style: Option<RenderStyle>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Where the channels of a stereo frame go (c switches) [default: mono]"
+    )]
+    pub channels: Option<ChannelView>,
+
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        value_parser = parse_inner_radius,
+        help = "Where the radial bars start, 0 is the center and 0.9 near the edge [default: 0.25]"
+    )]
+    pub inner_radius: Option<f64>,
+
+    #[arg(long, value_enum, help = "Where the values come from [default: cava]")]
+    pub source: Option<SourceKind>,
+
+    #[arg(
+        long,
+        value_name = "WIDTHxHEIGHT",
+        help = "Size of the screenshots taken with s [default: the size of the window]"
+    )]
+    pub screenshot_size: Option<Resolution>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "TOML file with default values for the options above"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        last = true,
+        value_name = "GTK_ARGS",
+        help = "Arguments passed on to GTK"
+    )]
+    pub gtk_args: Vec<String>,
+}
+
+impl Cli {
+    // Parse the process arguments and load the config file
+    // On any error (or --help) clap prints its message and exits, so this runs before
+    // GTK is started and no window ever shows up for a typo
+    pub fn load() -> Cli {
+        Cli::try_load_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_load_from<I, T>(args: I) -> Result<Cli, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut cli = Cli::try_parse_from(args)?;
+        if let Some(path) = &cli.config {
+            // Reported the same way as a bad argument, so all errors look alike
+            let file = ConfigFile::read(path).map_err(|e| {
+                Cli::command().error(
+                    ErrorKind::InvalidValue,
+                    format!("invalid config file '{}': {}", path.display(), e),
+                )
+            })?;
+            cli.fill_from(file);
+        }
+        Ok(cli)
+    }
+
+    // The command line wins, the file only fills in what it didn't set
+    fn fill_from(&mut self, file: ConfigFile) {
+        self.bars = self.bars.or(file.bars);
+        self.framerate = self.framerate.or(file.framerate);
+        self.max_fps = self.max_fps.or(file.max_fps);
+        self.style = self.style.or(file.style);
+        self.channels = self.channels.or(file.channels);
+        self.inner_radius = self.inner_radius.or(file.inner_radius);
+        self.source = self.source.or(file.source);
+        self.screenshot_size = self.screenshot_size.or(file.screenshot_size);
+        if file.debug_labels == Some(true) {
+            self.debug_labels = true;
+        }
+        if file.show_fps == Some(true) {
+            self.show_fps = true;
+        }
+        if file.transparent == Some(true) {
+            self.transparent = true;
+        }
+    }
+
+    // What the last run saved comes after the command line and the `--config` file,
+    // and before the defaults (see persist.rs)
+    pub fn fill_from_saved(&mut self, saved: &SavedSettings) {
+        self.bars = self.bars.or(Some(saved.bars));
+        self.framerate = self.framerate.or(Some(saved.framerate));
+        self.style = self.style.or(Some(saved.style));
+        self.channels = self.channels.or(Some(saved.channels));
+        self.debug_labels |= saved.debug_labels;
+        self.show_fps |= saved.show_fps;
+    }
+
+    pub fn settings(&self) -> Settings {
+        let default = Settings::default();
+        Settings {
+            bars: self.bars.unwrap_or(default.bars),
+            framerate: self.framerate.unwrap_or(default.framerate),
+            smoothing: default.smoothing,
+        }
+    }
+
+    pub fn max_fps(&self) -> u32 {
+        self.max_fps.unwrap_or(DEFAULT_MAX_FPS)
+    }
+
+    pub fn style(&self) -> RenderStyle {
+        self.style.unwrap_or_default()
+    }
+
+    pub fn channels(&self) -> ChannelView {
+        self.channels.unwrap_or_default()
+    }
+
+    pub fn inner_radius(&self) -> f64 {
+        self.inner_radius.unwrap_or(DEFAULT_INNER_RADIUS)
+    }
+
+    pub fn source(&self) -> SourceKind {
+        self.source.unwrap_or_default()
+    }
+}
+
+// The `--config` file, every key is optional:
+//
+//   bars = 32
+//   framerate = 30
+//   max_fps = 30
+//   debug_labels = true
+//   show_fps = true
+//   transparent = true
+//   style = "radial"
+//   channels = "split-horizontal"
+//   inner_radius = 0.4
+//   source = "synthetic"
+//   screenshot_size = "1920x1080"
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    bars: Option<usize>,
+    framerate: Option<u32>,
+    max_fps: Option<u32>,
+    debug_labels: Option<bool>,
+    show_fps: Option<bool>,
+    transparent: Option<bool>,
+    style: Option<RenderStyle>,
+    channels: Option<ChannelView>,
+    inner_radius: Option<f64>,
+    source: Option<SourceKind>,
+    screenshot_size: Option<Resolution>,
+}
+
+#[derive(Debug)]
+enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    // A value of the right type, but out of range
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            // The toml error is several lines long (it shows the faulty line), so it
+            // starts on a line of its own
+            ConfigError::Toml(e) => write!(f, "\n{}", e.to_string().trim_end()),
+            ConfigError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<ConfigFile, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let file: ConfigFile = toml::from_str(&text).map_err(ConfigError::Toml)?;
+
+        // The file gets the same limits as the command line
+        if let Some(bars) = file.bars {
+            check_bars(bars).map_err(ConfigError::Invalid)?;
+        }
+        if let Some(framerate) = file.framerate {
+            check_framerate(framerate).map_err(ConfigError::Invalid)?;
+        }
+        if let Some(max_fps) = file.max_fps {
+            check_max_fps(max_fps).map_err(ConfigError::Invalid)?;
+        }
+        if let Some(inner_radius) = file.inner_radius {
+            check_inner_radius(inner_radius).map_err(ConfigError::Invalid)?;
+        }
+        Ok(file)
+    }
+}
+
+// clap shows these messages as "invalid value '0' for '--bars <BARS>': ..."
+fn parse_bars(value: &str) -> Result<usize, String> {
+    let bars = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    check_bars(bars)
+}
+
+fn parse_framerate(value: &str) -> Result<u32, String> {
+    let framerate = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    check_framerate(framerate)
+}
+
+fn parse_max_fps(value: &str) -> Result<u32, String> {
+    let max_fps = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    check_max_fps(max_fps)
+}
+
+fn parse_inner_radius(value: &str) -> Result<f64, String> {
+    let inner_radius = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    check_inner_radius(inner_radius)
+}
+
+fn check_bars(bars: usize) -> Result<usize, String> {
+    if (MIN_BARS..=MAX_BARS).contains(&bars) {
+        Ok(bars)
+    } else {
+        Err(format!(
+            "bars must be between {} and {}, not {}",
+            MIN_BARS, MAX_BARS, bars
+        ))
+    }
+}
+
+fn check_framerate(framerate: u32) -> Result<u32, String> {
+    if (MIN_FRAMERATE..=MAX_FRAMERATE).contains(&framerate) {
+        Ok(framerate)
+    } else {
+        Err(format!(
+            "framerate must be between {} and {} fps, not {}",
+            MIN_FRAMERATE, MAX_FRAMERATE, framerate
+        ))
+    }
+}
+
+// The same range as the framerate, 240 is as fast as the fastest screens refresh
+fn check_max_fps(max_fps: u32) -> Result<u32, String> {
+    if (MIN_FRAMERATE..=MAX_FRAMERATE).contains(&max_fps) {
+        Ok(max_fps)
+    } else {
+        Err(format!(
+            "max-fps must be between {} and {}, not {}",
+            MIN_FRAMERATE, MAX_FRAMERATE, max_fps
+        ))
+    }
+}
+
+// Up to 0.9, so the bars always have some room to grow
+fn check_inner_radius(inner_radius: f64) -> Result<f64, String> {
+    if (0.0..=0.9).contains(&inner_radius) {
+        Ok(inner_radius)
+    } else {
+        Err(format!(
+            "the inner radius must be between 0 and 0.9, not {}",
+            inner_radius
+        ))
+    }
+}
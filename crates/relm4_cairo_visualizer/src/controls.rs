@@ -0,0 +1,127 @@
+use crate::render::RenderStyle;
+use crate::settings::{Settings, MAX_BARS, MIN_BARS};
+
+// The controls of the header bar: the number of bars, the style and pause
+//
+// Each of them can also be changed some other way (the preferences, a key), and then
+// the control is set to match. GTK tells us about that change too, like about one
+// made by the user, so a control always asks for what it shows and the app ignores
+// what it has already.
+//
+// No GTK in here, so what the app does with the controls can be checked on its own.
+
+// The styles in the order of the drop-down, with what it shows for them
+pub const STYLES: [(RenderStyle, &str); 4] = [
+    (RenderStyle::Bars, "Bars"),
+    (RenderStyle::Waveform, "Wave"),
+    (RenderStyle::Radial, "Radial"),
+    (RenderStyle::Mirrored, "Mirrored"),
+];
+
+pub fn style_labels() -> Vec<&'static str> {
+    STYLES.iter().map(|&(_, label)| label).collect()
+}
+
+// Where `style` is in the drop-down
+pub fn style_position(style: RenderStyle) -> u32 {
+    STYLES
+        .iter()
+        .position(|&(listed, _)| listed == style)
+        .unwrap_or(0) as u32
+}
+
+// The style at `position` in the drop-down
+// None for no selection (GTK_INVALID_LIST_POSITION, u32::MAX) and anything past the end
+pub fn style_at(position: u32) -> Option<RenderStyle> {
+    STYLES.get(position as usize).map(|&(style, _)| style)
+}
+
+// `settings` with the number of bars of the spin button
+// The spin button stays in its range, but someone typing in it can get anything in
+pub fn with_bars(settings: Settings, bars: i32) -> Settings {
+    Settings {
+        bars: (bars.max(0) as usize).clamp(MIN_BARS, MAX_BARS),
+        ..settings
+    }
+}
+
+// Lets through the frames with the number of bars we asked for
+//
+// Changing the number of bars starts a new visualizer, but the frames the old one
+// sent before it stopped are still on their way. Drawing them would put the wrong
+// number of bars on screen for a moment (and the peaks and the bars gliding to their
+// values expect as many values as before), so they are left out and counted.
+#[derive(Debug, Default)]
+pub struct FrameGate {
+    ignored: u64,
+}
+
+impl FrameGate {
+    pub fn new() -> FrameGate {
+        FrameGate::default()
+    }
+
+    // Whether a frame of `bars` bars (per channel) is one to show, when `expected` is
+    // the number of bars in the settings
+    pub fn accept(&mut self, expected: usize, bars: usize) -> bool {
+        if bars != expected {
+            self.ignored += 1;
+            return false;
+        }
+        true
+    }
+
+    // How many frames were left out since the start
+    pub fn ignored(&self) -> u64 {
+        self.ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_style_is_listed_once() {
+        for (position, &(style, _)) in STYLES.iter().enumerate() {
+            assert_eq!(style_position(style), position as u32);
+            assert_eq!(style_at(position as u32), Some(style));
+        }
+        assert_eq!(style_labels(), vec!["Bars", "Wave", "Radial", "Mirrored"]);
+    }
+
+    #[test]
+    fn no_selection_is_no_style() {
+        assert_eq!(style_at(u32::MAX), None);
+        assert_eq!(style_at(STYLES.len() as u32), None);
+    }
+
+    #[test]
+    fn the_spin_button_sets_the_bars_only() {
+        let settings = Settings::default();
+        let changed = with_bars(settings, 64);
+        assert_eq!(changed.bars, 64);
+        assert_eq!(changed.framerate, settings.framerate);
+        assert_eq!(changed.smoothing, settings.smoothing);
+    }
+
+    #[test]
+    fn typed_in_bars_are_clamped() {
+        let settings = Settings::default();
+        assert_eq!(with_bars(settings, 0).bars, MIN_BARS);
+        assert_eq!(with_bars(settings, -40).bars, MIN_BARS);
+        assert_eq!(with_bars(settings, 100_000).bars, MAX_BARS);
+        assert_eq!(with_bars(settings, MAX_BARS as i32).bars, MAX_BARS);
+    }
+
+    #[test]
+    fn frames_of_the_old_visualizer_are_left_out() {
+        let mut gate = FrameGate::new();
+        // Switched from 32 to 64 bars, the old ones are still coming in
+        assert!(!gate.accept(64, 32));
+        assert!(!gate.accept(64, 32));
+        assert!(gate.accept(64, 64));
+        assert!(gate.accept(64, 64));
+        assert_eq!(gate.ignored(), 2);
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+// Hiding the cursor in fullscreen, once the pointer stays still for a while
+//
+// There is only ever one timeout running (see `hide_cursor_after` in main.rs): when it
+// fires we look at how long the pointer has been still, and either hide the cursor or
+// wait for the rest of the time. Only numbers in here, so it can be checked without a
+// window.
+
+// How long the pointer has to stay still before the cursor hides
+pub const HIDE_CURSOR_AFTER: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hide {
+    Now,
+    // The pointer moved since the timeout started, wait that much longer
+    After(Duration),
+}
+
+// What to do when the timeout fires, the pointer last moved `still_for` ago
+pub fn when_to_hide(still_for: Duration) -> Hide {
+    match HIDE_CURSOR_AFTER.checked_sub(still_for) {
+        Some(left) if !left.is_zero() => Hide::After(left),
+        _ => Hide::Now,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_still_pointer_hides_the_cursor() {
+        assert_eq!(when_to_hide(HIDE_CURSOR_AFTER), Hide::Now);
+        assert_eq!(when_to_hide(Duration::from_secs(10)), Hide::Now);
+    }
+
+    #[test]
+    fn a_moved_pointer_waits_for_the_rest() {
+        assert_eq!(
+            when_to_hide(Duration::from_millis(500)),
+            Hide::After(Duration::from_millis(1500))
+        );
+        assert_eq!(when_to_hide(Duration::ZERO), Hide::After(HIDE_CURSOR_AFTER));
+    }
+}
@@ -0,0 +1,93 @@
+// Rendering the bars (gradients, shine, labels...) is the expensive part of drawing,
+// so we render them into an offscreen surface and the draw function only copies that
+// surface to the screen. GTK calls the draw function for many reasons (a resize,
+// another window moving over ours...), and most of the time the bars didn't change.
+//
+// This keeps track of when the offscreen surface is out of date:
+// - new bar values arrived (`mark_dirty`), or
+// - the area doesn't have the size the surface was rendered at
+pub struct DirtyTracker {
+    dirty: bool,
+    // None until the first render
+    rendered_size: Option<(i32, i32)>,
+}
+
+impl DirtyTracker {
+    // There is nothing rendered yet, so the first draw always renders
+    pub fn new() -> DirtyTracker {
+        DirtyTracker {
+            dirty: true,
+            rendered_size: None,
+        }
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn needs_render(&self, width: i32, height: i32) -> bool {
+        self.dirty || self.rendered_size != Some((width, height))
+    }
+
+    // Only call this once the surface was actually rendered, if rendering failed
+    // we want to try again on the next draw
+    pub fn rendered(&mut self, width: i32, height: i32) {
+        self.dirty = false;
+        self.rendered_size = Some((width, height));
+    }
+}
+
+impl Default for DirtyTracker {
+    fn default() -> DirtyTracker {
+        DirtyTracker::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_draw_renders() {
+        assert!(DirtyTracker::new().needs_render(300, 200));
+    }
+
+    // The bug this replaced: every draw rendered, new values or not
+    #[test]
+    fn a_draw_without_changes_does_not_render() {
+        let mut tracker = DirtyTracker::new();
+        tracker.rendered(300, 200);
+        assert!(!tracker.needs_render(300, 200));
+        assert!(!tracker.needs_render(300, 200));
+    }
+
+    #[test]
+    fn new_values_render_once() {
+        let mut tracker = DirtyTracker::new();
+        tracker.rendered(300, 200);
+        tracker.mark_dirty();
+        assert!(tracker.needs_render(300, 200));
+        tracker.rendered(300, 200);
+        assert!(!tracker.needs_render(300, 200));
+    }
+
+    #[test]
+    fn a_resize_renders() {
+        let mut tracker = DirtyTracker::new();
+        tracker.rendered(300, 200);
+        assert!(tracker.needs_render(301, 200));
+        assert!(tracker.needs_render(300, 199));
+        tracker.rendered(301, 200);
+        assert!(!tracker.needs_render(301, 200));
+    }
+
+    // Until `rendered` is called it stays dirty, so a failed render is tried again
+    #[test]
+    fn a_failed_render_is_retried() {
+        let mut tracker = DirtyTracker::new();
+        tracker.rendered(300, 200);
+        tracker.mark_dirty();
+        assert!(tracker.needs_render(300, 200));
+        assert!(tracker.needs_render(300, 200));
+    }
+}
@@ -0,0 +1,615 @@
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use relm4::gtk::cairo::{
+    Context, Error, FontSlant, FontWeight, Gradient, LinearGradient, Operator, RadialGradient,
+};
+
+use crate::background::BackgroundImage;
+use crate::render::{self, Channel, ChannelView, Geometry, RenderStyle};
+use crate::theme::{self, Background, Color, GradientDirection, Theme, Transparency};
+use crate::visualizer::{ChannelLayout, Frame};
+
+// The cairo side of rendering: render.rs says where things go, this draws them there
+
+const STROKE_WIDTH: f64 = 4.0;
+
+// How to draw the bars, everything but the colors (those are in the Theme)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    pub style: RenderStyle,
+    // The y/h labels above the bars, values around the radial ones
+    pub debug_labels: bool,
+    // Only for the radial style, see `radial_radii`
+    pub inner_radius: f64,
+    // Where the channels of a stereo frame go
+    pub channel_view: ChannelView,
+}
+
+// Draw the frame on `ctx`, whatever surface it draws on: the offscreen one the
+// window copies from, or a screenshot
+// The bars go on what's there already, see `draw_background`
+pub fn draw_scene(
+    ctx: &Context,
+    frame: &Frame,
+    // The peak of every bar, see peaks.rs
+    peaks: &[f64],
+    width: i32,
+    height: i32,
+    options: RenderOptions,
+    theme: &Theme,
+) -> Result<(), Error> {
+    let geometry = Geometry::new(width as f64, height as f64);
+
+    // Every channel in its own part of the area, or all the values across the whole
+    // area (see `channel_areas`)
+    let stereo = frame.layout == ChannelLayout::Stereo;
+    let (labels, inner_radius) = (options.debug_labels, options.inner_radius);
+    for area in render::channel_areas(options.channel_view, stereo, geometry) {
+        let values = area.select(&frame.values);
+        let peaks = area.select(peaks);
+        let geometry = area.geometry;
+
+        // The styles draw from (0, 0), in an area of `geometry`: move the origin to
+        // the channel's part, and keep the strokes from spilling out of it
+        ctx.save()?;
+        ctx.translate(area.x, area.y);
+        ctx.rectangle(0.0, 0.0, geometry.width, geometry.height);
+        ctx.clip();
+        match options.style {
+            // Only the bars have peak markers, the other styles have no flat top to put one on
+            RenderStyle::Bars => draw_bars(ctx, &values, &peaks, geometry, theme, labels),
+            RenderStyle::Waveform => draw_waveform(ctx, &values, geometry, theme),
+            RenderStyle::Radial => draw_radial(ctx, &values, geometry, theme, labels, inner_radius),
+            // No labels: above a bar is now the middle of the area, where the other bars are
+            RenderStyle::Mirrored => draw_mirrored(ctx, &values, geometry, theme),
+        }
+        if labels {
+            draw_channel_name(ctx, area.channel, geometry, theme);
+        }
+        ctx.restore()?;
+    }
+    Ok(())
+}
+
+// Paint the theme's background on all of `ctx`, `width` x `height` (black with the
+// default theme)
+// A new surface is fully transparent, so for a transparent window there is nothing to
+// paint at all. `image` is the theme's image, loaded, None when it has none or it
+// couldn't be loaded (it's black then)
+pub fn draw_background(
+    ctx: &Context,
+    width: i32,
+    height: i32,
+    theme: &Theme,
+    transparency: Transparency,
+    image: Option<&BackgroundImage>,
+) -> Result<(), Error> {
+    if transparency == Transparency::Transparent {
+        return Ok(());
+    }
+    match (&theme.background, image) {
+        (Background::Gradient { top, bottom }, _) => {
+            let gradient = theme::Gradient::two_colors(*top, *bottom);
+            ctx.set_source(&linear_gradient(&gradient, 0.0, 0.0, 0.0, height as f64))?;
+        }
+        (Background::Image { .. }, Some(image)) => {
+            ctx.set_source_surface(&image.surface(width, height)?, 0.0, 0.0)?;
+        }
+        (background, _) => set_color(ctx, background.color()),
+    }
+    ctx.paint()
+}
+
+// "left" or "right" at the top of a channel's part of the area, with the debug labels
+// The top-left and top-right corners have the "paused" and the rates, so it goes in
+// the middle
+fn draw_channel_name(ctx: &Context, channel: Channel, geometry: Geometry, theme: &Theme) {
+    let name = match channel {
+        Channel::All => return,
+        Channel::Left => "left",
+        Channel::Right => "right",
+    };
+    set_color(ctx, theme.text);
+    ctx.set_font_size(14.0);
+    let extents = ctx.text_extents(name).expect("Failed to get text extents");
+    ctx.move_to(
+        (geometry.width - extents.x_advance()) / 2.0,
+        8.0 - extents.y_bearing(),
+    );
+    ctx.show_text(name).expect("Failed to draw text");
+}
+
+pub fn set_color(ctx: &Context, color: Color) {
+    ctx.set_source_rgba(color.r, color.g, color.b, color.a);
+}
+
+// Paint the whole of `ctx` with the background color, when there are no bars to show
+// Not the image or the gradient: this is for when rendering failed
+pub fn clear(ctx: &Context, theme: &Theme, transparency: Transparency) {
+    match transparency {
+        Transparency::Opaque => set_color(ctx, theme.background.color()),
+        // With the default operator (Over) painting a transparent color changes
+        // nothing, Source replaces what's there, alpha included
+        Transparency::Transparent => {
+            ctx.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+            ctx.set_operator(Operator::Source);
+        }
+    }
+    ctx.paint().expect("Failed to paint the background");
+    ctx.set_operator(Operator::Over);
+}
+
+// Works for any kind of gradient, linear and radial ones deref to `Gradient`
+fn add_color_stop(gradient: &Gradient, offset: f64, color: Color) {
+    gradient.add_color_stop_rgba(offset, color.r, color.g, color.b, color.a);
+}
+
+// A cairo gradient from (x0, y0) to (x1, y1) with the stops of the theme's gradient
+fn linear_gradient(
+    gradient: &theme::Gradient,
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+) -> LinearGradient {
+    let linear = LinearGradient::new(x0, y0, x1, y1);
+    for stop in gradient.stops() {
+        add_color_stop(&linear, stop.offset, stop.color);
+    }
+    linear
+}
+
+// Fill with the bar colors, for a bar at `x` going from `top` down to `bottom`
+// `vertical` is the gradient along the bar, the theme's one or one made from it (the
+// mirrored bars have the start of the gradient in the middle)
+fn set_bar_source(
+    ctx: &Context,
+    theme: &Theme,
+    vertical: &theme::Gradient,
+    geometry: Geometry,
+    x: f64,
+    top: f64,
+    bottom: f64,
+) {
+    let gradient = match theme.bar_gradient_direction {
+        GradientDirection::Vertical => linear_gradient(vertical, x, top, x, bottom),
+        // The gradient is in the coordinates of the area and not of the bar, so every
+        // bar shows the part of it that's behind it
+        GradientDirection::Horizontal => {
+            linear_gradient(&theme.bar_gradient, 0.0, 0.0, geometry.width, 0.0)
+        }
+        // A full bar takes the start of the gradient (offset 0), an empty one its end
+        GradientDirection::PerBarValue => {
+            let full = ((bottom - top) / geometry.height).clamp(0.0, 1.0);
+            set_color(ctx, theme.bar_gradient.color_at(1.0 - full));
+            return;
+        }
+    };
+    ctx.set_source(&gradient).expect("Failed to set gradient");
+}
+
+// Draw the bars (lowest frequency first) on `ctx`
+pub fn draw_bars(
+    ctx: &Context,
+    values: &[u16],
+    peaks: &[f64],
+    geometry: Geometry,
+    theme: &Theme,
+    labels: bool,
+) {
+    // Iterate over the bars, drawing each one as a rectangle
+    // `bar_rects` works out where each bar goes and how tall it is
+    let bars = render::bar_rects(values, geometry);
+    let label_every = render::label_every(bars.len());
+    for (i, bar) in bars.into_iter().enumerate() {
+        let (x, y, bar_width, height) = (bar.x, bar.y, bar.width, bar.height);
+        // The stroke, the fill and the shine all follow the same (maybe rounded) outline
+        let radius = theme.corner_radius;
+
+        // Draw a stroke (border) around the bar
+        // Set the color for the stroke (light purple with some transparency by default)
+        set_color(ctx, theme.stroke);
+        ctx.set_line_width(STROKE_WIDTH);
+
+        // Draw the rectangle for the stroke and apply the stroke
+        rounded_rect(ctx, x, y, bar_width, height, radius);
+        ctx.stroke().expect("Failed to stroke bar");
+
+        // Fill the bar with the theme's gradient (light blue to darker blue by default)
+        set_bar_source(ctx, theme, &theme.bar_gradient, geometry, x, y, y + height);
+
+        // Draw and fill the rectangle for the bar
+        rounded_rect(ctx, x, y, bar_width, height, radius);
+        ctx.fill().expect("Failed to fill bar");
+
+        // Add a shine effect at the top of the bar, unless the theme turned it off
+        if theme.shine {
+            let shine_height = height * 0.1; // 10% of bar height
+            let shine_gradient = LinearGradient::new(x, y, x, y + shine_height);
+            // White, 30% opaque by default, fading out to fully transparent
+            shine_gradient.add_color_stop_rgba(0.0, 1.0, 1.0, 1.0, theme.shine_opacity);
+            shine_gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 0.0);
+            ctx.set_source(&shine_gradient)
+                .expect("Failed to set shine gradient");
+            // The whole bar: below `shine_height` the gradient is transparent, so only
+            // the top gets the shine, and it follows the rounded corners there
+            rounded_rect(ctx, x, y, bar_width, height, radius);
+            ctx.fill().expect("Failed to add shine effect");
+        }
+
+        // Two texts per bar add up with many bars, the text functions are only
+        // called at all for the bars that get a label
+        if !labels || i % label_every != 0 {
+            continue;
+        }
+
+        // Draw the current Y position on top of the rectangle
+        set_color(ctx, theme.text); // White by default
+        ctx.set_font_size(12.0);
+        let text = format!("y: {:.0}", y);
+        let extents = ctx.text_extents(&text).expect("Failed to get text extents");
+
+        // Calculate the center position for the text
+        // We start from the left edge of the bar (x) and add half the bar width
+        // Then we subtract half the text width to center it within the bar
+        let text_x = x + (bar_width - extents.width()) / 2.0;
+        let text_y = y - 5.0; // Position text slightly above the bar
+
+        // Move the drawing cursor to the specified (x, y) coordinates
+        // This sets the starting point for the next drawing operation (in this case, drawing text)
+        ctx.move_to(text_x, text_y);
+
+        // Draw the text on the canvas
+        ctx.show_text(&text).expect("Failed to draw text");
+
+        // Draw another text showing the bar's height
+        set_color(ctx, theme.text);
+        ctx.set_font_size(12.0);
+        let text = format!("h: {:.0}", height);
+        let text_y = text_y - 10f64; // Position text slightly above the y text
+        ctx.move_to(text_x, text_y);
+        ctx.show_text(&text).expect("Failed to draw text");
+    }
+
+    // The peak markers, in a color of their own (white by default)
+    set_color(ctx, theme.peak);
+    for marker in render::peak_markers(values, peaks, geometry) {
+        ctx.rectangle(marker.x, marker.y, marker.width, marker.height);
+    }
+    ctx.fill().expect("Failed to fill the peak markers");
+}
+
+// Draw the values as one shape: a smooth line through the top of every value, filled
+// down to the bottom of the area with the same colors as the bars
+pub fn draw_waveform(ctx: &Context, values: &[u16], geometry: Geometry, theme: &Theme) {
+    // Follow the top edge from left to right, then go down to the bottom-right corner
+    // and back along the bottom edge, so the shape can be filled
+    let wave = render::wave(values, geometry);
+    ctx.move_to(wave.start.0, wave.start.1);
+    for curve in &wave.curves {
+        ctx.curve_to(
+            curve.control1.0,
+            curve.control1.1,
+            curve.control2.0,
+            curve.control2.1,
+            curve.end.0,
+            curve.end.1,
+        );
+    }
+    // Only the top edge gets the stroke, so we keep a copy of that part of the path
+    let top_edge = ctx.copy_path().expect("Failed to copy the wave path");
+    ctx.line_to(geometry.width, geometry.height);
+    ctx.line_to(0.0, geometry.height);
+    ctx.close_path();
+
+    // Vertically the gradient covers the whole height, so a value's color depends on
+    // how high it is: that's already what `PerBarValue` asks for, and as there are no
+    // bars here to give a color each, it's what it gets
+    let gradient = match theme.bar_gradient_direction {
+        GradientDirection::Vertical | GradientDirection::PerBarValue => {
+            linear_gradient(&theme.bar_gradient, 0.0, 0.0, 0.0, geometry.height)
+        }
+        GradientDirection::Horizontal => {
+            linear_gradient(&theme.bar_gradient, 0.0, 0.0, geometry.width, 0.0)
+        }
+    };
+    ctx.set_source(&gradient).expect("Failed to set gradient");
+    ctx.fill().expect("Failed to fill wave");
+
+    ctx.append_path(&top_edge);
+    set_color(ctx, theme.stroke);
+    ctx.set_line_width(STROKE_WIDTH);
+    ctx.stroke().expect("Failed to stroke wave");
+}
+
+// Draw the bars around a circle in the middle of the area, each one a thick line
+// going outward from the inner circle
+pub fn draw_radial(
+    ctx: &Context,
+    values: &[u16],
+    geometry: Geometry,
+    theme: &Theme,
+    labels: bool,
+    inner_fraction: f64,
+) {
+    let (cx, cy) = geometry.center();
+    let (inner_r, outer_r) = geometry.radial_radii(inner_fraction);
+    let bars = values.len();
+
+    // Move the origin to the center, `radial_geometry` works relative to it
+    ctx.save().expect("Failed to save the context");
+    ctx.translate(cx, cy);
+
+    // The inner circle, so there is something to see when everything is quiet
+    set_color(ctx, theme.stroke);
+    ctx.set_line_width(1.0);
+    ctx.arc(0.0, 0.0, inner_r, 0.0, std::f64::consts::TAU);
+    ctx.stroke().expect("Failed to stroke the inner circle");
+
+    // On the inner circle, every bar gets an arc of 2π r / bars, the bars take 60% of
+    // it so there is some space between them (the bars get wider further out anyway)
+    let thickness = (std::f64::consts::TAU * inner_r / bars as f64 * 0.6).max(1.0);
+    ctx.set_line_width(thickness);
+
+    // The same colors as the bars
+    match theme.bar_gradient_direction {
+        // The bottom of a bar is on the inner circle, so the gradient goes from its
+        // end there to its start on the outer circle
+        GradientDirection::Vertical => {
+            let gradient = RadialGradient::new(0.0, 0.0, inner_r, 0.0, 0.0, outer_r);
+            for stop in theme.bar_gradient.reversed().stops() {
+                add_color_stop(&gradient, stop.offset, stop.color);
+            }
+            ctx.set_source(&gradient).expect("Failed to set gradient");
+        }
+        // Across the circle, from left to right
+        GradientDirection::Horizontal => {
+            let gradient = linear_gradient(&theme.bar_gradient, -outer_r, 0.0, outer_r, 0.0);
+            ctx.set_source(&gradient).expect("Failed to set gradient");
+        }
+        // Every bar gets a color of its own, below
+        GradientDirection::PerBarValue => {}
+    }
+    let per_bar = theme.bar_gradient_direction == GradientDirection::PerBarValue;
+    for (i, &value) in values.iter().enumerate() {
+        let (x1, y1, x2, y2) = render::radial_geometry(i, bars, value, inner_r, outer_r);
+        if per_bar {
+            let full = value as f64 / u16::MAX as f64;
+            set_color(ctx, theme.bar_gradient.color_at(1.0 - full));
+        }
+        ctx.move_to(x1, y1);
+        ctx.line_to(x2, y2);
+        // A stroke draws the whole path in one color, so with a color per bar every
+        // bar is stroked on its own, otherwise they all are at once
+        if per_bar {
+            ctx.stroke().expect("Failed to stroke the radial bar");
+        }
+    }
+    ctx.stroke().expect("Failed to stroke the radial bars");
+
+    // The bars are too close together for the y/h labels, so radial only shows the
+    // values, just outside the outer circle. With many bars only some of them get one
+    if labels {
+        set_color(ctx, theme.text);
+        ctx.set_font_size(10.0);
+        let label_every = render::label_every(bars);
+        for (i, &value) in values.iter().enumerate().step_by(label_every) {
+            let text = format!("{}", value as u64 * 100 / u16::MAX as u64);
+            let extents = ctx.text_extents(&text).expect("Failed to get text extents");
+            // The label goes where a full bar (plus a bit) would end
+            let (_, _, x, y) = render::radial_geometry(i, bars, u16::MAX, 0.0, outer_r + 8.0);
+            ctx.move_to(x - extents.width() / 2.0, y + extents.height() / 2.0);
+            ctx.show_text(&text).expect("Failed to draw text");
+        }
+    }
+
+    ctx.restore().expect("Failed to restore the context");
+}
+
+// Draw every bar as two halves, one above and one below the middle line
+pub fn draw_mirrored(ctx: &Context, values: &[u16], geometry: Geometry, theme: &Theme) {
+    // The gradient mirrors too: its start (the bright color by default) is in the
+    // middle where the halves meet, its end at both ends
+    let mirrored = theme.bar_gradient.mirrored();
+    for bar in render::mirrored_rects(values, geometry) {
+        let (upper, lower) = (bar.upper, bar.lower);
+        let top = upper.y;
+        let bottom = lower.y + lower.height;
+
+        set_bar_source(ctx, theme, &mirrored, geometry, upper.x, top, bottom);
+        ctx.rectangle(upper.x, upper.y, upper.width, upper.height);
+        ctx.rectangle(lower.x, lower.y, lower.width, lower.height);
+        ctx.fill().expect("Failed to fill bar");
+
+        // One outline around both halves, there is no line between them
+        set_color(ctx, theme.stroke);
+        ctx.set_line_width(STROKE_WIDTH);
+        ctx.rectangle(upper.x, top, upper.width, bottom - top);
+        ctx.stroke().expect("Failed to stroke bar");
+    }
+}
+
+// The draw rate, the rate frames come in at, the draws the limiter skipped (see
+// FrameLimiter) and the frames left out for having the wrong number of bars (see
+// FrameGate, both only count up), in the top-right corner of the area
+// The background is the theme's with some transparency, so the numbers can be read
+// over the bars. It's drawn straight on the screen after the rendered bars are copied,
+// so showing it doesn't make the bars render again.
+pub fn draw_fps(
+    ctx: &Context,
+    width: f64,
+    draws: f64,
+    frames: f64,
+    skipped: u64,
+    ignored: u64,
+    theme: &Theme,
+) {
+    const MARGIN: f64 = 8.0;
+    const PADDING: f64 = 8.0;
+    const LINE_HEIGHT: f64 = 16.0;
+
+    // "data" is the frames from cava (or the synthetic source): when it's lower than
+    // the framerate, cava is late, when only "draw" is low, GTK is. A "skip" going up
+    // fast means the bars want to be drawn more often than `--max-fps`, "old" goes up
+    // by a few when the number of bars changes (frames of the visualizer we replaced)
+    let lines = [
+        format!("draw {:5.1} fps", draws),
+        format!("data {:5.1} fps", frames),
+        format!("skip {:9}", skipped),
+        format!("old  {:9}", ignored),
+    ];
+
+    ctx.save().expect("Failed to save the context");
+    // Monospace, so the box doesn't change size when the numbers change
+    ctx.select_font_face("monospace", FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(12.0);
+    let text_width = lines
+        .iter()
+        .map(|line| {
+            ctx.text_extents(line)
+                .expect("Failed to get text extents")
+                .x_advance()
+        })
+        .fold(0.0, f64::max);
+
+    let box_width = text_width + 2.0 * PADDING;
+    let box_height = lines.len() as f64 * LINE_HEIGHT + PADDING;
+    let x = width - MARGIN - box_width;
+    let y = MARGIN;
+    rounded_rect(ctx, x, y, box_width, box_height, PADDING);
+    set_color(
+        ctx,
+        Color {
+            a: 0.7,
+            ..theme.background.color()
+        },
+    );
+    ctx.fill().expect("Failed to fill the fps background");
+
+    set_color(ctx, theme.text);
+    for (i, line) in lines.iter().enumerate() {
+        // `move_to` sets where the baseline of the text starts
+        ctx.move_to(x + PADDING, y + (i + 1) as f64 * LINE_HEIGHT);
+        ctx.show_text(line).expect("Failed to draw text");
+    }
+    ctx.restore().expect("Failed to restore the context");
+}
+
+// "paused" in the top-left corner, on the same background as the rates (they are in
+// the other corner so both can be shown)
+pub fn draw_paused(ctx: &Context, theme: &Theme) {
+    const MARGIN: f64 = 8.0;
+    const PADDING: f64 = 8.0;
+
+    ctx.save().expect("Failed to save the context");
+    ctx.set_font_size(12.0);
+    let text = "paused";
+    let extents = ctx.text_extents(text).expect("Failed to get text extents");
+
+    let box_width = extents.x_advance() + 2.0 * PADDING;
+    let box_height = extents.height() + 2.0 * PADDING;
+    rounded_rect(ctx, MARGIN, MARGIN, box_width, box_height, PADDING);
+    set_color(
+        ctx,
+        Color {
+            a: 0.7,
+            ..theme.background.color()
+        },
+    );
+    ctx.fill().expect("Failed to fill the paused background");
+
+    // `y_bearing` is how far above the baseline the text starts (a negative number)
+    set_color(ctx, theme.text);
+    ctx.move_to(MARGIN + PADDING, MARGIN + PADDING - extents.y_bearing());
+    ctx.show_text(text).expect("Failed to draw text");
+    ctx.restore().expect("Failed to restore the context");
+}
+
+// The name of the theme, for a moment after `t`, in the bottom-left corner on the same
+// background as the rates (in the new theme's colors)
+pub fn draw_theme_name(ctx: &Context, height: f64, name: &str, theme: &Theme) {
+    const MARGIN: f64 = 8.0;
+    const PADDING: f64 = 8.0;
+
+    ctx.save().expect("Failed to save the context");
+    ctx.set_font_size(14.0);
+    let extents = ctx.text_extents(name).expect("Failed to get text extents");
+
+    let box_width = extents.x_advance() + 2.0 * PADDING;
+    let box_height = extents.height() + 2.0 * PADDING;
+    let y = height - MARGIN - box_height;
+    rounded_rect(ctx, MARGIN, y, box_width, box_height, PADDING);
+    set_color(
+        ctx,
+        Color {
+            a: 0.7,
+            ..theme.background.color()
+        },
+    );
+    ctx.fill().expect("Failed to fill the name background");
+
+    set_color(ctx, theme.text);
+    ctx.move_to(MARGIN + PADDING, y + PADDING - extents.y_bearing());
+    ctx.show_text(name).expect("Failed to draw text");
+    ctx.restore().expect("Failed to restore the context");
+}
+
+// The track playing, centered near the top on the same background as the rates,
+// `opacity` from 0 (not there) to 1 for the fades (see nowplaying.rs)
+// A title too long for the area starts at the left margin and is cut on the right
+pub fn draw_now_playing(ctx: &Context, width: f64, text: &str, opacity: f64, theme: &Theme) {
+    const MARGIN: f64 = 8.0;
+    const PADDING: f64 = 8.0;
+
+    ctx.save().expect("Failed to save the context");
+    ctx.set_font_size(14.0);
+    let extents = ctx.text_extents(text).expect("Failed to get text extents");
+
+    let box_width = extents.x_advance() + 2.0 * PADDING;
+    let box_height = extents.height() + 2.0 * PADDING;
+    let x = ((width - box_width) / 2.0).max(MARGIN);
+    rounded_rect(ctx, x, MARGIN, box_width, box_height, PADDING);
+    set_color(
+        ctx,
+        Color {
+            a: 0.7 * opacity,
+            ..theme.background.color()
+        },
+    );
+    ctx.fill().expect("Failed to fill the track background");
+
+    set_color(
+        ctx,
+        Color {
+            a: theme.text.a * opacity,
+            ..theme.text
+        },
+    );
+    ctx.move_to(x + PADDING, MARGIN + PADDING - extents.y_bearing());
+    ctx.show_text(text).expect("Failed to draw text");
+    ctx.restore().expect("Failed to restore the context");
+}
+
+// A rectangle with its corners rounded, each corner is a quarter of a circle of
+// `radius`, going clockwise from the top-left one
+// The radius shrinks to fit small rectangles (see `render::corner_radius`), and
+// without one it's a plain rectangle, cheaper than four arcs of nothing
+pub fn rounded_rect(ctx: &Context, x: f64, y: f64, width: f64, height: f64, radius: f64) {
+    let radius = render::corner_radius(radius, width, height);
+    if radius <= 0.0 {
+        ctx.rectangle(x, y, width, height);
+        return;
+    }
+    ctx.new_sub_path();
+    ctx.arc(x + radius, y + radius, radius, PI, PI + FRAC_PI_2);
+    ctx.arc(x + width - radius, y + radius, radius, -FRAC_PI_2, 0.0);
+    ctx.arc(
+        x + width - radius,
+        y + height - radius,
+        radius,
+        0.0,
+        FRAC_PI_2,
+    );
+    ctx.arc(x + radius, y + height - radius, radius, FRAC_PI_2, PI);
+    ctx.close_path();
+}
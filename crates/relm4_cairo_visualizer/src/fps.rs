@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Counts how often something happens per second, e.g. how many times the draw
+// function ran during the last second
+//
+// We keep the time of every event of the last second, and the rate is how many there
+// are. Older ones are dropped as new ones come in, so the buffer never holds more
+// than a second of events. It's capped anyway, a burst of draws (resizing the window
+// quickly) shouldn't make it grow forever: past the cap the oldest ones are dropped
+// and the rate reads lower than it is.
+
+// How far back we look
+pub const FPS_WINDOW: Duration = Duration::from_secs(1);
+// Twice the highest framerate we accept (MAX_FRAMERATE)
+const CAPACITY: usize = 480;
+
+pub struct FpsCounter {
+    ticks: VecDeque<Instant>,
+}
+
+impl FpsCounter {
+    pub fn new() -> FpsCounter {
+        FpsCounter {
+            ticks: VecDeque::with_capacity(CAPACITY),
+        }
+    }
+
+    // Something happened at `now`
+    // `now` is a parameter (and not Instant::now()) so the counter can be checked
+    // with made up times
+    pub fn tick(&mut self, now: Instant) {
+        if self.ticks.len() == CAPACITY {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(now);
+        self.forget_before(now);
+    }
+
+    // How many times something happened during the second before `now`
+    pub fn fps(&mut self, now: Instant) -> f64 {
+        self.forget_before(now);
+        self.ticks.len() as f64 / FPS_WINDOW.as_secs_f64()
+    }
+
+    fn forget_before(&mut self, now: Instant) {
+        // `checked_sub`: right after the machine booted, `now` can be less than a
+        // second after the earliest Instant there is
+        let Some(start) = now.checked_sub(FPS_WINDOW) else {
+            return;
+        };
+        while self.ticks.front().is_some_and(|&tick| tick <= start) {
+            self.ticks.pop_front();
+        }
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> FpsCounter {
+        FpsCounter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn nothing_happened_is_zero() {
+        let mut counter = FpsCounter::new();
+        assert_eq!(counter.fps(Instant::now()), 0.0);
+    }
+
+    #[test]
+    fn counts_the_ticks_of_the_last_second() {
+        let start = Instant::now();
+        let mut counter = FpsCounter::new();
+        // 60 fps for two seconds
+        for i in 0..120 {
+            counter.tick(start + ms(i * 1000 / 60));
+        }
+        assert_eq!(counter.fps(start + ms(1990)), 60.0);
+    }
+
+    #[test]
+    fn old_ticks_are_forgotten() {
+        let start = Instant::now();
+        let mut counter = FpsCounter::new();
+        for i in 0..10 {
+            counter.tick(start + ms(i * 100));
+        }
+        assert_eq!(counter.fps(start + ms(950)), 10.0);
+        // Exactly a second after the first tick, it's out
+        assert_eq!(counter.fps(start + ms(1000)), 9.0);
+        assert_eq!(counter.fps(start + ms(1450)), 5.0);
+        assert_eq!(counter.fps(start + ms(5000)), 0.0);
+    }
+
+    #[test]
+    fn a_burst_is_capped() {
+        let now = Instant::now();
+        let mut counter = FpsCounter::new();
+        for _ in 0..CAPACITY * 3 {
+            counter.tick(now);
+        }
+        assert_eq!(counter.ticks.len(), CAPACITY);
+        assert_eq!(counter.fps(now), CAPACITY as f64);
+    }
+}
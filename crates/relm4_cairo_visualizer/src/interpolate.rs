@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use crate::visualizer::Frame;
+
+// Smooth bars between frames
+//
+// cava sends frames at its own rate (60 per second by default, often less), the
+// screen refreshes at its own (60, 120, 144 times per second...). Drawing every frame
+// as it comes makes the bars jump from one value to the next. Instead the bars on
+// screen move towards the latest values a bit on every refresh of the screen.
+//
+// The latest values are the target, the bars on screen (`displayed`) close a part of
+// the distance to it on every step: the further they are, the faster they move, and
+// they slow down as they get there. The part they close depends on the time since the
+// previous step, not on the number of steps, so the bars move as fast on a 60Hz
+// screen as on a 144Hz one.
+//
+// The displayed values are f64, in the same units as the frame values, so they can
+// move by less than one unit per step.
+
+// After this long a bar has closed 63% of the distance to its target (1 - 1/e), 95%
+// after three times as long
+pub const TIME_CONSTANT: Duration = Duration::from_millis(40);
+// Closer than that to its target a bar stops moving and takes the target value
+// A thousandth of the full height, less than a pixel in all but the tallest windows
+pub const SETTLE_DISTANCE: f64 = u16::MAX as f64 / 1000.0;
+
+// The part of the distance to close after `elapsed`
+// Moving by 1 - e^(-t/τ) of the distance, twice in a row for t/2, leaves
+// e^(-t/2τ) * e^(-t/2τ) = e^(-t/τ) of it: the same as once for t. That's what makes
+// it independent of the framerate.
+pub fn approach_factor(elapsed: Duration) -> f64 {
+    1.0 - (-elapsed.as_secs_f64() / TIME_CONSTANT.as_secs_f64()).exp()
+}
+
+// Move the displayed values on by `elapsed` towards the `targets`
+// Returns whether any of them moved, i.e. the bars have to be drawn again. Once they
+// are all at their target this returns false, and nothing is drawn until new values
+// come in.
+pub fn interpolate(displayed: &mut Vec<f64>, targets: &[u16], elapsed: Duration) -> bool {
+    // The first frame, or the number of bars changed: nothing to move from
+    if displayed.len() != targets.len() {
+        *displayed = targets.iter().map(|&target| target as f64).collect();
+        return true;
+    }
+
+    let factor = approach_factor(elapsed);
+    let mut moved = false;
+    for (value, &target) in displayed.iter_mut().zip(targets) {
+        let target = target as f64;
+        if *value == target {
+            continue;
+        }
+        let next = *value + (target - *value) * factor;
+        *value = if (target - next).abs() < SETTLE_DISTANCE {
+            target
+        } else {
+            next
+        };
+        moved = true;
+    }
+    moved
+}
+
+// The frame to draw: the latest one, with the displayed values instead of its own
+// Until the displayed values caught up with a change in the number of bars they
+// don't fit the frame, then it's drawn as it is
+pub fn displayed_frame(target: &Frame, displayed: &[f64]) -> Frame {
+    let mut frame = target.clone();
+    if displayed.len() == frame.values.len() {
+        frame.values = displayed
+            .iter()
+            .map(|&value| value.round() as u16)
+            .collect();
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn the_first_frame_is_shown_as_it_is() {
+        let mut displayed = Vec::new();
+        assert!(interpolate(&mut displayed, &[10, 20], ms(16)));
+        assert_eq!(displayed, vec![10.0, 20.0]);
+    }
+
+    #[test]
+    fn one_time_constant_closes_63_percent() {
+        let mut displayed = vec![0.0];
+        interpolate(&mut displayed, &[10_000], TIME_CONSTANT);
+        let expected = 10_000.0 * (1.0 - (-1.0f64).exp());
+        assert!((displayed[0] - expected).abs() < 1e-6);
+    }
+
+    // A sixth of a second on a 144Hz screen, a 60Hz one and a 30Hz one ends at the same
+    // place (not long enough for the bar to settle)
+    #[test]
+    fn the_approach_does_not_depend_on_the_framerate() {
+        let after = |steps: u32, step: Duration| {
+            let mut displayed = vec![0.0];
+            for _ in 0..steps {
+                interpolate(&mut displayed, &[u16::MAX], step);
+            }
+            displayed[0]
+        };
+        let a = after(5 * 144 / 30, Duration::from_secs(1) / 144);
+        let b = after(5 * 60 / 30, Duration::from_secs(1) / 60);
+        let c = after(5, Duration::from_secs(1) / 30);
+        assert!((a - c).abs() < 1e-3, "{} {}", a, c);
+        assert!((b - c).abs() < 1e-3, "{} {}", b, c);
+    }
+
+    #[test]
+    fn the_factor_is_between_0_and_1() {
+        assert_eq!(approach_factor(Duration::ZERO), 0.0);
+        assert!(approach_factor(ms(16)) > 0.0);
+        assert!(approach_factor(Duration::from_secs(3600)) <= 1.0);
+    }
+
+    #[test]
+    fn a_bar_close_to_its_target_settles_on_it() {
+        let mut displayed = vec![1000.0];
+        assert!(interpolate(&mut displayed, &[1050], ms(16)));
+        assert_eq!(displayed, vec![1050.0]);
+        // And then nothing moves anymore
+        assert!(!interpolate(&mut displayed, &[1050], ms(16)));
+    }
+
+    #[test]
+    fn the_bars_get_there_in_the_end() {
+        let mut displayed = vec![0.0, u16::MAX as f64];
+        let mut steps = 0;
+        while interpolate(&mut displayed, &[u16::MAX, 0], ms(16)) {
+            steps += 1;
+            assert!(steps < 100);
+        }
+        assert_eq!(displayed, vec![u16::MAX as f64, 0.0]);
+    }
+
+    #[test]
+    fn the_displayed_frame_has_the_displayed_values() {
+        let target = Frame::mono(vec![100, 200]);
+        assert_eq!(
+            displayed_frame(&target, &[49.6, 150.2]).values,
+            vec![50, 150]
+        );
+        // The number of bars just changed, the values don't fit
+        assert_eq!(displayed_frame(&target, &[1.0]).values, vec![100, 200]);
+    }
+}
@@ -0,0 +1,4 @@
+// The audio side of the visualizer: cava (or another source), the stages and the
+// frames going out. It doesn't know anything about GTK, so the tests in tests/ (and
+// any other program) can drive it without opening a window. The app is in main.rs
+pub mod visualizer;
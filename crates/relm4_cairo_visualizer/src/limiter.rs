@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant};
+
+// Keeps the bars from being drawn more often than `--max-fps`
+//
+// The bars want a draw whenever they move: on every refresh of the screen while they
+// glide, on every frame from cava for the peaks. On a slow machine all those draws
+// make the GTK main loop fall behind, and the key presses wait behind them.
+// So a draw that comes too soon after the last one waits: one draw is scheduled for
+// when it's allowed, and whatever else comes in until then is drawn by that one (the
+// draw uses the latest values anyway, so nothing is lost but the in-between states).
+//
+// The times are parameters, not Instant::now(), so it can be checked with made up
+// clocks.
+
+pub const DEFAULT_MAX_FPS: u32 = 60;
+
+// What to do about a draw that was asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    // Draw now
+    Draw,
+    // Too soon: schedule a draw in this long, and call `fire` when it happens
+    Later(Duration),
+    // A draw is scheduled already, it will show this too
+    Pending,
+}
+
+#[derive(Debug)]
+pub struct FrameLimiter {
+    // The least time between two draws, 1/max_fps
+    min_interval: Duration,
+    last_draw: Option<Instant>,
+    // Whether a draw is scheduled (we said Later, `fire` wasn't called yet)
+    pending: bool,
+    // How many draws were folded into a later one
+    skipped: u64,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: u32) -> FrameLimiter {
+        FrameLimiter {
+            min_interval: Duration::from_secs(1) / max_fps.max(1),
+            last_draw: None,
+            pending: false,
+            skipped: 0,
+        }
+    }
+
+    // Someone wants a draw at `now`
+    pub fn request(&mut self, now: Instant) -> Decision {
+        if self.pending {
+            self.skipped += 1;
+            return Decision::Pending;
+        }
+        let since_last = self.last_draw.map_or(self.min_interval, |last| {
+            now.saturating_duration_since(last)
+        });
+        if since_last >= self.min_interval {
+            self.last_draw = Some(now);
+            Decision::Draw
+        } else {
+            self.pending = true;
+            self.skipped += 1;
+            Decision::Later(self.min_interval - since_last)
+        }
+    }
+
+    // The scheduled draw happens at `now`
+    pub fn fire(&mut self, now: Instant) {
+        self.pending = false;
+        self.last_draw = Some(now);
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn the_first_request_is_drawn() {
+        let mut limiter = FrameLimiter::new(10);
+        assert_eq!(limiter.request(Instant::now()), Decision::Draw);
+        assert_eq!(limiter.skipped(), 0);
+    }
+
+    #[test]
+    fn a_request_too_soon_waits_for_the_rest_of_the_interval() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(10);
+        limiter.request(start);
+
+        assert_eq!(limiter.request(start + ms(30)), Decision::Later(ms(70)));
+        assert_eq!(limiter.skipped(), 1);
+    }
+
+    #[test]
+    fn requests_meanwhile_are_folded_into_the_scheduled_draw() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(10);
+        limiter.request(start);
+        limiter.request(start + ms(30));
+
+        for t in [40, 60, 99] {
+            assert_eq!(limiter.request(start + ms(t)), Decision::Pending);
+        }
+        assert_eq!(limiter.skipped(), 4);
+
+        // The scheduled draw happens, the next request counts from there
+        limiter.fire(start + ms(100));
+        assert_eq!(limiter.request(start + ms(150)), Decision::Later(ms(50)));
+    }
+
+    #[test]
+    fn a_request_after_the_interval_is_drawn() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(10);
+        limiter.request(start);
+        assert_eq!(limiter.request(start + ms(100)), Decision::Draw);
+        assert_eq!(limiter.request(start + ms(250)), Decision::Draw);
+        assert_eq!(limiter.skipped(), 0);
+    }
+
+    // A clock going backwards is treated as no time having passed
+    #[test]
+    fn an_earlier_time_waits_the_whole_interval() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(10);
+        limiter.request(start + ms(500));
+        assert_eq!(limiter.request(start), Decision::Later(ms(100)));
+    }
+
+    // 120 requests a second (a 120Hz screen) against a limit of 30: one draw in four
+    #[test]
+    fn the_draws_are_capped() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(30);
+        let mut draws = 0;
+        let mut scheduled: Option<Instant> = None;
+        for i in 0..120 {
+            let now = start + Duration::from_secs(1) * i / 120;
+            if scheduled.is_some_and(|at| at <= now) {
+                limiter.fire(scheduled.take().unwrap());
+                draws += 1;
+            }
+            match limiter.request(now) {
+                Decision::Draw => draws += 1,
+                Decision::Later(wait) => scheduled = Some(now + wait),
+                Decision::Pending => {}
+            }
+        }
+        assert_eq!(draws, 30);
+        // Past the first one, every request comes too soon after a draw and is left
+        // to a scheduled one
+        assert_eq!(limiter.skipped(), 119);
+    }
+
+    #[test]
+    fn a_limit_of_0_is_1() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(0);
+        limiter.request(start);
+        assert_eq!(limiter.request(start + ms(1)), Decision::Later(ms(999)));
+    }
+}
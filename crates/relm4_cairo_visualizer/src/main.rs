@@ -1,207 +1,643 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use relm4::gtk::cairo::LinearGradient;
+use background::BackgroundImage;
+use cli::{Cli, Resolution, SourceKind};
+use controls::FrameGate;
+use cursor::{Hide, HIDE_CURSOR_AFTER};
+use dirty::DirtyTracker;
+use fps::FpsCounter;
+use limiter::{Decision, FrameLimiter};
+use nowplaying::{Caption, Track};
+use persist::{SavedSettings, WindowPlacement};
+use preferences::{PreferencesDialog, PreferencesMsg, PreferencesOutput};
+use relm4::gtk::cairo::{self, Context, Format, ImageSurface};
 use relm4::gtk::prelude::*;
-use relm4::{gtk, Component, ComponentParts, ComponentSender, RelmApp};
-use visualizer::Visualizer;
+use relm4::{
+    gtk, Component, ComponentController, ComponentParts, ComponentSender, Controller, RelmApp,
+    RelmWidgetExt,
+};
+use relm4_cairo_visualizer::visualizer;
+use render::{Channel, ChannelView, Geometry, HoveredBar, Point, RenderStyle};
+use screenshot::ScreenshotError;
+use settings::Settings;
+use theme::{Background, Theme, Transparency};
+use themes::ThemeCycle;
+use visualizer::{ChannelLayout, Frame, Visualizer, VisualizerError, Waveform};
 
-pub mod visualizer;
+pub mod background;
+pub mod cli;
+pub mod controls;
+pub mod cursor;
+pub mod dirty;
+pub mod draw;
+pub mod fps;
+pub mod interpolate;
+pub mod limiter;
+#[cfg(feature = "mpris")]
+pub mod mpris;
+pub mod nowplaying;
+pub mod pause;
+pub mod peaks;
+pub mod persist;
+pub mod preferences;
+pub mod render;
+pub mod screenshot;
+pub mod settings;
+pub mod theme;
+pub mod themes;
+pub mod tui;
 
 pub struct AppModel {
-    // The number of bars we want to show
-    bars: usize,
+    // The number of bars, framerate and smoothing the visualizer runs with
+    settings: Settings,
+    source: SourceKind,
+    // Whether the y/h labels are drawn above the bars, `d` turns them on and off
+    // Shared with the drawing closure, they are part of the rendered bars
+    show_debug_labels: Rc<Cell<bool>>,
+    // Where the radial bars start, a fraction of the radius
+    inner_radius: f64,
+    // Transparent with `--transparent`, if the desktop can show it
+    transparency: Transparency,
+    // The theme's background image, loaded, None when it has none
+    background_image: Rc<Option<BackgroundImage>>,
+    // Bars, waveform or radial, shared with the drawing closure so it can change while we run
+    style: Rc<Cell<RenderStyle>>,
+    // Where the channels of a stereo frame go, `c` switches, shared like the style
+    channel_view: Rc<Cell<ChannelView>>,
+    // Bumped every time we start a visualizer, the older ones see it and stop
+    generation: Arc<AtomicU64>,
+    preferences: Controller<PreferencesDialog>,
     // We use Rc (Reference Counted) here to allow multiple ownership of the data
     // This is necessary because both the AppModel and the drawing closure need access to bars_data
-    // RefCell provides interior mutability, allowing us to mutate the Vec<u16> even when shared
+    // RefCell provides interior mutability, allowing us to replace the frame even when shared
     // This combination enables shared mutable state across different parts of our application
-    bars_data: Rc<RefCell<Vec<u16>>>, // The cava data (smoothed by the visualizer)
-    // Whether we should keep rendering in the DrawingArea
-    should_draw: Rc<RefCell<bool>>,
+    // The frame itself is behind an Arc, the same one the visualizer sent us, so keeping
+    // the latest frame around doesn't copy the values
+    // The bars move towards these values, they are not drawn as they are
+    bars_data: Rc<RefCell<Arc<Frame>>>, // The cava data (smoothed by the visualizer)
+    // The values the bars have on screen, moving towards bars_data on every refresh of
+    // the screen (see interpolate.rs), shared like bars_data
+    displayed: Rc<RefCell<Vec<f64>>>,
+    // The peak of every bar, falling back towards it (see peaks.rs), shared like bars_data
+    peaks: Rc<RefCell<Vec<f64>>>,
+    // When the peaks were last moved, they fall by how much time passed since
+    peaks_updated: Instant,
+    // Whether the bars have to be rendered again (see DirtyTracker)
+    dirty: Rc<RefCell<DirtyTracker>>,
+    // The colors to draw with, shared with the drawing closure like the bars
+    theme: Rc<RefCell<Theme>>,
+    // The themes `t` goes through, `theme` is a copy of the current one
+    themes: ThemeCycle,
+    // The name of the theme `t` switched to, in a corner for THEME_NAME_FOR, shared
+    // with the drawing closure
+    theme_name: Rc<Cell<Option<&'static str>>>,
+    // How many times `t` was pressed, the name goes THEME_NAME_FOR after the last one
+    theme_switches: u64,
+    // Whether the draw and data rates are shown, `f` turns them on and off
+    show_fps: Rc<Cell<bool>>,
+    // How often the draw function runs, ticked by the drawing closure
+    draw_fps: Rc<RefCell<FpsCounter>>,
+    // How often frames come in from the visualizer, ticked in `update_with_view`
+    frame_fps: Rc<RefCell<FpsCounter>>,
+    // Holds back the draws asked for too soon after the last one (see limiter.rs),
+    // shared with the tick callback, and the drawing closure shows how many it skipped
+    limiter: Rc<RefCell<FrameLimiter>>,
+    // Leaves out the frames of a visualizer we replaced (see controls.rs), shared with
+    // the drawing closure which shows how many
+    frames: Rc<RefCell<FrameGate>>,
+    // Whether the bars are frozen (the spacebar), the drawing closure shows it
+    paused: Rc<Cell<bool>>,
+    // The track the music player is playing, drawn over the bars (see nowplaying.rs)
+    caption: Rc<RefCell<Caption>>,
+    // The latest frame that came in while paused, shown when we resume (see pause.rs)
+    held_frame: Option<Arc<Frame>>,
+    // The size of the window before it went fullscreen, to go back to it after
+    windowed_size: Option<(i32, i32)>,
+    // When the pointer last moved over the bars, the cursor hides 2s after that
+    last_motion: Instant,
+    // Whether a HideCursor message is on its way (see `hide_cursor_after`)
+    hide_cursor_pending: bool,
+    // Where the pointer is over the bars, None when it's elsewhere
+    // The tooltip shows the values of the bar under it (see `update_tooltip`)
+    pointer: Option<Point>,
+    // The size of the screenshots, None for the size of the window
+    screenshot_size: Option<Resolution>,
+    // Where the settings are saved for the next run, None without a home directory
+    saved_path: Option<PathBuf>,
+    // The size of the window, when it isn't fullscreen
+    window: Option<WindowPlacement>,
+    // Whether a SaveSettings message is on its way (see `schedule_save`)
+    save_pending: bool,
 }
 
+// What the bars are drawn from, for the draw function and the screenshots
+// Only clones of the model's Rcs, so it always sees the current values
+#[derive(Clone)]
+struct Scene {
+    bars_data: Rc<RefCell<Arc<Frame>>>,
+    displayed: Rc<RefCell<Vec<f64>>>,
+    peaks: Rc<RefCell<Vec<f64>>>,
+    theme: Rc<RefCell<Theme>>,
+    style: Rc<Cell<RenderStyle>>,
+    channel_view: Rc<Cell<ChannelView>>,
+    show_debug_labels: Rc<Cell<bool>>,
+    inner_radius: f64,
+    transparency: Transparency,
+    background_image: Rc<Option<BackgroundImage>>,
+}
+
+impl Scene {
+    // Draw the bars as they are now on `ctx`, `width` by `height` pixels
+    fn draw(&self, ctx: &Context, width: i32, height: i32) -> Result<(), cairo::Error> {
+        // The bars are drawn where they are on their way to the latest values
+        let frame =
+            interpolate::displayed_frame(&self.bars_data.borrow(), &self.displayed.borrow());
+        let options = draw::RenderOptions {
+            style: self.style.get(),
+            debug_labels: self.show_debug_labels.get(),
+            inner_radius: self.inner_radius,
+            channel_view: self.channel_view.get(),
+        };
+        let theme = self.theme.borrow();
+        draw::draw_background(
+            ctx,
+            width,
+            height,
+            &theme,
+            self.transparency,
+            (*self.background_image).as_ref(),
+        )?;
+        draw::draw_scene(
+            ctx,
+            &frame,
+            &self.peaks.borrow(),
+            width,
+            height,
+            options,
+            &theme,
+        )
+    }
+
+    // The same, into a new offscreen surface
+    fn render(&self, width: i32, height: i32) -> Result<ImageSurface, cairo::Error> {
+        let surface = ImageSurface::create(Format::ARgb32, width, height)?;
+        self.draw(&Context::new(&surface)?, width, height)?;
+        Ok(surface)
+    }
+}
+
+// The title of the window, when it doesn't show a message for a moment
+const TITLE: &str = "Simple Manual";
+// How long a message stays in the title (see `show_in_title`)
+const TITLE_MESSAGE_FOR: Duration = Duration::from_secs(3);
+
+// How long the name of the theme stays on screen after `t`
+const THEME_NAME_FOR: Duration = Duration::from_secs(1);
+
 #[derive(Debug)]
 pub enum AppMsg {
-    UpdateBarValues(Vec<u16>),
+    UpdateBarValues(Arc<Frame>),
+    ShowPreferences,
+    SetStyle(RenderStyle),
+    // The next channel view, see ChannelView
+    NextChannelView,
+    ToggleFps,
+    ToggleDebugLabels,
+    TogglePause,
+    // The pause button of the header bar, pressed or not
+    SetPaused(bool),
+    // The bar count of the header bar
+    SetBars(i32),
+    // `h`, shows the header bar in fullscreen, or hides it again
+    ToggleHeaderBar,
+    // `t`, the next theme (see themes.rs)
+    NextTheme,
+    // THEME_NAME_FOR after `t`, with the number of that `t`: the name of the theme
+    // goes if it wasn't pressed again since
+    HideThemeName(u64),
+    // Save the bars as a PNG in ~/Pictures
+    Screenshot,
+    // F11 and double-click
+    ToggleFullscreen,
+    // Escape, does nothing when we're not fullscreen
+    ExitFullscreen,
+    // Where the pointer is now, over the bars
+    PointerMoved(f64, f64),
+    // It left the bars
+    PointerLeft,
+    HideCursor,
+    // Restart the visualizer with new settings
+    ApplySettings(Settings),
+    // The window was resized, maximized or restored
+    WindowChanged,
+    // Write the settings, a moment after they changed
+    SaveSettings,
+    // The music player plays another track (only with the `mpris` feature)
+    NowPlaying { title: String, artist: String },
+    // It stopped, or paused, or quit
+    NothingPlaying,
+    // The visualizer couldn't start, with what to tell the user
+    BackendError(String),
+    // The Retry button under that message
+    RetryBackend,
 }
 
 #[relm4::component(pub)]
 impl Component for AppModel {
     type Input = AppMsg;
     type Output = ();
-    type Init = usize;
+    // Everything the app needs to start comes from the command line (and --config)
+    type Init = Cli;
     type CommandOutput = ();
 
     view! {
         gtk::ApplicationWindow {
-            set_title: Some("Simple Manual"),
-            #[name="root"]
-            gtk::DrawingArea {
-                set_draw_func: {
-                    // We need to clone the Rc<RefCell> so we can move it into the closure
-                    // The closure will take ownership of the data and the should_draw flag
-                    let bars_data = model.bars_data.clone();
-                    let should_draw = model.should_draw.clone();
-                    move |_, ctx, width, height| {
-                        let area_width = width as f64;
-                        let area_height = height as f64;
-
-                        // `ctx` is a cairo context used for drawing on the surface
-                        // `area_width` and `area_height` represent the dimensions of the DrawingArea
-                        // Drawing occurs only when the `should_draw` flag is true
-                        // The flag is set to false when there's no change in the visualizer data
-                        // This optimization prevents unnecessary redrawing of the bars
-                        if !*should_draw.borrow() {
-                            ()
+            set_title: Some(TITLE),
+            // The size is saved for the next run
+            connect_default_width_notify => AppMsg::WindowChanged,
+            connect_default_height_notify => AppMsg::WindowChanged,
+            connect_maximized_notify => AppMsg::WindowChanged,
+            // The number of bars, the style and pause, which can also be changed with
+            // the preferences and the keys: they are set to match then (see controls.rs)
+            #[wrap(Some)]
+            #[name = "header"]
+            set_titlebar = &gtk::HeaderBar {
+                #[name = "bars"]
+                pack_start = &gtk::SpinButton::with_range(settings::MIN_BARS as f64, settings::MAX_BARS as f64, 1.0) {
+                    set_tooltip_text: Some("Bars"),
+                    set_value: model.settings.bars as f64,
+                    connect_value_changed[sender] => move |spin| {
+                        sender.input(AppMsg::SetBars(spin.value_as_int()));
+                    },
+                },
+                #[name = "style"]
+                pack_start = &gtk::DropDown::from_strings(&controls::style_labels()) {
+                    set_tooltip_text: Some("Style (b, w, r, m)"),
+                    set_focus_on_click: false,
+                    set_selected: controls::style_position(model.style.get()),
+                    connect_selected_notify[sender] => move |dropdown| {
+                        if let Some(style) = controls::style_at(dropdown.selected()) {
+                            sender.input(AppMsg::SetStyle(style));
+                        }
+                    },
+                },
+                #[name = "pause"]
+                pack_end = &gtk::ToggleButton {
+                    set_icon_name: "media-playback-pause-symbolic",
+                    set_tooltip_text: Some("Pause (Space)"),
+                    set_focus_on_click: false,
+                    connect_toggled[sender] => move |button| {
+                        sender.input(AppMsg::SetPaused(button.is_active()));
+                    },
+                },
+            },
+            // Ctrl+, opens the preferences, like in most GTK apps
+            // `b`, `w`, `r` and `m` switch between the bars, the waveform, the radial bars
+            // and the mirrored bars, `f` shows or hides the draw and data rates and `d`
+            // the labels on the bars, `c` splits a stereo frame in halves (two ways)
+            // F11 (or a double-click on the bars) goes fullscreen, Escape leaves it, `h`
+            // brings back the header bar there
+            // Space freezes the bars, and starts them again, `s` takes a screenshot and
+            // `t` switches to the next theme
+            add_controller = gtk::EventControllerKey {
+                connect_key_pressed[sender] => move |_, key, _, modifiers| {
+                    let control = modifiers.contains(gtk::gdk::ModifierType::CONTROL_MASK);
+                    let message = match key {
+                        gtk::gdk::Key::comma if control => AppMsg::ShowPreferences,
+                        gtk::gdk::Key::b if !control => AppMsg::SetStyle(RenderStyle::Bars),
+                        gtk::gdk::Key::w if !control => AppMsg::SetStyle(RenderStyle::Waveform),
+                        gtk::gdk::Key::r if !control => AppMsg::SetStyle(RenderStyle::Radial),
+                        gtk::gdk::Key::m if !control => AppMsg::SetStyle(RenderStyle::Mirrored),
+                        gtk::gdk::Key::c if !control => AppMsg::NextChannelView,
+                        gtk::gdk::Key::f if !control => AppMsg::ToggleFps,
+                        gtk::gdk::Key::d if !control => AppMsg::ToggleDebugLabels,
+                        gtk::gdk::Key::space if !control => AppMsg::TogglePause,
+                        gtk::gdk::Key::s if !control => AppMsg::Screenshot,
+                        gtk::gdk::Key::h if !control => AppMsg::ToggleHeaderBar,
+                        gtk::gdk::Key::t if !control => AppMsg::NextTheme,
+                        gtk::gdk::Key::F11 => AppMsg::ToggleFullscreen,
+                        gtk::gdk::Key::Escape => AppMsg::ExitFullscreen,
+                        _ => return gtk::glib::Propagation::Proceed,
+                    };
+                    sender.input(message);
+                    gtk::glib::Propagation::Stop
+                }
+            },
+            // The bars, or what went wrong when there can't be any (see BackendError)
+            #[name = "pages"]
+            gtk::Stack {
+                #[name="root"]
+                add_named[Some("bars")] = &gtk::DrawingArea {
+                    // Below that the bars get too thin to see, see `render::layout`
+                    set_size_request: (render::MIN_AREA_WIDTH, render::MIN_AREA_HEIGHT),
+                    // `n_press` counts the clicks in a row, 2 is a double-click
+                    add_controller = gtk::GestureClick {
+                        connect_pressed[sender] => move |_, n_press, _, _| {
+                            if n_press == 2 {
+                                sender.input(AppMsg::ToggleFullscreen);
+                            }
                         }
+                    },
+                    add_controller = gtk::EventControllerMotion {
+                        connect_motion[sender] => move |_, x, y| {
+                            sender.input(AppMsg::PointerMoved(x, y));
+                        },
+                        connect_leave => AppMsg::PointerLeft,
+                    },
+                    set_draw_func: {
+                        // We need to clone the Rc<RefCell> so we can move them into the closure
+                        // The closure will take ownership of the data and the dirty tracker
+                        let scene = model.scene();
+                        let dirty = model.dirty.clone();
+                        let show_fps = model.show_fps.clone();
+                        let draw_fps = model.draw_fps.clone();
+                        let frame_fps = model.frame_fps.clone();
+                        let limiter = model.limiter.clone();
+                        let frames = model.frames.clone();
+                        let paused = model.paused.clone();
+                        let caption = model.caption.clone();
+                        let theme_name = model.theme_name.clone();
+                        // The bars rendered offscreen, only the closure needs it so it owns it
+                        // It's in a RefCell because the draw function is a `Fn`, not a `FnMut`
+                        let surface: RefCell<Option<ImageSurface>> = RefCell::new(None);
+                        move |_, ctx, width, height| {
+                            // `ctx` is a cairo context used for drawing on the surface
+                            // `width` and `height` represent the dimensions of the DrawingArea
+                            // The bars are only rendered again when the data changed or the
+                            // area was resized, otherwise we reuse the previous surface
+                            let mut dirty = dirty.borrow_mut();
+                            let mut surface = surface.borrow_mut();
+                            let theme = scene.theme.borrow();
+                            if dirty.needs_render(width, height) {
+                                match scene.render(width, height) {
+                                    Ok(rendered) => {
+                                        *surface = Some(rendered);
+                                        dirty.rendered(width, height);
+                                    }
+                                    Err(e) => eprintln!("Failed to render the bars: {}", e),
+                                }
+                            }
 
-                        // Paint the background to dark
-                        ctx.set_source_rgb(0.0, 0.0, 0.0);
-                        ctx.paint().unwrap();
-
-                        // Calculate the width of each bar
-                        // Formula: bar_width = DrawingArea_width / number_of_bars
-                        // Example: For 20 bars in an 800px wide area, each bar is 40px wide
-                        // This ensures bars are evenly distributed across the available space
-                        let stroke_width = 4.0f64;
-                        let padding = 10.0;
-                        let bar_width = area_width / model.bars as f64;
-
-                        // Since we are using a RefCell, we need to borrow the data inside of it
-                        let bars_data = bars_data.borrow();
-
-                        // Iterate over the bars data, drawing each bar as a rectangle
-                        // The index 'i' determines the bar's horizontal position, while 'bar_height' sets its vertical size
-                        for (i, &bar_height) in bars_data.iter().enumerate() {
-                            // Calculate the X position of each bar
-                            // The X position is determined by the bar's index (i) multiplied by the bar width
-                            // This ensures equal spacing between bars across the drawing area
-                            // Example:
-                            //   let bar_width = 50.0;
-                            //   for i in 0..5 {
-                            //       let x = i as f64 * bar_width;
-                            //       println!("Bar {}: x = {}", i, x);
-                            //   }
-                            // Output:
-                            //   Bar 0: x = 0.0
-                            //   Bar 1: x = 50.0
-                            //   Bar 2: x = 100.0
-                            //   Bar 3: x = 150.0
-                            //   Bar 4: x = 200.0
-                            let x = (i as f64 * bar_width) + padding / 2.0;
-                            let bar_width = bar_width - padding;
-
-                            // Calculate the height of each bar
-                            // The bar height is normalized by dividing the current value by the maximum possible value (u16::MAX = 65535)
-                            // This ensures that the bar heights are proportional to their values and fit within the drawing area
-                            let height = (bar_height as u64 * height as u64) / u16::MAX as u64;
-
-                            // Calculate the Y position of the bar
-                            // The Y position is determined by subtracting the bar's height from the drawing area's height
-                            // This positions the bar from the bottom of the drawing area
-                            // Example:
-                            //   let area_height = 200.0;
-                            //   let bar_height = 50.0;
-                            //   let y = area_height - bar_height; // y = 150.0
-                            // The bar would start at y = 150.0 and extend upwards to y = 200.0
-                            // Note: (0,0) is at the top-left corner of the drawing area
-                            // Increasing Y moves downward, while increasing height moves upward
-                            let y = area_height - height as f64;
-
-                            // Draw a stroke (border) around the bar
-                            // Set the color for the stroke (light purple with some transparency)
-                            ctx.set_source_rgba(0.8, 0.2, 1.0, 0.8);
-                            ctx.set_line_width(stroke_width);
-
-                            // Draw the rectangle for the stroke and apply the stroke
-                            ctx.rectangle(x, y, bar_width, height as f64);
-                            ctx.stroke().expect("Failed to stroke bar");
-
-                            // Fill the bar with a gradient
-                            let gradient = LinearGradient::new(x, y, x, y + height as f64);
-                            gradient.add_color_stop_rgb(0.0, 0.1, 0.6, 0.8); // Top color (light blue)
-                            gradient.add_color_stop_rgb(1.0, 0.0, 0.3, 0.5); // Bottom color (darker blue)
-                            ctx.set_source(&gradient).expect("Failed to set gradient");
-
-                            // Draw and fill the rectangle for the bar
-                            ctx.rectangle(x, y, bar_width, height as f64);
-                            ctx.fill().expect("Failed to fill bar");
-
-                            // Add a shine effect at the top of the bar
-                            let shine_height = height as f64 * 0.1; // 10% of bar height
-                            let shine_gradient = LinearGradient::new(x, y, x, y + shine_height);
-                            shine_gradient.add_color_stop_rgba(0.0, 1.0, 1.0, 1.0, 0.3); // White with 30% opacity
-                            shine_gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 0.0); // Fully transparent
-                            ctx.set_source(&shine_gradient).expect("Failed to set shine gradient");
-                            ctx.rectangle(x, y, bar_width, shine_height);
-                            ctx.fill().expect("Failed to add shine effect");
-
-                            // Draw the current Y position on top of the rectangle
-                            ctx.set_source_rgb(1.0, 1.0, 1.0); // White color for text
-                            ctx.set_font_size(12.0);
-                            let text = format!("y: {:.0}", y);
-                            let extents = ctx.text_extents(&text).expect("Failed to get text extents");
-
-                            // Calculate the center position for the text
-                            // We start from the left edge of the bar (x) and add half the bar width
-                            // Then we subtract half the text width to center it within the bar
-                            let text_x = x + (bar_width - extents.width()) / 2.0;
-                            let text_y = y - 5.0; // Position text slightly above the bar
-
-                            // Move the drawing cursor to the specified (x, y) coordinates
-                            // This sets the starting point for the next drawing operation (in this case, drawing text)
-                            ctx.move_to(text_x, text_y);
-
-                            // Draw the text on the canvas
-                            ctx.show_text(&text).expect("Failed to draw text");
-
-                            // Draw another text showing the bar's height
-                            ctx.set_source_rgb(1.0, 1.0, 1.0);
-                            ctx.set_font_size(12.0);
-                            let text = format!("h: {:.0}", height);
-                            let text_y = text_y - 10f64; // Position text slightly above the y text
-                            ctx.move_to(text_x, text_y);
-                            ctx.show_text(&text).expect("Failed to draw text");
+                            // Copy the rendered bars to the screen
+                            // If we have nothing to show (rendering failed) we still paint the
+                            // background, GTK doesn't keep what was drawn before
+                            match surface.as_ref() {
+                                Some(surface) => {
+                                    ctx.set_source_surface(surface, 0.0, 0.0)
+                                        .expect("Failed to set the bars surface");
+                                    ctx.paint().expect("Failed to paint");
+                                }
+                                None => draw::clear(ctx, &theme, scene.transparency),
+                            }
+
+                            let now = Instant::now();
+                            let mut draw_fps = draw_fps.borrow_mut();
+                            draw_fps.tick(now);
+                            if show_fps.get() {
+                                draw::draw_fps(
+                                    ctx,
+                                    width as f64,
+                                    draw_fps.fps(now),
+                                    frame_fps.borrow_mut().fps(now),
+                                    limiter.borrow().skipped(),
+                                    frames.borrow().ignored(),
+                                    &theme,
+                                );
+                            }
+                            if paused.get() {
+                                draw::draw_paused(ctx, &theme);
+                            }
+                            if let Some((track, opacity)) = caption.borrow().visible(now) {
+                                draw::draw_now_playing(
+                                    ctx,
+                                    width as f64,
+                                    &track.text(),
+                                    opacity,
+                                    &theme,
+                                );
+                            }
+                            if let Some(name) = theme_name.get() {
+                                draw::draw_theme_name(ctx, height as f64, name, &theme);
+                            }
                         }
                     }
-                }
+                },
+                add_named[Some("error")] = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 12,
+                    set_margin_all: 24,
+                    set_halign: gtk::Align::Center,
+                    set_valign: gtk::Align::Center,
+
+                    #[name = "error_label"]
+                    gtk::Label {
+                        set_wrap: true,
+                        set_justify: gtk::Justification::Center,
+                        set_max_width_chars: 50,
+                    },
+                    gtk::Button {
+                        set_label: "Retry",
+                        set_halign: gtk::Align::Center,
+                        connect_clicked => AppMsg::RetryBackend,
+                    },
+                },
             }
         }
     }
 
     // Initialize the UI.
     fn init(
-        init: Self::Init,
+        mut init: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        // What the last run left, under what the command line asks for
+        let saved_path = persist::path();
+        let saved = saved_path
+            .as_deref()
+            .map(persist::load_or_reset)
+            .unwrap_or_default();
+        init.fill_from_saved(&saved);
+        // The smoothing is only ever set in the preferences, it's not an option
+        let settings = Settings {
+            smoothing: saved.smoothing,
+            ..init.settings()
+        };
+        let bars = settings.bars;
+        // A broken theme file shouldn't keep the visualizer from starting, we say
+        // what's wrong with it and go on with the built-in themes
+        let mut custom = Theme::load().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            eprintln!("Using the built-in themes");
+            None
+        });
+        // The same for an image that can't be read, the background is black then
+        // (only the user's theme can have one)
+        let background_image = custom.as_mut().and_then(|theme| match &theme.background {
+            Background::Image { path, dim } => match BackgroundImage::load(path, *dim) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    eprintln!("Failed to load {}: {}", path.display(), e);
+                    eprintln!("Using a black background");
+                    theme.background = Background::Solid(theme.background.color());
+                    None
+                }
+            },
+            _ => None,
+        });
+        // Back to the theme of the last run
+        let mut themes = ThemeCycle::new(custom);
+        if let Some(name) = &saved.theme {
+            if !themes.select(name) {
+                eprintln!("There is no theme called {}, using {}", name, themes.name());
+                eprintln!("The themes are {}", themes.names().join(", "));
+            }
+        }
+        let theme = themes.theme().clone();
+        // Without a compositor the transparent parts wouldn't show the desktop, we
+        // stay opaque then (rgba: the windows can have an alpha channel at all)
+        let display = root.display();
+        let transparency = theme::choose_transparency(
+            init.transparent,
+            display.is_composited() && display.is_rgba(),
+        );
+        if init.transparent && transparency == Transparency::Opaque {
+            eprintln!("Transparency isn't available, is a compositor running?");
+            eprintln!("Using the theme's background");
+        }
+        if transparency == Transparency::Transparent {
+            // GTK paints the window background too, under our drawing area
+            relm4::set_global_css("window.transparent { background: transparent; }");
+            root.add_css_class("transparent");
+        }
+
+        // The dialog is transient for our window, so it opens on top of it
+        let preferences = PreferencesDialog::builder()
+            .transient_for(&root)
+            .launch(settings)
+            .forward(sender.input_sender(), |output| match output {
+                PreferencesOutput::Apply(settings) => AppMsg::ApplySettings(settings),
+            });
+
         let model = AppModel {
-            bars: init,
-            should_draw: Rc::new(RefCell::new(false)),
-            bars_data: Rc::new(RefCell::new(vec![0_u16; init])),
+            settings,
+            source: init.source(),
+            show_debug_labels: Rc::new(Cell::new(init.debug_labels)),
+            inner_radius: init.inner_radius(),
+            transparency,
+            background_image: Rc::new(background_image),
+            style: Rc::new(Cell::new(init.style())),
+            channel_view: Rc::new(Cell::new(init.channels())),
+            generation: Arc::new(AtomicU64::new(0)),
+            preferences,
+            dirty: Rc::new(RefCell::new(DirtyTracker::new())),
+            theme: Rc::new(RefCell::new(theme)),
+            themes,
+            theme_name: Rc::new(Cell::new(None)),
+            theme_switches: 0,
+            bars_data: Rc::new(RefCell::new(Arc::new(Frame::mono(vec![0_u16; bars])))),
+            displayed: Rc::new(RefCell::new(Vec::new())),
+            peaks: Rc::new(RefCell::new(Vec::new())),
+            peaks_updated: Instant::now(),
+            show_fps: Rc::new(Cell::new(init.show_fps)),
+            draw_fps: Rc::new(RefCell::new(FpsCounter::new())),
+            frame_fps: Rc::new(RefCell::new(FpsCounter::new())),
+            limiter: Rc::new(RefCell::new(FrameLimiter::new(init.max_fps()))),
+            frames: Rc::new(RefCell::new(FrameGate::new())),
+            paused: Rc::new(Cell::new(false)),
+            caption: Rc::new(RefCell::new(Caption::new())),
+            held_frame: None,
+            windowed_size: None,
+            last_motion: Instant::now(),
+            hide_cursor_pending: false,
+            pointer: None,
+            screenshot_size: init.screenshot_size,
+            saved_path,
+            window: saved.window,
+            save_pending: false,
         };
 
-        // The sender is responsible for sending the data received from cava to the UI
-        // Every time we receive data from the visualizer, we will send it to the sender
-        // so it can be processed by the update_with_view function
-        let clone = sender.clone();
-        let bars = model.bars;
-        relm4::spawn(async move {
-            let rx = Visualizer::new(bars);
-
-            // As long as we are receiving data from the visualizer, send it to the UI
-            while let Ok(data) = rx.recv() {
-                // let smoothed_data = visualizer.smooth_data(data);
-                clone
-                    .input_sender()
-                    .send(AppMsg::UpdateBarValues(data))
-                    .unwrap();
-            }
-        });
+        start_visualizer(
+            &sender,
+            model.source,
+            model.settings,
+            model.generation.clone(),
+        );
 
         // Render our widgest declared with the view! macro
         let widgets = view_output!();
 
+        // What the music players play, from a thread of their own
+        let input = sender.input_sender().clone();
+        nowplaying::watch(move |track| {
+            let message = match track {
+                Some(Track { title, artist }) => AppMsg::NowPlaying { title, artist },
+                None => AppMsg::NothingPlaying,
+            };
+            // Nothing to do if the window is gone already
+            let _ = input.send(message);
+        });
+
+        if let Some(window) = saved.window {
+            root.set_default_size(window.width, window.height);
+            if window.maximized {
+                root.maximize();
+            }
+        }
+
+        // The area is only drawn again when the bars change, so when they don't the
+        // rates on screen would stay as they were. While they are shown we redraw twice
+        // a second to keep them current (those draws count in the draw rate too, but
+        // they only copy the rendered bars)
+        let area = widgets.root.clone();
+        let show_fps = model.show_fps.clone();
+        gtk::glib::timeout_add_local(Duration::from_millis(500), move || {
+            if show_fps.get() {
+                area.queue_draw();
+            }
+            gtk::glib::ControlFlow::Continue
+        });
+
+        // Once per refresh of the screen (right before GTK draws), move the bars on
+        // towards the latest values. Only while they move the bars are rendered and
+        // drawn again, the callback itself is cheap.
+        // `frame_time` is when the frame being prepared will show up on screen, in
+        // microseconds: the time between two of them is how far the bars move
+        // The draws go through the limiter: on a 144Hz screen with `--max-fps 60` the
+        // bars still move at every refresh, but they're only drawn at 60
+        let bars_data = model.bars_data.clone();
+        let displayed = model.displayed.clone();
+        let dirty = model.dirty.clone();
+        let limiter = model.limiter.clone();
+        let caption = model.caption.clone();
+        let last_frame_time: Cell<Option<i64>> = Cell::new(None);
+        // Whether the title was fading at the last refresh, it gets one more draw once
+        // it's done so it doesn't stay a little transparent
+        let was_fading = Cell::new(false);
+        widgets.root.add_tick_callback(move |area, clock| {
+            let frame_time = clock.frame_time();
+            let elapsed = match last_frame_time.replace(Some(frame_time)) {
+                Some(last) => Duration::from_micros((frame_time - last).max(0) as u64),
+                None => Duration::ZERO,
+            };
+            let moved =
+                interpolate::interpolate(&mut displayed.borrow_mut(), &bars_data.borrow(), elapsed);
+            if moved {
+                dirty.borrow_mut().mark_dirty();
+            }
+            // The title fading in or out only needs the rendered bars copied again
+            let fading = caption.borrow().fading(Instant::now());
+            if moved || fading || was_fading.replace(fading) {
+                queue_limited_draw(area, &limiter);
+            }
+            gtk::glib::ControlFlow::Continue
+        });
+
         ComponentParts { model, widgets }
     }
 
@@ -209,36 +645,511 @@ impl Component for AppModel {
         &mut self,
         widgets: &mut Self::Widgets,
         message: Self::Input,
-        _: ComponentSender<Self>,
-        _root: &Self::Root,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
     ) {
         match message {
             AppMsg::UpdateBarValues(data) => {
-                let mut should_draw = self.should_draw.borrow_mut();
-                *should_draw = false; // Start by assuming no drawing needed
-
-                let mut self_bar_values = self.bars_data.borrow_mut();
-
-                // Iterate through the new data, updating bar values and setting should_draw flag
-                // If any value changes, we need to redraw the entire visualization
-                for (i, &new_value) in data.iter().enumerate() {
-                    if self_bar_values[i] != new_value {
-                        self_bar_values[i] = new_value;
-                        if !*should_draw {
-                            *should_draw = true;
-                        }
+                // A frame still on its way from the visualizer we replaced can have
+                // another number of bars, we just wait for the new ones
+                // (`bars` is per channel, a stereo frame has twice as many values)
+                let bars = data.bars as usize;
+                if !self.frames.borrow_mut().accept(self.settings.bars, bars) {
+                    return;
+                }
+                self.frame_fps.borrow_mut().tick(Instant::now());
+
+                // The new values only become the target, the tick callback moves the
+                // bars towards it and draws them (or does nothing if they are there)
+                pause::receive_frame(
+                    self.paused.get(),
+                    &mut self.bars_data.borrow_mut(),
+                    &mut self.held_frame,
+                    data,
+                );
+                // The peaks follow the values that came in, not the bars on their way
+                // to them: they fall when the values don't change, which needs a render.
+                // While paused they stay where they are, like the bars.
+                let peaks_moved = !self.paused.get() && self.update_peaks();
+                if peaks_moved {
+                    self.dirty.borrow_mut().mark_dirty();
+                    queue_limited_draw(&widgets.root, &self.limiter);
+                }
+                self.update_tooltip(widgets);
+            }
+            AppMsg::ShowPreferences => self.preferences.emit(PreferencesMsg::Show),
+            AppMsg::SetStyle(style) => {
+                if self.style.replace(style) != style {
+                    widgets.style.set_selected(controls::style_position(style));
+                    self.dirty.borrow_mut().mark_dirty();
+                    widgets.root.queue_draw();
+                    self.schedule_save(&sender);
+                    self.update_tooltip(widgets);
+                }
+            }
+            AppMsg::NextChannelView => {
+                self.channel_view.set(self.channel_view.get().next());
+                self.schedule_save(&sender);
+                // Only a stereo frame looks different, a mono one ignores the view
+                if self.bars_data.borrow().layout == ChannelLayout::Stereo {
+                    self.dirty.borrow_mut().mark_dirty();
+                    widgets.root.queue_draw();
+                    self.update_tooltip(widgets);
+                }
+            }
+            AppMsg::ToggleFps => {
+                self.show_fps.set(!self.show_fps.get());
+                self.schedule_save(&sender);
+                // Only the overlay changes, the rendered bars are still good
+                widgets.root.queue_draw();
+            }
+            AppMsg::ToggleDebugLabels => {
+                self.show_debug_labels.set(!self.show_debug_labels.get());
+                self.schedule_save(&sender);
+                // The labels are drawn with the bars, so they have to be rendered again
+                self.dirty.borrow_mut().mark_dirty();
+                widgets.root.queue_draw();
+            }
+            AppMsg::TogglePause => {
+                sender.input(AppMsg::SetPaused(!self.paused.get()));
+            }
+            AppMsg::SetPaused(paused) => {
+                if self.paused.replace(paused) == paused {
+                    return;
+                }
+                widgets.pause.set_active(paused);
+                // Resuming jumps to the latest frame, not the first one we held back
+                // (the bars move there like to any new values)
+                if !paused {
+                    // The peaks didn't fall while paused, they go on from where they were
+                    self.peaks_updated = Instant::now();
+                    pause::resume(&mut self.bars_data.borrow_mut(), &mut self.held_frame);
+                }
+                // For the indicator, even if the bars didn't change
+                widgets.root.queue_draw();
+            }
+            AppMsg::Screenshot => {
+                let (width, height) = match self.screenshot_size {
+                    Some(size) => (size.width, size.height),
+                    None => (widgets.root.width(), widgets.root.height()),
+                };
+                let saved = self
+                    .scene()
+                    .render(width, height)
+                    .map_err(ScreenshotError::Render)
+                    .and_then(|surface| screenshot::save(&surface));
+                match saved {
+                    Ok(path) => {
+                        println!("Screenshot saved to {}", path.display());
+                        show_in_title(root, "Screenshot saved");
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        show_in_title(root, "Screenshot failed, see the log");
+                    }
+                }
+            }
+            AppMsg::ToggleFullscreen if root.is_fullscreen() => {
+                self.leave_fullscreen(widgets, root);
+            }
+            AppMsg::ToggleFullscreen => {
+                self.windowed_size = Some((root.width(), root.height()));
+                root.fullscreen();
+                // Only the bars, `h` brings the controls back
+                widgets.header.set_visible(false);
+                // Hide the cursor if the pointer doesn't move from now on
+                self.last_motion = Instant::now();
+                if !self.hide_cursor_pending {
+                    self.hide_cursor_pending = true;
+                    hide_cursor_after(&sender, HIDE_CURSOR_AFTER);
+                }
+            }
+            AppMsg::ExitFullscreen => {
+                if root.is_fullscreen() {
+                    self.leave_fullscreen(widgets, root);
+                }
+            }
+            AppMsg::PointerMoved(x, y) => {
+                self.pointer = Some((x, y));
+                self.update_tooltip(widgets);
+                self.last_motion = Instant::now();
+                widgets.root.set_cursor(None);
+                if root.is_fullscreen() && !self.hide_cursor_pending {
+                    self.hide_cursor_pending = true;
+                    hide_cursor_after(&sender, HIDE_CURSOR_AFTER);
+                }
+            }
+            AppMsg::ToggleHeaderBar => {
+                // Out of fullscreen the header bar is the window's title bar, it stays
+                if root.is_fullscreen() {
+                    widgets.header.set_visible(!widgets.header.is_visible());
+                }
+            }
+            AppMsg::NextTheme => {
+                self.themes.next();
+                *self.theme.borrow_mut() = self.themes.theme().clone();
+                self.theme_name.set(Some(self.themes.name()));
+                self.theme_switches += 1;
+                let (switch, hide) = (self.theme_switches, sender.clone());
+                gtk::glib::timeout_add_local_once(THEME_NAME_FOR, move || {
+                    hide.input(AppMsg::HideThemeName(switch))
+                });
+                self.schedule_save(&sender);
+                // The colors are in the rendered bars
+                self.dirty.borrow_mut().mark_dirty();
+                widgets.root.queue_draw();
+            }
+            AppMsg::HideThemeName(switch) => {
+                // Every `t` has its timeout, only the one of the last `t` hides the name
+                if switch == self.theme_switches {
+                    self.theme_name.set(None);
+                    widgets.root.queue_draw();
+                }
+            }
+            AppMsg::PointerLeft => {
+                self.pointer = None;
+                self.update_tooltip(widgets);
+            }
+            AppMsg::HideCursor => {
+                self.hide_cursor_pending = false;
+                if !root.is_fullscreen() {
+                    return;
+                }
+                // The pointer may have moved since the timeout started, then we wait
+                // for what's left of the 2s after that last move
+                match cursor::when_to_hide(self.last_motion.elapsed()) {
+                    Hide::Now => {
+                        let none = gtk::gdk::Cursor::from_name("none", None);
+                        widgets.root.set_cursor(none.as_ref());
+                    }
+                    Hide::After(left) => {
+                        self.hide_cursor_pending = true;
+                        hide_cursor_after(&sender, left);
                     }
                 }
+            }
+            AppMsg::SetBars(bars) => {
+                let settings = controls::with_bars(self.settings, bars);
+                sender.input(AppMsg::ApplySettings(settings));
+            }
+            AppMsg::ApplySettings(settings) => {
+                if settings == self.settings {
+                    return;
+                }
+                self.settings = settings;
+                self.schedule_save(&sender);
+                // The bar count may come from the header bar or from the preferences,
+                // the other one shows it too
+                widgets.bars.set_value(settings.bars as f64);
+                self.preferences.emit(PreferencesMsg::Changed(settings));
+
+                // Start from flat bars with the new count until the first frame arrives
+                // A frame held back while paused has the old count, it can go
+                *self.bars_data.borrow_mut() = Arc::new(Frame::mono(vec![0_u16; settings.bars]));
+                self.held_frame = None;
+                self.dirty.borrow_mut().mark_dirty();
+                widgets.root.queue_draw();
+
+                self.restart_visualizer(widgets, &sender);
+            }
+            AppMsg::NowPlaying { title, artist } => {
+                let track = Track { title, artist };
+                // The tick callback draws the fade from here
+                self.caption.borrow_mut().set(Some(track), Instant::now());
+            }
+            AppMsg::NothingPlaying => self.caption.borrow_mut().set(None, Instant::now()),
+            AppMsg::BackendError(message) => {
+                eprintln!("{}", message);
+                widgets.error_label.set_label(&message);
+                widgets.pages.set_visible_child_name("error");
+            }
+            AppMsg::RetryBackend => self.restart_visualizer(widgets, &sender),
+            AppMsg::WindowChanged => {
+                // Fullscreen is the size of the screen, we keep the size from before
+                if root.is_fullscreen() {
+                    return;
+                }
+                let window = Some(WindowPlacement {
+                    width: root.default_width(),
+                    height: root.default_height(),
+                    maximized: root.is_maximized(),
+                });
+                if window != self.window {
+                    self.window = window;
+                    self.schedule_save(&sender);
+                }
+            }
+            AppMsg::SaveSettings => {
+                self.save_pending = false;
+                self.save();
+            }
+        }
+    }
+
+    // A change less than SAVE_DELAY before closing the window would be lost otherwise
+    fn shutdown(&mut self, _widgets: &mut Self::Widgets, _output: relm4::Sender<Self::Output>) {
+        if self.save_pending {
+            self.save();
+        }
+    }
+}
+
+impl AppModel {
+    fn scene(&self) -> Scene {
+        Scene {
+            bars_data: self.bars_data.clone(),
+            displayed: self.displayed.clone(),
+            peaks: self.peaks.clone(),
+            theme: self.theme.clone(),
+            style: self.style.clone(),
+            channel_view: self.channel_view.clone(),
+            show_debug_labels: self.show_debug_labels.clone(),
+            inner_radius: self.inner_radius,
+            transparency: self.transparency,
+            background_image: self.background_image.clone(),
+        }
+    }
+
+    // Show the values of the bar under the pointer in its tooltip, or no tooltip when
+    // there is no bar there (see `render::bar_at`)
+    // The tooltip is GTK's own little window, changing it doesn't draw the bars again.
+    // The text is only set when it changed: the pointer moving within a bar, or a frame
+    // with the same values, leave it alone
+    fn update_tooltip(&self, widgets: &AppModelWidgets) {
+        let text = self.pointer.and_then(|pointer| {
+            let frame = self.bars_data.borrow();
+            let area = &widgets.root;
+            let hovered = render::bar_at(
+                self.style.get(),
+                self.channel_view.get(),
+                frame.layout == ChannelLayout::Stereo,
+                frame.values.len(),
+                Geometry::new(area.width() as f64, area.height() as f64),
+                pointer,
+            )?;
+            let shown = interpolate::displayed_frame(&frame, &self.displayed.borrow());
+            Some(tooltip_text(&hovered, &frame.values, &shown.values))
+        });
+        if widgets.root.tooltip_text().as_deref() != text.as_deref() {
+            widgets.root.set_tooltip_text(text.as_deref());
+        }
+    }
+
+    // Move the peaks on to the frame that is shown now
+    fn update_peaks(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now - self.peaks_updated;
+        self.peaks_updated = now;
+        peaks::update_peaks(
+            &mut self.peaks.borrow_mut(),
+            &self.bars_data.borrow(),
+            elapsed,
+        )
+    }
+
+    // What to save for the next run
+    fn saved_settings(&self) -> SavedSettings {
+        SavedSettings {
+            bars: self.settings.bars,
+            framerate: self.settings.framerate,
+            smoothing: self.settings.smoothing,
+            style: self.style.get(),
+            channels: self.channel_view.get(),
+            debug_labels: self.show_debug_labels.get(),
+            show_fps: self.show_fps.get(),
+            theme: Some(self.themes.name().to_string()),
+            window: self.window,
+        }
+    }
+
+    // Save the settings in a moment, with whatever else changes until then
+    // Like the cursor, there is only ever one SaveSettings on its way
+    fn schedule_save(&mut self, sender: &ComponentSender<Self>) {
+        if !self.save_pending {
+            self.save_pending = true;
+            let sender = sender.clone();
+            gtk::glib::timeout_add_local_once(persist::SAVE_DELAY, move || {
+                sender.input(AppMsg::SaveSettings)
+            });
+        }
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.saved_path {
+            if let Err(e) = persist::save_to(path, &self.saved_settings()) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    // Start a new visualizer with the current settings, back on the bars
+    // If it fails again, BackendError brings the message back right away
+    fn restart_visualizer(&self, widgets: &AppModelWidgets, sender: &ComponentSender<Self>) {
+        widgets.pages.set_visible_child_name("bars");
+        start_visualizer(sender, self.source, self.settings, self.generation.clone());
+    }
+
+    fn leave_fullscreen(&mut self, widgets: &AppModelWidgets, root: &gtk::ApplicationWindow) {
+        root.unfullscreen();
+        widgets.header.set_visible(true);
+        if let Some((width, height)) = self.windowed_size.take() {
+            root.set_default_size(width, height);
+        }
+        widgets.root.set_cursor(None);
+    }
+}
+
+// Show `message` as the title of the window for a moment
+// If another message replaced it meanwhile, that one stays: it gets its own moment
+fn show_in_title(root: &gtk::ApplicationWindow, message: &'static str) {
+    root.set_title(Some(message));
+    let root = root.clone();
+    gtk::glib::timeout_add_local_once(TITLE_MESSAGE_FOR, move || {
+        if root.title().as_deref() == Some(message) {
+            root.set_title(Some(TITLE));
+        }
+    });
+}
+
+// What the tooltip says about `hovered`: the value that came in from the visualizer
+// (smoothed by cava already), and where the bar is on its way to it
+// A bar that stands for several values has their average, like on screen
+fn tooltip_text(hovered: &HoveredBar, values: &[u16], shown: &[u16]) -> String {
+    let average = |values: &[u16]| render::average_values(&values[hovered.values.clone()], 1)[0];
+    let percent = |value: u16| value as f64 * 100.0 / u16::MAX as f64;
+    let (value, on_screen) = (average(values), average(shown));
+
+    let bars = &hovered.bars;
+    let mut text = if bars.len() == 1 {
+        format!("Bar {}", bars.start + 1)
+    } else {
+        format!("Bars {}–{}", bars.start + 1, bars.end)
+    };
+    match hovered.channel {
+        Channel::All => {}
+        Channel::Left => text.push_str(", left"),
+        Channel::Right => text.push_str(", right"),
+    }
+    text.push_str(&format!(
+        "\nvalue {} ({:.0}%)\non screen {} ({:.0}%)",
+        value,
+        percent(value),
+        on_screen,
+        percent(on_screen)
+    ));
+    text
+}
+
+// Draw `area` again now, or later if the last draw was too recent (see FrameLimiter)
+// The draws the user asks for (a key, the rates...) don't go through here, they are
+// few and should show at once
+fn queue_limited_draw(area: &gtk::DrawingArea, limiter: &Rc<RefCell<FrameLimiter>>) {
+    let decision = limiter.borrow_mut().request(Instant::now());
+    match decision {
+        Decision::Draw => area.queue_draw(),
+        Decision::Later(delay) => {
+            let (area, limiter) = (area.clone(), limiter.clone());
+            gtk::glib::timeout_add_local_once(delay, move || {
+                limiter.borrow_mut().fire(Instant::now());
+                area.queue_draw();
+            });
+        }
+        Decision::Pending => {}
+    }
+}
+
+// Send HideCursor after `delay`
+// The pointer moves all the time, so instead of removing the timeout and starting a
+// new one on every move, there is only ever one running: when it fires it checks when
+// the pointer last moved, and starts another one if that was less than 2s ago.
+// A GLib source can't be removed once it fired, so not removing them is also the
+// simplest way to never remove one twice.
+fn hide_cursor_after(sender: &ComponentSender<AppModel>, delay: Duration) {
+    let sender = sender.clone();
+    gtk::glib::timeout_add_local_once(delay, move || sender.input(AppMsg::HideCursor));
+}
+
+// Start a visualizer and send its frames to the UI, from its own task
+// Any visualizer started before stops at its next frame, dropping it stops its cava
+// If it can't start (no cava...) the UI gets a BackendError instead of frames
+fn start_visualizer(
+    sender: &ComponentSender<AppModel>,
+    source: SourceKind,
+    settings: Settings,
+    generation: Arc<AtomicU64>,
+) {
+    let current = generation.fetch_add(1, Ordering::Relaxed) + 1;
 
-                if *should_draw {
-                    widgets.root.queue_draw(); // Request a redraw if needed
+    // The sender is responsible for sending the data received from cava to the UI
+    // Every time we receive data from the visualizer, we will send it to the sender
+    // so it can be processed by the update_with_view function
+    let sender = sender.clone();
+    relm4::spawn(async move {
+        let config = settings.config();
+        let visualizer = match source {
+            SourceKind::Cava => Visualizer::with_config(config),
+            SourceKind::Synthetic => Visualizer::synthetic(config, Waveform::SineSweep, 0),
+        };
+        let visualizer = match visualizer {
+            Ok(visualizer) => visualizer,
+            Err(e) => {
+                // A newer visualizer is already on its way, its outcome is what counts
+                if generation.load(Ordering::Relaxed) == current {
+                    sender.input(AppMsg::BackendError(backend_error_message(&e)));
                 }
+                return;
+            }
+        };
+
+        // As long as we are receiving data from the visualizer, send it to the UI
+        for frame in visualizer {
+            if generation.load(Ordering::Relaxed) != current {
+                break;
             }
+            sender
+                .input_sender()
+                .send(AppMsg::UpdateBarValues(frame))
+                .unwrap();
         }
+    });
+}
+
+// What to tell the user when the visualizer couldn't start, with what they can do
+fn backend_error_message(error: &VisualizerError) -> String {
+    match error {
+        VisualizerError::CavaNotFound(_) => {
+            "cava not found — install it or run with --source synthetic".to_string()
+        }
+        VisualizerError::CavaTooOld { .. } => {
+            format!("{} — update it or run with --source synthetic", error)
+        }
+        // Nothing to do with cava, the synthetic source checks the config too
+        VisualizerError::InvalidConfig(_) => error.to_string(),
+        _ => format!("cava failed to start: {}", error),
     }
 }
 
 pub fn main() {
-    let app = RelmApp::new("fuhrmann.playground.relm4_audio_visualizer");
-    app.run::<AppModel>(20);
+    // Parse the arguments before anything GTK, a bad value exits right here with
+    // clap's message and no window is ever created (`--help` lists them all)
+    let mut cli = Cli::load();
+
+    // The terminal doesn't need GTK at all, not even a display
+    if cli.tui {
+        if let Err(e) = tui::run(&cli) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // GTK parses argv too, and it rejects options it doesn't know like `--bars`
+    // So GTK only gets the program name and what came after `--`, not our options
+    let program = std::env::args().next().unwrap_or_default();
+    let gtk_args = std::iter::once(program)
+        .chain(std::mem::take(&mut cli.gtk_args))
+        .collect();
+
+    let app = RelmApp::new("fuhrmann.playground.relm4_audio_visualizer").with_args(gtk_args);
+    app.run::<AppModel>(cli);
 }
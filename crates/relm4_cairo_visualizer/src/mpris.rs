@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::thread;
+
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{proxy, Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::names::BusName;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::MatchRule;
+
+use crate::nowplaying::Track;
+
+// What the music players are playing, from DBus (MPRIS)
+//
+// A player that speaks MPRIS owns a name like org.mpris.MediaPlayer2.spotify on the
+// session bus. Its object /org/mpris/MediaPlayer2 has a Player interface, with the
+// track in the Metadata property (a dictionary: "xesam:title", "xesam:artist"...) and
+// "Playing", "Paused" or "Stopped" in PlaybackStatus. When they change the player
+// sends a PropertiesChanged signal with the new values.
+//
+// With several players the title is the one of the player that last started playing
+// (or changed tracks while playing). No session bus, no player: no title, and nothing
+// said about it, the visualizer is the same without.
+//
+// This uses the blocking API of zbus on a thread of its own, the signals are read in
+// a plain loop.
+
+const NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+// Properties by name, like in a PropertiesChanged signal
+pub type Properties = HashMap<String, OwnedValue>;
+
+// A "v" in a DBus type is a value of any type, and it can come wrapped in one more
+// Value::Value when it's read that way
+fn unwrap<'a>(value: &'a Value<'a>) -> &'a Value<'a> {
+    match value {
+        Value::Value(inner) => unwrap(inner),
+        value => value,
+    }
+}
+
+fn as_str<'a>(value: &'a Value<'a>) -> Option<&'a str> {
+    match unwrap(value) {
+        Value::Str(text) => Some(text.as_str()),
+        _ => None,
+    }
+}
+
+// The track of a Metadata property, None without a title
+// "xesam:artist" is a list, several artists are joined with commas. Some players send
+// a single string instead, which is fine too
+pub fn track_from_metadata(metadata: &HashMap<String, OwnedValue>) -> Option<Track> {
+    let title = as_str(metadata.get("xesam:title")?)?.trim();
+    if title.is_empty() {
+        return None;
+    }
+    let artist = match metadata.get("xesam:artist").map(|artist| unwrap(artist)) {
+        Some(Value::Array(artists)) => artists
+            .iter()
+            .filter_map(as_str)
+            .map(str::trim)
+            .filter(|artist| !artist.is_empty())
+            .collect::<Vec<_>>()
+            .join(", "),
+        Some(Value::Str(artist)) => artist.trim().to_string(),
+        _ => String::new(),
+    };
+    Some(Track {
+        title: title.to_string(),
+        artist,
+    })
+}
+
+// The Metadata property itself is a value, a dictionary of them inside
+fn metadata_track(metadata: &OwnedValue) -> Option<Track> {
+    let metadata = HashMap::<String, OwnedValue>::try_from(metadata.try_clone().ok()?).ok()?;
+    track_from_metadata(&metadata)
+}
+
+#[derive(Debug, Default)]
+struct Player {
+    track: Option<Track>,
+    playing: bool,
+}
+
+// Every player we heard of, by its unique name on the bus (":1.42")
+#[derive(Debug, Default)]
+pub struct Players {
+    players: HashMap<String, Player>,
+    // The players in the order they were last active, the latest last
+    recent: Vec<String>,
+}
+
+impl Players {
+    // Some properties of `player` changed
+    pub fn update(&mut self, player: &str, changed: &Properties) {
+        let state = self.players.entry(player.to_string()).or_default();
+        if let Some(metadata) = changed.get("Metadata") {
+            state.track = metadata_track(metadata);
+        }
+        if let Some(status) = changed.get("PlaybackStatus").and_then(|s| as_str(s)) {
+            state.playing = status == "Playing";
+        }
+        // The volume going up doesn't make it the latest player, a new track does
+        let news = changed.contains_key("Metadata") || changed.contains_key("PlaybackStatus");
+        if news && state.playing {
+            self.recent.retain(|name| name != player);
+            self.recent.push(player.to_string());
+        }
+    }
+
+    // `player` went away (it quit)
+    pub fn remove(&mut self, player: &str) {
+        self.players.remove(player);
+        self.recent.retain(|name| name != player);
+    }
+
+    // The track of the latest player still playing
+    pub fn now_playing(&self) -> Option<Track> {
+        self.recent
+            .iter()
+            .rev()
+            .filter_map(|name| self.players.get(name))
+            .find(|player| player.playing)
+            .and_then(|player| player.track.clone())
+    }
+}
+
+// See `nowplaying::watch`
+pub fn watch(on_change: impl Fn(Option<Track>) + Send + 'static) {
+    thread::spawn(move || {
+        // No session bus (a console, a container...) or it went away: no title
+        let _ = listen(&on_change);
+    });
+}
+
+fn listen(on_change: &dyn Fn(Option<Track>)) -> zbus::Result<()> {
+    let conn = Connection::session()?;
+    let dbus = DBusProxy::new(&conn)?;
+    // The signals first, so what changes while we ask the players isn't missed
+    dbus.add_match_rule(
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .path(OBJECT_PATH)?
+            .build(),
+    )?;
+    // Players starting and quitting
+    dbus.add_match_rule(
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface("org.freedesktop.DBus")?
+            .member("NameOwnerChanged")?
+            .arg0ns("org.mpris.MediaPlayer2")?
+            .build(),
+    )?;
+    let messages = MessageIterator::from(&conn);
+
+    // The players running already
+    let mut players = Players::default();
+    for name in dbus.list_names()? {
+        if !name.starts_with(NAME_PREFIX) {
+            continue;
+        }
+        let owner = dbus.get_name_owner(BusName::try_from(name.as_str())?)?;
+        if let Ok(properties) = properties_of(&conn, &name) {
+            players.update(owner.as_str(), &properties);
+        }
+    }
+    let mut shown = players.now_playing();
+    on_change(shown.clone());
+
+    for message in messages {
+        let message = message?;
+        let header = message.header();
+        if header.message_type() != Type::Signal {
+            continue;
+        }
+        match header.member().map(|member| member.as_str()) {
+            Some("PropertiesChanged") => {
+                let body: zbus::Result<(String, Properties, Vec<String>)> =
+                    message.body().deserialize();
+                match (body, header.sender()) {
+                    (Ok((interface, changed, _)), Some(sender))
+                        if interface == PLAYER_INTERFACE =>
+                    {
+                        players.update(sender.as_str(), &changed)
+                    }
+                    _ => continue,
+                }
+            }
+            Some("NameOwnerChanged") => {
+                let Ok((name, old, new)) = message.body().deserialize::<(String, String, String)>()
+                else {
+                    continue;
+                };
+                if !old.is_empty() {
+                    players.remove(&old);
+                }
+                if !new.is_empty() {
+                    if let Ok(properties) = properties_of(&conn, &name) {
+                        players.update(&new, &properties);
+                    }
+                }
+            }
+            _ => continue,
+        }
+        let now = players.now_playing();
+        if now != shown {
+            shown = now.clone();
+            on_change(now);
+        }
+    }
+    Ok(())
+}
+
+// The track and the status of the player that owns `name`, asked for directly
+fn properties_of(conn: &Connection, name: &str) -> zbus::Result<Properties> {
+    // Not cached: we read them once, the signals tell us the rest
+    let player: Proxy = proxy::Builder::new(conn)
+        .destination(name)?
+        .path(OBJECT_PATH)?
+        .interface(PLAYER_INTERFACE)?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+    let mut properties = Properties::new();
+    for property in ["Metadata", "PlaybackStatus"] {
+        // A player without one of them just doesn't get it in the map
+        if let Ok(value) = player.get_property::<OwnedValue>(property) {
+            properties.insert(property.to_string(), value);
+        }
+    }
+    Ok(properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned(value: Value) -> OwnedValue {
+        OwnedValue::try_from(value).unwrap()
+    }
+
+    // A Metadata dictionary as a player sends it: every entry is a "v"
+    fn dict(entries: &[(&str, Value)]) -> OwnedValue {
+        let dict: HashMap<String, Value> = entries
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.to_string(),
+                    Value::Value(Box::new(value.try_clone().unwrap())),
+                )
+            })
+            .collect();
+        owned(Value::from(dict))
+    }
+
+    fn properties(entries: &[(&str, OwnedValue)]) -> Properties {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.try_clone().unwrap()))
+            .collect()
+    }
+
+    fn track(title: &str, artist: &str) -> Track {
+        Track {
+            title: title.to_string(),
+            artist: artist.to_string(),
+        }
+    }
+
+    fn playing(title: &str) -> Properties {
+        properties(&[
+            ("Metadata", dict(&[("xesam:title", Value::from(title))])),
+            ("PlaybackStatus", owned(Value::from("Playing"))),
+        ])
+    }
+
+    #[test]
+    fn title_and_artists() {
+        let metadata = dict(&[
+            (
+                "mpris:trackid",
+                Value::from("/org/mpris/MediaPlayer2/Track/1"),
+            ),
+            ("mpris:length", Value::from(215_000_000i64)),
+            ("xesam:title", Value::from("  Windowlicker ")),
+            (
+                "xesam:artist",
+                Value::from(vec!["Aphex Twin", " ", "Richard D. James"]),
+            ),
+            ("xesam:album", Value::from("Windowlicker")),
+        ]);
+        assert_eq!(
+            metadata_track(&metadata),
+            Some(track("Windowlicker", "Aphex Twin, Richard D. James"))
+        );
+    }
+
+    #[test]
+    fn artist_as_a_string() {
+        let metadata = dict(&[
+            ("xesam:title", Value::from("Teardrop")),
+            ("xesam:artist", Value::from("Massive Attack")),
+        ]);
+        assert_eq!(
+            metadata_track(&metadata),
+            Some(track("Teardrop", "Massive Attack"))
+        );
+    }
+
+    #[test]
+    fn no_artist() {
+        let metadata = dict(&[
+            ("xesam:title", Value::from("Stream")),
+            ("xesam:artist", Value::from(Vec::<&str>::new())),
+        ]);
+        assert_eq!(metadata_track(&metadata), Some(track("Stream", "")));
+        let metadata = dict(&[("xesam:title", Value::from("Stream"))]);
+        assert_eq!(metadata_track(&metadata), Some(track("Stream", "")));
+    }
+
+    #[test]
+    fn no_title_no_track() {
+        assert_eq!(metadata_track(&dict(&[])), None);
+        let blank = dict(&[
+            ("xesam:title", Value::from("   ")),
+            ("xesam:artist", Value::from(vec!["Someone"])),
+        ]);
+        assert_eq!(metadata_track(&blank), None);
+        let number = dict(&[("xesam:title", Value::from(7u32))]);
+        assert_eq!(metadata_track(&number), None);
+        // Not even a dictionary
+        assert_eq!(metadata_track(&owned(Value::from("Title"))), None);
+    }
+
+    #[test]
+    fn latest_player_playing() {
+        let mut players = Players::default();
+        assert_eq!(players.now_playing(), None);
+        players.update(":1.1", &playing("First"));
+        players.update(":1.2", &playing("Second"));
+        assert_eq!(players.now_playing(), Some(track("Second", "")));
+
+        // The volume changing doesn't bring the first one back
+        players.update(":1.1", &properties(&[("Volume", owned(Value::from(0.5)))]));
+        assert_eq!(players.now_playing(), Some(track("Second", "")));
+        // A new track does
+        players.update(":1.1", &playing("Third"));
+        assert_eq!(players.now_playing(), Some(track("Third", "")));
+
+        // Paused, the other one is still playing
+        let paused = properties(&[("PlaybackStatus", owned(Value::from("Paused")))]);
+        players.update(":1.1", &paused);
+        assert_eq!(players.now_playing(), Some(track("Second", "")));
+        players.remove(":1.2");
+        assert_eq!(players.now_playing(), None);
+    }
+
+    #[test]
+    fn tracks_change_without_status() {
+        let mut players = Players::default();
+        players.update(":1.1", &playing("First"));
+        let next = properties(&[("Metadata", dict(&[("xesam:title", Value::from("Next"))]))]);
+        players.update(":1.1", &next);
+        assert_eq!(players.now_playing(), Some(track("Next", "")));
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+// The title of the track playing, over the bars
+//
+// Where it comes from is up to mpris.rs (behind the `mpris` feature): the music
+// players say what they play on DBus. Without the feature, or without any player,
+// nothing is ever shown.
+//
+// When the track changes the old title fades out, then the new one fades in. The
+// caption only works out how opaque the title is at a given moment, the window draws
+// it over the rendered bars (like the rates) and asks for draws while it fades.
+
+// How long the old title takes to disappear, and then the new one to appear
+pub const FADE_OUT: Duration = Duration::from_millis(300);
+pub const FADE_IN: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    pub title: String,
+    // Empty when the player doesn't know
+    pub artist: String,
+}
+
+impl Track {
+    // What is shown: "title — artist", or just the title
+    pub fn text(&self) -> String {
+        if self.artist.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} — {}", self.title, self.artist)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Caption {
+    // What is fading out, and how opaque it was when it started to
+    leaving: Option<(Track, f64)>,
+    // What comes after it, None for no title at all
+    track: Option<Track>,
+    // When the track last changed, None if it never did
+    changed: Option<Instant>,
+}
+
+impl Caption {
+    pub fn new() -> Caption {
+        Caption::default()
+    }
+
+    // The track playing now, or None when nothing plays
+    // Setting the same track again changes nothing, the title doesn't blink
+    pub fn set(&mut self, track: Option<Track>, now: Instant) {
+        if track == self.track {
+            return;
+        }
+        // What's on screen fades out from where it is, even if it was still fading in
+        self.leaving = self
+            .visible(now)
+            .map(|(track, opacity)| (track.clone(), opacity));
+        self.track = track;
+        self.changed = Some(now);
+    }
+
+    // The title to draw at `now` and how opaque, from 0 to 1
+    pub fn visible(&self, now: Instant) -> Option<(&Track, f64)> {
+        let since = match self.changed {
+            Some(changed) => now.saturating_duration_since(changed),
+            None => return self.track.as_ref().map(|track| (track, 1.0)),
+        };
+        let mut arriving = since;
+        if let Some((leaving, from)) = &self.leaving {
+            if since < FADE_OUT {
+                return Some((leaving, from * (1.0 - since.div_duration_f64(FADE_OUT))));
+            }
+            arriving = since - FADE_OUT;
+        }
+        let track = self.track.as_ref()?;
+        Some((track, arriving.div_duration_f64(FADE_IN).min(1.0)))
+    }
+
+    // Whether the title is still fading, the window keeps drawing until it's done
+    pub fn fading(&self, now: Instant) -> bool {
+        self.changed
+            .is_some_and(|changed| now.saturating_duration_since(changed) < FADE_OUT + FADE_IN)
+    }
+}
+
+// Call `on_change` from now on with the track playing, whenever it changes
+// It's called from another thread. Without the `mpris` feature it never is
+#[cfg(feature = "mpris")]
+pub fn watch(on_change: impl Fn(Option<Track>) + Send + 'static) {
+    crate::mpris::watch(on_change);
+}
+
+#[cfg(not(feature = "mpris"))]
+pub fn watch(on_change: impl Fn(Option<Track>) + Send + 'static) {
+    let _ = on_change;
+}
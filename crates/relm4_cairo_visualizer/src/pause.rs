@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::visualizer::Frame;
+
+// Freezing the bars on screen (the spacebar)
+//
+// While paused the frames keep coming in and we keep taking them off the channel,
+// otherwise they would pile up in there. Instead of showing them we only remember the
+// latest one, and when we resume we jump straight to it: nobody wants to watch the
+// seconds they missed in fast forward.
+//
+// These only deal with the frames, the app decides what to redraw from what they
+// return, so they can be checked without a window.
+
+// A frame came in: show it, or hold it back while paused
+// Returns whether the bars on screen changed, i.e. they have to be drawn again
+pub fn receive_frame(
+    paused: bool,
+    shown: &mut Arc<Frame>,
+    held: &mut Option<Arc<Frame>>,
+    frame: Arc<Frame>,
+) -> bool {
+    if paused {
+        // Only the latest one matters, the previous one is dropped
+        *held = Some(frame);
+        return false;
+    }
+    show(shown, frame)
+}
+
+// Show the latest frame that came in while paused, if any did
+// Returns whether the bars on screen changed
+pub fn resume(shown: &mut Arc<Frame>, held: &mut Option<Arc<Frame>>) -> bool {
+    match held.take() {
+        Some(frame) => show(shown, frame),
+        None => false,
+    }
+}
+
+fn show(shown: &mut Arc<Frame>, frame: Arc<Frame>) -> bool {
+    // If any value changed, the bars have to be rendered again
+    let changed = shown.values != frame.values;
+    *shown = frame;
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(seq: u64, value: u16) -> Arc<Frame> {
+        Arc::new(Frame {
+            seq,
+            ..Frame::mono(vec![value; 4])
+        })
+    }
+
+    #[test]
+    fn a_new_frame_is_shown_while_running() {
+        let mut shown = frame(0, 10);
+        let mut held = None;
+
+        assert!(receive_frame(false, &mut shown, &mut held, frame(1, 20)));
+        assert_eq!(shown.seq, 1);
+        assert!(held.is_none());
+    }
+
+    #[test]
+    fn the_same_values_need_no_redraw() {
+        let mut shown = frame(0, 10);
+        let mut held = None;
+
+        assert!(!receive_frame(false, &mut shown, &mut held, frame(1, 10)));
+        // Still the latest frame
+        assert_eq!(shown.seq, 1);
+    }
+
+    #[test]
+    fn frames_are_held_back_while_paused() {
+        let mut shown = frame(0, 10);
+        let mut held = None;
+
+        for seq in 1..=5 {
+            assert!(!receive_frame(
+                true,
+                &mut shown,
+                &mut held,
+                frame(seq, seq as u16 * 100)
+            ));
+        }
+        assert_eq!(shown.seq, 0);
+        // Only the latest one is kept
+        assert_eq!(held.as_ref().unwrap().seq, 5);
+    }
+
+    #[test]
+    fn resuming_snaps_to_the_latest_frame() {
+        let mut shown = frame(0, 10);
+        let mut held = None;
+        for seq in 1..=5 {
+            receive_frame(true, &mut shown, &mut held, frame(seq, seq as u16 * 100));
+        }
+
+        assert!(resume(&mut shown, &mut held));
+        assert_eq!(shown.seq, 5);
+        assert!(held.is_none());
+        // The next frame after that is shown as usual
+        assert!(receive_frame(false, &mut shown, &mut held, frame(6, 10)));
+        assert_eq!(shown.seq, 6);
+    }
+
+    #[test]
+    fn resuming_without_new_frames_changes_nothing() {
+        let mut shown = frame(0, 10);
+        let mut held = None;
+
+        assert!(!resume(&mut shown, &mut held));
+        assert_eq!(shown.seq, 0);
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+// Peak-hold: every bar remembers the highest value it reached, and that peak slowly
+// falls back down until the bar pushes it up again. The bars move a lot, the peaks
+// show where they have been.
+//
+// The peaks are in the same units as the values (0 to u16::MAX), but as f64 so they
+// can fall by less than one unit per frame.
+
+// How fast a peak falls, in values per second: half of the full height
+pub const PEAK_FALL_SPEED: f64 = u16::MAX as f64 * 0.5;
+
+// Move the peaks on by `elapsed`, with the `values` of the new frame
+// The fall depends on the time since the last update and not on the number of frames,
+// so the peaks fall as fast at any framerate.
+// Returns whether any peak moved, i.e. they have to be drawn again
+pub fn update_peaks(peaks: &mut Vec<f64>, values: &[u16], elapsed: Duration) -> bool {
+    // The first frame, or the number of bars changed: every peak starts at its bar
+    if peaks.len() != values.len() {
+        *peaks = values.iter().map(|&value| value as f64).collect();
+        return true;
+    }
+
+    let fall = PEAK_FALL_SPEED * elapsed.as_secs_f64();
+    let mut moved = false;
+    for (peak, &value) in peaks.iter_mut().zip(values) {
+        // A bar above its peak pushes it up, otherwise it keeps falling, down to the bar
+        let next = (*peak - fall).max(value as f64);
+        if next != *peak {
+            *peak = next;
+            moved = true;
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn the_peaks_start_at_the_bars() {
+        let mut peaks = Vec::new();
+        assert!(update_peaks(&mut peaks, &[10, 20, 30], ms(16)));
+        assert_eq!(peaks, vec![10.0, 20.0, 30.0]);
+
+        // And again when the number of bars changes
+        assert!(update_peaks(&mut peaks, &[5, 6], ms(16)));
+        assert_eq!(peaks, vec![5.0, 6.0]);
+    }
+
+    #[test]
+    fn a_higher_bar_pushes_its_peak_up() {
+        let mut peaks = vec![100.0, 100.0];
+        assert!(update_peaks(&mut peaks, &[500, 0], Duration::ZERO));
+        assert_eq!(peaks, vec![500.0, 100.0]);
+    }
+
+    #[test]
+    fn the_peaks_fall_by_the_elapsed_time() {
+        let mut peaks = vec![u16::MAX as f64];
+        update_peaks(&mut peaks, &[0], ms(500));
+        // Half of the fall speed
+        assert_eq!(peaks, vec![u16::MAX as f64 * 0.75]);
+    }
+
+    #[test]
+    fn a_peak_falls_down_to_its_bar_and_no_further() {
+        let mut peaks = vec![1000.0];
+        update_peaks(&mut peaks, &[900], ms(1000));
+        assert_eq!(peaks, vec![900.0]);
+        assert!(!update_peaks(&mut peaks, &[900], ms(1000)));
+    }
+
+    // The same second in 50 steps or in 10 gets the peaks to the same place
+    #[test]
+    fn the_fall_does_not_depend_on_the_framerate() {
+        let (mut fast, mut slow) = (vec![u16::MAX as f64], vec![u16::MAX as f64]);
+        for _ in 0..50 {
+            update_peaks(&mut fast, &[0], ms(20));
+        }
+        for _ in 0..10 {
+            update_peaks(&mut slow, &[0], ms(100));
+        }
+        assert!((fast[0] - slow[0]).abs() < 1e-6);
+        assert!((fast[0] - u16::MAX as f64 * 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn no_time_no_move() {
+        let mut peaks = vec![300.0, 200.0];
+        assert!(!update_peaks(&mut peaks, &[100, 200], Duration::ZERO));
+        assert_eq!(peaks, vec![300.0, 200.0]);
+    }
+}
@@ -0,0 +1,344 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::{ChannelView, RenderStyle};
+use crate::settings::{Settings, Smoothing, MAX_BARS, MAX_FRAMERATE, MIN_BARS, MIN_FRAMERATE};
+
+// What the visualizer remembers from one run to the next
+//
+// Every change (the preferences, `w`, `d`, `t`, resizing the window...) is written to
+// ~/.config/playground-visualizer/config.toml, and the next start picks up from there.
+// The command line and the `--config` file still win, see `Cli::fill_from_saved`.
+//
+// We write it ourselves, so a file that doesn't parse was broken by someone else: it
+// is moved aside to config.toml.bak and we start over from the defaults, the
+// visualizer never refuses to start over it.
+
+// How long to wait after a change before writing, resizing the window or holding
+// down a key would write the file many times a second otherwise
+pub const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+// The size of the window when it isn't fullscreen (GTK 4 has no say in where the
+// window goes, that's up to the window manager)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WindowPlacement {
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+}
+
+// Every key is optional when reading: a file from an older version, or one written
+// by hand, gets the defaults for what it doesn't have
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SavedSettings {
+    pub bars: usize,
+    pub framerate: u32,
+    pub smoothing: Smoothing,
+    pub style: RenderStyle,
+    pub channels: ChannelView,
+    pub debug_labels: bool,
+    pub show_fps: bool,
+    // The name of the theme in use, `t` switches (see themes.rs)
+    // None in a file from before there were several: the user's theme, or the default
+    pub theme: Option<String>,
+    // None until the window was resized once, GTK picks the size then
+    // Last, TOML wants the tables after the plain values
+    pub window: Option<WindowPlacement>,
+}
+
+impl Default for SavedSettings {
+    fn default() -> SavedSettings {
+        let settings = Settings::default();
+        SavedSettings {
+            bars: settings.bars,
+            framerate: settings.framerate,
+            smoothing: settings.smoothing,
+            style: RenderStyle::default(),
+            channels: ChannelView::default(),
+            debug_labels: false,
+            show_fps: false,
+            theme: None,
+            window: None,
+        }
+    }
+}
+
+impl SavedSettings {
+    // The part the preferences dialog changes
+    pub fn settings(&self) -> Settings {
+        Settings {
+            bars: self.bars,
+            framerate: self.framerate,
+            smoothing: self.smoothing,
+        }
+    }
+
+    // The values that parse but that we would never have written
+    fn check(&self) -> Result<(), String> {
+        if !(MIN_BARS..=MAX_BARS).contains(&self.bars) {
+            return Err(format!(
+                "bars is {}, it goes from {} to {}",
+                self.bars, MIN_BARS, MAX_BARS
+            ));
+        }
+        if !(MIN_FRAMERATE..=MAX_FRAMERATE).contains(&self.framerate) {
+            return Err(format!(
+                "framerate is {}, it goes from {} to {}",
+                self.framerate, MIN_FRAMERATE, MAX_FRAMERATE
+            ));
+        }
+        if let Some(window) = self.window {
+            if window.width < 1 || window.height < 1 {
+                return Err(format!(
+                    "the window is {}x{}, it can't be that small",
+                    window.width, window.height
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum PersistError {
+    Io(PathBuf, io::Error),
+    // Not TOML, or a key with a value of the wrong type
+    Parse(PathBuf, toml::de::Error),
+    // TOML with the right types, but out of range
+    Invalid(PathBuf, String),
+    Serialize(toml::ser::Error),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            PersistError::Parse(path, e) => write!(
+                f,
+                "Invalid settings {}:\n{}",
+                path.display(),
+                e.to_string().trim_end()
+            ),
+            PersistError::Invalid(path, reason) => {
+                write!(f, "Invalid settings {}: {}", path.display(), reason)
+            }
+            PersistError::Serialize(e) => write!(f, "Failed to write the settings: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PersistError {}
+
+// ~/.config/playground-visualizer, or the same under $XDG_CONFIG_HOME
+// None if we can't even tell where the home directory is
+pub fn config_dir() -> Option<PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("playground-visualizer"))
+}
+
+pub fn path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+// The saved settings, the defaults if there are none yet
+pub fn load_from(path: &Path) -> Result<SavedSettings, PersistError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(SavedSettings::default()),
+        Err(e) => return Err(PersistError::Io(path.to_path_buf(), e)),
+    };
+    let saved: SavedSettings =
+        toml::from_str(&text).map_err(|e| PersistError::Parse(path.to_path_buf(), e))?;
+    saved
+        .check()
+        .map_err(|reason| PersistError::Invalid(path.to_path_buf(), reason))?;
+    Ok(saved)
+}
+
+// Load the settings whatever it takes, saying on stderr what went wrong
+// A broken file is moved to `<path>.bak` and replaced with the defaults. A file we
+// can't read at all (permissions...) is left alone, we just don't use it
+pub fn load_or_reset(path: &Path) -> SavedSettings {
+    let error = match load_from(path) {
+        Ok(saved) => return saved,
+        Err(e) => e,
+    };
+    eprintln!("{}", error);
+    let defaults = SavedSettings::default();
+    if let PersistError::Io(..) = error {
+        return defaults;
+    }
+
+    let backup = backup_path(path);
+    match fs::rename(path, &backup) {
+        Ok(()) => eprintln!("Moved it to {}, using the defaults", backup.display()),
+        Err(e) => eprintln!("Failed to move it to {}: {}", backup.display(), e),
+    }
+    if let Err(e) = save_to(path, &defaults) {
+        eprintln!("{}", e);
+    }
+    defaults
+}
+
+// config.toml.bak next to config.toml, an older backup is replaced
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+// Write the settings to `path`, in one go: they go to a file next to it first, which
+// then replaces the old one. A crash (or a full disk) halfway through leaves the old
+// settings, never half of the new ones
+pub fn save_to(path: &Path, saved: &SavedSettings) -> Result<(), PersistError> {
+    let text = toml::to_string(saved).map_err(PersistError::Serialize)?;
+    let io_error = |e| PersistError::Io(path.to_path_buf(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(text.as_bytes())?;
+        // On disk before the rename, or the rename could land first
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(io_error(e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn changed() -> SavedSettings {
+        SavedSettings {
+            bars: 64,
+            framerate: 30,
+            smoothing: Smoothing::Strong,
+            style: RenderStyle::Radial,
+            channels: ChannelView::SplitVertical,
+            debug_labels: true,
+            show_fps: true,
+            theme: Some("sunset".to_string()),
+            window: Some(WindowPlacement {
+                width: 1280,
+                height: 720,
+                maximized: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn what_is_saved_is_loaded() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+
+        save_to(&path, &changed()).unwrap();
+        assert_eq!(load_from(&path).unwrap(), changed());
+        // The temporary file is gone
+        assert!(!dir.path().join("config.toml.tmp").exists());
+    }
+
+    #[test]
+    fn saving_creates_the_directory() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("playground-visualizer").join("config.toml");
+
+        save_to(&path, &SavedSettings::default()).unwrap();
+        assert_eq!(load_from(&path).unwrap(), SavedSettings::default());
+    }
+
+    #[test]
+    fn no_file_is_the_defaults() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(load_from(&path).unwrap(), SavedSettings::default());
+        assert_eq!(load_or_reset(&path), SavedSettings::default());
+        // Nothing to move aside
+        assert!(!backup_path(&path).exists());
+    }
+
+    #[test]
+    fn missing_keys_get_the_defaults() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "bars = 64\n").unwrap();
+
+        let saved = load_from(&path).unwrap();
+        assert_eq!(
+            saved,
+            SavedSettings {
+                bars: 64,
+                ..SavedSettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn a_corrupt_file_is_moved_aside() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "bars = [this is not toml").unwrap();
+
+        assert!(matches!(load_from(&path), Err(PersistError::Parse(..))));
+        assert_eq!(load_or_reset(&path), SavedSettings::default());
+        // The broken file is kept for whoever wants to see it, the defaults replace it
+        assert_eq!(
+            fs::read_to_string(backup_path(&path)).unwrap(),
+            "bars = [this is not toml"
+        );
+        assert_eq!(load_from(&path).unwrap(), SavedSettings::default());
+    }
+
+    #[test]
+    fn values_out_of_range_are_invalid() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        for text in [
+            "bars = 0",
+            "framerate = 100000",
+            "[window]\nwidth = 0\nheight = 10\nmaximized = false",
+        ] {
+            fs::write(&path, text).unwrap();
+            assert!(
+                matches!(load_from(&path), Err(PersistError::Invalid(..))),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn a_value_of_the_wrong_type_is_a_parse_error() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "style = \"sparkles\"").unwrap();
+        assert!(matches!(load_from(&path), Err(PersistError::Parse(..))));
+    }
+
+    #[test]
+    fn the_backup_is_next_to_the_file() {
+        assert_eq!(
+            backup_path(Path::new("/a/b/config.toml")),
+            PathBuf::from("/a/b/config.toml.bak")
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use relm4::gtk::prelude::*;
+use relm4::{gtk, Component, ComponentParts, ComponentSender, RelmWidgetExt};
+
+use crate::settings::{Settings, Smoothing, MAX_BARS, MAX_FRAMERATE, MIN_BARS, MIN_FRAMERATE};
+
+// A small window to change the number of bars, the framerate and the smoothing
+// The widgets are only read when the user clicks Apply, so Cancel (or closing the
+// window) just puts the values we had back into them
+pub struct PreferencesDialog {
+    // The settings currently in use, what Cancel goes back to
+    settings: Settings,
+}
+
+#[derive(Debug)]
+pub enum PreferencesMsg {
+    Show,
+    Apply,
+    Cancel,
+    // The settings were changed somewhere else (the header bar), Cancel goes back to
+    // those from now on
+    Changed(Settings),
+}
+
+#[derive(Debug)]
+pub enum PreferencesOutput {
+    Apply(Settings),
+}
+
+#[relm4::component(pub)]
+impl Component for PreferencesDialog {
+    type Input = PreferencesMsg;
+    type Output = PreferencesOutput;
+    type Init = Settings;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            set_title: Some("Preferences"),
+            set_modal: true,
+            set_resizable: false,
+            // Closing the window hides it, so it can be shown again
+            set_hide_on_close: true,
+            // Closing it without applying is the same as clicking Cancel
+            connect_close_request[sender] => move |_| {
+                sender.input(PreferencesMsg::Cancel);
+                gtk::glib::Propagation::Stop
+            },
+
+            gtk::Grid {
+                set_margin_all: 12,
+                set_row_spacing: 6,
+                set_column_spacing: 12,
+
+                attach[0, 0, 1, 1] = &gtk::Label {
+                    set_label: "Bars",
+                    set_halign: gtk::Align::Start,
+                },
+                #[name = "bars"]
+                attach[1, 0, 1, 1] = &gtk::SpinButton::with_range(MIN_BARS as f64, MAX_BARS as f64, 1.0) {},
+
+                attach[0, 1, 1, 1] = &gtk::Label {
+                    set_label: "Framerate",
+                    set_halign: gtk::Align::Start,
+                },
+                #[name = "framerate"]
+                attach[1, 1, 1, 1] = &gtk::SpinButton::with_range(MIN_FRAMERATE as f64, MAX_FRAMERATE as f64, 1.0) {},
+
+                attach[0, 2, 1, 1] = &gtk::Label {
+                    set_label: "Smoothing",
+                    set_halign: gtk::Align::Start,
+                },
+                #[name = "smoothing"]
+                attach[1, 2, 1, 1] = &gtk::ComboBoxText {},
+
+                attach[0, 3, 2, 1] = &gtk::Box {
+                    set_halign: gtk::Align::End,
+                    set_spacing: 6,
+                    set_margin_top: 6,
+
+                    gtk::Button {
+                        set_label: "Cancel",
+                        connect_clicked => PreferencesMsg::Cancel,
+                    },
+                    gtk::Button {
+                        set_label: "Apply",
+                        add_css_class: "suggested-action",
+                        connect_clicked => PreferencesMsg::Apply,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        settings: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = PreferencesDialog { settings };
+        let widgets = view_output!();
+
+        for smoothing in Smoothing::ALL {
+            widgets
+                .smoothing
+                .append(Some(smoothing.id()), smoothing.label());
+        }
+        show_settings(&widgets, &model.settings);
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match message {
+            PreferencesMsg::Show => {
+                show_settings(widgets, &self.settings);
+                root.present();
+            }
+            PreferencesMsg::Apply => {
+                let settings = read_settings(widgets, &self.settings);
+                self.settings = settings;
+                // The app may already be gone if the dialog outlives it, nothing to do then
+                let _ = sender.output(PreferencesOutput::Apply(settings));
+                root.set_visible(false);
+            }
+            PreferencesMsg::Cancel => {
+                show_settings(widgets, &self.settings);
+                root.set_visible(false);
+            }
+            PreferencesMsg::Changed(settings) => {
+                self.settings = settings;
+                show_settings(widgets, &self.settings);
+            }
+        }
+    }
+}
+
+fn show_settings(widgets: &PreferencesDialogWidgets, settings: &Settings) {
+    widgets.bars.set_value(settings.bars as f64);
+    widgets.framerate.set_value(settings.framerate as f64);
+    widgets
+        .smoothing
+        .set_active_id(Some(settings.smoothing.id()));
+}
+
+// The spin buttons never go outside of their range, so the values are always valid
+// `current` is only used if somehow no smoothing is selected
+fn read_settings(widgets: &PreferencesDialogWidgets, current: &Settings) -> Settings {
+    Settings {
+        bars: widgets.bars.value_as_int() as usize,
+        framerate: widgets.framerate.value_as_int() as u32,
+        smoothing: widgets
+            .smoothing
+            .active_id()
+            .and_then(|id| Smoothing::from_id(&id))
+            .unwrap_or(current.smoothing),
+    }
+}
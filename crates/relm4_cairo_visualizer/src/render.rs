@@ -0,0 +1,1220 @@
+use std::f64::consts::{FRAC_PI_2, TAU};
+use std::ops::Range;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// Where things go on screen, for every render style
+//
+// Only numbers in here, no cairo: the drawing functions (see draw.rs) ask these
+// functions where to draw and then draw there, so the math can be checked on its own.
+//
+// (0, 0) is the top-left corner of the drawing area, x grows to the right and y grows
+// downward. A value of 0 is at the bottom of the area, u16::MAX at the top.
+
+// How the values are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, Serialize)]
+pub enum RenderStyle {
+    // One rectangle per value
+    #[default]
+    #[value(name = "bars")]
+    #[serde(rename = "bars")]
+    Bars,
+    // A smooth line through the top of every value, filled down to the bottom
+    // Called `wave` on the command line and in the config file
+    #[value(name = "wave")]
+    #[serde(rename = "wave")]
+    Waveform,
+    // The bars around a circle, pointing outward from the center
+    #[value(name = "radial")]
+    #[serde(rename = "radial")]
+    Radial,
+    // The bars growing up and down from a line across the middle
+    #[value(name = "mirrored")]
+    #[serde(rename = "mirrored")]
+    Mirrored,
+}
+
+// How the channels of a stereo frame share the area
+// A mono frame is always drawn across the whole area, whatever this says
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelView {
+    // The values as they come: a stereo frame shows its left bars, then its right ones
+    #[default]
+    Mono,
+    // The left channel on the left half, the right one on the right half, mirrored so
+    // the lowest frequencies are at both outer edges
+    SplitHorizontal,
+    // The left channel on the upper half, the right one on the lower half
+    SplitVertical,
+}
+
+impl ChannelView {
+    // The next view, for the key that goes through them
+    pub fn next(self) -> ChannelView {
+        match self {
+            ChannelView::Mono => ChannelView::SplitHorizontal,
+            ChannelView::SplitHorizontal => ChannelView::SplitVertical,
+            ChannelView::SplitVertical => ChannelView::Mono,
+        }
+    }
+}
+
+// The space between the two halves of a split view
+pub const CHANNEL_GAP: f64 = 10.0;
+
+// Which values of a frame a part of the area shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    All,
+    Left,
+    Right,
+}
+
+// A part of the area with the values of one channel, (x, y) is its top-left corner
+// Every style draws in it as if it was the whole area, with `geometry` as its size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelArea {
+    pub channel: Channel,
+    pub x: f64,
+    pub y: f64,
+    pub geometry: Geometry,
+    // The values go right to left: the highest frequency first
+    pub reversed: bool,
+}
+
+impl ChannelArea {
+    // The values (or peaks) this area shows, out of all the values of the frame
+    // A stereo frame has the left channel's values first, then the right channel's
+    pub fn select<T: Copy>(&self, values: &[T]) -> Vec<T> {
+        let half = values.len() / 2;
+        let selected = match self.channel {
+            Channel::All => values,
+            Channel::Left => &values[..half],
+            Channel::Right => &values[half..],
+        };
+        if self.reversed {
+            selected.iter().rev().copied().collect()
+        } else {
+            selected.to_vec()
+        }
+    }
+}
+
+// Where each channel goes in an area of `geometry`
+// One area for a mono frame or the mono view, two for a stereo frame split in halves
+// with CHANNEL_GAP between them
+pub fn channel_areas(view: ChannelView, stereo: bool, geometry: Geometry) -> Vec<ChannelArea> {
+    let whole = ChannelArea {
+        channel: Channel::All,
+        x: 0.0,
+        y: 0.0,
+        geometry,
+        reversed: false,
+    };
+    if !stereo {
+        return vec![whole];
+    }
+    match view {
+        ChannelView::Mono => vec![whole],
+        ChannelView::SplitHorizontal => {
+            let width = ((geometry.width - CHANNEL_GAP) / 2.0).max(0.0);
+            let half = Geometry::new(width, geometry.height);
+            vec![
+                ChannelArea {
+                    channel: Channel::Left,
+                    geometry: half,
+                    ..whole
+                },
+                ChannelArea {
+                    channel: Channel::Right,
+                    x: geometry.width - width,
+                    geometry: half,
+                    reversed: true,
+                    ..whole
+                },
+            ]
+        }
+        ChannelView::SplitVertical => {
+            let height = ((geometry.height - CHANNEL_GAP) / 2.0).max(0.0);
+            let half = Geometry::new(geometry.width, height);
+            vec![
+                ChannelArea {
+                    channel: Channel::Left,
+                    geometry: half,
+                    ..whole
+                },
+                ChannelArea {
+                    channel: Channel::Right,
+                    y: geometry.height - height,
+                    geometry: half,
+                    ..whole
+                },
+            ]
+        }
+    }
+}
+
+// The space between two bars, half of it on each side of a bar
+pub const BAR_PADDING: f64 = 10.0;
+
+// The empty space kept between the radial bars and the edges of the area
+pub const RADIAL_MARGIN: f64 = 10.0;
+// Where the radial bars start, as a fraction of the radius (0 is the center)
+pub const DEFAULT_INNER_RADIUS: f64 = 0.25;
+
+pub type Point = (f64, f64);
+
+// The size of the area we draw on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Geometry {
+    pub fn new(width: f64, height: f64) -> Geometry {
+        Geometry { width, height }
+    }
+
+    // The space each of `count` values gets, side by side across the whole width
+    // Formula: slot_width = DrawingArea_width / number_of_bars
+    // Example: For 20 bars in an 800px wide area, each bar gets 40px
+    // This ensures bars are evenly distributed across the available space
+    pub fn slot_width(&self, count: usize) -> f64 {
+        self.width / count as f64
+    }
+
+    // How many pixels tall a value is
+    // The value is normalized by dividing it by the maximum possible value (u16::MAX = 65535)
+    // This ensures that the heights are proportional to the values and fit within the area
+    // The division is done on integers, so the heights are whole pixels
+    pub fn value_height(&self, value: u16) -> f64 {
+        ((value as u64 * self.height as u64) / u16::MAX as u64) as f64
+    }
+
+    pub fn center(&self) -> Point {
+        (self.width / 2.0, self.height / 2.0)
+    }
+
+    // The radii the radial bars go between, `inner_fraction` of the way out to the edge
+    // The circle has to fit both ways, so in a wide window the height decides how big
+    // it is and in a tall one the width does
+    pub fn radial_radii(&self, inner_fraction: f64) -> (f64, f64) {
+        let outer = (self.width.min(self.height) / 2.0 - RADIAL_MARGIN).max(0.0);
+        (outer * inner_fraction, outer)
+    }
+}
+
+// A rectangle, (x, y) is its top-left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// The smallest a bar slot gets (the bar and the space next to it), in pixels
+// When the area is too narrow to give every bar that much, neighbouring values are
+// averaged into fewer bars (see `layout`)
+pub const MIN_SLOT_WIDTH: f64 = 3.0;
+
+// The smallest size of the drawing area, the window can't be made smaller than this
+// At this width 256 bars become 53, which still looks like a spectrum
+pub const MIN_AREA_WIDTH: i32 = 160;
+pub const MIN_AREA_HEIGHT: i32 = 100;
+
+// Where `bars` bars go in an area of `width` x `height`, with `padding` between them,
+// as rectangles as tall as the area (the values decide how much of that is drawn)
+//
+// GTK keeps the area at least MIN_AREA_WIDTH wide, but the math doesn't rely on it:
+// - when the slots would be narrower than MIN_SLOT_WIDTH there are fewer of them, so
+//   this can return fewer rectangles than `bars` (and none for an empty area)
+// - the padding never takes more than half of a slot, so a bar is never less than
+//   half of its slot wide, and never negative
+pub fn layout(width: f64, height: f64, bars: usize, padding: f64) -> Vec<BarRect> {
+    let fitting = (width.max(0.0) / MIN_SLOT_WIDTH) as usize;
+    let count = bars.min(fitting);
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let slot_width = width / count as f64;
+    let padding = padding.clamp(0.0, slot_width / 2.0);
+
+    (0..count)
+        .map(|i| {
+            // Calculate the X position of each bar
+            // The X position is determined by the bar's index (i) multiplied by the slot width
+            // This ensures equal spacing between bars across the drawing area
+            // Example:
+            //   let slot_width = 50.0;
+            //   for i in 0..5 {
+            //       let x = i as f64 * slot_width;
+            //       println!("Bar {}: x = {}", i, x);
+            //   }
+            // Output:
+            //   Bar 0: x = 0.0
+            //   Bar 1: x = 50.0
+            //   Bar 2: x = 100.0
+            //   Bar 3: x = 150.0
+            //   Bar 4: x = 200.0
+            // The padding is split between both sides of the bar
+            BarRect {
+                x: (i as f64 * slot_width) + padding / 2.0,
+                y: 0.0,
+                width: slot_width - padding,
+                height: height.max(0.0),
+            }
+        })
+        .collect()
+}
+
+// `values` squeezed into `count` values, each one the average of the values it
+// replaces (the first of `count` gets the first values, and so on)
+// With as many or more slots than values, nothing changes
+pub fn average_values(values: &[u16], count: usize) -> Vec<u16> {
+    if count >= values.len() {
+        return values.to_vec();
+    }
+    (0..count)
+        .map(|i| {
+            // Every value ends up in exactly one group, the groups differ by one value
+            // at most when the division isn't exact
+            let group = &values[i * values.len() / count..(i + 1) * values.len() / count];
+            let sum: u64 = group.iter().map(|&value| value as u64).sum();
+            (sum / group.len() as u64) as u16
+        })
+        .collect()
+}
+
+// The rectangle of every value, lowest frequency first
+// Fewer rectangles than values when the area is too narrow for them all, see `layout`
+pub fn bar_rects(values: &[u16], geometry: Geometry) -> Vec<BarRect> {
+    let slots = layout(geometry.width, geometry.height, values.len(), BAR_PADDING);
+    let values = average_values(values, slots.len());
+
+    slots
+        .into_iter()
+        .zip(values)
+        .map(|(slot, value)| {
+            let height = geometry.value_height(value);
+
+            // Calculate the Y position of the bar
+            // The Y position is determined by subtracting the bar's height from the drawing area's height
+            // This positions the bar from the bottom of the drawing area
+            // Example:
+            //   let area_height = 200.0;
+            //   let bar_height = 50.0;
+            //   let y = area_height - bar_height; // y = 150.0
+            // The bar would start at y = 150.0 and extend upwards to y = 200.0
+            // Increasing Y moves downward, while increasing height moves upward
+            let y = geometry.height - height;
+
+            BarRect { y, height, ..slot }
+        })
+        .collect()
+}
+
+// The bar under the pointer, for the tooltip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoveredBar {
+    pub channel: Channel,
+    // Which values of its channel the bar shows, lowest frequency first: just one, or
+    // the few averaged into it when the area is too narrow for them all
+    pub bars: Range<usize>,
+    // The same values, as indices into the values of the whole frame
+    pub values: Range<usize>,
+}
+
+// The bar at (x, y), in an area of `geometry` showing a frame of `count` values
+// Only the styles with bars side by side have one, the wave and the radial bars don't.
+// The bars are as tall as the area for this, a short bar can be hovered over anywhere
+// above it. There is no bar:
+// - in the padding between two bars, and in the half of it at the left and right edges
+// - in the gap between the two channels of a split view
+// - outside of the area (a bar has its left edge, not its right one, so neither does
+//   the area: x == width is outside)
+pub fn bar_at(
+    style: RenderStyle,
+    view: ChannelView,
+    stereo: bool,
+    count: usize,
+    geometry: Geometry,
+    (x, y): Point,
+) -> Option<HoveredBar> {
+    if !matches!(style, RenderStyle::Bars | RenderStyle::Mirrored) {
+        return None;
+    }
+    let area = channel_areas(view, stereo, geometry)
+        .into_iter()
+        .find(|area| {
+            (area.x..area.x + area.geometry.width).contains(&x)
+                && (area.y..area.y + area.geometry.height).contains(&y)
+        })?;
+    // The values of this area, and where they start in the frame (`select` for ranges)
+    let (first, len) = match area.channel {
+        Channel::All => (0, count),
+        Channel::Left => (0, count / 2),
+        Channel::Right => (count / 2, count - count / 2),
+    };
+
+    let slots = layout(area.geometry.width, area.geometry.height, len, BAR_PADDING);
+    let x = x - area.x;
+    let slot = slots
+        .iter()
+        .position(|slot| (slot.x..slot.x + slot.width).contains(&x))?;
+    // The values averaged into that slot, grouped like `average_values` does
+    let shown = slot * len / slots.len()..(slot + 1) * len / slots.len();
+    let bars = if area.reversed {
+        len - shown.end..len - shown.start
+    } else {
+        shown
+    };
+    Some(HoveredBar {
+        channel: area.channel,
+        values: first + bars.start..first + bars.end,
+        bars,
+    })
+}
+
+// Past this many bars, only some of them get debug labels
+pub const MAX_LABELS: usize = 40;
+
+// With debug labels on, every how many bars one gets a label
+// Up to MAX_LABELS bars all of them do, beyond that the labels would overlap, so we
+// only label every 2nd, 3rd... bar: never more than MAX_LABELS labels in total
+pub fn label_every(bars: usize) -> usize {
+    bars.div_ceil(MAX_LABELS).max(1)
+}
+
+// The radius the corners of a `width` x `height` rectangle can have, at most `radius`
+// Two corners side by side take twice the radius: past half the width (or height)
+// the arcs would overlap and the outline cross itself. A bar shorter than two radii
+// gets a rounder top, down to a half circle, and an empty one has no corners at all
+pub fn corner_radius(radius: f64, width: f64, height: f64) -> f64 {
+    radius.min(width / 2.0).min(height / 2.0).max(0.0)
+}
+
+// How tall a peak marker is
+pub const PEAK_MARKER_HEIGHT: f64 = 3.0;
+
+// The peak marker of every bar (see peaks.rs), a thin rectangle sitting on the peak
+// There is no marker for a bar that is at its peak: it would be drawn right on the
+// bar's stroke. A peak near the top gets its marker pushed down to stay inside the area.
+pub fn peak_markers(values: &[u16], peaks: &[f64], geometry: Geometry) -> Vec<BarRect> {
+    let bars = bar_rects(values, geometry);
+    // Squeezed like the values when the area is too narrow for all the bars
+    let peaks: Vec<u16> = peaks.iter().map(|&peak| peak.round() as u16).collect();
+    let peaks = average_values(&peaks, bars.len());
+
+    bars.into_iter()
+        .zip(peaks)
+        .filter_map(|(bar, peak)| {
+            // Not `value_height`, the marker can be between two pixels
+            let peak_height = peak as f64 * geometry.height / u16::MAX as f64;
+            if peak_height - bar.height < 1.0 {
+                return None;
+            }
+            let y = (geometry.height - peak_height - PEAK_MARKER_HEIGHT).max(0.0);
+            Some(BarRect {
+                y,
+                height: PEAK_MARKER_HEIGHT,
+                ..bar
+            })
+        })
+        .collect()
+}
+
+// A bar of the mirrored style: the same bar twice, one half going up from the middle
+// of the area and the other going down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MirroredBar {
+    pub upper: BarRect,
+    pub lower: BarRect,
+}
+
+// A bar of height h becomes two halves of h / 2 around the middle line, so a full
+// bar still spans the whole height. Each half is clamped to half of the area anyway,
+// a half that's too tall would otherwise leave the window at the top or the bottom.
+pub fn mirrored_rects(values: &[u16], geometry: Geometry) -> Vec<MirroredBar> {
+    let middle = geometry.height / 2.0;
+
+    bar_rects(values, geometry)
+        .into_iter()
+        .map(|bar| {
+            let half = (bar.height / 2.0).min(middle);
+            MirroredBar {
+                upper: BarRect {
+                    y: middle - half,
+                    height: half,
+                    ..bar
+                },
+                lower: BarRect {
+                    y: middle,
+                    height: half,
+                    ..bar
+                },
+            }
+        })
+        .collect()
+}
+
+// A cubic Bézier segment, it starts where the previous one ended
+// This is exactly what cairo's `curve_to` takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curve {
+    pub control1: Point,
+    pub control2: Point,
+    pub end: Point,
+}
+
+// The top edge of the waveform, from the left edge of the area to the right one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wave {
+    pub start: Point,
+    pub curves: Vec<Curve>,
+}
+
+// The top of every value, in the middle of where its bar would be
+// The first and last values are also repeated on the left and right edges of the
+// area, so the wave spans the whole width
+pub fn wave_points(values: &[u16], geometry: Geometry) -> Vec<Point> {
+    let slot_width = geometry.slot_width(values.len());
+    let top = |value: u16| geometry.height - geometry.value_height(value);
+
+    let (Some(&first), Some(&last)) = (values.first(), values.last()) else {
+        return Vec::new();
+    };
+
+    let mut points = Vec::with_capacity(values.len() + 2);
+    points.push((0.0, top(first)));
+    for (i, &value) in values.iter().enumerate() {
+        points.push(((i as f64 + 0.5) * slot_width, top(value)));
+    }
+    points.push((geometry.width, top(last)));
+    points
+}
+
+// A smooth line through the values
+//
+// Drawing straight lines between the points gives sharp corners. Instead the curve
+// goes through the *midpoints* between two neighbouring points, and uses the points
+// themselves as control points: the curve bends towards each point without going
+// through it, and where two segments meet (at a midpoint) they have the same
+// direction, so there is no corner.
+//
+//   points:    P0      P1      P2      P3
+//   curve:     P0 - M01 ~(P1)~ M12 ~(P2)~ M23 - P3
+//
+// Each segment is a quadratic curve (one control point), cairo only does cubic ones
+// (two control points), so we convert: the same curve as a cubic has its control
+// points 2/3 of the way from each end towards the quadratic control point.
+pub fn wave(values: &[u16], geometry: Geometry) -> Wave {
+    let points = wave_points(values, geometry);
+    let Some(&start) = points.first() else {
+        // Nothing to draw, a flat line at the bottom
+        return Wave {
+            start: (0.0, geometry.height),
+            curves: vec![line(
+                (0.0, geometry.height),
+                (geometry.width, geometry.height),
+            )],
+        };
+    };
+
+    let mut curves = Vec::with_capacity(points.len());
+    // From the first point to the first midpoint there is nothing to bend around
+    let mut from = midpoint(points[0], points[1]);
+    curves.push(line(start, from));
+    for window in points[1..].windows(2) {
+        let to = midpoint(window[0], window[1]);
+        curves.push(quadratic(from, window[0], to));
+        from = to;
+    }
+    // Same from the last midpoint to the last point
+    curves.push(line(from, points[points.len() - 1]));
+
+    Wave { start, curves }
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+// A straight line is a curve with its control points on the line
+fn line(from: Point, to: Point) -> Curve {
+    Curve {
+        control1: from,
+        control2: to,
+        end: to,
+    }
+}
+
+// The quadratic curve from `from` to `to` bending towards `control`, as a cubic
+fn quadratic(from: Point, control: Point, to: Point) -> Curve {
+    let towards = |p: Point| {
+        (
+            p.0 + 2.0 / 3.0 * (control.0 - p.0),
+            p.1 + 2.0 / 3.0 * (control.1 - p.1),
+        )
+    };
+    Curve {
+        control1: towards(from),
+        control2: towards(to),
+        end: to,
+    }
+}
+
+// The radial bar number `i` of `bars`, as a line (x1, y1) -> (x2, y2) relative to the
+// center of the circle
+//
+// The bars are spread evenly around the circle, `2π / bars` apart, the first one
+// pointing straight up and the next ones going clockwise. A bar starts on the inner
+// circle and is as long as its value: 0 is a dot on the inner circle, u16::MAX
+// reaches the outer circle.
+//
+// Polar to cartesian: the point at distance `r` and angle `a` is (r cos a, r sin a).
+// Since y grows downward on screen, growing angles go clockwise, and straight up
+// is -π/2.
+pub fn radial_geometry(
+    i: usize,
+    bars: usize,
+    value: u16,
+    inner_r: f64,
+    outer_r: f64,
+) -> (f64, f64, f64, f64) {
+    let angle = i as f64 * TAU / bars as f64 - FRAC_PI_2;
+    let length = (outer_r - inner_r) * value as f64 / u16::MAX as f64;
+    let (sin, cos) = angle.sin_cos();
+    let end = inner_r + length;
+    (inner_r * cos, inner_r * sin, end * cos, end * sin)
+}
+
+// Where an image goes to cover an area of `width` x `height`: scaled (keeping its
+// proportions) until it's as wide and as high as the area, or more, and centered.
+// What's past the edges of the area is cut off.
+// `width` and `height` are the size of the scaled image, (x, y) is where its top-left
+// corner goes, at or above the top-left corner of the area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cover {
+    pub width: i32,
+    pub height: i32,
+    pub x: i32,
+    pub y: i32,
+}
+
+// Never less than a pixel each way, whatever the sizes (the scaled image is made
+// with that size, an empty image isn't one)
+pub fn cover(image_width: i32, image_height: i32, width: i32, height: i32) -> Cover {
+    let (image_width, image_height) = (image_width.max(1), image_height.max(1));
+    let (width, height) = (width.max(1), height.max(1));
+    let scale = (width as f64 / image_width as f64).max(height as f64 / image_height as f64);
+    // Rounding could make the image a pixel short of the area, it's at least as big
+    let scaled_width = ((image_width as f64 * scale).round() as i32).max(width);
+    let scaled_height = ((image_height as f64 * scale).round() as i32).max(height);
+    Cover {
+        width: scaled_width,
+        height: scaled_height,
+        x: (width - scaled_width) / 2,
+        y: (height - scaled_height) / 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn close_points(a: Point, b: Point) -> bool {
+        close(a.0, b.0) && close(a.1, b.1)
+    }
+
+    #[test]
+    fn the_bars_stand_on_the_bottom_of_the_area() {
+        let rects = bar_rects(
+            &[0, u16::MAX, u16::MAX / 2, 655],
+            Geometry::new(400.0, 100.0),
+        );
+
+        let heights: Vec<_> = rects.iter().map(|rect| rect.height).collect();
+        // Whole pixels, rounded down
+        assert_eq!(heights, vec![0.0, 100.0, 49.0, 0.0]);
+        for rect in &rects {
+            assert_eq!(rect.y + rect.height, 100.0);
+        }
+    }
+
+    #[test]
+    fn the_bars_share_the_width_with_padding_between_them() {
+        let rects = bar_rects(&[u16::MAX; 4], Geometry::new(400.0, 100.0));
+
+        let xs: Vec<_> = rects.iter().map(|rect| rect.x).collect();
+        assert_eq!(xs, vec![5.0, 105.0, 205.0, 305.0]);
+        assert!(rects.iter().all(|rect| rect.width == 90.0));
+    }
+
+    #[test]
+    fn no_values_no_bars() {
+        assert!(bar_rects(&[], Geometry::new(400.0, 100.0)).is_empty());
+    }
+
+    #[test]
+    fn the_wave_goes_through_the_middle_of_every_slot() {
+        let points = wave_points(&[0, u16::MAX], Geometry::new(200.0, 100.0));
+        // Plus the first and last values again at the edges
+        assert_eq!(
+            points,
+            vec![(0.0, 100.0), (50.0, 100.0), (150.0, 0.0), (200.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn the_wave_spans_the_whole_width() {
+        let geometry = Geometry::new(300.0, 100.0);
+        let wave = wave(&[100, 20000, 65535, 0, 4000], geometry);
+
+        assert_eq!(wave.start.0, 0.0);
+        assert_eq!(wave.curves.last().unwrap().end.0, 300.0);
+        // A line to the first midpoint, one curve per value after the first, and a
+        // line to the right edge
+        assert_eq!(wave.curves.len(), 7);
+    }
+
+    #[test]
+    fn the_wave_ends_on_the_midpoints() {
+        let values = [100, 20000, 65535, 0, 4000];
+        let geometry = Geometry::new(300.0, 100.0);
+        let points = wave_points(&values, geometry);
+        let wave = wave(&values, geometry);
+
+        for (curve, pair) in wave.curves.iter().zip(points.windows(2)) {
+            assert!(close_points(curve.end, midpoint(pair[0], pair[1])));
+        }
+    }
+
+    // Where two curves meet, the control points on both sides are in line with the
+    // meeting point, otherwise there would be a corner
+    #[test]
+    fn the_wave_has_no_corners() {
+        let wave = wave(&[100, 20000, 65535, 0, 4000], Geometry::new(300.0, 100.0));
+
+        for pair in wave.curves.windows(2) {
+            let (before, at, after) = (pair[0].control2, pair[0].end, pair[1].control1);
+            let cross = (at.0 - before.0) * (after.1 - at.1) - (at.1 - before.1) * (after.0 - at.0);
+            assert!(close(cross, 0.0), "corner at {:?}", at);
+        }
+    }
+
+    #[test]
+    fn the_quadratic_control_points_are_two_thirds_of_the_way() {
+        let curve = quadratic((0.0, 0.0), (30.0, 60.0), (60.0, 0.0));
+        assert!(close_points(curve.control1, (20.0, 40.0)));
+        assert!(close_points(curve.control2, (40.0, 40.0)));
+        assert_eq!(curve.end, (60.0, 0.0));
+    }
+
+    #[test]
+    fn silence_is_a_flat_wave_at_the_bottom() {
+        for values in [&[][..], &[0; 6][..]] {
+            let wave = wave(values, Geometry::new(300.0, 100.0));
+            assert_eq!(wave.start, (0.0, 100.0));
+            for curve in &wave.curves {
+                assert_eq!(curve.control1.1, 100.0);
+                assert_eq!(curve.control2.1, 100.0);
+                assert_eq!(curve.end.1, 100.0);
+            }
+            assert_eq!(wave.curves.last().unwrap().end, (300.0, 100.0));
+        }
+    }
+
+    #[test]
+    fn the_first_radial_bar_points_up() {
+        let (x1, y1, x2, y2) = radial_geometry(0, 4, u16::MAX, 10.0, 50.0);
+        assert!(close_points((x1, y1), (0.0, -10.0)));
+        assert!(close_points((x2, y2), (0.0, -50.0)));
+    }
+
+    #[test]
+    fn the_radial_bars_go_clockwise() {
+        // A quarter turn each: right, down, left
+        let ends: Vec<Point> = (1..4)
+            .map(|i| {
+                let (_, _, x2, y2) = radial_geometry(i, 4, u16::MAX, 10.0, 50.0);
+                (x2, y2)
+            })
+            .collect();
+        assert!(close_points(ends[0], (50.0, 0.0)));
+        assert!(close_points(ends[1], (0.0, 50.0)));
+        assert!(close_points(ends[2], (-50.0, 0.0)));
+    }
+
+    #[test]
+    fn the_radial_bars_are_evenly_spread() {
+        let bars = 7;
+        for i in 0..bars {
+            let (x1, y1, _, _) = radial_geometry(i, bars, 0, 1.0, 2.0);
+            let angle = y1.atan2(x1);
+            let expected = i as f64 * TAU / bars as f64 - FRAC_PI_2;
+            // Compared on the circle, the angles can differ by a whole turn
+            assert!(close(angle.sin(), expected.sin()) && close(angle.cos(), expected.cos()));
+        }
+    }
+
+    #[test]
+    fn a_radial_bar_is_as_long_as_its_value() {
+        let length = |value| {
+            let (x1, y1, x2, y2) = radial_geometry(3, 10, value, 20.0, 100.0);
+            (x2 - x1).hypot(y2 - y1)
+        };
+        assert!(close(length(0), 0.0));
+        assert!(close(length(u16::MAX), 80.0));
+        assert!(close(
+            length(u16::MAX / 4),
+            80.0 * (u16::MAX / 4) as f64 / u16::MAX as f64
+        ));
+
+        // Starting on the inner circle whatever the value
+        let (x1, y1, _, _) = radial_geometry(3, 10, 1234, 20.0, 100.0);
+        assert!(close(x1.hypot(y1), 20.0));
+    }
+
+    #[test]
+    fn the_circle_fits_the_smaller_side() {
+        let wide = Geometry::new(800.0, 300.0).radial_radii(DEFAULT_INNER_RADIUS);
+        let tall = Geometry::new(300.0, 800.0).radial_radii(DEFAULT_INNER_RADIUS);
+        assert_eq!(wide, (35.0, 140.0));
+        assert_eq!(wide, tall);
+        assert_eq!(Geometry::new(300.0, 800.0).center(), (150.0, 400.0));
+    }
+
+    #[test]
+    fn a_tiny_area_has_no_circle() {
+        assert_eq!(Geometry::new(15.0, 400.0).radial_radii(0.5), (0.0, 0.0));
+    }
+
+    #[test]
+    fn a_full_mirrored_bar_spans_the_whole_height() {
+        let bars = mirrored_rects(&[u16::MAX], Geometry::new(100.0, 100.0));
+        let bar = bars[0];
+        assert_eq!((bar.upper.y, bar.upper.height), (0.0, 50.0));
+        assert_eq!((bar.lower.y, bar.lower.height), (50.0, 50.0));
+    }
+
+    #[test]
+    fn the_mirrored_halves_meet_on_the_middle_line() {
+        let geometry = Geometry::new(400.0, 100.0);
+        let values = [0, 1000, u16::MAX / 2, u16::MAX];
+        let straight = bar_rects(&values, geometry);
+
+        for (bar, rect) in mirrored_rects(&values, geometry).iter().zip(straight) {
+            assert_eq!(bar.upper.y + bar.upper.height, 50.0);
+            assert_eq!(bar.lower.y, 50.0);
+            assert_eq!(bar.upper.height, bar.lower.height);
+            // Together as tall as the bar would be, at the same place
+            assert_eq!(bar.upper.height * 2.0, rect.height);
+            assert_eq!((bar.upper.x, bar.upper.width), (rect.x, rect.width));
+            assert_eq!((bar.lower.x, bar.lower.width), (rect.x, rect.width));
+        }
+    }
+
+    #[test]
+    fn silence_is_nothing_on_the_middle_line() {
+        let bars = mirrored_rects(&[0; 3], Geometry::new(300.0, 80.0));
+        for bar in bars {
+            assert_eq!((bar.upper.y, bar.upper.height), (40.0, 0.0));
+            assert_eq!((bar.lower.y, bar.lower.height), (40.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn layout_at_a_normal_size() {
+        let rects = layout(800.0, 200.0, 20, BAR_PADDING);
+
+        assert_eq!(rects.len(), 20);
+        for (i, rect) in rects.iter().enumerate() {
+            assert_eq!(rect.x, i as f64 * 40.0 + 5.0);
+            assert_eq!((rect.y, rect.width, rect.height), (0.0, 30.0, 200.0));
+        }
+    }
+
+    #[test]
+    fn layout_at_a_huge_size() {
+        let rects = layout(100_000.0, 50_000.0, 256, BAR_PADDING);
+
+        assert_eq!(rects.len(), 256);
+        let last = rects.last().unwrap();
+        assert!(close(last.x + last.width + BAR_PADDING / 2.0, 100_000.0));
+    }
+
+    #[test]
+    fn layout_at_a_tiny_size_has_fewer_bars() {
+        let rects = layout(10.0, 5.0, 64, BAR_PADDING);
+
+        // 3 slots of 3.33px, the padding takes half of each
+        assert_eq!(rects.len(), 3);
+        for rect in &rects {
+            assert!(close(rect.width, 10.0 / 6.0));
+            assert!(rect.width > 0.0);
+        }
+    }
+
+    #[test]
+    fn layout_of_an_empty_area_has_no_bars() {
+        for (width, height) in [(0.0, 100.0), (2.9, 100.0), (-50.0, -50.0)] {
+            assert!(layout(width, height, 20, BAR_PADDING).is_empty());
+        }
+        assert!(layout(800.0, 200.0, 0, BAR_PADDING).is_empty());
+        // A negative height is an empty bar, not a negative one
+        assert_eq!(layout(800.0, -10.0, 1, BAR_PADDING)[0].height, 0.0);
+    }
+
+    #[test]
+    fn a_negative_padding_is_none() {
+        let rects = layout(100.0, 10.0, 2, -20.0);
+        assert_eq!((rects[0].x, rects[0].width), (0.0, 50.0));
+    }
+
+    #[test]
+    fn the_values_are_averaged_into_fewer_bars() {
+        assert_eq!(average_values(&[10, 20, 30, 40], 2), vec![15, 35]);
+        // Uneven groups, no value left out
+        assert_eq!(average_values(&[10, 20, 30, 40, 50], 2), vec![15, 40]);
+        // Without overflowing
+        assert_eq!(average_values(&[u16::MAX; 6], 1), vec![u16::MAX]);
+        assert_eq!(average_values(&[1, 2, 3], 5), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn a_narrow_area_draws_averaged_bars() {
+        let values = [0, u16::MAX, 0, u16::MAX, 0, u16::MAX];
+        let rects = bar_rects(&values, Geometry::new(9.0, 100.0));
+
+        assert_eq!(rects.len(), 3);
+        assert!(rects.iter().all(|rect| rect.height == 49.0));
+    }
+
+    // One pixel per value, so the heights are whole pixels
+    const TALL: Geometry = Geometry {
+        width: 20.0,
+        height: u16::MAX as f64,
+    };
+
+    #[test]
+    fn a_peak_marker_sits_on_the_peak() {
+        let markers = peak_markers(&[0], &[1000.0], TALL);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].y, TALL.height - 1000.0 - PEAK_MARKER_HEIGHT);
+        assert_eq!(markers[0].height, PEAK_MARKER_HEIGHT);
+    }
+
+    #[test]
+    fn a_bar_at_its_peak_has_no_marker() {
+        let markers = peak_markers(&[1000, 20000], &[1000.0, 20000.4], TALL);
+        assert!(markers.is_empty());
+    }
+
+    #[test]
+    fn a_peak_at_the_top_stays_inside_the_area() {
+        let markers = peak_markers(&[0], &[u16::MAX as f64], TALL);
+        assert_eq!(markers[0].y, 0.0);
+    }
+
+    #[test]
+    fn a_mono_frame_uses_the_whole_area() {
+        let geometry = Geometry::new(400.0, 100.0);
+        for view in [
+            ChannelView::Mono,
+            ChannelView::SplitHorizontal,
+            ChannelView::SplitVertical,
+        ] {
+            let areas = channel_areas(view, false, geometry);
+            assert_eq!(areas.len(), 1);
+            assert_eq!(areas[0].channel, Channel::All);
+            assert_eq!((areas[0].x, areas[0].y), (0.0, 0.0));
+            assert_eq!(areas[0].geometry, geometry);
+        }
+        // And so does a stereo one in the mono view
+        assert_eq!(
+            channel_areas(ChannelView::Mono, true, geometry)[0].channel,
+            Channel::All
+        );
+    }
+
+    #[test]
+    fn the_horizontal_split() {
+        let areas = channel_areas(
+            ChannelView::SplitHorizontal,
+            true,
+            Geometry::new(410.0, 100.0),
+        );
+        let [left, right] = areas[..] else {
+            panic!("two areas, got {:?}", areas);
+        };
+        assert_eq!((left.channel, left.x, left.y), (Channel::Left, 0.0, 0.0));
+        assert_eq!(
+            (right.channel, right.x, right.y),
+            (Channel::Right, 210.0, 0.0)
+        );
+        assert_eq!(left.geometry, Geometry::new(200.0, 100.0));
+        assert_eq!(right.geometry, Geometry::new(200.0, 100.0));
+        // The right one is mirrored, its low frequencies on the outer edge
+        assert!(!left.reversed && right.reversed);
+    }
+
+    #[test]
+    fn the_vertical_split() {
+        let areas = channel_areas(
+            ChannelView::SplitVertical,
+            true,
+            Geometry::new(400.0, 110.0),
+        );
+        let [upper, lower] = areas[..] else {
+            panic!("two areas, got {:?}", areas);
+        };
+        assert_eq!((upper.channel, upper.x, upper.y), (Channel::Left, 0.0, 0.0));
+        assert_eq!(
+            (lower.channel, lower.x, lower.y),
+            (Channel::Right, 0.0, 60.0)
+        );
+        assert_eq!(upper.geometry, Geometry::new(400.0, 50.0));
+        assert_eq!(lower.geometry, Geometry::new(400.0, 50.0));
+        assert!(!upper.reversed && !lower.reversed);
+    }
+
+    #[test]
+    fn a_split_smaller_than_the_gap_is_empty() {
+        for view in [ChannelView::SplitHorizontal, ChannelView::SplitVertical] {
+            for area in channel_areas(view, true, Geometry::new(4.0, 4.0)) {
+                assert!(area.geometry.width >= 0.0 && area.geometry.height >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn each_area_selects_its_channel() {
+        let values = [1, 2, 3, 4, 5, 6];
+        let geometry = Geometry::new(410.0, 100.0);
+        let selected: Vec<Vec<u16>> = channel_areas(ChannelView::SplitHorizontal, true, geometry)
+            .iter()
+            .map(|area| area.select(&values))
+            .collect();
+        assert_eq!(selected, vec![vec![1, 2, 3], vec![6, 5, 4]]);
+
+        let whole = channel_areas(ChannelView::Mono, true, geometry)[0];
+        assert_eq!(whole.select(&values), values);
+    }
+
+    #[test]
+    fn the_views_go_round() {
+        let view = ChannelView::Mono;
+        assert_eq!(view.next(), ChannelView::SplitHorizontal);
+        assert_eq!(view.next().next(), ChannelView::SplitVertical);
+        assert_eq!(view.next().next().next(), view);
+    }
+
+    #[test]
+    fn every_bar_is_labeled_up_to_max_labels() {
+        for bars in [0, 1, 20, MAX_LABELS] {
+            assert_eq!(label_every(bars), 1, "{} bars", bars);
+        }
+    }
+
+    #[test]
+    fn labels_are_thinned_out_past_max_labels() {
+        assert_eq!(label_every(MAX_LABELS + 1), 2);
+        assert_eq!(label_every(2 * MAX_LABELS), 2);
+        assert_eq!(label_every(2 * MAX_LABELS + 1), 3);
+        assert_eq!(label_every(200), 5);
+        assert_eq!(label_every(usize::MAX), usize::MAX.div_ceil(MAX_LABELS));
+    }
+
+    #[test]
+    fn never_more_than_max_labels() {
+        for bars in 1..=2000 {
+            let every = label_every(bars);
+            // The bars drawn with a label, the same way as the draw code picks them
+            let labeled = (0..bars).filter(|i| i % every == 0).count();
+            assert!(
+                labeled <= MAX_LABELS,
+                "{} labels for {} bars",
+                labeled,
+                bars
+            );
+            // And not needlessly few: one bar less apart would be too many
+            if every > 1 {
+                assert!(bars.div_ceil(every - 1) > MAX_LABELS, "{} bars", bars);
+            }
+        }
+    }
+
+    #[test]
+    fn the_corner_radius_is_kept_when_it_fits() {
+        assert_eq!(corner_radius(4.0, 30.0, 100.0), 4.0);
+        assert_eq!(corner_radius(0.0, 30.0, 100.0), 0.0);
+    }
+
+    #[test]
+    fn the_corner_radius_is_at_most_half_the_width() {
+        assert_eq!(corner_radius(10.0, 8.0, 100.0), 4.0);
+    }
+
+    // A short bar gets a half circle on top, an empty one no corners at all
+    #[test]
+    fn the_corner_radius_is_at_most_half_the_height() {
+        assert_eq!(corner_radius(10.0, 30.0, 6.0), 3.0);
+        assert_eq!(corner_radius(10.0, 30.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn the_corner_radius_is_never_negative() {
+        assert_eq!(corner_radius(-5.0, 30.0, 100.0), 0.0);
+        assert_eq!(corner_radius(5.0, -2.0, 100.0), 0.0);
+    }
+
+    // Two corners side by side never overlap, whatever the size
+    #[test]
+    fn the_corners_fit_every_bar() {
+        let geometry = Geometry::new(300.0, 200.0);
+        let values: Vec<u16> = (0..40).map(|i| i * 1600).collect();
+        for rect in bar_rects(&values, geometry) {
+            let radius = corner_radius(6.0, rect.width, rect.height);
+            assert!(2.0 * radius <= rect.width && 2.0 * radius <= rect.height);
+        }
+    }
+
+    fn hover(
+        style: RenderStyle,
+        view: ChannelView,
+        stereo: bool,
+        count: usize,
+        width: f64,
+        at: Point,
+    ) -> Option<HoveredBar> {
+        bar_at(style, view, stereo, count, Geometry::new(width, 100.0), at)
+    }
+
+    fn hover_mono(x: f64, y: f64) -> Option<Range<usize>> {
+        hover(
+            RenderStyle::Bars,
+            ChannelView::Mono,
+            false,
+            4,
+            400.0,
+            (x, y),
+        )
+        .map(|bar| bar.values)
+    }
+
+    #[test]
+    fn the_bar_under_the_pointer() {
+        assert_eq!(
+            hover(
+                RenderStyle::Bars,
+                ChannelView::Mono,
+                false,
+                4,
+                400.0,
+                (150.0, 50.0)
+            ),
+            Some(HoveredBar {
+                channel: Channel::All,
+                bars: 1..2,
+                values: 1..2,
+            })
+        );
+    }
+
+    // The bars are 5..95, 105..195, 205..295 and 305..395
+    #[test]
+    fn the_edges_of_a_bar() {
+        assert_eq!(hover_mono(5.0, 50.0), Some(0..1));
+        assert_eq!(hover_mono(94.9, 50.0), Some(0..1));
+        assert_eq!(hover_mono(95.0, 50.0), None);
+        assert_eq!(hover_mono(105.0, 50.0), Some(1..2));
+    }
+
+    #[test]
+    fn no_bar_in_the_padding() {
+        for x in [0.0, 4.9, 100.0, 199.0, 396.0, 399.9] {
+            assert_eq!(hover_mono(x, 50.0), None, "{}", x);
+        }
+    }
+
+    // Short bars can be hovered over anywhere above them
+    #[test]
+    fn the_bars_are_as_tall_as_the_area() {
+        assert_eq!(hover_mono(50.0, 0.0), Some(0..1));
+        assert_eq!(hover_mono(50.0, 99.9), Some(0..1));
+    }
+
+    #[test]
+    fn no_bar_outside_of_the_area() {
+        for (x, y) in [(400.0, 50.0), (-1.0, 50.0), (50.0, 100.0), (50.0, -1.0)] {
+            assert_eq!(hover_mono(x, y), None, "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn only_the_styles_with_bars_side_by_side() {
+        let at = |style| hover(style, ChannelView::Mono, false, 4, 400.0, (50.0, 50.0));
+        assert!(at(RenderStyle::Bars).is_some());
+        assert!(at(RenderStyle::Mirrored).is_some());
+        assert!(at(RenderStyle::Waveform).is_none());
+        assert!(at(RenderStyle::Radial).is_none());
+    }
+
+    // The left half is 0..200, the right one 210..410, mirrored
+    #[test]
+    fn the_channels_of_a_split_view() {
+        let at = |x| {
+            hover(
+                RenderStyle::Bars,
+                ChannelView::SplitHorizontal,
+                true,
+                8,
+                410.0,
+                (x, 50.0),
+            )
+        };
+        assert_eq!(
+            at(7.0),
+            Some(HoveredBar {
+                channel: Channel::Left,
+                bars: 0..1,
+                values: 0..1,
+            })
+        );
+        // In the gap between the halves
+        assert_eq!(at(205.0), None);
+        // The first bar of the right half is its highest frequency
+        assert_eq!(
+            at(217.0),
+            Some(HoveredBar {
+                channel: Channel::Right,
+                bars: 3..4,
+                values: 7..8,
+            })
+        );
+        assert_eq!(at(403.0).map(|bar| bar.values), Some(4..5));
+    }
+
+    #[test]
+    fn an_averaged_bar_shows_all_its_values() {
+        // 6 values in 3 slots of 3px, the middle bar is 3.75..5.25
+        let bar = hover(
+            RenderStyle::Bars,
+            ChannelView::Mono,
+            false,
+            6,
+            9.0,
+            (4.5, 50.0),
+        );
+        assert_eq!(bar.map(|bar| bar.values), Some(2..4));
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use relm4::gtk::cairo::{self, ImageSurface};
+use relm4::gtk::glib;
+
+// Saving the bars as a PNG (the `s` key)
+//
+// The screenshot is rendered again, into a surface of its own, with the same code as
+// the window (`draw::draw_scene`): it can have another size than the window, and it
+// only has the bars, not the rates or the "paused" on top of them.
+//
+// Nothing here panics, a screenshot that can't be saved is reported and that's it.
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    Render(cairo::Error),
+    // The directory doesn't exist and can't be created
+    Directory(PathBuf, io::Error),
+    Write(PathBuf, cairo::IoError),
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::Render(e) => write!(f, "Failed to render the screenshot: {}", e),
+            ScreenshotError::Directory(path, e) => {
+                write!(f, "Failed to create {}: {}", path.display(), e)
+            }
+            ScreenshotError::Write(path, e) => {
+                write!(f, "Failed to write {}: {}", path.display(), e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+// ~/Pictures, or wherever the user's XDG settings put the pictures
+pub fn directory() -> PathBuf {
+    glib::user_special_dir(glib::UserDirectory::Pictures)
+        .unwrap_or_else(|| glib::home_dir().join("Pictures"))
+}
+
+// visualizer-2024-05-17_21-04-33.png, in local time
+// If the clock can't be read in local time (no timezone data), the seconds since 1970
+fn file_stem() -> String {
+    let timestamp = glib::DateTime::now_local()
+        .and_then(|now| now.format("%Y-%m-%d_%H-%M-%S"))
+        .map(|timestamp| timestamp.to_string())
+        .unwrap_or_else(|_| {
+            let since_epoch = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            since_epoch.as_secs().to_string()
+        });
+    format!("visualizer-{}", timestamp)
+}
+
+// Create a new file in `dir`, `stem.png`, or `stem-2.png`, `stem-3.png`... when two
+// screenshots are taken within the same second
+// `create_new` fails if the file exists, so we never overwrite one, even if it shows
+// up between looking and creating
+fn create_file(dir: &Path, stem: &str) -> io::Result<(PathBuf, File)> {
+    for n in 1.. {
+        let name = match n {
+            1 => format!("{}.png", stem),
+            n => format!("{}-{}.png", stem, n),
+        };
+        let path = dir.join(name);
+        match File::create_new(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("There is always a free name")
+}
+
+// Write the rendered bars to a new PNG in the pictures directory
+// Returns where it went
+pub fn save(surface: &ImageSurface) -> Result<PathBuf, ScreenshotError> {
+    let dir = directory();
+    fs::create_dir_all(&dir).map_err(|e| ScreenshotError::Directory(dir.clone(), e))?;
+    let (path, mut file) = create_file(&dir, &file_stem())
+        .map_err(|e| ScreenshotError::Write(dir.clone(), cairo::IoError::Io(e)))?;
+    match surface.write_to_png(&mut file) {
+        Ok(()) => Ok(path),
+        Err(e) => {
+            // Don't leave half a PNG behind
+            let _ = fs::remove_file(&path);
+            Err(ScreenshotError::Write(path, e))
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::visualizer::VisualizerConfig;
+
+// The limits of the preferences dialog
+pub const MIN_BARS: usize = 1;
+pub const MAX_BARS: usize = 256;
+pub const MIN_FRAMERATE: u32 = 1;
+pub const MAX_FRAMERATE: u32 = 240;
+
+// How much the bars are smoothed over time (see TemporalSmoother)
+// Saved by its id, see persist.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Smoothing {
+    Off,
+    Light,
+    #[default]
+    Medium,
+    Strong,
+}
+
+impl Smoothing {
+    pub const ALL: [Smoothing; 4] = [
+        Smoothing::Off,
+        Smoothing::Light,
+        Smoothing::Medium,
+        Smoothing::Strong,
+    ];
+
+    // The visualizer doesn't smooth over time unless told to, the app does (Medium)
+    pub fn time_constant(self) -> Duration {
+        match self {
+            Smoothing::Off => Duration::ZERO,
+            Smoothing::Light => Duration::from_millis(25),
+            Smoothing::Medium => Duration::from_millis(50),
+            Smoothing::Strong => Duration::from_millis(100),
+        }
+    }
+
+    // A stable name, used as the id of the combo box entries
+    pub fn id(self) -> &'static str {
+        match self {
+            Smoothing::Off => "off",
+            Smoothing::Light => "light",
+            Smoothing::Medium => "medium",
+            Smoothing::Strong => "strong",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Smoothing> {
+        Smoothing::ALL
+            .into_iter()
+            .find(|smoothing| smoothing.id() == id)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Smoothing::Off => "Off",
+            Smoothing::Light => "Light",
+            Smoothing::Medium => "Medium",
+            Smoothing::Strong => "Strong",
+        }
+    }
+}
+
+// What can be changed from the preferences dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub bars: usize,
+    pub framerate: u32,
+    pub smoothing: Smoothing,
+}
+
+impl Settings {
+    // The visualizer config these settings describe
+    pub fn config(&self) -> VisualizerConfig {
+        VisualizerConfig::new(self.bars)
+            .framerate(self.framerate)
+            .smoothing_time_constant(self.smoothing.time_constant())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            bars: 20,
+            framerate: 60,
+            smoothing: Smoothing::default(),
+        }
+    }
+}
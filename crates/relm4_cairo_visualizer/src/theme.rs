@@ -0,0 +1,947 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::persist;
+
+// A color as cairo wants it, every channel between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Color {
+    pub const fn rgb(r: f64, g: f64, b: f64) -> Color {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color { r, g, b, a }
+    }
+
+    // "#8a2be2", "#8a2be280" or "rgba(138, 43, 226, 0.5)", the way CSS writes them
+    pub fn parse(text: &str) -> Result<Color, ColorError> {
+        let text = text.trim();
+        if text.starts_with('#') {
+            parse_hex(text)
+        } else if text.starts_with("rgb") {
+            parse_rgba(text)
+        } else {
+            Err(ColorError::UnknownFormat(text.to_string()))
+        }
+    }
+}
+
+// Lets serde read colors from strings, the error message ends up in the TOML error
+impl TryFrom<String> for Color {
+    type Error = ColorError;
+
+    fn try_from(text: String) -> Result<Color, ColorError> {
+        Color::parse(&text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorError {
+    // Neither "#..." nor "rgb(...)"/"rgba(...)"
+    UnknownFormat(String),
+    InvalidHex(String),
+    InvalidRgba(String),
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorError::UnknownFormat(text) => write!(
+                f,
+                "`{}` is not a color, expected \"#rrggbb\", \"#rrggbbaa\" or \"rgba(r, g, b, a)\"",
+                text
+            ),
+            ColorError::InvalidHex(text) => write!(
+                f,
+                "`{}` is not a hex color, expected 6 or 8 hex digits after the #",
+                text
+            ),
+            ColorError::InvalidRgba(text) => write!(
+                f,
+                "`{}` is not a valid rgba color, r, g and b go from 0 to 255 and a from 0 to 1",
+                text
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
+// "#rrggbb" or "#rrggbbaa"
+pub fn parse_hex(text: &str) -> Result<Color, ColorError> {
+    let error = || ColorError::InvalidHex(text.to_string());
+
+    let digits = text.strip_prefix('#').ok_or_else(error)?;
+    // Only hex digits, which also means slicing two bytes at a time can't split a char
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error());
+    }
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&digits[i..i + 2], 16)
+            .map(|value| value as f64 / 255.0)
+            .map_err(|_| error())
+    };
+    let alpha = if digits.len() == 8 { channel(6)? } else { 1.0 };
+    Ok(Color::rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+// "rgba(138, 43, 226, 0.5)" or "rgb(138, 43, 226)"
+pub fn parse_rgba(text: &str) -> Result<Color, ColorError> {
+    let error = || ColorError::InvalidRgba(text.to_string());
+
+    let (with_alpha, arguments) = if let Some(rest) = text.strip_prefix("rgba(") {
+        (true, rest)
+    } else if let Some(rest) = text.strip_prefix("rgb(") {
+        (false, rest)
+    } else {
+        return Err(error());
+    };
+    let arguments = arguments.strip_suffix(')').ok_or_else(error)?;
+    let values: Vec<&str> = arguments.split(',').map(str::trim).collect();
+    if values.len() != if with_alpha { 4 } else { 3 } {
+        return Err(error());
+    }
+
+    let channel = |value: &str| {
+        value
+            .parse::<u8>()
+            .map(|value| value as f64 / 255.0)
+            .map_err(|_| error())
+    };
+    let alpha = match values.get(3) {
+        Some(value) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|alpha| (0.0..=1.0).contains(alpha))
+            .ok_or_else(error)?,
+        None => 1.0,
+    };
+    Ok(Color::rgba(
+        channel(values[0])?,
+        channel(values[1])?,
+        channel(values[2])?,
+        alpha,
+    ))
+}
+
+// One color of a gradient, and where it goes: 0 is the start of the gradient (the top
+// of a bar), 1 its end
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GradientStop {
+    pub offset: f64,
+    pub color: Color,
+}
+
+impl GradientStop {
+    pub const fn new(offset: f64, color: Color) -> GradientStop {
+        GradientStop { offset, color }
+    }
+}
+
+// The colors of the bars, any number of stops, checked when the theme is read:
+//
+//   bar_gradient = [
+//     { offset = 0.0, color = "#1a99cc" },
+//     { offset = 0.6, color = "#8a2be2" },
+//     { offset = 1.0, color = "#004d80" },
+//   ]
+//
+// cairo itself takes any offsets, it clamps them to 0..=1 and sorts the stops, so a
+// typo would silently give other colors than the ones written. We'd rather say so.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<GradientStop>")]
+pub struct Gradient {
+    // At least one, sorted by offset, every offset within 0..=1
+    stops: Vec<GradientStop>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GradientError {
+    NoStops,
+    // The offset of the stop at that index isn't within 0..=1 (or is NaN)
+    OffsetOutOfRange(usize, f64),
+    // The stop at that index comes before the previous one
+    NotSorted(usize, f64),
+}
+
+impl fmt::Display for GradientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GradientError::NoStops => write!(f, "a gradient needs at least one stop"),
+            GradientError::OffsetOutOfRange(i, offset) => write!(
+                f,
+                "the offset of stop {} is {}, offsets go from 0 to 1",
+                i + 1,
+                offset
+            ),
+            GradientError::NotSorted(i, offset) => write!(
+                f,
+                "the offset of stop {} is {}, less than the one before it, the stops go in order",
+                i + 1,
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GradientError {}
+
+impl Gradient {
+    // Two stops with the same offset are fine, the color changes at once there
+    pub fn new(stops: Vec<GradientStop>) -> Result<Gradient, GradientError> {
+        if stops.is_empty() {
+            return Err(GradientError::NoStops);
+        }
+        for (i, stop) in stops.iter().enumerate() {
+            // `contains` is false for NaN too
+            if !(0.0..=1.0).contains(&stop.offset) {
+                return Err(GradientError::OffsetOutOfRange(i, stop.offset));
+            }
+            if i > 0 && stop.offset < stops[i - 1].offset {
+                return Err(GradientError::NotSorted(i, stop.offset));
+            }
+        }
+        Ok(Gradient { stops })
+    }
+
+    // From `top` at 0 to `bottom` at 1
+    pub fn two_colors(top: Color, bottom: Color) -> Gradient {
+        Gradient {
+            stops: vec![GradientStop::new(0.0, top), GradientStop::new(1.0, bottom)],
+        }
+    }
+
+    // What to hand to cairo's `add_color_stop_rgba`, one call per stop
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    // The same colors the other way round, 0 is where 1 was
+    // The radial bars start at the inner circle, the bottom of the bar
+    pub fn reversed(&self) -> Gradient {
+        let stops = self.stops.iter().rev();
+        Gradient {
+            stops: stops
+                .map(|stop| GradientStop::new(1.0 - stop.offset, stop.color))
+                .collect(),
+        }
+    }
+
+    // Squeezed into the first half and mirrored into the second one: the start of
+    // the gradient (0) is in the middle, its end (1) at both ends. That's the mirrored
+    // bars, which grow from the middle line.
+    pub fn mirrored(&self) -> Gradient {
+        let upper = self
+            .stops
+            .iter()
+            .rev()
+            .map(|stop| GradientStop::new(0.5 - stop.offset / 2.0, stop.color));
+        let lower = self
+            .stops
+            .iter()
+            .map(|stop| GradientStop::new(0.5 + stop.offset / 2.0, stop.color));
+        Gradient {
+            stops: upper.chain(lower).collect(),
+        }
+    }
+
+    // The color at `offset`, the way cairo would draw it: between two stops the
+    // channels go linearly from one color to the other, before the first stop it's the
+    // first color and after the last one the last color
+    pub fn color_at(&self, offset: f64) -> Color {
+        let first = self.stops[0];
+        if offset <= first.offset {
+            return first.color;
+        }
+        for pair in self.stops.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if offset < to.offset {
+                // `offset` is past `from`, so the stops can't have the same offset here
+                let t = (offset - from.offset) / (to.offset - from.offset);
+                let mix = |a: f64, b: f64| a + (b - a) * t;
+                return Color::rgba(
+                    mix(from.color.r, to.color.r),
+                    mix(from.color.g, to.color.g),
+                    mix(from.color.b, to.color.b),
+                    mix(from.color.a, to.color.a),
+                );
+            }
+        }
+        self.stops[self.stops.len() - 1].color
+    }
+}
+
+// Lets serde read a gradient from the list of stops, the error ends up in the TOML error
+impl TryFrom<Vec<GradientStop>> for Gradient {
+    type Error = GradientError;
+
+    fn try_from(stops: Vec<GradientStop>) -> Result<Gradient, GradientError> {
+        Gradient::new(stops)
+    }
+}
+
+// Which way the bar gradient goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientDirection {
+    // From the top of every bar (0) to its bottom (1), what the visualizer always did
+    #[default]
+    Vertical,
+    // From the left of the area to its right, every bar gets the colors at its place:
+    // the low frequencies get the start of the gradient
+    Horizontal,
+    // Every bar is one color, picked by how high it is: a full bar gets the start
+    // of the gradient, an empty one its end
+    PerBarValue,
+}
+
+// What is painted behind the bars, in the TOML one of:
+//
+//   background = "#000000"
+//   background = { top = "#1a1a2e", bottom = "#000000" }
+//   background = { image = "wallpaper.jpg", dim = 0.6 }
+//
+// A relative image path is relative to the theme file. The image covers the whole
+// area, cut off on the sides that don't fit, and `dim` darkens it (0 leaves it as it
+// is, 1 makes it black, 0.5 by default) so the bars can still be seen on top of it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    Solid(Color),
+    // From `top` at the top of the window to `bottom` at its bottom
+    Gradient { top: Color, bottom: Color },
+    // Loaded when the window opens, see background.rs
+    Image { path: PathBuf, dim: f32 },
+}
+
+// How dark an image is made when the theme doesn't say
+pub const DEFAULT_DIM: f32 = 0.5;
+
+impl Background {
+    // The one color that stands for the background: the panels behind the rates and
+    // the titles are drawn in it (with some transparency), and it's what an image
+    // that can't be loaded is replaced with
+    pub fn color(&self) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            // The panels are at the top
+            Background::Gradient { top, .. } => *top,
+            Background::Image { .. } => Color::rgb(0.0, 0.0, 0.0),
+        }
+    }
+
+    // With a relative image path made relative to `dir`
+    fn relative_to(self, dir: &Path) -> Background {
+        match self {
+            Background::Image { path, dim } if path.is_relative() => Background::Image {
+                path: dir.join(path),
+                dim,
+            },
+            background => background,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackgroundError {
+    // A table with neither `top` and `bottom` nor `image`
+    Empty,
+    // `top` without `bottom`, or the other way round
+    MissingColor(&'static str),
+    // Both `top`/`bottom` and `image`, or `dim` without an image
+    Mixed,
+    DimOutOfRange(f32),
+}
+
+impl fmt::Display for BackgroundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackgroundError::Empty => write!(
+                f,
+                "a background is a color, {{ top, bottom }} for a gradient or {{ image, dim }}"
+            ),
+            BackgroundError::MissingColor(missing) => {
+                write!(f, "the background gradient has no `{}` color", missing)
+            }
+            BackgroundError::Mixed => write!(
+                f,
+                "a background is either a gradient ({{ top, bottom }}) or an image ({{ image, dim }})"
+            ),
+            BackgroundError::DimOutOfRange(dim) => {
+                write!(f, "the background dim is {}, it goes from 0 to 1", dim)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackgroundError {}
+
+// The table form of the background, as it's written
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackgroundTable {
+    top: Option<Color>,
+    bottom: Option<Color>,
+    image: Option<PathBuf>,
+    dim: Option<f32>,
+}
+
+impl TryFrom<BackgroundTable> for Background {
+    type Error = BackgroundError;
+
+    fn try_from(table: BackgroundTable) -> Result<Background, BackgroundError> {
+        match table {
+            BackgroundTable {
+                top: None,
+                bottom: None,
+                image: Some(path),
+                dim,
+            } => {
+                let dim = dim.unwrap_or(DEFAULT_DIM);
+                // `contains` is false for NaN too
+                if !(0.0..=1.0).contains(&dim) {
+                    return Err(BackgroundError::DimOutOfRange(dim));
+                }
+                Ok(Background::Image { path, dim })
+            }
+            BackgroundTable {
+                image: None,
+                dim: None,
+                top,
+                bottom,
+            } => match (top, bottom) {
+                (Some(top), Some(bottom)) => Ok(Background::Gradient { top, bottom }),
+                (Some(_), None) => Err(BackgroundError::MissingColor("bottom")),
+                (None, Some(_)) => Err(BackgroundError::MissingColor("top")),
+                (None, None) => Err(BackgroundError::Empty),
+            },
+            _ => Err(BackgroundError::Mixed),
+        }
+    }
+}
+
+// A string is a color, a table a gradient or an image: serde can't tell them apart
+// with a derive (an untagged enum would lose the color's error message), so we look
+// at what the TOML has ourselves
+impl<'de> Deserialize<'de> for Background {
+    fn deserialize<D>(deserializer: D) -> Result<Background, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct BackgroundVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BackgroundVisitor {
+            type Value = Background;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a color, {{ top, bottom }} or {{ image, dim }}")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<Background, E> {
+                Color::parse(text).map(Background::Solid).map_err(E::custom)
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Background, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let table = BackgroundTable::deserialize(
+                    serde::de::value::MapAccessDeserializer::new(map),
+                )?;
+                Background::try_from(table).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(BackgroundVisitor)
+    }
+}
+
+// Every color the visualizer draws with
+//
+// Read from ~/.config/playground-visualizer/theme.toml, every key is optional and
+// the ones that are missing keep their default:
+//
+//   background = "#000000"  # or a gradient or an image, see Background
+//   bar_gradient = [{ offset = 0.0, color = "#1a99cc" }, { offset = 1.0, color = "#004d80" }]
+//   bar_gradient_direction = "vertical"  # or "horizontal", "per_bar_value"
+//   shine = true
+//   shine_opacity = 0.3
+//   corner_radius = 4.0
+//   stroke = "rgba(204, 51, 255, 0.8)"
+//   peak = "#ffffff"
+//   text = "#ffffff"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub background: Background,
+    pub bar_gradient: Gradient,
+    pub bar_gradient_direction: GradientDirection,
+    // The white highlight at the top of every bar (the bars style only)
+    pub shine: bool,
+    // How white the highlight starts, it fades out from there
+    #[serde(deserialize_with = "opacity")]
+    pub shine_opacity: f64,
+    // How round the corners of the bars are, in pixels, 0 for square ones (the bars
+    // style only). Small bars get rounder corners, see `render::corner_radius`
+    #[serde(deserialize_with = "non_negative")]
+    pub corner_radius: f64,
+    pub stroke: Color,
+    pub peak: Color,
+    pub text: Color,
+}
+
+// The colors the visualizer always had
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            background: Background::Solid(Color::rgb(0.0, 0.0, 0.0)),
+            bar_gradient: Gradient::two_colors(
+                Color::rgb(0.1, 0.6, 0.8),
+                Color::rgb(0.0, 0.3, 0.5),
+            ),
+            bar_gradient_direction: GradientDirection::Vertical,
+            shine: true,
+            shine_opacity: 0.3,
+            corner_radius: 0.0,
+            stroke: Color::rgba(0.8, 0.2, 1.0, 0.8),
+            peak: Color::rgb(1.0, 1.0, 1.0),
+            text: Color::rgb(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// A number from 0 to 1, anything else is an error at its line of the TOML
+fn opacity<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "the opacity is {}, it goes from 0 to 1",
+            value
+        )))
+    }
+}
+
+// A size in pixels, 0 or more
+fn non_negative<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if value >= 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "{} is not a size, it's 0 or more",
+            value
+        )))
+    }
+}
+
+// Whether the window shows the theme's background behind the bars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    // The theme's background
+    Opaque,
+    // Nothing, the desktop shows through the window
+    Transparent,
+}
+
+// `--transparent` asks for a see-through window, but it takes a compositor (a window
+// manager that blends the windows together) to see what's behind it. Without one the
+// transparent parts show up black, or as garbage, so we keep the opaque background.
+pub fn choose_transparency(transparent: bool, compositor_alpha: bool) -> Transparency {
+    if transparent && compositor_alpha {
+        Transparency::Transparent
+    } else {
+        Transparency::Opaque
+    }
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(PathBuf, io::Error),
+    // Bad TOML, an unknown key, or a color or gradient that doesn't check out
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Io(path, e) => write!(f, "Failed to read {}: {}", path.display(), e),
+            // The toml error shows the faulty line, so it goes on lines of its own
+            ThemeError::Parse(path, e) => write!(
+                f,
+                "Invalid theme {}:\n{}",
+                path.display(),
+                e.to_string().trim_end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl Theme {
+    // ~/.config/playground-visualizer/theme.toml, or the same under $XDG_CONFIG_HOME
+    // None if we can't even tell where the home directory is
+    pub fn path() -> Option<PathBuf> {
+        Some(persist::config_dir()?.join("theme.toml"))
+    }
+
+    // The user's theme, None if they don't have a theme file
+    // It's one of the themes `t` goes through, see themes.rs
+    pub fn load() -> Result<Option<Theme>, ThemeError> {
+        match Theme::path() {
+            Some(path) => Theme::load_from(&path),
+            None => Ok(None),
+        }
+    }
+
+    // A missing file is not an error, it just means no theme of the user's own
+    pub fn load_from(path: &Path) -> Result<Option<Theme>, ThemeError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ThemeError::Io(path.to_path_buf(), e)),
+        };
+        let theme: Theme =
+            toml::from_str(&text).map_err(|e| ThemeError::Parse(path.to_path_buf(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Ok(Some(Theme {
+            background: theme.background.relative_to(dir),
+            ..theme
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Color, b: Color) -> bool {
+        [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)]
+            .iter()
+            .all(|(x, y)| (x - y).abs() < 1e-9)
+    }
+
+    #[test]
+    fn six_hex_digits_are_opaque() {
+        let color = parse_hex("#8a2be2").unwrap();
+        assert!(close(
+            color,
+            Color::rgb(138.0 / 255.0, 43.0 / 255.0, 226.0 / 255.0)
+        ));
+    }
+
+    #[test]
+    fn eight_hex_digits_have_an_alpha() {
+        assert_eq!(parse_hex("#ff000080").unwrap().a, 128.0 / 255.0);
+        assert_eq!(parse_hex("#FFFFFFFF").unwrap(), Color::rgb(1.0, 1.0, 1.0));
+        assert_eq!(
+            parse_hex("#00000000").unwrap(),
+            Color::rgba(0.0, 0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn bad_hex_colors_are_errors() {
+        for text in [
+            "#8a2be",
+            "#8a2be2f",
+            "#8a2be2ff0",
+            "#8a2bg2",
+            "8a2be2",
+            "#",
+            "#é12345",
+        ] {
+            assert_eq!(
+                parse_hex(text),
+                Err(ColorError::InvalidHex(text.to_string())),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn rgba_and_rgb() {
+        let color = parse_rgba("rgba(138, 43, 226, 0.5)").unwrap();
+        assert!(close(
+            color,
+            Color::rgba(138.0 / 255.0, 43.0 / 255.0, 226.0 / 255.0, 0.5)
+        ));
+        assert_eq!(
+            parse_rgba("rgb(255,0,255)").unwrap(),
+            Color::rgb(1.0, 0.0, 1.0)
+        );
+        assert_eq!(parse_rgba("rgba(0, 0, 0, 1)").unwrap().a, 1.0);
+    }
+
+    #[test]
+    fn bad_rgba_colors_are_errors() {
+        for text in [
+            "rgba(1, 2, 3)",
+            "rgb(1, 2, 3, 0.5)",
+            "rgba(256, 0, 0, 1)",
+            "rgba(-1, 0, 0, 1)",
+            "rgba(0, 0, 0, 1.5)",
+            "rgba(0, 0, 0, NaN)",
+            "rgba(0, 0, 0, 1",
+            "rgb(1.5, 0, 0)",
+            "rgb()",
+        ] {
+            assert_eq!(
+                parse_rgba(text),
+                Err(ColorError::InvalidRgba(text.to_string())),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn parse_picks_the_format() {
+        assert_eq!(Color::parse("  #ffffff "), Ok(Color::rgb(1.0, 1.0, 1.0)));
+        assert_eq!(Color::parse("rgb(0, 0, 0)"), Ok(Color::rgb(0.0, 0.0, 0.0)));
+        assert_eq!(
+            Color::parse("blueviolet"),
+            Err(ColorError::UnknownFormat("blueviolet".to_string()))
+        );
+    }
+
+    #[test]
+    fn a_bad_color_in_the_toml_is_an_error_not_a_panic() {
+        let error = toml::from_str::<Theme>("peak = \"#8a2be\"").unwrap_err();
+        let message = error.to_string();
+        assert!(
+            message.contains("`#8a2be` is not a hex color"),
+            "{}",
+            message
+        );
+        // The line is shown
+        assert!(message.contains("peak = \"#8a2be\""), "{}", message);
+    }
+
+    #[test]
+    fn missing_keys_keep_their_default() {
+        let theme: Theme = toml::from_str("text = \"#000000\"").unwrap();
+        assert_eq!(
+            theme,
+            Theme {
+                text: Color::rgb(0.0, 0.0, 0.0),
+                ..Theme::default()
+            }
+        );
+        assert!(toml::from_str::<Theme>("colour = \"#000000\"").is_err());
+    }
+
+    #[test]
+    fn a_missing_file_is_no_theme() {
+        let path = std::env::temp_dir().join("playground-visualizer-no-such-theme.toml");
+        assert!(Theme::load_from(&path).unwrap().is_none());
+    }
+
+    fn stops(offsets: &[f64]) -> Vec<GradientStop> {
+        let white = Color::rgb(1.0, 1.0, 1.0);
+        offsets
+            .iter()
+            .map(|&offset| GradientStop::new(offset, white))
+            .collect()
+    }
+
+    // The error of a theme with these stops, as the user sees it
+    fn stop_list_error(toml: &str) -> String {
+        let toml = format!("bar_gradient = [{}]", toml);
+        toml::from_str::<Theme>(&toml).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn a_gradient_needs_a_stop() {
+        assert_eq!(Gradient::new(vec![]), Err(GradientError::NoStops));
+        let message = stop_list_error("");
+        assert!(message.contains("at least one stop"), "{}", message);
+    }
+
+    #[test]
+    fn offsets_go_from_0_to_1() {
+        assert_eq!(
+            Gradient::new(stops(&[1.5])),
+            Err(GradientError::OffsetOutOfRange(0, 1.5))
+        );
+        assert_eq!(
+            Gradient::new(stops(&[0.0, -0.1])),
+            Err(GradientError::OffsetOutOfRange(1, -0.1))
+        );
+        assert!(matches!(
+            Gradient::new(stops(&[0.0, f64::NAN, 1.0])),
+            Err(GradientError::OffsetOutOfRange(1, offset)) if offset.is_nan()
+        ));
+        // The stops are counted from 1 in the message
+        let message = stop_list_error(
+            "{ offset = 0.0, color = \"#ffffff\" }, { offset = 2.0, color = \"#000000\" }",
+        );
+        assert!(
+            message.contains("the offset of stop 2 is 2, offsets go from 0 to 1"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn stops_go_in_order() {
+        assert_eq!(
+            Gradient::new(stops(&[0.5, 0.2])),
+            Err(GradientError::NotSorted(1, 0.2))
+        );
+        assert_eq!(
+            Gradient::new(stops(&[0.0, 0.6, 0.8, 0.7, 1.0])),
+            Err(GradientError::NotSorted(3, 0.7))
+        );
+        let message = stop_list_error(
+            "{ offset = 0.8, color = \"#ffffff\" }, { offset = 0.2, color = \"#000000\" }",
+        );
+        assert!(
+            message.contains("less than the one before it"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn good_stops_are_kept_as_written() {
+        // One stop, the ends, and two stops at the same offset are all fine
+        for offsets in [&[0.3][..], &[0.0, 1.0], &[0.0, 0.5, 0.5, 1.0]] {
+            let gradient = Gradient::new(stops(offsets)).unwrap();
+            assert_eq!(gradient.stops(), stops(offsets));
+        }
+        let theme: Theme = toml::from_str(
+            "bar_gradient = [
+                { offset = 0.0, color = \"#ff0000\" },
+                { offset = 0.6, color = \"rgba(0, 255, 0, 0.5)\" },
+                { offset = 1.0, color = \"#0000ff\" },
+            ]",
+        )
+        .unwrap();
+        assert_eq!(
+            theme.bar_gradient.stops(),
+            [
+                GradientStop::new(0.0, Color::rgb(1.0, 0.0, 0.0)),
+                GradientStop::new(0.6, Color::rgba(0.0, 1.0, 0.0, 0.5)),
+                GradientStop::new(1.0, Color::rgb(0.0, 0.0, 1.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_stop_needs_an_offset_and_a_color() {
+        for bad in [
+            "{ color = \"#ffffff\" }",
+            "{ offset = 0.5 }",
+            "{ offset = 0.5, color = \"#ffffff\", width = 2 }",
+        ] {
+            assert!(toml::from_str::<Theme>(&format!("bar_gradient = [{}]", bad)).is_err());
+        }
+    }
+
+    #[test]
+    fn color_at_blends_between_the_stops() {
+        let black = Color::rgb(0.0, 0.0, 0.0);
+        let white = Color::rgb(1.0, 1.0, 1.0);
+        let gradient = Gradient::new(vec![
+            GradientStop::new(0.2, black),
+            GradientStop::new(0.6, white),
+        ])
+        .unwrap();
+        assert_eq!(gradient.color_at(0.0), black);
+        assert_eq!(gradient.color_at(1.0), white);
+        assert!(close(gradient.color_at(0.4), Color::rgb(0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn reversed_and_mirrored_gradients() {
+        let black = Color::rgb(0.0, 0.0, 0.0);
+        let white = Color::rgb(1.0, 1.0, 1.0);
+        let gradient = Gradient::two_colors(black, white);
+
+        let reversed = gradient.reversed();
+        assert_eq!(
+            reversed.stops(),
+            [GradientStop::new(0.0, white), GradientStop::new(1.0, black)]
+        );
+
+        let mirrored = gradient.mirrored();
+        let offsets: Vec<_> = mirrored.stops().iter().map(|stop| stop.offset).collect();
+        assert_eq!(offsets, vec![0.0, 0.5, 0.5, 1.0]);
+        assert_eq!(mirrored.color_at(0.0), white);
+        assert_eq!(mirrored.color_at(0.5), black);
+        assert_eq!(mirrored.color_at(1.0), white);
+    }
+
+    #[test]
+    fn the_background_forms() {
+        let read = |toml: &str| toml::from_str::<Theme>(toml).map(|theme| theme.background);
+
+        assert_eq!(
+            read("background = \"#ffffff\"").unwrap(),
+            Background::Solid(Color::rgb(1.0, 1.0, 1.0))
+        );
+        assert_eq!(
+            read("background = { top = \"#ffffff\", bottom = \"#000000\" }").unwrap(),
+            Background::Gradient {
+                top: Color::rgb(1.0, 1.0, 1.0),
+                bottom: Color::rgb(0.0, 0.0, 0.0)
+            }
+        );
+        assert_eq!(
+            read("background = { image = \"a.png\" }").unwrap(),
+            Background::Image {
+                path: PathBuf::from("a.png"),
+                dim: DEFAULT_DIM
+            }
+        );
+        for bad in [
+            "background = { top = \"#ffffff\" }",
+            "background = { image = \"a.png\", dim = 2.0 }",
+            "background = { image = \"a.png\", top = \"#ffffff\" }",
+            "background = {}",
+        ] {
+            assert!(read(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn transparent_with_a_compositor() {
+        assert_eq!(choose_transparency(true, true), Transparency::Transparent);
+    }
+
+    #[test]
+    fn opaque_without_a_compositor() {
+        // The fallback: the theme's background, as if --transparent wasn't given
+        assert_eq!(choose_transparency(true, false), Transparency::Opaque);
+    }
+
+    #[test]
+    fn opaque_unless_asked() {
+        assert_eq!(choose_transparency(false, true), Transparency::Opaque);
+        assert_eq!(choose_transparency(false, false), Transparency::Opaque);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::theme::{Background, Color, Gradient, GradientStop, Theme};
+
+// The themes that come with the visualizer, and going through them with `t`
+//
+// The user's own theme (theme.toml) is one of them, first, called "custom". The name
+// of the one picked is saved with the settings, so the next run starts with it.
+
+// What the user's theme is called in the cycle, it has no name of its own
+pub const CUSTOM: &str = "custom";
+
+// A theme's name and what makes it (a Theme can't be a constant, its gradient is a Vec)
+pub type BuiltIn = (&'static str, fn() -> Theme);
+
+// In the order `t` goes through them, after the user's theme
+pub const BUILT_IN: [BuiltIn; 5] = [
+    ("default", Theme::default),
+    ("ocean", ocean),
+    ("sunset", sunset),
+    ("mono", mono),
+    ("neon", neon),
+];
+
+// The colors below are written like in a theme file, and they are all valid
+fn hex(text: &str) -> Color {
+    Color::parse(text).expect("Invalid built-in color")
+}
+
+// Deep blue water, the bars go from turquoise to blue
+pub fn ocean() -> Theme {
+    Theme {
+        background: Background::Gradient {
+            top: hex("#0b2239"),
+            bottom: hex("#02070d"),
+        },
+        bar_gradient: Gradient::two_colors(hex("#4fd1c5"), hex("#1a5f9e")),
+        stroke: hex("#4fd1c5cc"),
+        peak: hex("#e0f7fa"),
+        text: hex("#e0f7fa"),
+        ..Theme::default()
+    }
+}
+
+// Yellow to orange to red, over a dark purple
+pub fn sunset() -> Theme {
+    let stops = vec![
+        GradientStop::new(0.0, hex("#ffd166")),
+        GradientStop::new(0.5, hex("#f77f00")),
+        GradientStop::new(1.0, hex("#d62828")),
+    ];
+    Theme {
+        background: Background::Gradient {
+            top: hex("#2b1331"),
+            bottom: hex("#0d0509"),
+        },
+        bar_gradient: Gradient::new(stops).expect("Invalid built-in gradient"),
+        stroke: hex("#f77f00cc"),
+        peak: hex("#fff3d6"),
+        text: hex("#ffe8c2"),
+        ..Theme::default()
+    }
+}
+
+// Shades of grey, no shine
+pub fn mono() -> Theme {
+    Theme {
+        background: Background::Solid(hex("#111111")),
+        bar_gradient: Gradient::two_colors(hex("#f0f0f0"), hex("#707070")),
+        shine: false,
+        stroke: hex("#ffffffb3"),
+        peak: hex("#ffffff"),
+        text: hex("#ffffff"),
+        ..Theme::default()
+    }
+}
+
+// Bright green to pink on black, with round corners
+pub fn neon() -> Theme {
+    Theme {
+        background: Background::Solid(hex("#05000a")),
+        bar_gradient: Gradient::two_colors(hex("#39ff14"), hex("#ff00ff")),
+        corner_radius: 3.0,
+        stroke: hex("#00ffffe6"),
+        peak: hex("#00ffff"),
+        text: hex("#39ff14"),
+        ..Theme::default()
+    }
+}
+
+// The themes `t` goes through, and the one in use
+pub struct ThemeCycle {
+    themes: Vec<(&'static str, Theme)>,
+    current: usize,
+}
+
+impl ThemeCycle {
+    // The built-in themes, after the user's if they have one
+    // It starts with the first one: the user's theme, or the default one
+    pub fn new(custom: Option<Theme>) -> ThemeCycle {
+        let custom = custom.map(|theme| (CUSTOM, theme));
+        let built_in = BUILT_IN.iter().map(|&(name, theme)| (name, theme()));
+        ThemeCycle {
+            themes: custom.into_iter().chain(built_in).collect(),
+            current: 0,
+        }
+    }
+
+    // Go to the theme called `name`, the one saved by the last run
+    // false if there is none by that name (a custom theme whose file is gone, or a
+    // theme of another version), the theme stays the same then
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.themes.iter().position(|&(listed, _)| listed == name) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // On to the next theme, back to the first one after the last
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % self.themes.len();
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.themes[self.current].0
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.themes[self.current].1
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.themes.iter().map(|&(name, _)| name).collect()
+    }
+}
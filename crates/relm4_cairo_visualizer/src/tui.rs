@@ -0,0 +1,300 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, style, terminal};
+
+use crate::cli::{Cli, SourceKind};
+use crate::render;
+use crate::visualizer::{Frame, Visualizer, VisualizerError, Waveform};
+
+// The bars in a terminal (`--tui`), for machines without a display
+//
+// The same Visualizer as the window, drawn with the block characters ▁▂▃▄▅▆▇█: every
+// row of the terminal is eight steps of bar height. The frames come in as fast as
+// cava sends them, the terminal is only drawn ~30 times per second: in between we
+// keep the latest frame and drop the others.
+//
+// The terminal is put in raw mode (no echo, keys arrive one by one) on an alternate
+// screen with the cursor hidden, and put back the way it was when we leave, even on
+// a panic. Raw mode means Ctrl-C is a key press like any other, not a signal.
+
+// A bar `n` eighths of a row high ends in BLOCKS[n]
+pub const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+// Drawing a terminal faster than that mostly makes it flicker
+pub const TUI_FRAME_TIME: Duration = Duration::from_millis(33);
+
+// How high a value is, in eighths of a row, `rows` being the full height
+// Rounded to the nearest eighth: a value has to be at least half an eighth to show
+pub fn eighths(value: u16, rows: u16) -> u32 {
+    let full = rows as u64 * 8;
+    let max = u16::MAX as u64;
+    ((value as u64 * full + max / 2) / max) as u32
+}
+
+// The character of a bar `eighths` high on `row`, counted from the bottom (0)
+// Full blocks below the top of the bar, the partial one at its top, nothing above
+pub fn block(eighths: u32, row: u16) -> char {
+    let below = row as u32 * 8;
+    BLOCKS[eighths.saturating_sub(below).min(8) as usize]
+}
+
+// The whole picture, `rows` lines of exactly `columns` characters, top line first
+// Every bar gets the same width, with a column of space between bars when they are
+// at least 3 wide. More values than columns are averaged down to one per column,
+// like the window does when it's too narrow.
+pub fn lines(values: &[u16], columns: u16, rows: u16) -> Vec<String> {
+    let columns = columns as usize;
+    let values = if values.len() > columns {
+        render::average_values(values, columns)
+    } else {
+        values.to_vec()
+    };
+    if values.is_empty() {
+        return vec![" ".repeat(columns); rows as usize];
+    }
+
+    let slot = columns / values.len();
+    let gap = if slot >= 3 { 1 } else { 0 };
+    let heights: Vec<u32> = values.iter().map(|&value| eighths(value, rows)).collect();
+    (0..rows)
+        .rev()
+        .map(|row| {
+            let mut line = String::with_capacity(columns * 3);
+            for &height in &heights {
+                let c = block(height, row);
+                line.extend(std::iter::repeat_n(c, slot - gap));
+                line.extend(std::iter::repeat_n(' ', gap));
+            }
+            // What's left on the right when the bars don't divide the width
+            line.extend(std::iter::repeat_n(' ', columns - slot * values.len()));
+            line
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum TuiError {
+    Visualizer(VisualizerError),
+    Terminal(io::Error),
+    // The visualizer's thread ended, cava is gone for good
+    Stopped,
+}
+
+impl fmt::Display for TuiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TuiError::Visualizer(e) => write!(f, "Failed to start the visualizer: {}", e),
+            TuiError::Terminal(e) => write!(f, "Terminal error: {}", e),
+            TuiError::Stopped => write!(f, "The visualizer stopped"),
+        }
+    }
+}
+
+impl std::error::Error for TuiError {}
+
+impl From<io::Error> for TuiError {
+    fn from(e: io::Error) -> TuiError {
+        TuiError::Terminal(e)
+    }
+}
+
+// Raw mode, alternate screen, hidden cursor, as long as it lives
+// Dropping it restores the terminal, also when we leave because of an error or a panic
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        // Nothing we can do if this fails, and a panic in a drop would abort
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Run until q, Escape or Ctrl-C
+pub fn run(cli: &Cli) -> Result<(), TuiError> {
+    let config = cli.settings().config();
+    let visualizer = match cli.source() {
+        SourceKind::Cava => Visualizer::with_config(config).map_err(TuiError::Visualizer)?,
+        SourceKind::Synthetic => {
+            Visualizer::synthetic(config, Waveform::SineSweep, 0).map_err(TuiError::Visualizer)?
+        }
+    };
+
+    let _guard = TerminalGuard::enter()?;
+    let mut stdout = io::stdout();
+    let mut shown: Option<Arc<Frame>> = None;
+    // Something to draw: a new frame, or the terminal was resized
+    let mut changed = false;
+    let mut next_draw = Instant::now();
+    loop {
+        // Keys and resizes, waiting at most until it's time to draw
+        let wait = next_draw.saturating_duration_since(Instant::now());
+        if event::poll(wait)? {
+            match event::read()? {
+                Event::Key(key) if is_quit(key) => return Ok(()),
+                Event::Resize(_, _) => changed = true,
+                _ => {}
+            }
+            continue;
+        }
+
+        // Every frame that came in since the last draw, only the latest one counts
+        loop {
+            match visualizer.next_timeout(Duration::ZERO) {
+                Ok(frame) => {
+                    shown = Some(frame);
+                    changed = true;
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Err(TuiError::Stopped),
+            }
+        }
+
+        if changed {
+            if let Some(frame) = &shown {
+                draw(&mut stdout, frame)?;
+                changed = false;
+            }
+        }
+        next_draw = Instant::now() + TUI_FRAME_TIME;
+    }
+}
+
+fn is_quit(key: KeyEvent) -> bool {
+    // Some terminals also report releases, we only act on presses
+    key.kind == KeyEventKind::Press
+        && match key.code {
+            KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+            KeyCode::Char('q') | KeyCode::Esc => true,
+            _ => false,
+        }
+}
+
+// Draw the bars over the whole terminal, with a help line at the bottom
+// Every line is written in full, so there is no need to clear the screen first (which
+// would flicker)
+fn draw(stdout: &mut io::Stdout, frame: &Frame) -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    if columns == 0 || rows < 2 {
+        return Ok(());
+    }
+    let bar_rows = rows - 1;
+    for (y, line) in lines(frame, columns, bar_rows).iter().enumerate() {
+        queue!(stdout, cursor::MoveTo(0, y as u16), style::Print(line))?;
+    }
+    let help: String = format!("{} bars, q to quit", frame.len())
+        .chars()
+        .take(columns as usize)
+        .collect();
+    queue!(
+        stdout,
+        cursor::MoveTo(0, bar_rows),
+        terminal::Clear(terminal::ClearType::CurrentLine),
+        style::Print(help)
+    )?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEventState;
+
+    use super::*;
+
+    #[test]
+    fn eighths_of_the_full_height() {
+        assert_eq!(eighths(0, 10), 0);
+        assert_eq!(eighths(u16::MAX, 10), 80);
+        assert_eq!(eighths(u16::MAX / 2, 10), 40);
+        assert_eq!(eighths(u16::MAX, 0), 0);
+    }
+
+    #[test]
+    fn eighths_are_rounded_to_the_nearest() {
+        // One eighth of a single row is 8192
+        assert_eq!(eighths(4095, 1), 0);
+        assert_eq!(eighths(4097, 1), 1);
+        assert_eq!(eighths(8192 + 4095, 1), 1);
+    }
+
+    #[test]
+    fn full_blocks_below_the_top_partial_one_on_it() {
+        // 2 rows and 3 eighths
+        let column: Vec<char> = (0..4).map(|row| block(19, row)).collect();
+        assert_eq!(column, vec!['█', '█', '▃', ' ']);
+        assert_eq!(block(0, 0), ' ');
+        assert_eq!(block(8, 0), '█');
+        assert_eq!(block(8, 1), ' ');
+    }
+
+    #[test]
+    fn the_lines_fill_the_terminal() {
+        let lines = lines(&[0, u16::MAX / 2, u16::MAX], 10, 2);
+        // 3 wide bars with a space after each, and the column that's left over
+        assert_eq!(lines, vec!["      ██  ", "   ██ ██  "]);
+    }
+
+    #[test]
+    fn narrow_bars_have_no_gap() {
+        assert_eq!(lines(&[u16::MAX, 0, u16::MAX], 6, 1), vec!["██  ██"]);
+    }
+
+    #[test]
+    fn more_values_than_columns_are_averaged() {
+        let lines = lines(&[u16::MAX, u16::MAX, 0, 0], 2, 1);
+        assert_eq!(lines, vec!["█ "]);
+    }
+
+    #[test]
+    fn no_values_is_an_empty_screen() {
+        assert_eq!(lines(&[], 4, 2), vec!["    ", "    "]);
+    }
+
+    #[test]
+    fn every_line_is_exactly_as_wide_as_the_terminal() {
+        let values: Vec<u16> = (0..37).map(|i| i * 1700).collect();
+        for columns in [1, 7, 36, 37, 80, 211] {
+            for line in lines(&values, columns, 5) {
+                assert_eq!(line.chars().count(), columns as usize);
+            }
+        }
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers,
+            kind,
+            state: KeyEventState::NONE,
+        }
+    }
+
+    #[test]
+    fn ctrl_c_q_and_escape_quit() {
+        let press = |code, modifiers| is_quit(key(code, modifiers, KeyEventKind::Press));
+        assert!(press(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert!(press(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert!(press(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(!press(KeyCode::Char('c'), KeyModifiers::NONE));
+        assert!(!press(KeyCode::Char('x'), KeyModifiers::CONTROL));
+        // A release of q is not a press of q
+        assert!(!is_quit(key(
+            KeyCode::Char('q'),
+            KeyModifiers::NONE,
+            KeyEventKind::Release
+        )));
+    }
+}
@@ -1,126 +1,548 @@
 use std::{
     fs::File,
-    io::{Read, Write},
-    process::Stdio,
-    sync::mpsc::Receiver,
+    io::{self, BufWriter},
+    path::Path,
+    sync::{
+        atomic::{self, AtomicU64},
+        mpsc::{self, Receiver, RecvError, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-pub struct Visualizer;
-
-// This is defined as a macro so we can replace the `{0}` with the number of bars
-// This way we can just call format!(cava_config!(), bars) and get the correct configuration
-// You can play around with the configuration to get different effects
-macro_rules! cava_config {
-    () => {
-        r#"
-        [general]
-        bars = {0}
-        framerate = 60
-
-        [output]
-        method = raw
-        channels = mono
-        raw_target = /dev/stdout
-        data_format = binary
-        bit_format = 16bit
-
-        [smoothing]
-        integral = 70
-        monstercat = 0
-        waves = 1
-        gravity = 100
-"#
-    };
+use broadcast::{Broadcaster, Subscriber, SUBSCRIBER_CAPACITY};
+use events::EventSubscribers;
+use health::HealthState;
+
+pub mod broadcast;
+pub mod cava;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod frame;
+pub mod framing;
+pub mod health;
+pub mod history;
+pub mod input;
+pub mod level;
+pub mod ordering;
+pub mod pipeline;
+pub mod source;
+pub mod stats;
+pub mod synthetic;
+pub mod udp;
+#[cfg(feature = "ws")]
+pub mod ws;
+
+pub use cava::CavaVersion;
+pub use config::{VisualizerConfig, DEFAULT_SENSITIVITY};
+pub use error::VisualizerError;
+pub use events::{SilenceDetector, VisualizerEvent};
+pub use export::{CaptureLimit, CsvSink};
+pub use frame::{ChannelLayout, Frame};
+pub use framing::FrameReader;
+pub use health::{Health, ProcessMonitor};
+pub use history::FrameHistory;
+pub use input::InputMethod;
+pub use level::{rms, LevelMeter};
+pub use ordering::{reorder, Ordering, Reorder};
+pub use pipeline::{NoiseGate, Smoother, Stage, TemporalSmoother};
+pub use source::{AudioSource, CavaSource, ScriptedSource};
+pub use stats::VisualizerStats;
+pub use synthetic::{SyntheticSource, Waveform};
+pub use udp::{PacketFormat, UdpSink};
+#[cfg(feature = "ws")]
+pub use ws::WsServer;
+
+// The handle the UI holds on to
+// Frames are received through it and the stats can be inspected at any time
+// Other consumers (a recorder, a terminal meter...) can get their own copy of
+// every frame with `subscribe`
+pub struct Visualizer {
+    rx: Receiver<Arc<Frame>>,
+    stats: Arc<VisualizerStats>,
+    subscribe_tx: Sender<Subscriber>,
+    // The drop counter of every subscriber, in subscription order
+    // (the handle's own receiver is the first one)
+    drop_counters: Mutex<Vec<Arc<AtomicU64>>>,
+    // The most recent frames, written by the reader thread
+    history: Arc<Mutex<FrameHistory>>,
+    // The loudness of the latest frame and its slow average
+    level: Arc<LevelMeter>,
+    // Hands the sending end of every `events` receiver to the reader thread
+    events_tx: Sender<Sender<VisualizerEvent>>,
+    // The config the current source was started with, updated on every restart
+    config: Mutex<VisualizerConfig>,
+    // Replacement sources for the reader thread, see `nudge_sensitivity`
+    source_tx: Sender<Box<dyn AudioSource + Send>>,
+    // Only a cava source can be restarted with a new config
+    restartable: bool,
+    // The process, restarts and errors, see `health`
+    health: Arc<HealthState>,
+    // Tells us whether the pipeline is still running when there is no process to ask
+    reader: JoinHandle<()>,
 }
 
 impl Visualizer {
-    pub fn new(bars: usize) -> Receiver<Vec<u16>> {
-        // Create a new temporary configuration for cava and save it to
-        // `/tmp/cava-config.conf` so we can pass it as a argument to cava
-        let path = std::env::temp_dir().join("cava-config.conf");
-        let config = format!(cava_config!(), bars);
-        let mut temp = File::create(&path).unwrap();
-        temp.write_all(config.as_bytes()).unwrap();
-        temp.flush().unwrap();
-
-        // Spawn the cava process with the configuration file
-        let mut process = std::process::Command::new("cava")
-            .arg("-p")
-            .arg(&path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .stdin(Stdio::null())
-            .spawn()
-            .unwrap();
-
-        // Create a channel so we can send data from the cava process to the main thread
-        // The `rx` will be returned to the UI so it can get the data
-        let (tx, rx) = std::sync::mpsc::channel::<Vec<u16>>();
-        std::thread::spawn(move || {
-            // Initialize a buffer to receive raw data from cava
+    pub fn new(bars: usize) -> Result<Visualizer, VisualizerError> {
+        Visualizer::with_config(VisualizerConfig::new(bars))
+    }
+
+    pub fn with_config(config: VisualizerConfig) -> Result<Visualizer, VisualizerError> {
+        // Refuse to start cava with values it doesn't understand
+        config.validate()?;
+
+        // Spawn cava and plug it into the pipeline
+        let source = CavaSource::spawn(&config)?;
+        let process = source.monitor();
+        Ok(Visualizer::start(Box::new(source), config, Some(process)))
+    }
+
+    // The PulseAudio sources cava can capture from, for a device picker
+    // Pass one of the names to `VisualizerConfig::input`
+    pub fn list_pulse_sources() -> Result<Vec<String>, VisualizerError> {
+        input::list_pulse_sources()
+    }
+
+    // Run the pipeline on a made up signal, no cava or sound card needed
+    // The frames arrive at the configured framerate, just like with cava
+    pub fn synthetic(
+        config: VisualizerConfig,
+        waveform: Waveform,
+        seed: u64,
+    ) -> Result<Visualizer, VisualizerError> {
+        let source = SyntheticSource::new(waveform, config.framerate, seed);
+        Visualizer::from_source(Box::new(source), config)
+    }
+
+    // Build the pipeline on top of any audio source
+    // The source is moved into the reader thread, that's why it needs to be `Send`
+    pub fn from_source(
+        source: Box<dyn AudioSource + Send>,
+        config: VisualizerConfig,
+    ) -> Result<Visualizer, VisualizerError> {
+        // Without cava nobody else would catch a framerate of 0 or too many bars
+        config.validate()?;
+
+        Ok(Visualizer::start(source, config, None))
+    }
+
+    // `process` is the cava behind the source, if there is one
+    // Only then can the visualizer be restarted
+    // The config must have gone through `validate` already
+    fn start(
+        mut source: Box<dyn AudioSource + Send>,
+        config: VisualizerConfig,
+        process: Option<Arc<ProcessMonitor>>,
+    ) -> Visualizer {
+        let restartable = process.is_some();
+        let health = Arc::new(HealthState::new(process));
+        let thread_health = health.clone();
+
+        // The stats are shared between the reader thread (which updates them)
+        // and the handle we return (so the UI can read them)
+        let stats = Arc::new(VisualizerStats::default());
+        let thread_stats = stats.clone();
+
+        // The time between two frames when everything runs on schedule
+        // (validate() made sure the framerate isn't 0)
+        let expected_period = Duration::from_secs(1) / config.framerate;
+
+        // The stages every frame goes through, in order
+        let mut stages = build_stages(&config);
+        // validate() makes sure the number of bars fits in a u16
+        let bars = config.bars as u16;
+
+        // The reader thread only holds the lock while it copies a frame in, and `history`
+        // while it copies the frames out, so a snapshot never sees a half written frame
+        let history = Arc::new(Mutex::new(FrameHistory::new(config.history_capacity)));
+        let thread_history = history.clone();
+
+        let level = Arc::new(LevelMeter::new(config.level_time_constant));
+        let thread_level = level.clone();
+
+        // New subscribers are handed to the reader thread through this channel
+        let (subscribe_tx, subscribe_rx) = std::sync::mpsc::channel::<Subscriber>();
+        let mut broadcaster = Broadcaster::new(subscribe_rx);
+
+        // The handle receives frames like any other subscriber. It subscribes before the
+        // reader thread starts, a fast source could otherwise be done before it is listening
+        let (subscriber, rx) = Subscriber::new(SUBSCRIBER_CAPACITY);
+        let drop_counters = Mutex::new(vec![subscriber.dropped.clone()]);
+        let _ = subscribe_tx.send(subscriber);
+
+        // A new source (e.g. cava restarted with another sensitivity) replaces the
+        // current one between two frames, everything after it keeps running untouched
+        let (source_tx, source_rx) = std::sync::mpsc::channel::<Box<dyn AudioSource + Send>>();
+
+        // Same thing for the idle/active events
+        let (events_tx, events_rx) = std::sync::mpsc::channel();
+        let mut events = EventSubscribers::new(events_rx);
+        let mut silence = SilenceDetector::new(
+            config.silence_threshold,
+            config.wake_threshold,
+            config.silence_duration,
+        );
+
+        let reader = thread::spawn(move || {
+            // Initialize a buffer to receive raw data from the source
             // Buffer size is 2 * bars because:
             // - Each bar's data is represented by 2 bytes (16 bits)
             // - We need to accommodate data for all bars
             // Process:
             // 1. Read raw binary data into this buffer
             // 2. Convert buffer contents to Vec<u16>
-            // 3. Send Vec<u16> to main thread for UI updates
+            // 3. Wrap it in a Frame and send it to main thread for UI updates
             // This approach allows for efficient data transfer and easy iteration in the UI
-            let mut buf = vec![0_u8; 2 * bars];
-
-            // Get the stdout of the cava process so we can read its output
-            let stdout = process.stdout.as_mut().unwrap();
+            let mut buf = vec![0_u8; 2 * bars as usize];
+            let mut last_frame_at: Option<Instant> = None;
 
             loop {
-                // Initialize a vector to store data received from cava
-                // Each element is a u16 (16-bit unsigned integer) representing the amplitude of a frequency bar
-                // The vector is pre-filled with zeros and has a length equal to the number of bars
-                // Rust's type inference allows us to use 0_u16 to specify the element type
-                let mut data = vec![0_u16; bars];
-
-                // Read the data from stdout into the buffer
-                // We are reading the exact amount of bytes that we need
-                let read_res = stdout.read_exact(&mut buf);
-                if let Err(e) = read_res {
-                    let stderr = process.stderr.as_mut().unwrap();
-                    let mut stderr_contents = String::new();
-                    stderr.read_to_string(&mut stderr_contents).unwrap();
-                    panic!(
-                        "cava proccess panicked: {:?} Error: {:?}",
-                        stderr_contents, e
-                    );
+                // Dropping the old source stops it (for cava this kills the process)
+                while let Ok(new_source) = source_rx.try_recv() {
+                    source = new_source;
+                }
+
+                if let Err(e) = source.read_frame(&mut buf) {
+                    // The source ran out of frames, we just stop and let the
+                    // receiver notice that the channel was closed
+                    if e.kind() == io::ErrorKind::UnexpectedEof {
+                        break;
+                    }
+                    eprintln!("visualizer stopped: {}", e);
+                    thread_health.record_error(e.into());
+
+                    // cava died, but it can be started again (see `restart`) so we wait
+                    // for the new one. `recv` fails once the handle is gone, since
+                    // then nobody is left to send it
+                    if !restartable {
+                        break;
+                    }
+                    match source_rx.recv() {
+                        Ok(new_source) => {
+                            source = new_source;
+                            continue;
+                        }
+                        Err(_) => break,
+                    }
                 }
+                thread_stats.record_resyncs(source.take_resyncs());
+
+                // Take the timestamp right after the read so it is as close as
+                // possible to the moment cava produced the frame
+                let captured_at = Instant::now();
+                let gap = last_frame_at.map(|last| captured_at.duration_since(last));
+                last_frame_at = Some(captured_at);
+                thread_stats.record_frame(gap, expected_period);
+                thread_health.record_frame(captured_at);
 
-                // Convert the raw binary buffer into a Vec<u16>
-                // Each pair of bytes in the buffer represents one u16 value
-                // This conversion is necessary because cava outputs data in binary format
-                // (as specified in the cava configuration: `data_format = binary` and `bit_format = 16bit`)
-                for i in 0..data.len() {
-                    data[i] = u16::from_le_bytes([buf[2 * i], buf[2 * i + 1]]);
+                if let Some(gap) = gap {
+                    for stage in stages.iter_mut() {
+                        stage.frame_interval(gap);
+                    }
                 }
+                let values = stages
+                    .iter_mut()
+                    .fold(decode(&buf), |values, stage| stage.process(values));
 
-                // Apply a simple moving average smoothing
-                // This part makes the bars look smoother, like when we are drawing and you use ours finger to blend colors together
-                let window_size = 3; // This is like how many bars we look at to make each bar smoother
-                let mut smoothed_data = vec![0_u16; bars]; // We make a new list to put our smoother bars in
-                for i in 0..bars {
-                    // For each bar, we look at the bars next to it
-                    let start = i.saturating_sub(window_size / 2); // We start looking a little bit before our bar
-                    let end = (i + window_size / 2 + 1).min(bars); // We stop looking a little bit after our bar
-
-                    // We add up the heights of all these bars
-                    let sum: u32 = data[start..end].iter().map(|&x| x as u32).sum();
-
-                    // Then we divide by how many bars we looked at to get an average
-                    // This average becomes the new height of our bar
-                    smoothed_data[i] = (sum / (end - start) as u32) as u16;
+                // The very first frame has no previous one, so we pretend it came on time
+                let elapsed = gap.unwrap_or(expected_period);
+                let frame_level = rms(&values);
+                thread_level.record(frame_level, elapsed);
+                if let Some(event) = silence.update(frame_level, elapsed) {
+                    events.send(event);
                 }
 
-                // And finally we send the smoothed data to the UI
-                tx.send(smoothed_data).unwrap();
+                let frame = Frame {
+                    seq: thread_stats.next_seq(),
+                    captured_at,
+                    bars,
+                    // We always ask cava for a single channel
+                    layout: ChannelLayout::Mono,
+                    values,
+                };
+
+                thread_history.lock().unwrap().push(&frame);
+
+                // And finally we send the smoothed data to the UI (and everybody else listening)
+                // If they are all gone there is nobody to draw for, so we stop reading
+                broadcaster.send(frame, &thread_stats);
+                if broadcaster.is_finished() {
+                    break;
+                }
             }
         });
 
+        Visualizer {
+            rx,
+            stats,
+            subscribe_tx,
+            drop_counters,
+            history,
+            level,
+            events_tx,
+            config: Mutex::new(config),
+            source_tx,
+            restartable,
+            health,
+            reader,
+        }
+    }
+
+    // Block until the next frame arrives
+    // Returns an error once the pipeline has shut down
+    pub fn recv(&self) -> Result<Arc<Frame>, RecvError> {
+        self.rx.recv()
+    }
+
+    // Like `recv`, but gives up after `timeout`
+    // Useful for consumers that need to do other work between frames:
+    // a `RecvTimeoutError::Timeout` just means no frame arrived in time, while
+    // `RecvTimeoutError::Disconnected` means the pipeline has shut down
+    pub fn next_timeout(&self, timeout: Duration) -> Result<Arc<Frame>, RecvTimeoutError> {
+        self.rx.recv_timeout(timeout)
+    }
+
+    /// Iterate over the frames as they arrive
+    ///
+    /// Each call to `next` blocks (without spinning) until a frame is available,
+    /// and the iteration simply ends once the pipeline shuts down:
+    ///
+    /// ```
+    /// use relm4_cairo_visualizer::visualizer::{ScriptedSource, Visualizer, VisualizerConfig};
+    ///
+    /// let source = ScriptedSource::new(vec![vec![100; 4], vec![200; 4]]);
+    /// let visualizer = Visualizer::from_source(Box::new(source), VisualizerConfig::new(4))?;
+    /// let mut seqs = Vec::new();
+    /// for frame in visualizer.iter() {
+    ///     println!("frame #{}: {:?}", frame.seq, frame.values);
+    ///     seqs.push(frame.seq);
+    /// }
+    /// // The source ran out of frames, so the pipeline shut down and the loop ended
+    /// assert_eq!(seqs, [0, 1]);
+    /// # Ok::<(), relm4_cairo_visualizer::visualizer::VisualizerError>(())
+    /// ```
+    pub fn iter(&self) -> mpsc::Iter<'_, Arc<Frame>> {
+        self.rx.iter()
+    }
+
+    // Get a new receiver that gets every frame from now on
+    // Can be called as many times as needed. A subscriber that doesn't keep up
+    // misses frames instead of slowing down the others
+    pub fn subscribe(&self) -> Receiver<Arc<Frame>> {
+        let (subscriber, rx) = Subscriber::new(SUBSCRIBER_CAPACITY);
+        self.drop_counters
+            .lock()
+            .unwrap()
+            .push(subscriber.dropped.clone());
+
+        // If the reader thread already stopped the subscriber is dropped here,
+        // which disconnects `rx` right away, exactly what the caller should see
+        let _ = self.subscribe_tx.send(subscriber);
         rx
     }
+
+    // Get a receiver for the idle/active events
+    // Like `subscribe`, every receiver gets every event from now on:
+    //
+    //   let events = visualizer.events();
+    //   match events.try_recv() {
+    //       Ok(VisualizerEvent::Idle) => { /* dim the window */ }
+    //       Ok(VisualizerEvent::Active) => { /* back to normal */ }
+    //       Err(_) => {}
+    //   }
+    pub fn events(&self) -> Receiver<VisualizerEvent> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _ = self.events_tx.send(tx);
+        rx
+    }
+
+    // Record the frames of the next `duration` to a CSV file (see CsvSink for the format)
+    // The capture runs on its own thread like any other subscriber, join the handle to
+    // wait for it and get the number of frames written. It also ends early if the
+    // pipeline shuts down. The file is created right away so a bad path fails here
+    pub fn export_csv(
+        &self,
+        path: impl AsRef<Path>,
+        duration: Duration,
+    ) -> Result<JoinHandle<io::Result<u64>>, VisualizerError> {
+        let file = File::create(path)?;
+        let frames = self.subscribe();
+
+        Ok(std::thread::spawn(move || {
+            let mut sink = CsvSink::new(BufWriter::new(file), CaptureLimit::Duration(duration));
+            for frame in frames {
+                if !sink.write_frame(&frame)? {
+                    break;
+                }
+            }
+            let frames_written = sink.frames_written();
+            sink.finish()?;
+            Ok(frames_written)
+        }))
+    }
+
+    // Serve the frames over WebSocket as JSON arrays, for a renderer running in a browser
+    // Each client gets at most `max_rate` frames per second (see WsServer)
+    // Only available with the `ws` feature:
+    //
+    //   cargo run -p relm4_cairo_visualizer --features ws
+    #[cfg(feature = "ws")]
+    pub fn ws_server(
+        &self,
+        addr: impl std::net::ToSocketAddrs,
+        max_rate: u32,
+    ) -> io::Result<WsServer> {
+        WsServer::start(self.subscribe(), addr, max_rate)
+    }
+
+    // How many frames each subscriber missed, in subscription order
+    pub fn dropped_per_subscriber(&self) -> Vec<u64> {
+        self.drop_counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|dropped| dropped.load(atomic::Ordering::Relaxed))
+            .collect()
+    }
+
+    // The most recent frames, oldest first
+    pub fn history(&self) -> Vec<Frame> {
+        self.history.lock().unwrap().snapshot()
+    }
+
+    // The loudness of the latest frame, between 0.0 and 1.0
+    // Handy when a single number is enough (a tray icon, idle detection...)
+    pub fn level(&self) -> f32 {
+        self.level.level()
+    }
+
+    // The level averaged over the configured time constant, it doesn't jump on every beat
+    pub fn average_level(&self) -> f32 {
+        self.level.average()
+    }
+
+    // Make the bars taller (positive delta) or shorter (negative delta)
+    // The sensitivity is in percent and never goes below 1. This switches to a manual
+    // sensitivity, so autosens is turned off
+    //
+    // cava can't change its settings while running, so we rewrite the config and start
+    // a new cava. The frames keep flowing through the same pipeline, consumers only
+    // notice the bars changing height. Returns the new sensitivity
+    pub fn nudge_sensitivity(&self, delta: i32) -> Result<u32, VisualizerError> {
+        if !self.restartable {
+            return Err(VisualizerError::NotRestartable);
+        }
+
+        let mut config = self.config.lock().unwrap();
+        let new_config = config.nudged(delta);
+        new_config.validate()?;
+
+        self.replace_source(&new_config)?;
+        *config = new_config;
+
+        Ok(config.sensitivity.unwrap_or(DEFAULT_SENSITIVITY))
+    }
+
+    // Start a new cava with the current config, e.g. after `health` says it's not running
+    // anymore. The pipeline waits for it when cava dies, so the frames just pick up again
+    pub fn restart(&self) -> Result<(), VisualizerError> {
+        if !self.restartable {
+            return Err(VisualizerError::NotRestartable);
+        }
+
+        let config = self.config.lock().unwrap();
+        self.replace_source(&config)
+    }
+
+    // Start the new cava before touching anything, so if it fails the old one keeps running
+    fn replace_source(&self, config: &VisualizerConfig) -> Result<(), VisualizerError> {
+        let result = CavaSource::spawn(config).and_then(|source| {
+            let process = source.monitor();
+            self.source_tx
+                .send(Box::new(source))
+                .map_err(|_| io::Error::other("the visualizer already stopped"))?;
+            Ok(process)
+        });
+
+        match result {
+            Ok(process) => {
+                self.health.record_restart(process);
+                Ok(())
+            }
+            Err(e) => {
+                self.health.record_error(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    // How the source is doing: is cava still running, when did the last frame arrive...
+    // It only reads a few shared values and asks the OS about the process without
+    // waiting, so it's fine to call from the GTK main loop every second
+    pub fn health(&self) -> Health {
+        self.health.snapshot(!self.reader.is_finished())
+    }
+
+    // The most recent failure: cava dying, or failing to start again
+    pub fn last_error(&self) -> Option<VisualizerError> {
+        self.health.last_error()
+    }
+
+    pub fn stats(&self) -> Arc<VisualizerStats> {
+        self.stats.clone()
+    }
+}
+
+// This is what makes `for frame in visualizer { ... }` work
+// The loop takes ownership of the handle, so nobody can subscribe anymore once it starts
+impl IntoIterator for Visualizer {
+    type Item = Arc<Frame>;
+    type IntoIter = mpsc::IntoIter<Arc<Frame>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rx.into_iter()
+    }
+}
+
+// And this makes `for frame in &visualizer { ... }` work, keeping the handle usable
+impl<'a> IntoIterator for &'a Visualizer {
+    type Item = Arc<Frame>;
+    type IntoIter = mpsc::Iter<'a, Arc<Frame>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// Convert the raw binary buffer into a Vec<u16>
+// Each pair of bytes in the buffer represents one u16 value
+// This conversion is necessary because cava outputs data in binary format
+// (as specified in the cava configuration: `data_format = binary` and `bit_format = 16bit`)
+pub fn decode(buf: &[u8]) -> Vec<u16> {
+    buf.chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+// The noise gate must run before the smoothers, otherwise the noise would already
+// be blended into the neighbour bars (and frames) by the time we try to cut it
+fn build_stages(config: &VisualizerConfig) -> Vec<Box<dyn Stage>> {
+    let mut stages: Vec<Box<dyn Stage>> = Vec::new();
+    if let Some(threshold) = config.noise_gate {
+        stages.push(Box::new(NoiseGate::new(threshold, config.hysteresis)));
+    }
+    stages.push(Box::new(Smoother::default()));
+    if !config.smoothing_time_constant.is_zero() {
+        stages.push(Box::new(TemporalSmoother::new(
+            config.smoothing_time_constant,
+            config.framerate,
+        )));
+    }
+    if config.ordering != Ordering::LeftToRight {
+        stages.push(Box::new(Reorder::new(config.ordering)));
+    }
+    stages
 }
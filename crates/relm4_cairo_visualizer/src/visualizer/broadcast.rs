@@ -0,0 +1,197 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc::{Receiver, SyncSender, TryRecvError, TrySendError},
+    Arc,
+};
+
+use super::{Frame, VisualizerStats};
+
+// How many frames a subscriber can fall behind before we start dropping frames for it
+// At 60 fps this is about one second of frames
+pub const SUBSCRIBER_CAPACITY: usize = 64;
+
+// One consumer of frames
+pub(crate) struct Subscriber {
+    pub(crate) tx: SyncSender<Arc<Frame>>,
+    // How many frames this subscriber missed because its channel was full
+    pub(crate) dropped: Arc<AtomicU64>,
+}
+
+impl Subscriber {
+    // Create a subscriber and the receiving end of its channel
+    pub(crate) fn new(capacity: usize) -> (Subscriber, Receiver<Arc<Frame>>) {
+        let (tx, rx) = std::sync::mpsc::sync_channel(capacity);
+        let subscriber = Subscriber {
+            tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (subscriber, rx)
+    }
+}
+
+// Lives in the reader thread and hands every frame to all the subscribers
+//
+// New subscribers don't get added to the list directly (that would need a lock
+// shared with the handle), they are sent over a channel instead and picked up
+// by the reader thread before the next frame goes out
+pub(crate) struct Broadcaster {
+    subscribers: Vec<Subscriber>,
+    new_subscribers: Receiver<Subscriber>,
+    // False once the Visualizer handle was dropped, nobody can subscribe anymore
+    handle_alive: bool,
+}
+
+impl Broadcaster {
+    pub(crate) fn new(new_subscribers: Receiver<Subscriber>) -> Broadcaster {
+        Broadcaster {
+            subscribers: Vec::new(),
+            new_subscribers,
+            handle_alive: true,
+        }
+    }
+
+    pub(crate) fn send(&mut self, frame: Frame, stats: &VisualizerStats) {
+        self.accept_new_subscribers();
+
+        // The frame is shared by everybody, so cloning it per subscriber
+        // is just bumping a reference count
+        let frame = Arc::new(frame);
+
+        // `retain` lets us drop the subscribers that went away while we iterate
+        self.subscribers
+            .retain(|subscriber| match subscriber.tx.try_send(frame.clone()) {
+                Ok(()) => true,
+                // A slow subscriber just misses this frame, we never wait for it
+                // because that would also delay everybody else
+                Err(TrySendError::Full(_)) => {
+                    subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                    stats.record_dropped();
+                    true
+                }
+                // The receiver was dropped, forget about this subscriber
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+
+    // True when nobody is listening and nobody can start listening anymore
+    pub(crate) fn is_finished(&mut self) -> bool {
+        self.accept_new_subscribers();
+        !self.handle_alive && self.subscribers.is_empty()
+    }
+
+    fn accept_new_subscribers(&mut self) {
+        loop {
+            match self.new_subscribers.try_recv() {
+                Ok(subscriber) => self.subscribers.push(subscriber),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.handle_alive = false;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    fn frame(seq: u64) -> Frame {
+        Frame {
+            seq,
+            ..Frame::mono(vec![seq as u16; 2])
+        }
+    }
+
+    fn seqs(rx: &Receiver<Arc<Frame>>) -> Vec<u64> {
+        rx.try_iter().map(|frame| frame.seq).collect()
+    }
+
+    #[test]
+    fn every_subscriber_gets_every_frame() {
+        let (subscribe, new_subscribers) = mpsc::channel();
+        let mut broadcaster = Broadcaster::new(new_subscribers);
+        let stats = VisualizerStats::default();
+        let mut receivers = Vec::new();
+        for _ in 0..3 {
+            let (subscriber, rx) = Subscriber::new(8);
+            subscribe.send(subscriber).unwrap();
+            receivers.push(rx);
+        }
+
+        for seq in 0..4 {
+            broadcaster.send(frame(seq), &stats);
+        }
+        for rx in &receivers {
+            assert_eq!(seqs(rx), [0, 1, 2, 3]);
+        }
+        assert_eq!(stats.frames_dropped(), 0);
+    }
+
+    #[test]
+    fn a_late_subscriber_gets_the_frames_from_then_on() {
+        let (subscribe, new_subscribers) = mpsc::channel();
+        let mut broadcaster = Broadcaster::new(new_subscribers);
+        let stats = VisualizerStats::default();
+        broadcaster.send(frame(0), &stats);
+
+        let (subscriber, rx) = Subscriber::new(8);
+        subscribe.send(subscriber).unwrap();
+        broadcaster.send(frame(1), &stats);
+        assert_eq!(seqs(&rx), [1]);
+    }
+
+    // A full subscriber misses frames, the others don't wait for it
+    #[test]
+    fn a_slow_subscriber_only_slows_itself() {
+        let (subscribe, new_subscribers) = mpsc::channel();
+        let mut broadcaster = Broadcaster::new(new_subscribers);
+        let stats = VisualizerStats::default();
+        let (slow, slow_rx) = Subscriber::new(2);
+        let slow_dropped = slow.dropped.clone();
+        let (fast, fast_rx) = Subscriber::new(8);
+        subscribe.send(slow).unwrap();
+        subscribe.send(fast).unwrap();
+
+        for seq in 0..5 {
+            broadcaster.send(frame(seq), &stats);
+        }
+        assert_eq!(seqs(&slow_rx), [0, 1]);
+        assert_eq!(seqs(&fast_rx), [0, 1, 2, 3, 4]);
+        assert_eq!(slow_dropped.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.frames_dropped(), 3);
+
+        // Once it catches up it gets frames again
+        broadcaster.send(frame(5), &stats);
+        assert_eq!(seqs(&slow_rx), [5]);
+    }
+
+    #[test]
+    fn it_is_finished_once_the_handle_and_the_subscribers_are_gone() {
+        let (subscribe, new_subscribers) = mpsc::channel();
+        let mut broadcaster = Broadcaster::new(new_subscribers);
+        let stats = VisualizerStats::default();
+        let (subscriber, rx) = Subscriber::new(8);
+        subscribe.send(subscriber).unwrap();
+
+        drop(subscribe);
+        assert!(!broadcaster.is_finished());
+        // A dropped receiver is forgotten at the next frame
+        drop(rx);
+        broadcaster.send(frame(0), &stats);
+        assert!(broadcaster.is_finished());
+        assert_eq!(stats.frames_dropped(), 0);
+    }
+
+    #[test]
+    fn the_handle_alone_keeps_it_going() {
+        let (subscribe, new_subscribers) = mpsc::channel::<Subscriber>();
+        let mut broadcaster = Broadcaster::new(new_subscribers);
+        assert!(!broadcaster.is_finished());
+        drop(subscribe);
+        assert!(broadcaster.is_finished());
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use super::{VisualizerConfig, VisualizerError};
+
+// Set this environment variable to use a cava that isn't in the PATH
+// (or a specific build), unless the config already points to one
+pub const CAVA_ENV: &str = "PLAYGROUND_CAVA";
+
+// We need the raw output writing 16 bit binary numbers for a single channel,
+// older versions don't know all the options we put in the config file
+pub const MIN_CAVA_VERSION: CavaVersion = CavaVersion {
+    major: 0,
+    minor: 7,
+    patch: 0,
+};
+
+// A cava version like 0.10.1
+// The fields are compared in order, so the derived Ord gives 0.9.1 < 0.10.0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CavaVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl fmt::Display for CavaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// Find the version in the output of `cava -v`
+// Upstream prints "cava 0.10.1", but distributions patch it into all sorts of things:
+// "cava 0.8.2-1ubuntu1", "cava v0.9.1", "cava 0.10.2-r0", "cava 0.7.4.r12.g3a5f2b1"...
+// So we take the first word that starts with (an optional "v" and) a number, and read
+// the numbers separated by dots until something else shows up
+pub fn parse_version(output: &str) -> Option<CavaVersion> {
+    output.split_whitespace().find_map(|word| {
+        let word = word.strip_prefix(['v', 'V']).unwrap_or(word);
+        let end = word
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(word.len());
+
+        // Anything after the patch number (a 4th number, an empty part...) is ignored
+        let mut numbers = word[..end].split('.').map(|part| part.parse::<u32>());
+        let major = numbers.next()?.ok()?;
+        let minor = numbers.next()?.ok()?;
+        let patch = numbers.next().and_then(Result::ok).unwrap_or(0);
+
+        Some(CavaVersion {
+            major,
+            minor,
+            patch,
+        })
+    })
+}
+
+// The cava binary to run: the one from the config, then the one from the
+// environment variable, and finally whatever `cava` is in the PATH
+pub fn cava_path(config: &VisualizerConfig) -> PathBuf {
+    config
+        .cava_path
+        .clone()
+        .or_else(|| std::env::var_os(CAVA_ENV).map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("cava"))
+}
+
+// Run `cava -v` to make sure cava is there and recent enough
+// A version we can't make sense of isn't an error, we just warn and hope for the best
+pub fn check_version(path: &Path) -> Result<Option<CavaVersion>, VisualizerError> {
+    let output = match Command::new(path).arg("-v").output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(VisualizerError::CavaNotFound(path.to_path_buf()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Some builds print the version on stderr
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let Some(found) = parse_version(&text) else {
+        eprintln!(
+            "could not find a version in the output of `{} -v`: {:?}",
+            path.display(),
+            text.trim()
+        );
+        return Ok(None);
+    };
+
+    if found < MIN_CAVA_VERSION {
+        return Err(VisualizerError::CavaTooOld {
+            found,
+            required: MIN_CAVA_VERSION,
+        });
+    }
+
+    Ok(Some(found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u32, minor: u32, patch: u32) -> Option<CavaVersion> {
+        Some(CavaVersion {
+            major,
+            minor,
+            patch,
+        })
+    }
+
+    #[test]
+    fn versions_from_the_wild() {
+        for (output, expected) in [
+            ("cava 0.10.1", version(0, 10, 1)),
+            ("cava 0.10.1\n", version(0, 10, 1)),
+            ("cava 0.8.2-1ubuntu1", version(0, 8, 2)),
+            ("cava v0.9.1", version(0, 9, 1)),
+            ("Cava V0.9.1", version(0, 9, 1)),
+            ("cava 0.10.2-r0", version(0, 10, 2)),
+            ("cava 0.7.4.r12.g3a5f2b1", version(0, 7, 4)),
+            ("cava 0.6", version(0, 6, 0)),
+            ("cava 0.10.", version(0, 10, 0)),
+            ("\n\ncava 0.10.1\n", version(0, 10, 1)),
+        ] {
+            assert_eq!(parse_version(output), expected, "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn no_version() {
+        for output in ["", "cava", "cava version unknown", "cava 7", "cava .1.2"] {
+            assert_eq!(parse_version(output), None, "{:?}", output);
+        }
+    }
+
+    // 0.9.1 < 0.10.0, which comparing the strings would get wrong
+    #[test]
+    fn versions_compare_as_numbers() {
+        assert!(version(0, 9, 1) < version(0, 10, 0));
+        assert!(version(0, 6, 9) < Some(MIN_CAVA_VERSION));
+        assert!(version(1, 0, 0) > version(0, 99, 99));
+        assert_eq!(MIN_CAVA_VERSION.to_string(), "0.7.0");
+    }
+
+    #[test]
+    fn the_configured_path_comes_first() {
+        let config = VisualizerConfig::new(8).cava_path("/opt/cava/bin/cava");
+        assert_eq!(cava_path(&config), Path::new("/opt/cava/bin/cava"));
+    }
+
+    #[test]
+    fn a_missing_binary_is_not_found() {
+        let path = Path::new("/nonexistent/cava");
+        assert!(matches!(
+            check_version(path),
+            Err(VisualizerError::CavaNotFound(found)) if found == path
+        ));
+    }
+
+    // A fake cava that prints `output` for `cava -v`
+    #[cfg(unix)]
+    fn fake_cava(name: &str, output: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("fake-cava-{}-{}", name, std::process::id()));
+        std::fs::write(&path, format!("#!/bin/sh\necho '{}'\n", output)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_version_against_a_fake_cava() {
+        let recent = fake_cava("recent", "cava 0.10.1-2");
+        assert_eq!(check_version(&recent).unwrap(), version(0, 10, 1));
+
+        let old = fake_cava("old", "cava 0.6.3");
+        assert!(matches!(
+            check_version(&old),
+            Err(VisualizerError::CavaTooOld { found, .. }) if Some(found) == version(0, 6, 3)
+        ));
+
+        // Can't tell, so let it run
+        let odd = fake_cava("odd", "a custom build");
+        assert_eq!(check_version(&odd).unwrap(), None);
+
+        for path in [recent, old, odd] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
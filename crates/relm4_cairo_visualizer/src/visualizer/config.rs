@@ -0,0 +1,654 @@
+use std::{fmt::Write, path::PathBuf, time::Duration};
+
+use super::{
+    events::{DEFAULT_SILENCE_DURATION, DEFAULT_SILENCE_THRESHOLD, DEFAULT_WAKE_THRESHOLD},
+    history::DEFAULT_HISTORY_CAPACITY,
+    input::InputMethod,
+    level::DEFAULT_LEVEL_TIME_CONSTANT,
+    ordering::Ordering,
+    VisualizerError,
+};
+
+// How many frames per second we ask cava for unless told otherwise
+pub const DEFAULT_FRAMERATE: u32 = 60;
+
+// The frequencies cava accepts for the cutoffs, roughly what humans can hear
+// (and 22050 Hz is the highest frequency a 44.1 kHz signal can represent)
+pub const MIN_FREQ_HZ: u32 = 20;
+pub const MAX_FREQ_HZ: u32 = 22050;
+
+// The sensitivity cava starts from when none is set, in percent
+pub const DEFAULT_SENSITIVITY: u32 = 100;
+
+// Everything the visualizer needs to know before it starts
+// It's built with chained methods, only `bars` is required:
+//
+//   let config = VisualizerConfig::new(20).noise_gate(500).hysteresis(true);
+//   let visualizer = Visualizer::with_config(config);
+#[derive(Debug, Clone)]
+pub struct VisualizerConfig {
+    pub(crate) bars: usize,
+    // The cava binary to run, None = PLAYGROUND_CAVA or `cava` from the PATH
+    pub(crate) cava_path: Option<PathBuf>,
+    pub(crate) framerate: u32,
+    // cava [general] options, None means "let cava decide" like the smoothing ones below
+    pub(crate) autosens: Option<bool>,
+    pub(crate) sensitivity: Option<u32>,
+    // Values below this threshold are zeroed before smoothing (None = no gate)
+    pub(crate) noise_gate: Option<u16>,
+    // Keep an open gate open until the value drops below half the threshold
+    pub(crate) hysteresis: bool,
+    // cava [smoothing] options, when they are None the key is not written
+    // to the config file at all so cava uses its own default
+    pub(crate) monstercat: Option<f32>,
+    pub(crate) waves: Option<bool>,
+    pub(crate) noise_reduction: Option<u8>,
+    // Lowest and highest frequency (in Hz) covered by the bars
+    pub(crate) freq_range: Option<(u32, u32)>,
+    // Where cava captures the audio from, None = cava's default
+    pub(crate) input: Option<InputMethod>,
+    // How many recent frames `Visualizer::history` keeps
+    pub(crate) history_capacity: usize,
+    // How slowly `Visualizer::average_level` follows the current level
+    pub(crate) level_time_constant: Duration,
+    // How long each bar is averaged over time (see TemporalSmoother), zero = not at all
+    pub(crate) smoothing_time_constant: Duration,
+    // Where the bars end up on screen
+    pub(crate) ordering: Ordering,
+    // When the visualizer goes idle and wakes up again (see SilenceDetector)
+    pub(crate) silence_threshold: f32,
+    pub(crate) wake_threshold: f32,
+    pub(crate) silence_duration: Duration,
+}
+
+impl VisualizerConfig {
+    pub fn new(bars: usize) -> VisualizerConfig {
+        VisualizerConfig {
+            bars,
+            cava_path: None,
+            framerate: DEFAULT_FRAMERATE,
+            autosens: None,
+            sensitivity: None,
+            noise_gate: None,
+            hysteresis: false,
+            monstercat: None,
+            waves: None,
+            noise_reduction: None,
+            freq_range: None,
+            input: None,
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            level_time_constant: DEFAULT_LEVEL_TIME_CONSTANT,
+            smoothing_time_constant: Duration::ZERO,
+            ordering: Ordering::default(),
+            silence_threshold: DEFAULT_SILENCE_THRESHOLD,
+            wake_threshold: DEFAULT_WAKE_THRESHOLD,
+            silence_duration: DEFAULT_SILENCE_DURATION,
+        }
+    }
+
+    // Run this cava binary instead of looking for one (see `cava::cava_path`)
+    pub fn cava_path(mut self, path: impl Into<PathBuf>) -> VisualizerConfig {
+        self.cava_path = Some(path.into());
+        self
+    }
+
+    pub fn framerate(mut self, framerate: u32) -> VisualizerConfig {
+        self.framerate = framerate;
+        self
+    }
+
+    // Let cava adjust the sensitivity on its own so the bars use the whole height
+    // without clipping, whatever the volume is
+    pub fn autosens(mut self, enabled: bool) -> VisualizerConfig {
+        self.autosens = Some(enabled);
+        self
+    }
+
+    // A fixed sensitivity in percent (100 is cava's default), higher makes the bars taller
+    // A manual sensitivity only makes sense without autosens, so unless autosens was
+    // asked for explicitly it gets turned off in the cava config
+    pub fn sensitivity(mut self, percent: u32) -> VisualizerConfig {
+        self.sensitivity = Some(percent);
+        self
+    }
+
+    pub fn noise_gate(mut self, threshold: u16) -> VisualizerConfig {
+        self.noise_gate = Some(threshold);
+        self
+    }
+
+    pub fn hysteresis(mut self, enabled: bool) -> VisualizerConfig {
+        self.hysteresis = enabled;
+        self
+    }
+
+    // Blend each bar with its neighbours in the style of the Monstercat logo
+    // 0 disables it, higher values spread the bars further
+    pub fn monstercat(mut self, strength: f32) -> VisualizerConfig {
+        self.monstercat = Some(strength);
+        self
+    }
+
+    // Use a "wave" shape instead of the monstercat one
+    pub fn waves(mut self, enabled: bool) -> VisualizerConfig {
+        self.waves = Some(enabled);
+        self
+    }
+
+    // How much cava smooths the bars over time (0 = fast and noisy, 100 = slow and smooth)
+    pub fn noise_reduction(mut self, amount: u8) -> VisualizerConfig {
+        self.noise_reduction = Some(amount);
+        self
+    }
+
+    // Only show the frequencies between `low_hz` and `high_hz`
+    // e.g. 50..10000 Hz covers most of what matters in music
+    pub fn freq_range(mut self, low_hz: u32, high_hz: u32) -> VisualizerConfig {
+        self.freq_range = Some((low_hz, high_hz));
+        self
+    }
+
+    // Capture the audio from a specific device (or a fifo) instead of cava's default
+    //
+    //   let config = VisualizerConfig::new(20).input(InputMethod::Pulse {
+    //       source: Some("alsa_output.usb-headset.analog-stereo.monitor".to_string()),
+    //   });
+    pub fn input(mut self, input: InputMethod) -> VisualizerConfig {
+        self.input = Some(input);
+        self
+    }
+
+    // Keep the last `frames` frames around (0 disables the history)
+    pub fn history_capacity(mut self, frames: usize) -> VisualizerConfig {
+        self.history_capacity = frames;
+        self
+    }
+
+    // A longer time constant makes the average level steadier but slower to react
+    pub fn level_time_constant(mut self, time_constant: Duration) -> VisualizerConfig {
+        self.level_time_constant = time_constant;
+        self
+    }
+
+    // Average every bar over this much time, whatever the frame rate is
+    // It's off (zero) unless set, since it makes the bars lag behind the sound
+    // by about that much. 50ms (3 frames at 60 fps) calms them down nicely
+    pub fn smoothing_time_constant(mut self, time_constant: Duration) -> VisualizerConfig {
+        self.smoothing_time_constant = time_constant;
+        self
+    }
+
+    // Put the bass on the left (cava's order), on the right or in the middle
+    pub fn ordering(mut self, ordering: Ordering) -> VisualizerConfig {
+        self.ordering = ordering;
+        self
+    }
+
+    // Go idle once the level stayed below `threshold` for `duration`
+    // Levels are between 0.0 and 1.0, like `Visualizer::level`
+    pub fn silence(mut self, threshold: f32, duration: Duration) -> VisualizerConfig {
+        self.silence_threshold = threshold;
+        self.silence_duration = duration;
+        self
+    }
+
+    // Once idle, wake up when the level goes above `threshold`
+    // Keep it a bit above the silence threshold, otherwise a level hovering
+    // around it keeps toggling between idle and active
+    pub fn wake_threshold(mut self, threshold: f32) -> VisualizerConfig {
+        self.wake_threshold = threshold;
+        self
+    }
+
+    // The same config with the sensitivity moved by `delta` percent, see
+    // `Visualizer::nudge_sensitivity`. It never goes below 1, and it's a manual
+    // sensitivity from then on, so autosens is off
+    pub(crate) fn nudged(&self, delta: i32) -> VisualizerConfig {
+        let current = self.sensitivity.unwrap_or(DEFAULT_SENSITIVITY);
+        let mut config = self.clone();
+        config.autosens = None;
+        config.sensitivity = Some(current.saturating_add_signed(delta).max(1));
+        config
+    }
+
+    pub fn bars(&self) -> usize {
+        self.bars
+    }
+
+    // Check the values against the ranges documented by cava
+    pub fn validate(&self) -> Result<(), VisualizerError> {
+        if self.bars == 0 {
+            return Err(invalid("bars must be at least 1".to_string()));
+        }
+
+        // Frames store the number of bars in a u16
+        if self.bars > u16::MAX as usize {
+            return Err(invalid(format!(
+                "bars must be at most {}, got {}",
+                u16::MAX,
+                self.bars
+            )));
+        }
+
+        if self.framerate == 0 {
+            return Err(invalid("framerate must be at least 1".to_string()));
+        }
+
+        // autosens would overwrite the manual sensitivity right away
+        if let (Some(true), Some(sensitivity)) = (self.autosens, self.sensitivity) {
+            return Err(invalid(format!(
+                "sensitivity ({}) can't be set together with autosens",
+                sensitivity
+            )));
+        }
+
+        if self.sensitivity == Some(0) {
+            return Err(invalid("sensitivity must be at least 1".to_string()));
+        }
+
+        if let Some(monstercat) = self.monstercat {
+            if !monstercat.is_finite() || monstercat < 0.0 {
+                return Err(invalid(format!(
+                    "monstercat must be a positive number, got {}",
+                    monstercat
+                )));
+            }
+        }
+
+        if let Some(noise_reduction) = self.noise_reduction {
+            if noise_reduction > 100 {
+                return Err(invalid(format!(
+                    "noise_reduction must be between 0 and 100, got {}",
+                    noise_reduction
+                )));
+            }
+        }
+
+        // cava doesn't complain about a bad range, it just draws nonsense
+        // so we have to be the ones catching it
+        if let Some((low, high)) = self.freq_range {
+            for freq in [low, high] {
+                if !(MIN_FREQ_HZ..=MAX_FREQ_HZ).contains(&freq) {
+                    return Err(invalid(format!(
+                        "frequency {} Hz is outside of {}..={} Hz",
+                        freq, MIN_FREQ_HZ, MAX_FREQ_HZ
+                    )));
+                }
+            }
+
+            if low >= high {
+                return Err(invalid(format!(
+                    "lower cutoff ({} Hz) must be below the higher cutoff ({} Hz)",
+                    low, high
+                )));
+            }
+        }
+
+        if let Some(input) = &self.input {
+            if let InputMethod::Fifo { sample_rate: 0, .. } = input {
+                return Err(invalid("fifo sample rate must be at least 1".to_string()));
+            }
+
+            // A line break in the source would let it write its own keys into the config
+            if let Some(source) = input.source() {
+                if source.is_empty() || source.contains(['\n', '\r']) {
+                    return Err(invalid(format!("invalid input source {:?}", source)));
+                }
+            }
+        }
+
+        for (name, threshold) in [
+            ("silence threshold", self.silence_threshold),
+            ("wake threshold", self.wake_threshold),
+        ] {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(invalid(format!(
+                    "{} must be between 0.0 and 1.0, got {}",
+                    name, threshold
+                )));
+            }
+        }
+
+        if self.wake_threshold < self.silence_threshold {
+            return Err(invalid(format!(
+                "wake threshold ({}) must not be below the silence threshold ({})",
+                self.wake_threshold, self.silence_threshold
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Render the INI file we hand to cava with `-p`
+    // You can play around with the configuration to get different effects
+    pub fn to_cava_config(&self) -> String {
+        let mut ini = String::new();
+
+        // Writing into a String can't fail, so the unwraps below never panic
+        writeln!(ini, "[general]").unwrap();
+        writeln!(ini, "bars = {}", self.bars).unwrap();
+        writeln!(ini, "framerate = {}", self.framerate).unwrap();
+        if let Some(sensitivity) = self.sensitivity {
+            // validate() rejects autosens(true) together with a sensitivity,
+            // so a manual sensitivity always means autosens is off
+            writeln!(ini, "autosens = 0").unwrap();
+            writeln!(ini, "sensitivity = {}", sensitivity).unwrap();
+        } else if let Some(autosens) = self.autosens {
+            writeln!(ini, "autosens = {}", autosens as u8).unwrap();
+        }
+        if let Some((low, high)) = self.freq_range {
+            writeln!(ini, "lower_cutoff_freq = {}", low).unwrap();
+            writeln!(ini, "higher_cutoff_freq = {}", high).unwrap();
+        }
+
+        if let Some(input) = &self.input {
+            writeln!(ini).unwrap();
+            input.write_ini(&mut ini);
+        }
+
+        // We want cava to write raw 16 bit numbers to stdout so we can read them
+        writeln!(ini).unwrap();
+        writeln!(ini, "[output]").unwrap();
+        writeln!(ini, "method = raw").unwrap();
+        writeln!(ini, "channels = mono").unwrap();
+        writeln!(ini, "raw_target = /dev/stdout").unwrap();
+        writeln!(ini, "data_format = binary").unwrap();
+        writeln!(ini, "bit_format = 16bit").unwrap();
+
+        writeln!(ini).unwrap();
+        writeln!(ini, "[smoothing]").unwrap();
+        writeln!(ini, "integral = 70").unwrap();
+        if let Some(monstercat) = self.monstercat {
+            writeln!(ini, "monstercat = {}", monstercat).unwrap();
+        }
+        if let Some(waves) = self.waves {
+            writeln!(ini, "waves = {}", waves as u8).unwrap();
+        }
+        if let Some(noise_reduction) = self.noise_reduction {
+            writeln!(ini, "noise_reduction = {}", noise_reduction).unwrap();
+        }
+        writeln!(ini, "gravity = 100").unwrap();
+
+        ini
+    }
+}
+
+fn invalid(reason: String) -> VisualizerError {
+    VisualizerError::InvalidConfig(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The reason validate() gives, panics if the config is accepted
+    fn rejection(config: VisualizerConfig) -> String {
+        match config.validate() {
+            Err(VisualizerError::InvalidConfig(reason)) => reason,
+            other => panic!("expected InvalidConfig, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn the_defaults_are_valid() {
+        assert!(VisualizerConfig::new(1).validate().is_ok());
+        assert!(VisualizerConfig::new(u16::MAX as usize).validate().is_ok());
+    }
+
+    // One line per rule, each config breaks exactly that one
+    #[test]
+    fn every_out_of_range_field_is_rejected() {
+        let config = || VisualizerConfig::new(20);
+        for (config, reason) in [
+            (
+                VisualizerConfig::new(0),
+                "bars must be at least 1".to_string(),
+            ),
+            (
+                VisualizerConfig::new(70_000),
+                "bars must be at most 65535, got 70000".to_string(),
+            ),
+            (
+                config().framerate(0),
+                "framerate must be at least 1".to_string(),
+            ),
+            (
+                config().sensitivity(0),
+                "sensitivity must be at least 1".to_string(),
+            ),
+            (
+                config().noise_reduction(101),
+                "noise_reduction must be between 0 and 100, got 101".to_string(),
+            ),
+            (
+                config().silence(-0.1, Duration::from_secs(2)),
+                "silence threshold must be between 0.0 and 1.0, got -0.1".to_string(),
+            ),
+            (
+                config()
+                    .silence(0.01, Duration::from_secs(2))
+                    .wake_threshold(1.5),
+                "wake threshold must be between 0.0 and 1.0, got 1.5".to_string(),
+            ),
+            (
+                config()
+                    .silence(0.3, Duration::from_secs(2))
+                    .wake_threshold(0.2),
+                "wake threshold (0.2) must not be below the silence threshold (0.3)".to_string(),
+            ),
+        ] {
+            assert_eq!(rejection(config), reason);
+        }
+    }
+
+    #[test]
+    fn a_nan_threshold_is_rejected() {
+        let config = VisualizerConfig::new(20).silence(f32::NAN, Duration::from_secs(2));
+        assert!(rejection(config).starts_with("silence threshold must be between"));
+    }
+
+    #[test]
+    fn a_valid_freq_range_is_accepted() {
+        for (low, high) in [(50, 10_000), (MIN_FREQ_HZ, MAX_FREQ_HZ), (20, 21)] {
+            assert!(VisualizerConfig::new(20)
+                .freq_range(low, high)
+                .validate()
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn a_bad_freq_range_is_rejected() {
+        for (low, high, reason) in [
+            (19, 10_000, "frequency 19 Hz is outside of 20..=22050 Hz"),
+            (50, 22_051, "frequency 22051 Hz is outside of 20..=22050 Hz"),
+            (0, 0, "frequency 0 Hz is outside of 20..=22050 Hz"),
+            (
+                10_000,
+                50,
+                "lower cutoff (10000 Hz) must be below the higher cutoff (50 Hz)",
+            ),
+            (
+                440,
+                440,
+                "lower cutoff (440 Hz) must be below the higher cutoff (440 Hz)",
+            ),
+        ] {
+            let config = VisualizerConfig::new(20).freq_range(low, high);
+            assert_eq!(rejection(config), reason);
+        }
+    }
+
+    #[test]
+    fn the_freq_range_goes_in_the_general_section() {
+        let ini = VisualizerConfig::new(20)
+            .freq_range(50, 10_000)
+            .to_cava_config();
+        assert!(ini.starts_with(
+            "[general]\nbars = 20\nframerate = 60\nlower_cutoff_freq = 50\nhigher_cutoff_freq = 10000\n\n"
+        ));
+        assert!(!VisualizerConfig::new(20)
+            .to_cava_config()
+            .contains("cutoff"));
+    }
+
+    // The [general] section, up to the empty line after it
+    fn general(config: &VisualizerConfig) -> String {
+        let ini = config.to_cava_config();
+        ini[..ini.find("\n\n").unwrap() + 1].to_string()
+    }
+
+    #[test]
+    fn autosens_is_left_to_cava_unless_set() {
+        let ini = general(&VisualizerConfig::new(20));
+        assert!(!ini.contains("autosens"), "{}", ini);
+        assert!(!ini.contains("sensitivity"), "{}", ini);
+    }
+
+    #[test]
+    fn autosens_goes_in_the_general_section() {
+        assert_eq!(
+            general(&VisualizerConfig::new(20).autosens(true)),
+            "[general]\nbars = 20\nframerate = 60\nautosens = 1\n"
+        );
+        assert_eq!(
+            general(&VisualizerConfig::new(20).autosens(false)),
+            "[general]\nbars = 20\nframerate = 60\nautosens = 0\n"
+        );
+    }
+
+    #[test]
+    fn a_sensitivity_turns_autosens_off() {
+        let expected = "[general]\nbars = 20\nframerate = 60\nautosens = 0\nsensitivity = 150\n";
+        let config = VisualizerConfig::new(20).sensitivity(150);
+        assert!(config.validate().is_ok());
+        assert_eq!(general(&config), expected);
+        // Saying so is fine too
+        let config = VisualizerConfig::new(20).autosens(false).sensitivity(150);
+        assert!(config.validate().is_ok());
+        assert_eq!(general(&config), expected);
+    }
+
+    #[test]
+    fn autosens_and_a_sensitivity_are_rejected_together() {
+        for config in [
+            VisualizerConfig::new(20).autosens(true).sensitivity(150),
+            VisualizerConfig::new(20).sensitivity(150).autosens(true),
+        ] {
+            assert_eq!(
+                rejection(config),
+                "sensitivity (150) can't be set together with autosens"
+            );
+        }
+    }
+
+    #[test]
+    fn a_nudge_starts_from_cavas_default() {
+        let nudged = VisualizerConfig::new(20).nudged(10);
+        assert_eq!(nudged.sensitivity, Some(DEFAULT_SENSITIVITY + 10));
+        assert_eq!(nudged.autosens, None);
+        let nudged = VisualizerConfig::new(20).sensitivity(50).nudged(-20);
+        assert_eq!(nudged.sensitivity, Some(30));
+        // The rest of the config is kept
+        assert_eq!(nudged.bars, 20);
+    }
+
+    #[test]
+    fn a_nudge_turns_autosens_off() {
+        let nudged = VisualizerConfig::new(20).autosens(true).nudged(5);
+        assert!(nudged.validate().is_ok());
+        assert_eq!(
+            general(&nudged),
+            "[general]\nbars = 20\nframerate = 60\nautosens = 0\nsensitivity = 105\n"
+        );
+    }
+
+    #[test]
+    fn a_nudge_stays_within_range() {
+        let nudged = VisualizerConfig::new(20).nudged(-1000);
+        assert_eq!(nudged.sensitivity, Some(1));
+        assert!(nudged.validate().is_ok());
+        let nudged = VisualizerConfig::new(20).sensitivity(u32::MAX).nudged(10);
+        assert_eq!(nudged.sensitivity, Some(u32::MAX));
+    }
+
+    // Everything after [general], which only depends on the smoothing options
+    fn output_and_smoothing(config: &VisualizerConfig) -> String {
+        let ini = config.to_cava_config();
+        ini[ini.find("[output]").unwrap()..].to_string()
+    }
+
+    #[test]
+    fn unset_smoothing_options_are_left_to_cava() {
+        assert_eq!(
+            output_and_smoothing(&VisualizerConfig::new(20)),
+            "[output]\n\
+             method = raw\n\
+             channels = mono\n\
+             raw_target = /dev/stdout\n\
+             data_format = binary\n\
+             bit_format = 16bit\n\
+             \n\
+             [smoothing]\n\
+             integral = 70\n\
+             gravity = 100\n"
+        );
+    }
+
+    #[test]
+    fn the_smoothing_options_are_written_when_set() {
+        let config = VisualizerConfig::new(20)
+            .monstercat(1.5)
+            .waves(true)
+            .noise_reduction(77);
+        assert!(output_and_smoothing(&config).ends_with(
+            "[smoothing]\n\
+             integral = 70\n\
+             monstercat = 1.5\n\
+             waves = 1\n\
+             noise_reduction = 77\n\
+             gravity = 100\n"
+        ));
+
+        let config = VisualizerConfig::new(20).monstercat(0.0).waves(false);
+        assert!(output_and_smoothing(&config).ends_with(
+            "[smoothing]\n\
+             integral = 70\n\
+             monstercat = 0\n\
+             waves = 0\n\
+             gravity = 100\n"
+        ));
+
+        let config = VisualizerConfig::new(20).noise_reduction(0);
+        assert!(output_and_smoothing(&config).ends_with(
+            "[smoothing]\n\
+             integral = 70\n\
+             noise_reduction = 0\n\
+             gravity = 100\n"
+        ));
+    }
+
+    #[test]
+    fn smoothing_options_outside_of_cavas_ranges_are_rejected() {
+        for (config, reason) in [
+            (
+                VisualizerConfig::new(20).monstercat(-1.0),
+                "monstercat must be a positive number, got -1",
+            ),
+            (
+                VisualizerConfig::new(20).monstercat(f32::INFINITY),
+                "monstercat must be a positive number, got inf",
+            ),
+            (
+                VisualizerConfig::new(20).noise_reduction(255),
+                "noise_reduction must be between 0 and 100, got 255",
+            ),
+        ] {
+            assert_eq!(rejection(config), reason);
+        }
+        assert!(VisualizerConfig::new(20)
+            .noise_reduction(100)
+            .validate()
+            .is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+use std::{fmt, io, path::PathBuf};
+
+use super::cava::CavaVersion;
+
+// Everything that can go wrong while setting up the visualizer
+#[derive(Debug)]
+pub enum VisualizerError {
+    // The configuration has a value cava would not accept (or would silently misbehave with)
+    InvalidConfig(String),
+    // Writing the cava config file or spawning the process failed
+    Io(io::Error),
+    // There is no cava at this path (or in the PATH)
+    CavaNotFound(PathBuf),
+    // cava is there, but too old for the output format we need
+    CavaTooOld {
+        found: CavaVersion,
+        required: CavaVersion,
+    },
+    // The visualizer doesn't read from cava, so there is nothing to restart
+    NotRestartable,
+}
+
+impl fmt::Display for VisualizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VisualizerError::InvalidConfig(reason) => {
+                write!(f, "invalid configuration: {}", reason)
+            }
+            VisualizerError::Io(e) => write!(f, "i/o error: {}", e),
+            VisualizerError::CavaNotFound(path) => {
+                write!(f, "cava not found at {}", path.display())
+            }
+            VisualizerError::CavaTooOld { found, required } => {
+                write!(
+                    f,
+                    "cava {} is too old, {} or newer is required",
+                    found, required
+                )
+            }
+            VisualizerError::NotRestartable => {
+                write!(f, "the visualizer is not reading from cava")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VisualizerError {}
+
+// io::Error can't be cloned, so we make a new one with the same kind and message
+// That's all anyone looking at `Visualizer::last_error` needs from it
+impl Clone for VisualizerError {
+    fn clone(&self) -> VisualizerError {
+        match self {
+            VisualizerError::InvalidConfig(reason) => {
+                VisualizerError::InvalidConfig(reason.clone())
+            }
+            VisualizerError::Io(e) => VisualizerError::Io(io::Error::new(e.kind(), e.to_string())),
+            VisualizerError::CavaNotFound(path) => VisualizerError::CavaNotFound(path.clone()),
+            VisualizerError::CavaTooOld { found, required } => VisualizerError::CavaTooOld {
+                found: *found,
+                required: *required,
+            },
+            VisualizerError::NotRestartable => VisualizerError::NotRestartable,
+        }
+    }
+}
+
+// This lets us use `?` on io results inside functions returning a VisualizerError
+impl From<io::Error> for VisualizerError {
+    fn from(e: io::Error) -> VisualizerError {
+        VisualizerError::Io(e)
+    }
+}
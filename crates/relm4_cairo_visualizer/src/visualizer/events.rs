@@ -0,0 +1,210 @@
+use std::{
+    sync::mpsc::{Receiver, Sender},
+    time::Duration,
+};
+
+// Below this level a frame counts as silent
+pub const DEFAULT_SILENCE_THRESHOLD: f32 = 0.01;
+// Once idle, the level has to go above this to count as sound again
+// It's higher than the silence threshold so a level hovering around it doesn't flip-flop
+pub const DEFAULT_WAKE_THRESHOLD: f32 = 0.02;
+// How long it has to stay silent before we call it idle
+pub const DEFAULT_SILENCE_DURATION: Duration = Duration::from_secs(2);
+
+// Things that happen to the visualizer as a whole, as opposed to the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisualizerEvent {
+    // Nothing has been playing for a while, the UI can dim or stop redrawing
+    Idle,
+    // Sound is back
+    Active,
+}
+
+// Decides when the visualizer goes idle and when it wakes up again
+//
+// There are two kinds of hysteresis here, so brief quiet passages in a song don't
+// toggle it all the time:
+// - in time: the level has to stay below the silence threshold for `duration`
+//   before we go idle, a short pause just resets the countdown once the sound is back
+// - in level: going idle happens below `silence_threshold` but waking up needs the
+//   (higher) `wake_threshold`, so some noise right at the threshold doesn't wake us up
+pub struct SilenceDetector {
+    silence_threshold: f32,
+    wake_threshold: f32,
+    duration: Duration,
+    // How long the level has been below the silence threshold
+    quiet_for: Duration,
+    idle: bool,
+}
+
+impl SilenceDetector {
+    pub fn new(silence_threshold: f32, wake_threshold: f32, duration: Duration) -> SilenceDetector {
+        SilenceDetector {
+            silence_threshold,
+            wake_threshold,
+            duration,
+            quiet_for: Duration::ZERO,
+            idle: false,
+        }
+    }
+
+    // Feed the level of a frame and the time since the previous one
+    // Returns an event only when the state changes
+    pub fn update(&mut self, level: f32, elapsed: Duration) -> Option<VisualizerEvent> {
+        if self.idle {
+            if level >= self.wake_threshold {
+                self.idle = false;
+                self.quiet_for = Duration::ZERO;
+                return Some(VisualizerEvent::Active);
+            }
+            return None;
+        }
+
+        if level >= self.silence_threshold {
+            self.quiet_for = Duration::ZERO;
+            return None;
+        }
+
+        self.quiet_for += elapsed;
+        if self.quiet_for >= self.duration {
+            self.idle = true;
+            return Some(VisualizerEvent::Idle);
+        }
+        None
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+}
+
+// Lives in the reader thread and hands every event to everybody who called
+// `Visualizer::events`, the same way the Broadcaster does for frames
+// Events are rare, so the channels are unbounded and nobody ever misses one
+pub(crate) struct EventSubscribers {
+    senders: Vec<Sender<VisualizerEvent>>,
+    new_senders: Receiver<Sender<VisualizerEvent>>,
+}
+
+impl EventSubscribers {
+    pub(crate) fn new(new_senders: Receiver<Sender<VisualizerEvent>>) -> EventSubscribers {
+        EventSubscribers {
+            senders: Vec::new(),
+            new_senders,
+        }
+    }
+
+    pub(crate) fn send(&mut self, event: VisualizerEvent) {
+        // Once the handle is gone nobody can subscribe anymore, but the existing
+        // receivers still get their events
+        while let Ok(sender) = self.new_senders.try_recv() {
+            self.senders.push(sender);
+        }
+
+        // Forget the receivers that were dropped
+        self.senders.retain(|sender| sender.send(event).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    fn detector() -> SilenceDetector {
+        SilenceDetector::new(0.1, 0.2, Duration::from_millis(500))
+    }
+
+    // The events for a sequence of levels, one frame apart
+    fn events(detector: &mut SilenceDetector, levels: &[f32]) -> Vec<Option<VisualizerEvent>> {
+        levels
+            .iter()
+            .map(|&level| detector.update(level, FRAME))
+            .collect()
+    }
+
+    #[test]
+    fn goes_idle_after_the_duration() {
+        let mut detector = detector();
+        let events = events(&mut detector, &[0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(
+            events,
+            [
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(VisualizerEvent::Idle),
+                None
+            ]
+        );
+        assert!(detector.is_idle());
+    }
+
+    #[test]
+    fn a_short_pause_resets_the_countdown() {
+        let mut detector = detector();
+        let levels = [0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0];
+        assert!(events(&mut detector, &levels).iter().all(Option::is_none));
+        assert!(!detector.is_idle());
+        assert_eq!(detector.update(0.0, FRAME), Some(VisualizerEvent::Idle));
+    }
+
+    // Between the two thresholds doesn't wake it up
+    #[test]
+    fn waking_up_needs_the_wake_threshold() {
+        let mut detector = detector();
+        detector.update(0.0, Duration::from_secs(1));
+        assert!(detector.is_idle());
+
+        assert_eq!(events(&mut detector, &[0.15, 0.19, 0.1]), [None; 3]);
+        assert_eq!(detector.update(0.2, FRAME), Some(VisualizerEvent::Active));
+        assert!(!detector.is_idle());
+    }
+
+    // Once awake it's the silence threshold again, and a fresh countdown
+    #[test]
+    fn idle_active_idle() {
+        let mut detector = detector();
+        let mut levels = vec![0.0; 5];
+        levels.push(0.9);
+        levels.extend([0.15; 5]);
+        levels.extend([0.05; 5]);
+        let changes: Vec<_> = events(&mut detector, &levels)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, event)| Some((i, event?)))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (4, VisualizerEvent::Idle),
+                (5, VisualizerEvent::Active),
+                (15, VisualizerEvent::Idle),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_subscriber_gets_every_event() {
+        let (subscribe, new_senders) = mpsc::channel();
+        let mut subscribers = EventSubscribers::new(new_senders);
+        let (first, first_rx) = mpsc::channel();
+        let (second, second_rx) = mpsc::channel();
+        subscribe.send(first).unwrap();
+        subscribe.send(second).unwrap();
+
+        subscribers.send(VisualizerEvent::Idle);
+        drop(second_rx);
+        drop(subscribe);
+        subscribers.send(VisualizerEvent::Active);
+
+        let received: Vec<_> = first_rx.try_iter().collect();
+        assert_eq!(received, [VisualizerEvent::Idle, VisualizerEvent::Active]);
+        assert_eq!(subscribers.senders.len(), 1);
+    }
+}
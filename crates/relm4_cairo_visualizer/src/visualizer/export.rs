@@ -0,0 +1,101 @@
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use super::Frame;
+
+// Flush the rows to the writer at least every that many frames (about a second at 60 fps)
+// so an interrupted capture still leaves a usable file behind
+pub const FLUSH_EVERY: u64 = 60;
+
+// When a capture stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureLimit {
+    // Stop once a frame arrives this long after the first one
+    Duration(Duration),
+    // Stop after writing this many frames
+    Frames(u64),
+}
+
+// Writes frames as CSV, one row per frame, for a spreadsheet or a Python notebook:
+//
+//   timestamp_ms,bar_0,bar_1,bar_2
+//   0,120,3400,65535
+//   16,118,3390,60012
+//
+// The timestamp is the time since the first frame of the capture. If the number of
+// bars changes in the middle of a capture, a blank line and a new header start a new
+// section, so every section can be loaded as its own table
+pub struct CsvSink<W: Write> {
+    writer: W,
+    limit: CaptureLimit,
+    // When the first frame was captured, None until we got one
+    started_at: Option<Instant>,
+    // The number of bars of the current section
+    bars: Option<usize>,
+    frames_written: u64,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W, limit: CaptureLimit) -> CsvSink<W> {
+        CsvSink {
+            writer,
+            limit,
+            started_at: None,
+            bars: None,
+            frames_written: 0,
+        }
+    }
+
+    // Write one frame, returns false (without writing it) once the limit is reached
+    pub fn write_frame(&mut self, frame: &Frame) -> io::Result<bool> {
+        let started_at = *self.started_at.get_or_insert(frame.captured_at);
+        let elapsed = frame.captured_at.saturating_duration_since(started_at);
+
+        let done = match self.limit {
+            CaptureLimit::Duration(duration) => elapsed > duration,
+            CaptureLimit::Frames(frames) => self.frames_written >= frames,
+        };
+        if done {
+            self.writer.flush()?;
+            return Ok(false);
+        }
+
+        if self.bars != Some(frame.values.len()) {
+            // Separate the sections with a blank line, but don't start the file with one
+            if self.bars.is_some() {
+                writeln!(self.writer)?;
+            }
+            write!(self.writer, "timestamp_ms")?;
+            for bar in 0..frame.values.len() {
+                write!(self.writer, ",bar_{}", bar)?;
+            }
+            writeln!(self.writer)?;
+            self.bars = Some(frame.values.len());
+        }
+
+        write!(self.writer, "{}", elapsed.as_millis())?;
+        for value in &frame.values {
+            write!(self.writer, ",{}", value)?;
+        }
+        writeln!(self.writer)?;
+
+        self.frames_written += 1;
+        if self.frames_written.is_multiple_of(FLUSH_EVERY) {
+            self.writer.flush()?;
+        }
+
+        Ok(true)
+    }
+
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    // Flush what's left and give the writer back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
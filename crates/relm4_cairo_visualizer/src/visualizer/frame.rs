@@ -0,0 +1,94 @@
+use std::{ops::Deref, time::Instant};
+
+// How the values of a frame are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    // One value per bar
+    Mono,
+    // The bars of the left channel followed by the bars of the right channel
+    Stereo,
+}
+
+// A single frame of bar values as it leaves the visualizer
+#[derive(Debug, Clone)]
+pub struct Frame {
+    // Increases by one for every frame, so consumers can spot gaps
+    pub seq: u64,
+    // When the frame was read from the audio source
+    pub captured_at: Instant,
+    // How many bars the frame describes (per channel)
+    pub bars: u16,
+    pub layout: ChannelLayout,
+    pub values: Vec<u16>,
+}
+
+impl Frame {
+    // A mono frame made from bare values, for code that only has a Vec<u16> at hand
+    // (tests, the synthetic demos...). It gets the sequence number 0
+    pub fn mono(values: Vec<u16>) -> Frame {
+        Frame {
+            seq: 0,
+            captured_at: Instant::now(),
+            bars: values.len() as u16,
+            layout: ChannelLayout::Mono,
+            values,
+        }
+    }
+}
+
+// Lets a frame be used like a slice of values:
+//
+//   for &value in frame.iter() { ... }
+//   let loudest = frame.iter().max();
+//   let first = frame[0];
+impl Deref for Frame {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.values
+    }
+}
+
+// So code written when frames were a bare Vec<u16> keeps compiling with a `.into()`
+impl From<Vec<u16>> for Frame {
+    fn from(values: Vec<u16>) -> Frame {
+        Frame::mono(values)
+    }
+}
+
+impl From<Frame> for Vec<u16> {
+    fn from(frame: Frame) -> Vec<u16> {
+        frame.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_mono_frame_from_values() {
+        let frame = Frame::mono(vec![1, 2, 3]);
+        assert_eq!((frame.seq, frame.bars), (0, 3));
+        assert_eq!(frame.layout, ChannelLayout::Mono);
+        assert_eq!(frame.values, [1, 2, 3]);
+    }
+
+    #[test]
+    fn a_frame_reads_like_a_slice() {
+        let frame = Frame::mono(vec![4, 9, 2]);
+        assert_eq!(frame[1], 9);
+        assert_eq!(frame.len(), 3);
+        assert_eq!(frame.iter().max(), Some(&9));
+        let slice: &[u16] = &frame;
+        assert_eq!(slice, [4, 9, 2]);
+    }
+
+    #[test]
+    fn conversions_to_and_from_a_vec() {
+        let frame: Frame = vec![5, 6].into();
+        assert_eq!((frame.bars, frame.layout), (2, ChannelLayout::Mono));
+        let values: Vec<u16> = frame.into();
+        assert_eq!(values, [5, 6]);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    io::{self, Read},
+    time::Duration,
+};
+
+// How many implausible frames in a row make us think we lost the frame boundaries
+pub const RESYNC_AFTER: u32 = 3;
+
+// How long to wait before reading again when a non blocking reader has nothing for us
+const WOULD_BLOCK_WAIT: Duration = Duration::from_millis(1);
+
+// How much we ask the reader for at once
+const CHUNK_SIZE: usize = 4096;
+
+// Cuts a stream of bytes into frames
+//
+// A pipe doesn't know about our frames: a read can return half a frame, or a frame
+// and a half, so we collect the bytes in a buffer and only hand out complete frames.
+//
+// If we ever lose a byte, every value after that is made of the high byte of one bar
+// and the low byte of the next one, and the frames are garbage forever. That tends to
+// show up as values stuck at exactly 0 or exactly u16::MAX, so when that happens for a
+// few frames in a row we drop one byte and try again from there
+pub struct FrameReader<R> {
+    reader: R,
+    // Bytes we read but didn't hand out yet
+    pending: Vec<u8>,
+    // How many implausible frames we got in a row
+    implausible_streak: u32,
+    // Resyncs since the last `take_resyncs`
+    resyncs: u64,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(reader: R) -> FrameReader<R> {
+        FrameReader {
+            reader,
+            pending: Vec::with_capacity(CHUNK_SIZE),
+            implausible_streak: 0,
+            resyncs: 0,
+        }
+    }
+
+    // Fill `buf` with the next frame, the frame size is the size of `buf`
+    // An `UnexpectedEof` error means the stream ended (a partial frame at the end is lost)
+    pub fn read_frame(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let frame_len = buf.len();
+
+        loop {
+            if self.pending.len() >= frame_len {
+                if is_implausible(&self.pending[..frame_len]) {
+                    self.implausible_streak += 1;
+                    if self.implausible_streak >= RESYNC_AFTER {
+                        // Shift everything by one byte and look at the next frame again
+                        self.pending.remove(0);
+                        self.implausible_streak = 0;
+                        self.resyncs += 1;
+                        continue;
+                    }
+                } else {
+                    self.implausible_streak = 0;
+                }
+
+                buf.copy_from_slice(&self.pending[..frame_len]);
+                self.pending.drain(..frame_len);
+                return Ok(());
+            }
+
+            let mut chunk = [0_u8; CHUNK_SIZE];
+            match self.reader.read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.pending.extend_from_slice(&chunk[..n]),
+                // A signal interrupted the read, nothing was lost, just read again
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // Nothing to read yet on a non blocking reader
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(WOULD_BLOCK_WAIT);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // How many times we resynced since the last call
+    pub fn take_resyncs(&mut self) -> u64 {
+        std::mem::take(&mut self.resyncs)
+    }
+}
+
+// A frame where every value is exactly 0 or exactly u16::MAX, with both showing up
+// Real audio (even silence, which is all zeros) practically never looks like that
+fn is_implausible(frame: &[u8]) -> bool {
+    let mut zeros = false;
+    let mut maxes = false;
+    for pair in frame.chunks_exact(2) {
+        match u16::from_le_bytes([pair[0], pair[1]]) {
+            0 => zeros = true,
+            u16::MAX => maxes = true,
+            _ => return false,
+        }
+    }
+    zeros && maxes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // Hands out the bytes in the chunks it's given, with the errors in between
+    struct Chunked(VecDeque<io::Result<Vec<u8>>>);
+
+    impl Chunked {
+        fn new(chunks: Vec<io::Result<Vec<u8>>>) -> Chunked {
+            Chunked(chunks.into())
+        }
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Ok(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                Some(Err(e)) => Err(e),
+                None => Ok(0),
+            }
+        }
+    }
+
+    fn frames<R: Read>(reader: &mut FrameReader<R>, bars: usize) -> Vec<Vec<u16>> {
+        let mut buf = vec![0; bars * 2];
+        let mut frames = Vec::new();
+        while reader.read_frame(&mut buf).is_ok() {
+            frames.push(crate::visualizer::decode(&buf));
+        }
+        frames
+    }
+
+    fn bytes(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn frames_are_put_together_from_short_reads() {
+        let stream = bytes(&[1, 2, 3, 4, 5, 6]);
+        let chunks = stream.chunks(1).map(|c| Ok(c.to_vec())).collect();
+        let mut reader = FrameReader::new(Chunked::new(chunks));
+
+        assert_eq!(frames(&mut reader, 2), [[1, 2], [3, 4], [5, 6]]);
+    }
+
+    #[test]
+    fn a_read_can_hold_several_frames() {
+        let chunks = vec![Ok(bytes(&[1, 2, 3])), Ok(bytes(&[4, 5, 6, 7]))];
+        let mut reader = FrameReader::new(Chunked::new(chunks));
+
+        // The 7 on its own is half a frame, lost at the end
+        assert_eq!(frames(&mut reader, 2), [[1, 2], [3, 4], [5, 6]]);
+    }
+
+    #[test]
+    fn interrupted_and_would_block_are_retried() {
+        let chunks = vec![
+            Ok(bytes(&[1])),
+            Err(io::ErrorKind::Interrupted.into()),
+            Ok(bytes(&[2])),
+            Err(io::ErrorKind::WouldBlock.into()),
+            Ok(bytes(&[3, 4])),
+        ];
+        let mut reader = FrameReader::new(Chunked::new(chunks));
+
+        assert_eq!(frames(&mut reader, 2), [[1, 2], [3, 4]]);
+    }
+
+    #[test]
+    fn other_errors_and_the_end_of_the_stream_are_reported() {
+        let chunks = vec![Ok(bytes(&[1])), Err(io::ErrorKind::BrokenPipe.into())];
+        let mut reader = FrameReader::new(Chunked::new(chunks));
+        let mut buf = [0; 4];
+        let error = reader.read_frame(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        let error = reader.read_frame(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn a_misaligned_stream_recovers_after_resync_after_frames() {
+        // Read one byte off, 0xFF00 and 0x00FF come out as 0 and u16::MAX
+        let mut stream = vec![0];
+        for _ in 0..5 {
+            stream.extend(bytes(&[0xFF00, 0x00FF]));
+        }
+        let mut reader = FrameReader::new(stream.as_slice());
+
+        let frames = frames(&mut reader, 2);
+        let garbage = RESYNC_AFTER as usize - 1;
+        assert!(frames[..garbage].iter().all(|f| f == &[0, u16::MAX]));
+        // One byte dropped, and the rest are the real frames (the ones read while
+        // misaligned are lost)
+        assert_eq!(frames[garbage..], [[0xFF00, 0x00FF]; 3]);
+        assert_eq!(reader.take_resyncs(), 1);
+        assert_eq!(reader.take_resyncs(), 0);
+    }
+
+    #[test]
+    fn one_odd_frame_is_not_a_misalignment() {
+        let mut values = vec![];
+        for _ in 0..3 {
+            values.extend([0, u16::MAX]);
+            values.extend([5, 6]);
+        }
+        let mut reader = FrameReader::new(Chunked::new(vec![Ok(bytes(&values))]));
+
+        assert_eq!(frames(&mut reader, 2).len(), 6);
+        assert_eq!(reader.take_resyncs(), 0);
+    }
+
+    // All zeros, misaligned or not, is only silence: it passes through as it is
+    #[test]
+    fn silence_is_never_resynced() {
+        let mut stream = vec![0];
+        stream.extend(bytes(&[0; 20]));
+        let mut reader = FrameReader::new(stream.as_slice());
+
+        assert_eq!(frames(&mut reader, 2), [[0, 0]; 10]);
+        assert_eq!(reader.take_resyncs(), 0);
+    }
+
+    #[test]
+    fn a_frame_at_the_maximum_is_plausible() {
+        assert!(!is_implausible(&bytes(&[u16::MAX; 4])));
+        assert!(!is_implausible(&bytes(&[0; 4])));
+        assert!(!is_implausible(&bytes(&[0, u16::MAX, 1])));
+        assert!(is_implausible(&bytes(&[u16::MAX, 0, 0])));
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    process::Child,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::VisualizerError;
+
+// A snapshot of how the audio source is doing, see `Visualizer::health`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    // The cava process id, None when the frames don't come from cava
+    pub pid: Option<u32>,
+    // Whether cava is still running (or the reader thread, for other sources)
+    pub running: bool,
+    // How long ago the last frame arrived, None until we got the first one
+    pub since_last_frame: Option<Duration>,
+    // How many times cava was started again (see `Visualizer::restart`)
+    pub restarts: u64,
+    // The last thing cava complained about
+    pub last_stderr_line: Option<String>,
+}
+
+// The cava process, shared between the CavaSource reading from it and the handle
+// asking how it is doing. Only the process itself is behind the lock, its stdout
+// belongs to the source, so checking on it never waits for a frame
+pub struct ProcessMonitor {
+    pid: u32,
+    child: Mutex<Child>,
+    last_stderr_line: Mutex<Option<String>>,
+}
+
+impl ProcessMonitor {
+    pub(crate) fn new(child: Child) -> ProcessMonitor {
+        ProcessMonitor {
+            pid: child.id(),
+            child: Mutex::new(child),
+            last_stderr_line: Mutex::new(None),
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    // `try_wait` only asks the OS whether the process exited, it never blocks
+    // Once it did, the exit status is remembered so asking again is just as cheap
+    pub fn is_running(&self) -> bool {
+        matches!(self.child.lock().unwrap().try_wait(), Ok(None))
+    }
+
+    pub fn last_stderr_line(&self) -> Option<String> {
+        self.last_stderr_line.lock().unwrap().clone()
+    }
+
+    pub(crate) fn record_stderr_line(&self, line: String) {
+        *self.last_stderr_line.lock().unwrap() = Some(line);
+    }
+
+    // Stop the process and wait for it, so it doesn't linger as a zombie
+    pub(crate) fn kill(&self) {
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+// Everything `Visualizer::health` and `Visualizer::last_error` report on
+// The reader thread writes the frame time and its errors, the handle writes the rest
+#[derive(Default)]
+pub(crate) struct HealthState {
+    process: Mutex<Option<Arc<ProcessMonitor>>>,
+    last_frame_at: Mutex<Option<Instant>>,
+    restarts: AtomicU64,
+    last_error: Mutex<Option<VisualizerError>>,
+}
+
+impl HealthState {
+    pub(crate) fn new(process: Option<Arc<ProcessMonitor>>) -> HealthState {
+        HealthState {
+            process: Mutex::new(process),
+            ..HealthState::default()
+        }
+    }
+
+    pub(crate) fn record_frame(&self, captured_at: Instant) {
+        *self.last_frame_at.lock().unwrap() = Some(captured_at);
+    }
+
+    pub(crate) fn record_error(&self, error: VisualizerError) {
+        *self.last_error.lock().unwrap() = Some(error);
+    }
+
+    // A new cava took over
+    pub(crate) fn record_restart(&self, process: Arc<ProcessMonitor>) {
+        *self.process.lock().unwrap() = Some(process);
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn last_error(&self) -> Option<VisualizerError> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    // `reader_running` is only used when there is no process to ask
+    pub(crate) fn snapshot(&self, reader_running: bool) -> Health {
+        let process = self.process.lock().unwrap().clone();
+        let since_last_frame = self
+            .last_frame_at
+            .lock()
+            .unwrap()
+            .map(|last| last.elapsed());
+
+        Health {
+            pid: process.as_ref().map(|process| process.pid()),
+            running: process
+                .as_ref()
+                .map_or(reader_running, |process| process.is_running()),
+            since_last_frame,
+            restarts: self.restarts.load(Ordering::Relaxed),
+            last_stderr_line: process.and_then(|process| process.last_stderr_line()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, process::Command, thread};
+
+    use super::*;
+
+    // A fake cava that runs for `seconds`
+    #[cfg(unix)]
+    fn fake_cava(seconds: &str) -> Arc<ProcessMonitor> {
+        let child = Command::new("sleep").arg(seconds).spawn().unwrap();
+        Arc::new(ProcessMonitor::new(child))
+    }
+
+    // Exiting takes a moment after the time is up
+    #[cfg(unix)]
+    fn wait_until_exited(process: &ProcessMonitor) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while process.is_running() {
+            assert!(Instant::now() < deadline, "the fake cava never exited");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn running_then_exited_then_restarted() {
+        let first = fake_cava("0.1");
+        let state = HealthState::new(Some(first.clone()));
+        let health = state.snapshot(false);
+        assert_eq!(health.pid, Some(first.pid()));
+        // The process is asked, not the reader thread
+        assert!(health.running);
+        assert_eq!(health.restarts, 0);
+
+        wait_until_exited(&first);
+        assert!(!state.snapshot(true).running);
+
+        let second = fake_cava("30");
+        state.record_restart(second.clone());
+        let health = state.snapshot(false);
+        assert_eq!(health.pid, Some(second.pid()));
+        assert!(health.running);
+        assert_eq!(health.restarts, 1);
+
+        second.kill();
+        assert!(!state.snapshot(false).running);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_last_stderr_line_is_kept() {
+        let process = fake_cava("0");
+        let state = HealthState::new(Some(process.clone()));
+        assert_eq!(state.snapshot(false).last_stderr_line, None);
+        process.record_stderr_line("first".to_string());
+        process.record_stderr_line("could not open the audio device".to_string());
+        assert_eq!(
+            state.snapshot(false).last_stderr_line.as_deref(),
+            Some("could not open the audio device")
+        );
+        process.kill();
+    }
+
+    // Without cava, running is whatever the reader thread says
+    #[test]
+    fn without_a_process() {
+        let state = HealthState::new(None);
+        let health = state.snapshot(true);
+        assert_eq!(health.pid, None);
+        assert!(health.running);
+        assert!(!state.snapshot(false).running);
+        assert_eq!(health.since_last_frame, None);
+    }
+
+    #[test]
+    fn time_since_the_last_frame() {
+        let state = HealthState::new(None);
+        state.record_frame(Instant::now() - Duration::from_secs(3));
+        let since = state.snapshot(true).since_last_frame.unwrap();
+        assert!(since >= Duration::from_secs(3) && since < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn the_last_error_is_the_latest() {
+        let state = HealthState::new(None);
+        assert!(state.last_error().is_none());
+        state.record_error(VisualizerError::Io(io::ErrorKind::BrokenPipe.into()));
+        state.record_error(VisualizerError::Io(io::ErrorKind::InvalidData.into()));
+        assert!(matches!(
+            state.last_error(),
+            Some(VisualizerError::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+}
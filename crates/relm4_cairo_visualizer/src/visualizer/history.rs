@@ -0,0 +1,137 @@
+use super::Frame;
+
+// How many frames we keep around unless told otherwise (about 4 seconds at 60 fps)
+pub const DEFAULT_HISTORY_CAPACITY: usize = 256;
+
+// The last `capacity` frames, oldest first
+// It's a ring buffer: once it is full the newest frame overwrites the oldest one,
+// and the slots are reused (the values are copied into the existing Vec) so the
+// memory used never grows after the first `capacity` frames
+pub struct FrameHistory {
+    frames: Vec<Frame>,
+    // The slot the next frame goes into, which is also the oldest frame once we are full
+    next: usize,
+    capacity: usize,
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> FrameHistory {
+        FrameHistory {
+            frames: Vec::with_capacity(capacity),
+            next: 0,
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, frame: &Frame) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.frames.len() < self.capacity {
+            self.frames.push(frame.clone());
+        } else {
+            let slot = &mut self.frames[self.next];
+            slot.seq = frame.seq;
+            slot.captured_at = frame.captured_at;
+            slot.bars = frame.bars;
+            slot.layout = frame.layout;
+            // `clone_from` copies into the Vec we already have instead of allocating a new one
+            slot.values.clone_from(&frame.values);
+        }
+
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    // A copy of the frames in the order they arrived
+    pub fn snapshot(&self) -> Vec<Frame> {
+        if self.frames.len() < self.capacity {
+            return self.frames.clone();
+        }
+
+        // When the buffer is full the oldest frame is at `next`, so we start
+        // there and wrap around to the beginning
+        let (newest, oldest) = self.frames.split_at(self.next);
+        oldest.iter().chain(newest).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(seq: u64) -> Frame {
+        Frame {
+            seq,
+            ..Frame::mono(vec![seq as u16; 3])
+        }
+    }
+
+    fn filled(capacity: usize, frames: u64) -> FrameHistory {
+        let mut history = FrameHistory::new(capacity);
+        for seq in 0..frames {
+            history.push(&frame(seq));
+        }
+        history
+    }
+
+    fn seqs(history: &FrameHistory) -> Vec<u64> {
+        history.snapshot().iter().map(|frame| frame.seq).collect()
+    }
+
+    #[test]
+    fn the_frames_come_oldest_first() {
+        let history = filled(4, 3);
+        assert_eq!(history.len(), 3);
+        assert_eq!(seqs(&history), [0, 1, 2]);
+    }
+
+    #[test]
+    fn past_capacity_the_oldest_are_evicted() {
+        for (frames, expected) in [
+            (4, vec![0, 1, 2, 3]),
+            (5, vec![1, 2, 3, 4]),
+            (7, vec![3, 4, 5, 6]),
+            (8, vec![4, 5, 6, 7]),
+            (13, vec![9, 10, 11, 12]),
+        ] {
+            let history = filled(4, frames);
+            assert_eq!(history.len(), 4);
+            assert_eq!(seqs(&history), expected, "after {} frames", frames);
+        }
+    }
+
+    // The values of the reused slots are the new ones, not the evicted ones
+    #[test]
+    fn reused_slots_hold_the_new_frames() {
+        let mut history = filled(2, 2);
+        history.push(&Frame {
+            seq: 2,
+            ..Frame::mono(vec![7, 8])
+        });
+        let snapshot = history.snapshot();
+        assert_eq!(snapshot[0].values, [1, 1, 1]);
+        assert_eq!((snapshot[1].seq, snapshot[1].bars), (2, 2));
+        assert_eq!(snapshot[1].values, [7, 8]);
+    }
+
+    #[test]
+    fn a_capacity_of_one_keeps_the_latest() {
+        assert_eq!(seqs(&filled(1, 5)), [4]);
+    }
+
+    #[test]
+    fn a_capacity_of_zero_keeps_nothing() {
+        let history = filled(0, 5);
+        assert!(history.is_empty());
+        assert!(history.snapshot().is_empty());
+    }
+}
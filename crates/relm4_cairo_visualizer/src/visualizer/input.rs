@@ -0,0 +1,178 @@
+use std::{fmt::Write, path::PathBuf, process::Command};
+
+use super::VisualizerError;
+
+// Where cava captures the audio from
+// Without one, cava picks its own default (usually the monitor of the default output),
+// which is often the wrong device on machines with several outputs
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputMethod {
+    // A PulseAudio source, None = cava's `auto` (the default output's monitor)
+    // The names are the ones `list_pulse_sources` returns
+    Pulse { source: Option<String> },
+    // A PipeWire node, None = cava's `auto`
+    Pipewire { source: Option<String> },
+    // Raw samples written to a named pipe, e.g. by MPD's fifo output
+    Fifo { path: PathBuf, sample_rate: u32 },
+}
+
+impl InputMethod {
+    // The `[input]` section of the cava config
+    pub(crate) fn write_ini(&self, ini: &mut String) {
+        writeln!(ini, "[input]").unwrap();
+        match self {
+            InputMethod::Pulse { source } => {
+                writeln!(ini, "method = pulse").unwrap();
+                writeln!(ini, "source = {}", source.as_deref().unwrap_or("auto")).unwrap();
+            }
+            InputMethod::Pipewire { source } => {
+                writeln!(ini, "method = pipewire").unwrap();
+                writeln!(ini, "source = {}", source.as_deref().unwrap_or("auto")).unwrap();
+            }
+            InputMethod::Fifo { path, sample_rate } => {
+                writeln!(ini, "method = fifo").unwrap();
+                writeln!(ini, "source = {}", path.display()).unwrap();
+                writeln!(ini, "sample_rate = {}", sample_rate).unwrap();
+            }
+        }
+    }
+
+    // The value we write after `source = `, checked by `VisualizerConfig::validate`
+    pub(crate) fn source(&self) -> Option<String> {
+        match self {
+            InputMethod::Pulse { source } | InputMethod::Pipewire { source } => source.clone(),
+            InputMethod::Fifo { path, .. } => Some(path.display().to_string()),
+        }
+    }
+}
+
+// The names of the PulseAudio sources, in the order `pactl` lists them
+// Works with PipeWire too, through its PulseAudio compatibility (pipewire-pulse)
+pub fn list_pulse_sources() -> Result<Vec<String>, VisualizerError> {
+    let output = Command::new("pactl")
+        .args(["list", "short", "sources"])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "pactl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_pactl_sources(&stdout))
+}
+
+// `pactl list short sources` prints one source per line, with tab separated columns:
+//
+//   57	alsa_output.pci-0000_00_1f.3.analog-stereo.monitor	PipeWire	s32le 2ch 48000Hz	SUSPENDED
+//
+// The index, the name, the driver, the sample format and the state
+// We only keep the name, that's what cava wants as `source`
+pub fn parse_pactl_sources(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            // Lines that don't start with an index aren't sources (warnings and such)
+            columns.next()?.trim().parse::<u32>().ok()?;
+            let name = columns.next()?.trim();
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::visualizer::VisualizerConfig;
+
+    // Captured on a laptop running PipeWire (through pipewire-pulse)
+    const PIPEWIRE: &str = "\
+57\talsa_output.pci-0000_00_1f.3.analog-stereo.monitor\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED
+58\talsa_input.pci-0000_00_1f.3.analog-stereo\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED
+112\tbluez_output.AC_80_0A_12_34_56.1.monitor\tPipeWire\ts24le 2ch 48000Hz\tRUNNING
+";
+
+    // Captured on PulseAudio, with a warning mixed in
+    const PULSE: &str = "\
+W: [pulseaudio] main.c: This program is not intended to be run as root.
+0\talsa_output.usb-Focusrite_Scarlett_2i2-00.analog-stereo.monitor\tmodule-alsa-card.c\ts32le 2ch 44100Hz\tIDLE
+1\talsa_input.usb-Focusrite_Scarlett_2i2-00.analog-stereo\tmodule-alsa-card.c\ts32le 2ch 44100Hz\tSUSPENDED
+
+";
+
+    #[test]
+    fn the_names_are_parsed_in_order() {
+        assert_eq!(
+            parse_pactl_sources(PIPEWIRE),
+            [
+                "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor",
+                "alsa_input.pci-0000_00_1f.3.analog-stereo",
+                "bluez_output.AC_80_0A_12_34_56.1.monitor",
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_that_arent_sources_are_skipped() {
+        assert_eq!(
+            parse_pactl_sources(PULSE),
+            [
+                "alsa_output.usb-Focusrite_Scarlett_2i2-00.analog-stereo.monitor",
+                "alsa_input.usb-Focusrite_Scarlett_2i2-00.analog-stereo",
+            ]
+        );
+        assert!(parse_pactl_sources("").is_empty());
+        assert!(parse_pactl_sources("3\t\tPipeWire").is_empty());
+        assert!(parse_pactl_sources("3").is_empty());
+    }
+
+    #[test]
+    fn every_method_writes_its_input_section() {
+        for (input, section) in [
+            (
+                InputMethod::Pulse { source: None },
+                "[input]\nmethod = pulse\nsource = auto\n",
+            ),
+            (
+                InputMethod::Pipewire {
+                    source: Some("alsa_output.monitor".to_string()),
+                },
+                "[input]\nmethod = pipewire\nsource = alsa_output.monitor\n",
+            ),
+            (
+                InputMethod::Fifo {
+                    path: "/tmp/mpd.fifo".into(),
+                    sample_rate: 44100,
+                },
+                "[input]\nmethod = fifo\nsource = /tmp/mpd.fifo\nsample_rate = 44100\n",
+            ),
+        ] {
+            let mut ini = String::new();
+            input.write_ini(&mut ini);
+            assert_eq!(ini, section);
+        }
+    }
+
+    #[test]
+    fn a_source_that_would_break_the_config_is_rejected() {
+        for input in [
+            InputMethod::Pulse {
+                source: Some("monitor\nmethod = fifo".to_string()),
+            },
+            InputMethod::Pipewire {
+                source: Some(String::new()),
+            },
+            InputMethod::Fifo {
+                path: "/tmp/mpd.fifo".into(),
+                sample_rate: 0,
+            },
+        ] {
+            let result = VisualizerConfig::new(20).input(input).validate();
+            assert!(matches!(result, Err(VisualizerError::InvalidConfig(_))));
+        }
+    }
+}
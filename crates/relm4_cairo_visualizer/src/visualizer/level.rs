@@ -0,0 +1,135 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+// How long the average level takes to catch up with a change in loudness
+// After one time constant it covered about 63% of the way, after three about 95%
+pub const DEFAULT_LEVEL_TIME_CONSTANT: Duration = Duration::from_millis(300);
+
+// The loudness of a whole frame as a single number between 0.0 (silence) and 1.0
+// (every bar at the maximum)
+// RMS (the square root of the mean of the squares) gives more weight to the loud
+// bars than a plain average would, which is closer to how loud it actually sounds
+pub fn rms(frame: &[u16]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+
+    let sum_of_squares: f64 = frame
+        .iter()
+        .map(|&value| {
+            let normalized = value as f64 / u16::MAX as f64;
+            normalized * normalized
+        })
+        .sum();
+
+    (sum_of_squares / frame.len() as f64).sqrt() as f32
+}
+
+// The current level and a slower moving average of it
+// Like the stats, they are written by the reader thread and read by anybody holding
+// the handle. There is no AtomicF32, so we store the bits of the f32 in an AtomicU32
+#[derive(Debug)]
+pub struct LevelMeter {
+    level: AtomicU32,
+    average: AtomicU32,
+    time_constant: Duration,
+}
+
+impl LevelMeter {
+    pub fn new(time_constant: Duration) -> LevelMeter {
+        LevelMeter {
+            level: AtomicU32::new(0.0_f32.to_bits()),
+            average: AtomicU32::new(0.0_f32.to_bits()),
+            time_constant,
+        }
+    }
+
+    // Record the level of a new frame, `elapsed` being the time since the previous one
+    //
+    // The average is an exponential moving average: every frame moves it towards the
+    // new level by a fraction that depends on how much time passed, so it behaves the
+    // same whatever the framerate is
+    pub(crate) fn record(&self, level: f32, elapsed: Duration) {
+        let previous = f32::from_bits(self.average.load(Ordering::Relaxed));
+        let alpha = if self.time_constant.is_zero() {
+            1.0
+        } else {
+            1.0 - (-elapsed.as_secs_f32() / self.time_constant.as_secs_f32()).exp()
+        };
+        let average = previous + alpha * (level - previous);
+
+        self.level.store(level.to_bits(), Ordering::Relaxed);
+        self.average.store(average.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.level.load(Ordering::Relaxed))
+    }
+
+    pub fn average(&self) -> f32 {
+        f32::from_bits(self.average.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn rms_of_silence() {
+        assert_eq!(rms(&[0; 16]), 0.0);
+        assert_eq!(rms(&[]), 0.0);
+    }
+
+    #[test]
+    fn rms_at_full_scale() {
+        assert_eq!(rms(&[u16::MAX; 16]), 1.0);
+    }
+
+    #[test]
+    fn rms_at_half_scale() {
+        assert!(close(rms(&[u16::MAX / 2; 16]), 0.5));
+    }
+
+    // Half the bars at the maximum is louder than half the level everywhere
+    #[test]
+    fn rms_weighs_the_loud_bars_more() {
+        assert!(close(rms(&[0, u16::MAX]), 0.5_f32.sqrt()));
+    }
+
+    #[test]
+    fn the_average_follows_the_level_with_the_time_constant() {
+        let meter = LevelMeter::new(Duration::from_millis(100));
+        meter.record(1.0, Duration::from_millis(100));
+        assert_eq!(meter.level(), 1.0);
+        // One time constant covers about 63% of the way
+        assert!(close(meter.average(), 1.0 - (-1.0_f32).exp()));
+        meter.record(1.0, Duration::from_millis(200));
+        assert!(close(meter.average(), 1.0 - (-3.0_f32).exp()));
+    }
+
+    // Ten short steps end up where one long step does
+    #[test]
+    fn the_average_does_not_depend_on_the_framerate() {
+        let fast = LevelMeter::new(DEFAULT_LEVEL_TIME_CONSTANT);
+        let slow = LevelMeter::new(DEFAULT_LEVEL_TIME_CONSTANT);
+        for _ in 0..10 {
+            fast.record(0.8, Duration::from_millis(10));
+        }
+        slow.record(0.8, Duration::from_millis(100));
+        assert!(close(fast.average(), slow.average()));
+    }
+
+    #[test]
+    fn without_a_time_constant_the_average_is_the_level() {
+        let meter = LevelMeter::new(Duration::ZERO);
+        meter.record(0.3, Duration::from_millis(16));
+        assert_eq!(meter.average(), 0.3);
+    }
+}
@@ -0,0 +1,156 @@
+use super::Stage;
+
+// Where each bar goes on screen
+// cava always sends the bars from the lowest frequency to the highest one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ordering {
+    // Bass on the left, like cava sends them
+    #[default]
+    LeftToRight,
+    // Bass on the right
+    RightToLeft,
+    // Bass in the middle and the higher frequencies fanning out on both sides
+    CenterOut,
+}
+
+// Move the bars of a frame (lowest frequency first) to where `ordering` wants them
+//
+// CenterOut deals the bars out alternately to the left and to the right of the middle:
+//
+//   4 bars: [0, 1, 2, 3]    -> [2, 0, 1, 3]
+//   5 bars: [0, 1, 2, 3, 4] -> [3, 1, 0, 2, 4]
+//
+// With an odd number of bars the lowest one gets the middle bar for itself
+pub fn reorder(frame: &[u16], ordering: Ordering) -> Vec<u16> {
+    match ordering {
+        Ordering::LeftToRight => frame.to_vec(),
+        Ordering::RightToLeft => frame.iter().rev().copied().collect(),
+        Ordering::CenterOut => {
+            let bars = frame.len();
+            let mut reordered = vec![0_u16; bars];
+            let middle = bars / 2;
+
+            // `left` is the last slot we filled on the left, `right` the next one on the right
+            let (mut left, mut right, rest) = if bars % 2 == 1 {
+                reordered[middle] = frame[0];
+                (middle, middle + 1, &frame[1..])
+            } else {
+                (middle, middle, frame)
+            };
+
+            for (i, &value) in rest.iter().enumerate() {
+                if i % 2 == 0 {
+                    left -= 1;
+                    reordered[left] = value;
+                } else {
+                    reordered[right] = value;
+                    right += 1;
+                }
+            }
+
+            reordered
+        }
+    }
+}
+
+// The last stage of the pipeline, everything before it still sees the bars in cava's
+// order (the smoother blends neighbour frequencies, not neighbours on screen)
+pub struct Reorder {
+    ordering: Ordering,
+}
+
+impl Reorder {
+    pub fn new(ordering: Ordering) -> Reorder {
+        Reorder { ordering }
+    }
+}
+
+impl Stage for Reorder {
+    fn process(&mut self, values: Vec<u16>) -> Vec<u16> {
+        reorder(&values, self.ordering)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bars 0, 1, 2... so the output shows where each one went
+    fn bars(count: u16) -> Vec<u16> {
+        (0..count).collect()
+    }
+
+    #[test]
+    fn center_out_for_every_small_count() {
+        let expected: [&[u16]; 8] = [
+            &[],
+            &[0],
+            &[0, 1],
+            &[1, 0, 2],
+            &[2, 0, 1, 3],
+            &[3, 1, 0, 2, 4],
+            &[4, 2, 0, 1, 3, 5],
+            &[5, 3, 1, 0, 2, 4, 6],
+        ];
+        for (count, expected) in expected.iter().enumerate() {
+            assert_eq!(
+                reorder(&bars(count as u16), Ordering::CenterOut),
+                *expected,
+                "{} bars",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn left_to_right_and_right_to_left_for_every_small_count() {
+        for count in 0..8 {
+            let frame = bars(count);
+            assert_eq!(reorder(&frame, Ordering::LeftToRight), frame);
+            let reversed: Vec<u16> = (0..count).rev().collect();
+            assert_eq!(reorder(&frame, Ordering::RightToLeft), reversed);
+        }
+    }
+
+    // Every bar shows up exactly once, whatever the ordering
+    #[test]
+    fn every_ordering_is_a_permutation() {
+        for ordering in [
+            Ordering::LeftToRight,
+            Ordering::RightToLeft,
+            Ordering::CenterOut,
+        ] {
+            for count in 0..40 {
+                let mut reordered = reorder(&bars(count), ordering);
+                reordered.sort_unstable();
+                assert_eq!(reordered, bars(count), "{:?}, {} bars", ordering, count);
+            }
+        }
+    }
+
+    // The higher the frequency, the further from the middle
+    #[test]
+    fn center_out_fans_out_from_the_middle() {
+        for count in 1..40_u16 {
+            let reordered = reorder(&bars(count), Ordering::CenterOut);
+            let middle = (count as f32 - 1.0) / 2.0;
+            let mut distances = vec![0.0; count as usize];
+            for (slot, &bar) in reordered.iter().enumerate() {
+                distances[bar as usize] = (slot as f32 - middle).abs();
+            }
+            assert!(
+                distances.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{} bars: {:?}",
+                count,
+                reordered
+            );
+        }
+    }
+
+    #[test]
+    fn the_stage_reorders_what_it_gets() {
+        let mut stage = Reorder::new(Ordering::RightToLeft);
+        assert_eq!(stage.process(vec![1, 2, 3]), [3, 2, 1]);
+        assert_eq!(Ordering::default(), Ordering::LeftToRight);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{collections::VecDeque, time::Duration};
+
+// How often TemporalSmoother looks at the measured frame rate again
+pub const ADAPT_EVERY: Duration = Duration::from_secs(1);
+
+// A stage takes the bar values of a frame and returns new (transformed) values
+// The visualizer runs every frame through a list of stages, in order, before sending it to the UI
+// Stages can keep state between frames, that's why `process` takes `&mut self`
+pub trait Stage: Send {
+    fn process(&mut self, values: Vec<u16>) -> Vec<u16>;
+
+    // Called before `process` with the time since the previous frame (from the frame
+    // timestamps), for the stages whose effect depends on the frame rate
+    fn frame_interval(&mut self, _interval: Duration) {}
+}
+
+// Apply a simple moving average smoothing
+// This part makes the bars look smoother, like when we are drawing and you use ours finger to blend colors together
+pub struct Smoother {
+    // This is like how many bars we look at to make each bar smoother
+    window_size: usize,
+}
+
+impl Smoother {
+    pub fn new(window_size: usize) -> Smoother {
+        Smoother { window_size }
+    }
+}
+
+impl Default for Smoother {
+    fn default() -> Smoother {
+        Smoother::new(3)
+    }
+}
+
+impl Stage for Smoother {
+    fn process(&mut self, values: Vec<u16>) -> Vec<u16> {
+        smooth(&values, self.window_size)
+    }
+}
+
+// Smoother blends every bar with its neighbours, this one blends every bar with its
+// own values in the last few frames, so the bars don't jump from one frame to the next
+//
+// Averaging a fixed number of frames would smooth over a different amount of time
+// depending on the frame rate: 3 frames are 50ms at 60 fps but 100ms at 30 fps.
+// So we keep the time constant instead and pick the number of frames from the
+// measured frame rate. It's only recomputed every ADAPT_EVERY so the bars don't
+// change how they move on every little hiccup
+//
+// Every frame it averages in is a frame of lag, so it's only part of the pipeline
+// when asked for (see `VisualizerConfig::smoothing_time_constant`)
+pub struct TemporalSmoother {
+    time_constant: Duration,
+    // How many frames we average
+    window: usize,
+    // The last `window` frames, oldest first
+    recent: VecDeque<Vec<u16>>,
+    // The intervals measured since the window was last computed
+    measured: Duration,
+    intervals: u32,
+}
+
+impl TemporalSmoother {
+    // Until we measured anything we trust the frame rate we asked for
+    pub fn new(time_constant: Duration, framerate: u32) -> TemporalSmoother {
+        TemporalSmoother {
+            time_constant,
+            window: window_for(time_constant, Duration::from_secs(1) / framerate.max(1)),
+            recent: VecDeque::new(),
+            measured: Duration::ZERO,
+            intervals: 0,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+}
+
+impl Stage for TemporalSmoother {
+    fn process(&mut self, values: Vec<u16>) -> Vec<u16> {
+        // A frame with a different number of bars can't be blended with the old ones
+        if self
+            .recent
+            .front()
+            .is_some_and(|frame| frame.len() != values.len())
+        {
+            self.recent.clear();
+        }
+
+        self.recent.push_back(values);
+        while self.recent.len() > self.window {
+            self.recent.pop_front();
+        }
+
+        let frames = self.recent.len() as u32;
+        let mut sums = vec![0_u32; self.recent[0].len()];
+        for frame in &self.recent {
+            for (sum, &value) in sums.iter_mut().zip(frame) {
+                *sum += value as u32;
+            }
+        }
+        sums.into_iter().map(|sum| (sum / frames) as u16).collect()
+    }
+
+    fn frame_interval(&mut self, interval: Duration) {
+        self.measured += interval;
+        self.intervals += 1;
+
+        if self.measured >= ADAPT_EVERY {
+            self.window = window_for(self.time_constant, self.measured / self.intervals);
+            self.measured = Duration::ZERO;
+            self.intervals = 0;
+        }
+    }
+}
+
+// How many frames, `interval` apart, cover `time_constant` (at least the current one)
+pub fn window_for(time_constant: Duration, interval: Duration) -> usize {
+    if interval.is_zero() {
+        return 1;
+    }
+    (time_constant.as_secs_f64() / interval.as_secs_f64())
+        .round()
+        .max(1.0) as usize
+}
+
+pub fn smooth(data: &[u16], window_size: usize) -> Vec<u16> {
+    let bars = data.len();
+    let mut smoothed_data = vec![0_u16; bars]; // We make a new list to put our smoother bars in
+    for (i, smoothed) in smoothed_data.iter_mut().enumerate() {
+        // For each bar, we look at the bars next to it
+        let start = i.saturating_sub(window_size / 2); // We start looking a little bit before our bar
+        let end = (i + window_size / 2 + 1).min(bars); // We stop looking a little bit after our bar
+
+        // We add up the heights of all these bars
+        let sum: u32 = data[start..end].iter().map(|&x| x as u32).sum();
+
+        // Then we divide by how many bars we looked at to get an average
+        // This average becomes the new height of our bar
+        *smoothed = (sum / (end - start) as u32) as u16;
+    }
+
+    smoothed_data
+}
+
+// Silence is never really silent: the sound card picks up a little noise and cava
+// turns it into bars a few pixels tall that flicker all the time.
+// The gate zeroes every value below `threshold` so quiet bars stay flat.
+//
+// With hysteresis a bar that went above the threshold (the gate "opened") only
+// closes again once it drops below half the threshold. Without it, a value hovering
+// right around the threshold would make the bar blink on and off every frame.
+pub struct NoiseGate {
+    threshold: u16,
+    hysteresis: bool,
+    // Whether the gate of each bar is currently open
+    open: Vec<bool>,
+}
+
+impl NoiseGate {
+    pub fn new(threshold: u16, hysteresis: bool) -> NoiseGate {
+        NoiseGate {
+            threshold,
+            hysteresis,
+            open: Vec::new(),
+        }
+    }
+}
+
+impl Stage for NoiseGate {
+    fn process(&mut self, mut values: Vec<u16>) -> Vec<u16> {
+        // The first frame (or a frame with a different number of bars)
+        // starts with every gate closed
+        if self.open.len() != values.len() {
+            self.open = vec![false; values.len()];
+        }
+
+        for (value, open) in values.iter_mut().zip(self.open.iter_mut()) {
+            let close_below = if self.hysteresis && *open {
+                self.threshold / 2
+            } else {
+                self.threshold
+            };
+
+            *open = *value >= close_below;
+            if !*open {
+                *value = 0;
+            }
+        }
+
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMOOTHING: Duration = Duration::from_millis(50);
+
+    // Feed one second (and a bit) of frames `1/fps` apart, like the reader thread does
+    fn run_at(smoother: &mut TemporalSmoother, fps: u32) {
+        let interval = Duration::from_secs(1) / fps;
+        for _ in 0..=fps {
+            smoother.frame_interval(interval);
+            smoother.process(vec![0; 4]);
+        }
+    }
+
+    #[test]
+    fn the_window_covers_the_same_time_at_any_frame_rate() {
+        for (fps, window) in [(30, 2), (60, 3), (120, 6)] {
+            // Asked for 60 fps, but the frames really come at `fps`
+            let mut smoother = TemporalSmoother::new(SMOOTHING, 60);
+            run_at(&mut smoother, fps);
+            assert_eq!(smoother.window(), window, "at {} fps", fps);
+        }
+    }
+
+    #[test]
+    fn the_window_starts_from_the_configured_frame_rate() {
+        assert_eq!(TemporalSmoother::new(SMOOTHING, 30).window(), 2);
+        assert_eq!(TemporalSmoother::new(SMOOTHING, 60).window(), 3);
+        assert_eq!(TemporalSmoother::new(SMOOTHING, 120).window(), 6);
+    }
+
+    #[test]
+    fn the_window_only_changes_once_per_second() {
+        let mut smoother = TemporalSmoother::new(SMOOTHING, 60);
+        // 125 fps, 0.8s in
+        let interval = Duration::from_millis(8);
+        for _ in 0..100 {
+            smoother.frame_interval(interval);
+        }
+        assert_eq!(smoother.window(), 3);
+        for _ in 0..25 {
+            smoother.frame_interval(interval);
+        }
+        assert_eq!(smoother.window(), 6);
+    }
+
+    #[test]
+    fn a_slow_frame_rate_still_keeps_the_current_frame() {
+        assert_eq!(window_for(SMOOTHING, Duration::from_millis(200)), 1);
+        assert_eq!(window_for(SMOOTHING, Duration::ZERO), 1);
+        assert_eq!(window_for(Duration::ZERO, Duration::from_millis(16)), 1);
+    }
+
+    #[test]
+    fn the_last_frames_of_the_window_are_averaged() {
+        let mut smoother = TemporalSmoother::new(SMOOTHING, 30);
+        assert_eq!(smoother.process(vec![100, 0]), [100, 0]);
+        assert_eq!(smoother.process(vec![300, 0]), [200, 0]);
+        assert_eq!(smoother.process(vec![500, 60]), [400, 30]);
+        // A different number of bars starts over
+        assert_eq!(smoother.process(vec![10, 10, 10]), [10, 10, 10]);
+    }
+
+    #[test]
+    fn neighbour_bars_are_averaged() {
+        assert_eq!(smooth(&[0, 300, 0, 300], 3), [150, 100, 200, 150]);
+        assert_eq!(smooth(&[9, 3], 1), [9, 3]);
+        assert_eq!(smooth(&[], 3), Vec::<u16>::new());
+    }
+
+    #[test]
+    fn the_noise_gate_zeroes_quiet_bars() {
+        let mut gate = NoiseGate::new(100, false);
+        assert_eq!(gate.process(vec![50, 100, 200]), [0, 100, 200]);
+        assert_eq!(gate.process(vec![99, 60, 200]), [0, 0, 200]);
+    }
+
+    #[test]
+    fn with_hysteresis_an_open_gate_closes_below_half_the_threshold() {
+        let mut gate = NoiseGate::new(100, true);
+        assert_eq!(gate.process(vec![60, 150]), [0, 150]);
+        // The second bar is open, it stays open down to 50
+        assert_eq!(gate.process(vec![60, 60]), [0, 60]);
+        assert_eq!(gate.process(vec![60, 49]), [0, 0]);
+        // Closed again, so it needs the full threshold to open
+        assert_eq!(gate.process(vec![60, 60]), [0, 0]);
+    }
+}
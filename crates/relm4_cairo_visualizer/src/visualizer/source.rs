@@ -0,0 +1,184 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    process::{ChildStdout, Stdio},
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use super::{cava, framing::FrameReader, ProcessMonitor, VisualizerConfig, VisualizerError};
+
+// An audio source is anything that can fill a buffer with one frame of raw cava data
+// The frame layout is always the same: 2 bytes (little endian u16) per bar
+// By hiding the cava process behind this trait the rest of the pipeline (decoding,
+// smoothing and delivery) doesn't care where the bytes come from, so we can feed it
+// from a real cava process or from a list of frames we wrote by hand
+pub trait AudioSource {
+    // Fill the whole `buf` with exactly one frame
+    // Returning an `UnexpectedEof` error means the source has no more frames and
+    // the pipeline should shut down quietly, any other error is reported
+    fn read_frame(&mut self, buf: &mut [u8]) -> io::Result<()>;
+
+    // How many times the source lost the frame boundaries and had to find them again
+    // since the last call (see FrameReader). Sources that can't get misaligned keep the default
+    fn take_resyncs(&mut self) -> u64 {
+        0
+    }
+}
+
+// The real thing: a cava child process writing binary frames to its stdout
+pub struct CavaSource {
+    // Shared with the handle so it can check on the process (see `Visualizer::health`)
+    process: Arc<ProcessMonitor>,
+    // cava's stdout, cut into frames
+    frames: FrameReader<ChildStdout>,
+    // Keeps the last line cava wrote to stderr, and makes sure the pipe never fills up
+    stderr_reader: Option<JoinHandle<()>>,
+}
+
+impl CavaSource {
+    pub fn spawn(config: &VisualizerConfig) -> Result<CavaSource, VisualizerError> {
+        // Fail with a clear error now rather than with a broken pipe on the first frame
+        let cava_path = cava::cava_path(config);
+        let version = cava::check_version(&cava_path)?;
+
+        // Create a new temporary configuration for cava and save it to
+        // `/tmp/cava-config.conf` so we can pass it as a argument to cava
+        let path = std::env::temp_dir().join("cava-config.conf");
+        let mut temp = File::create(&path)?;
+        temp.write_all(config.to_cava_config().as_bytes())?;
+        temp.flush()?;
+
+        // Print what we are about to run, cava doesn't complain about a bad frequency
+        // range, it just draws odd bars
+        let freq_range = match config.freq_range {
+            Some((low, high)) => format!("{}-{} Hz", low, high),
+            None => "cava default".to_string(),
+        };
+        let version = version.map_or("unknown".to_string(), |version| version.to_string());
+        eprintln!(
+            "spawning cava {} ({}): bars={} framerate={} freq_range={} config={}",
+            cava_path.display(),
+            version,
+            config.bars,
+            config.framerate,
+            freq_range,
+            path.display()
+        );
+
+        // Spawn the cava process with the configuration file
+        let mut process = std::process::Command::new(&cava_path)
+            .arg("-p")
+            .arg(&path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdin(Stdio::null())
+            .spawn()?;
+
+        // `take` moves stdout and stderr out of the Child, they are always there since we piped them
+        let stdout = process.stdout.take().unwrap();
+        let stderr = process.stderr.take().unwrap();
+        let process = Arc::new(ProcessMonitor::new(process));
+
+        // The thread ends by itself once cava exits and closes its stderr
+        let thread_process = process.clone();
+        let stderr_reader = std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if !line.trim().is_empty() {
+                    thread_process.record_stderr_line(line);
+                }
+            }
+        });
+
+        Ok(CavaSource {
+            process,
+            frames: FrameReader::new(stdout),
+            stderr_reader: Some(stderr_reader),
+        })
+    }
+
+    // A handle on the process that stays valid after the source is gone
+    pub fn monitor(&self) -> Arc<ProcessMonitor> {
+        self.process.clone()
+    }
+}
+
+impl AudioSource for CavaSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        // The FrameReader deals with short reads and interruptions, so an error
+        // here means cava is gone (or its output can't be read anymore)
+        if let Err(e) = self.frames.read_frame(buf) {
+            // If cava died it usually tells us why on stderr, so we attach it to the error
+            // We make sure it is gone and wait for the stderr thread to read everything first
+            // We don't use `UnexpectedEof` here because cava stopping is never expected
+            self.process.kill();
+            if let Some(stderr_reader) = self.stderr_reader.take() {
+                let _ = stderr_reader.join();
+            }
+            return Err(io::Error::other(format!(
+                "cava process failed: {:?} Error: {:?}",
+                self.process.last_stderr_line().unwrap_or_default(),
+                e
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn take_resyncs(&mut self) -> u64 {
+        self.frames.take_resyncs()
+    }
+}
+
+// Make sure we don't leave a cava process running in the background
+// when the source goes away (e.g. the reader thread stopped)
+impl Drop for CavaSource {
+    fn drop(&mut self) {
+        self.process.kill();
+    }
+}
+
+// A fake source that plays back a list of frames we prepared beforehand and then
+// reports the end of the stream. This lets us drive the whole visualizer without
+// having cava (or even a sound card) installed
+pub struct ScriptedSource {
+    frames: VecDeque<Vec<u16>>,
+}
+
+impl ScriptedSource {
+    pub fn new(frames: Vec<Vec<u16>>) -> ScriptedSource {
+        ScriptedSource {
+            frames: frames.into(),
+        }
+    }
+}
+
+impl AudioSource for ScriptedSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let Some(frame) = self.frames.pop_front() else {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        };
+
+        if frame.len() * 2 != buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "scripted frame has {} bars but the visualizer expects {}",
+                    frame.len(),
+                    buf.len() / 2
+                ),
+            ));
+        }
+
+        // Write the values exactly like cava would: little endian, 2 bytes per bar
+        for (i, value) in frame.iter().enumerate() {
+            buf[2 * i..2 * i + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+// Counters describing how frames are flowing through the visualizer
+// The reader thread is the only writer, everybody else just reads them
+// Everything is an atomic so recording a frame never has to take a lock,
+// the struct is shared with the UI behind an Arc (see `Visualizer::stats`)
+#[derive(Debug, Default)]
+pub struct VisualizerStats {
+    // The next sequence number to hand out
+    // It lives here (and not in the reader thread) so it keeps increasing
+    // even if the audio source is replaced/restarted
+    next_seq: AtomicU64,
+    frames_received: AtomicU64,
+    frames_dropped: AtomicU64,
+    late_frames: AtomicU64,
+    // Sum of all the intervals between frames, we divide it by the number of intervals
+    // to get the average. Durations are stored as microseconds so they fit in an AtomicU64
+    total_interval_us: AtomicU64,
+    intervals: AtomicU64,
+    max_gap_us: AtomicU64,
+    // How many times we lost the frame boundaries and had to find them again
+    resyncs: AtomicU64,
+}
+
+impl VisualizerStats {
+    // Hand out the sequence number for a new frame
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // Record a frame that was just read from the source
+    // `gap` is the time since the previous frame (None for the very first one) and
+    // `expected` is the frame period we asked cava for (1 / framerate)
+    pub(crate) fn record_frame(&self, gap: Option<Duration>, expected: Duration) {
+        self.frames_received.fetch_add(1, Ordering::Relaxed);
+
+        let Some(gap) = gap else {
+            return;
+        };
+
+        let gap_us = gap.as_micros() as u64;
+        self.total_interval_us.fetch_add(gap_us, Ordering::Relaxed);
+        self.intervals.fetch_add(1, Ordering::Relaxed);
+        self.max_gap_us.fetch_max(gap_us, Ordering::Relaxed);
+
+        // A frame arriving more than two frame periods after the previous one
+        // means something (cava, the pipe or our thread) stalled
+        if gap > expected * 2 {
+            self.late_frames.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Record a frame that was read but couldn't be delivered to the consumer
+    pub(crate) fn record_dropped(&self) {
+        self.frames_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_resyncs(&self, resyncs: u64) {
+        self.resyncs.fetch_add(resyncs, Ordering::Relaxed);
+    }
+
+    pub fn frames_received(&self) -> u64 {
+        self.frames_received.load(Ordering::Relaxed)
+    }
+
+    pub fn frames_dropped(&self) -> u64 {
+        self.frames_dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn late_frames(&self) -> u64 {
+        self.late_frames.load(Ordering::Relaxed)
+    }
+
+    // The average time between two frames, zero until we got at least two frames
+    pub fn average_interval(&self) -> Duration {
+        let intervals = self.intervals.load(Ordering::Relaxed);
+        if intervals == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_micros(self.total_interval_us.load(Ordering::Relaxed) / intervals)
+    }
+
+    pub fn resyncs(&self) -> u64 {
+        self.resyncs.load(Ordering::Relaxed)
+    }
+
+    // The longest time we waited between two frames
+    pub fn max_gap(&self) -> Duration {
+        Duration::from_micros(self.max_gap_us.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn sequence_numbers_keep_increasing() {
+        let stats = VisualizerStats::default();
+        let seqs: Vec<_> = (0..4).map(|_| stats.next_seq()).collect();
+        assert_eq!(seqs, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn nothing_recorded() {
+        let stats = VisualizerStats::default();
+        assert_eq!(stats.frames_received(), 0);
+        assert_eq!(stats.average_interval(), Duration::ZERO);
+        assert_eq!(stats.max_gap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_first_frame_has_no_interval() {
+        let stats = VisualizerStats::default();
+        stats.record_frame(None, PERIOD);
+        assert_eq!(stats.frames_received(), 1);
+        assert_eq!(stats.average_interval(), Duration::ZERO);
+        assert_eq!(stats.late_frames(), 0);
+    }
+
+    #[test]
+    fn intervals_are_averaged_and_the_longest_is_kept() {
+        let stats = VisualizerStats::default();
+        stats.record_frame(None, PERIOD);
+        for ms in [10, 20, 30] {
+            stats.record_frame(Some(Duration::from_millis(ms)), PERIOD);
+        }
+        assert_eq!(stats.frames_received(), 4);
+        assert_eq!(stats.average_interval(), Duration::from_millis(20));
+        assert_eq!(stats.max_gap(), Duration::from_millis(30));
+    }
+
+    // Late is more than two frame periods, exactly two is still on time
+    #[test]
+    fn late_frames() {
+        let stats = VisualizerStats::default();
+        stats.record_frame(Some(PERIOD * 2), PERIOD);
+        assert_eq!(stats.late_frames(), 0);
+        stats.record_frame(Some(PERIOD * 2 + Duration::from_micros(1)), PERIOD);
+        stats.record_frame(Some(Duration::from_secs(1)), PERIOD);
+        assert_eq!(stats.late_frames(), 2);
+    }
+
+    #[test]
+    fn drops_and_resyncs_are_counted() {
+        let stats = VisualizerStats::default();
+        stats.record_dropped();
+        stats.record_dropped();
+        stats.record_resyncs(0);
+        stats.record_resyncs(3);
+        assert_eq!(stats.frames_dropped(), 2);
+        assert_eq!(stats.resyncs(), 3);
+        // Dropping doesn't change what was received
+        assert_eq!(stats.frames_received(), 0);
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    f64::consts::PI,
+    io,
+    time::{Duration, Instant},
+};
+
+use super::AudioSource;
+
+// The shape of the fake signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    // A bump that sweeps from the left bar to the right one and starts over
+    SineSweep,
+    // Every bar drifts up and down randomly, but smoothly
+    RandomWalk,
+    // A kick drum at the given beats per minute: the bass bars jump on every beat and decay
+    Pulse { bpm: u32 },
+}
+
+// An audio source that makes up its frames instead of listening to the sound card
+// Handy to run the app on a machine without audio (or without cava) and in tests,
+// since the same (waveform, seed) always produces exactly the same frames
+pub struct SyntheticSource {
+    waveform: Waveform,
+    framerate: u32,
+    seed: u64,
+    frame_index: u64,
+    // Stop after this many frames (None = never stop)
+    limit: Option<u64>,
+    // When the next frame is due, so we produce frames at `framerate` like cava would
+    next_frame_at: Option<Instant>,
+}
+
+impl SyntheticSource {
+    pub fn new(waveform: Waveform, framerate: u32, seed: u64) -> SyntheticSource {
+        SyntheticSource {
+            waveform,
+            framerate,
+            seed,
+            frame_index: 0,
+            limit: None,
+            next_frame_at: None,
+        }
+    }
+
+    // Only produce `frames` frames and then end the stream
+    pub fn limit(mut self, frames: u64) -> SyntheticSource {
+        self.limit = Some(frames);
+        self
+    }
+}
+
+impl AudioSource for SyntheticSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if self.limit.is_some_and(|limit| self.frame_index >= limit) {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        // Wait until the frame is due. We add the period to the previous deadline
+        // (instead of sleeping a fixed amount) so small delays don't add up over time
+        let period = Duration::from_secs(1) / self.framerate.max(1);
+        let now = Instant::now();
+        let due = self.next_frame_at.unwrap_or(now);
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+        self.next_frame_at = Some(due + period);
+
+        let bars = buf.len() / 2;
+        let values = generate(
+            self.waveform,
+            self.frame_index,
+            bars,
+            self.seed,
+            self.framerate,
+        );
+        for (i, value) in values.iter().enumerate() {
+            buf[2 * i..2 * i + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        self.frame_index += 1;
+        Ok(())
+    }
+}
+
+// The generators are pure functions: the same arguments always give the same frame
+// They don't remember anything between frames, everything is computed from the frame index
+pub fn generate(
+    waveform: Waveform,
+    frame_index: u64,
+    bars: usize,
+    seed: u64,
+    framerate: u32,
+) -> Vec<u16> {
+    match waveform {
+        Waveform::SineSweep => sine_sweep(frame_index, bars, seed),
+        Waveform::RandomWalk => random_walk(frame_index, bars, seed),
+        Waveform::Pulse { bpm } => pulse_train(frame_index, bars, seed, bpm, framerate),
+    }
+}
+
+// How many frames the bump needs to cross all the bars
+const SWEEP_FRAMES: u64 = 120;
+
+pub fn sine_sweep(frame_index: u64, bars: usize, seed: u64) -> Vec<u16> {
+    // The seed shifts where in the sweep we start
+    let phase = ((frame_index + seed) % SWEEP_FRAMES) as f64 / SWEEP_FRAMES as f64;
+    let center = phase * bars as f64;
+
+    (0..bars)
+        .map(|bar| {
+            // Half a cosine wave, 4 bars wide, centered on the bump
+            let distance = (bar as f64 + 0.5 - center).abs();
+            let height = if distance < 2.0 {
+                (distance / 2.0 * PI / 2.0).cos()
+            } else {
+                0.0
+            };
+            to_u16(height)
+        })
+        .collect()
+}
+
+// Every bar picks a new random target every KNOT_FRAMES frames and
+// slides towards it, so it looks like a (smooth) random walk
+const KNOT_FRAMES: u64 = 15;
+
+pub fn random_walk(frame_index: u64, bars: usize, seed: u64) -> Vec<u16> {
+    let knot = frame_index / KNOT_FRAMES;
+    let t = (frame_index % KNOT_FRAMES) as f64 / KNOT_FRAMES as f64;
+
+    (0..bars)
+        .map(|bar| {
+            let from = random_unit(seed, bar as u64, knot);
+            let to = random_unit(seed, bar as u64, knot + 1);
+            // Smoothstep makes the movement ease in and out of each target
+            let t = t * t * (3.0 - 2.0 * t);
+            to_u16(from + (to - from) * t)
+        })
+        .collect()
+}
+
+pub fn pulse_train(frame_index: u64, bars: usize, seed: u64, bpm: u32, framerate: u32) -> Vec<u16> {
+    // How many frames there are between two beats
+    let frames_per_beat = (framerate as f64 * 60.0 / bpm.max(1) as f64).max(1.0);
+    let since_beat = (frame_index as f64) % frames_per_beat;
+    // The kick fades out in about a fifth of a second
+    let envelope = (-since_beat / (framerate as f64 * 0.2)).exp();
+
+    (0..bars)
+        .map(|bar| {
+            // Bass bars (on the left) get hit the hardest
+            let weight = 1.0 - bar as f64 / bars as f64;
+            // A little bit of noise so the bars don't look perfectly synthetic
+            let noise = random_unit(seed, bar as u64, frame_index) * 0.1;
+            to_u16(envelope * weight * 0.9 + noise)
+        })
+        .collect()
+}
+
+// A number between 0.0 and 1.0 that only depends on its inputs
+// This is the splitmix64 mixing function: a cheap hash where every input bit
+// affects every output bit, good enough to look random
+fn random_unit(seed: u64, bar: u64, step: u64) -> f64 {
+    let mut x = seed
+        .wrapping_add(bar.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        .wrapping_add(step.wrapping_mul(0xD1B5_4A32_D192_ED03));
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+fn to_u16(unit: f64) -> u16 {
+    (unit.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // If one of these changes, the frames every demo and test sees change with it
+    #[test]
+    fn sine_sweep_golden_values() {
+        assert_eq!(sine_sweep(0, 8, 0), [60546, 25079, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            sine_sweep(30, 8, 0),
+            [25079, 60546, 60546, 25079, 0, 0, 0, 0]
+        );
+        // The seed only moves the bump
+        assert_eq!(
+            sine_sweep(0, 8, 42),
+            [0, 34242, 63724, 55878, 15299, 0, 0, 0]
+        );
+        assert_eq!(sine_sweep(30, 8, 42), sine_sweep(72, 8, 0));
+    }
+
+    #[test]
+    fn random_walk_golden_values() {
+        assert_eq!(random_walk(0, 6, 0), [0, 57888, 28280, 1732, 63627, 6969]);
+        assert_eq!(
+            random_walk(7, 6, 0),
+            [14983, 37026, 29092, 16444, 45603, 20070]
+        );
+        assert_eq!(
+            random_walk(0, 6, 42),
+            [42841, 48598, 10480, 18258, 22557, 2492]
+        );
+        assert_eq!(
+            random_walk(7, 6, 42),
+            [24861, 51611, 6849, 33373, 41751, 19591]
+        );
+    }
+
+    #[test]
+    fn pulse_train_golden_values() {
+        // 120 bpm at 60 fps is a beat every 30 frames
+        assert_eq!(pulse_train(0, 4, 0, 120, 60), [58982, 50025, 32319, 14919]);
+        assert_eq!(pulse_train(15, 4, 0, 120, 60), [21438, 15741, 13008, 8296]);
+        assert_eq!(pulse_train(30, 4, 0, 120, 60), [61377, 46809, 30943, 16671]);
+        assert_eq!(pulse_train(0, 4, 42, 120, 60), [63266, 49096, 30539, 16571]);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_frames() {
+        for waveform in [
+            Waveform::SineSweep,
+            Waveform::RandomWalk,
+            Waveform::Pulse { bpm: 90 },
+        ] {
+            for frame_index in [0, 1, 59, 1000] {
+                assert_eq!(
+                    generate(waveform, frame_index, 16, 7, 60),
+                    generate(waveform, frame_index, 16, 7, 60)
+                );
+            }
+            assert_ne!(
+                generate(waveform, 3, 16, 7, 60),
+                generate(waveform, 3, 16, 8, 60)
+            );
+        }
+    }
+
+    #[test]
+    fn the_source_plays_the_generated_frames_and_stops_at_the_limit() {
+        let mut source = SyntheticSource::new(Waveform::RandomWalk, 1000, 5).limit(3);
+        let mut buf = [0_u8; 8];
+
+        for frame_index in 0..3 {
+            source.read_frame(&mut buf).unwrap();
+            let expected: Vec<u8> = generate(Waveform::RandomWalk, frame_index, 4, 5, 1000)
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            assert_eq!(buf.as_slice(), expected);
+        }
+        let end = source.read_frame(&mut buf).unwrap_err();
+        assert_eq!(end.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
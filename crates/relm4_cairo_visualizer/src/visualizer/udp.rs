@@ -0,0 +1,132 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use super::{Frame, Visualizer};
+
+// The OSC address every frame is sent to
+pub const OSC_ADDRESS: &str = "/cava/bars";
+
+// How a frame is turned into a UDP packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketFormat {
+    // The sequence number (u64) followed by the values (u16), all little endian
+    // The number of bars is (packet length - 8) / 2
+    Binary,
+    // An OSC message to `/cava/bars` with one float (0.0 to 1.0) per bar, which
+    // lighting and music software (TouchDesigner, Max, SuperCollider...) understands
+    Osc,
+}
+
+pub fn encode_binary(frame: &Frame) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(8 + 2 * frame.values.len());
+    packet.extend_from_slice(&frame.seq.to_le_bytes());
+    for value in &frame.values {
+        packet.extend_from_slice(&value.to_le_bytes());
+    }
+    packet
+}
+
+// An OSC message is made of:
+// - the address, a string
+// - the type tags, a string starting with a comma and one letter per argument ("f" = float32)
+// - the arguments, big endian
+// Strings end with a 0 byte and are padded with more 0 bytes to a multiple of 4 bytes
+pub fn encode_osc(frame: &Frame) -> Vec<u8> {
+    let mut packet = Vec::new();
+    push_osc_string(&mut packet, OSC_ADDRESS);
+
+    let type_tags: String = std::iter::once(',')
+        .chain(frame.values.iter().map(|_| 'f'))
+        .collect();
+    push_osc_string(&mut packet, &type_tags);
+
+    for &value in &frame.values {
+        let normalized = value as f32 / u16::MAX as f32;
+        packet.extend_from_slice(&normalized.to_be_bytes());
+    }
+    packet
+}
+
+fn push_osc_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend_from_slice(s.as_bytes());
+    // At least one 0 byte, then as many as needed to reach a multiple of 4
+    let padding = 4 - s.len() % 4;
+    packet.resize(packet.len() + padding, 0);
+}
+
+// Sends every frame of a visualizer to `target` over UDP
+//
+// It's a regular subscriber with its own thread, so the reader thread never waits
+// on the network: when the socket can't keep up, the subscriber queue fills and the
+// broadcaster drops frames for it (see `Visualizer::dropped_per_subscriber`).
+// Errors while sending (nobody listening, network down...) are counted and the next
+// frame is sent anyway, UDP has no connection that could break for good
+pub struct UdpSink {
+    local_addr: SocketAddr,
+    packets_sent: Arc<AtomicU64>,
+    send_errors: Arc<AtomicU64>,
+}
+
+impl UdpSink {
+    pub fn attach(
+        visualizer: &Visualizer,
+        target: SocketAddr,
+        format: PacketFormat,
+    ) -> io::Result<UdpSink> {
+        // Let the OS pick the port we send from, on the same IP version as the target
+        let bind_addr: SocketAddr = if target.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(bind_addr)?;
+        // `connect` on a UDP socket only sets the default destination for `send`
+        socket.connect(target)?;
+        let local_addr = socket.local_addr()?;
+
+        let packets_sent = Arc::new(AtomicU64::new(0));
+        let send_errors = Arc::new(AtomicU64::new(0));
+        let thread_sent = packets_sent.clone();
+        let thread_errors = send_errors.clone();
+
+        let frames = visualizer.subscribe();
+        std::thread::spawn(move || {
+            // The loop ends when the pipeline shuts down
+            for frame in frames {
+                let packet = match format {
+                    PacketFormat::Binary => encode_binary(&frame),
+                    PacketFormat::Osc => encode_osc(&frame),
+                };
+                match socket.send(&packet) {
+                    Ok(_) => thread_sent.fetch_add(1, Ordering::Relaxed),
+                    Err(_) => thread_errors.fetch_add(1, Ordering::Relaxed),
+                };
+            }
+        });
+
+        Ok(UdpSink {
+            local_addr,
+            packets_sent,
+            send_errors,
+        })
+    }
+
+    // The address the packets come from
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn send_errors(&self) -> u64 {
+        self.send_errors.load(Ordering::Relaxed)
+    }
+}
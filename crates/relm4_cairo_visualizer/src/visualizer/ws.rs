@@ -0,0 +1,174 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use tungstenite::Message;
+
+use super::Frame;
+
+// How many frames a client can be behind before we give up on it
+pub const CLIENT_QUEUE: usize = 16;
+
+// How often the accept loop checks if it should stop
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+
+// Serialize the values as a JSON array, e.g. `[0,1200,65535]`
+// It's just numbers and commas, no need for a JSON library
+pub fn frame_to_json(frame: &Frame) -> String {
+    let values: Vec<String> = frame.values.iter().map(u16::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+// A WebSocket server pushing every frame to the connected browsers as a JSON array
+// Built with `Visualizer::ws_server`, it runs on its own threads:
+// - the accept thread takes new connections and does the WebSocket handshake
+// - the fan-out thread reads the frames (it's a regular subscriber) and puts them in
+//   the queue of every client
+// - each client has a thread writing its queue to the socket
+//
+// A client whose queue is full is disconnected: it's better for it to reconnect than
+// to get frames from seconds ago, and it keeps the memory bounded.
+// The server stops when the pipeline shuts down or when this handle is dropped
+pub struct WsServer {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl WsServer {
+    // `max_rate` is the most frames per second a client gets, the frames in between
+    // are skipped (a browser drawing at 30 fps doesn't need 60 frames a second)
+    pub(crate) fn start(
+        frames: Receiver<Arc<Frame>>,
+        addr: impl ToSocketAddrs,
+        max_rate: u32,
+    ) -> io::Result<WsServer> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        // A blocking `accept` would never notice that it should stop, so we poll
+        listener.set_nonblocking(true)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let min_interval = Duration::from_secs(1) / max_rate.max(1);
+
+        let (clients_tx, clients_rx) = mpsc::channel();
+
+        let accept_stop = stop.clone();
+        thread::spawn(move || {
+            while !accept_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let (tx, rx) = mpsc::sync_channel(CLIENT_QUEUE);
+                        if clients_tx.send(tx).is_err() {
+                            break;
+                        }
+                        thread::spawn(move || serve_client(stream, rx, min_interval));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL);
+                    }
+                    Err(e) => eprintln!("websocket accept failed: {}", e),
+                }
+            }
+        });
+
+        let fan_out_stop = stop.clone();
+        thread::spawn(move || {
+            fan_out(frames, clients_rx, &fan_out_stop);
+            // Whatever ended the fan-out, the accept thread has to stop too
+            fan_out_stop.store(true, Ordering::Relaxed);
+        });
+
+        Ok(WsServer { local_addr, stop })
+    }
+
+    // The address the server listens on, useful after binding to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn fan_out(
+    frames: Receiver<Arc<Frame>>,
+    new_clients: Receiver<SyncSender<Arc<Frame>>>,
+    stop: &AtomicBool,
+) {
+    let mut clients: Vec<SyncSender<Arc<Frame>>> = Vec::new();
+
+    // The loop ends when the pipeline shuts down, dropping the senders tells
+    // every client thread to close its connection
+    for frame in frames {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+
+        while let Ok(client) = new_clients.try_recv() {
+            clients.push(client);
+        }
+
+        // A full queue (the client is too slow) and a disconnected one (the client left)
+        // both end the same way: we drop the sender, which makes the client thread
+        // close the connection if it is still open
+        clients.retain(|client| client.try_send(frame.clone()).is_ok());
+    }
+}
+
+fn serve_client(stream: TcpStream, frames: Receiver<Arc<Frame>>, min_interval: Duration) {
+    // The listener is non blocking, but the client socket should block on writes
+    if stream.set_nonblocking(false).is_err() {
+        return;
+    }
+
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("websocket handshake failed: {}", e);
+            return;
+        }
+    };
+
+    let mut last_sent: Option<Instant> = None;
+    for frame in &frames {
+        if last_sent.is_some_and(|last| last.elapsed() < min_interval) {
+            continue;
+        }
+        last_sent = Some(Instant::now());
+
+        // The browser went away
+        if socket.send(Message::text(frame_to_json(&frame))).is_err() {
+            return;
+        }
+    }
+
+    // The server stopped or we were too slow, say goodbye properly
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_flat_json_arrays() {
+        assert_eq!(
+            frame_to_json(&Frame::mono(vec![0, 1200, 65535])),
+            "[0,1200,65535]"
+        );
+        assert_eq!(frame_to_json(&Frame::mono(vec![7])), "[7]");
+        assert_eq!(frame_to_json(&Frame::mono(vec![])), "[]");
+    }
+}
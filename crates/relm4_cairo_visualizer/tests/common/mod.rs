@@ -0,0 +1,38 @@
+// Helpers shared by the integration tests
+use std::{
+    io,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use relm4_cairo_visualizer::visualizer::{AudioSource, ScriptedSource};
+
+// Holds the frames back until the test says go, so the subscribers are all
+// in place before the first frame goes out
+pub struct GatedSource {
+    go: Receiver<()>,
+    frames: ScriptedSource,
+    started: bool,
+}
+
+impl GatedSource {
+    // The source, and what to send on once everybody subscribed
+    pub fn new(frames: Vec<Vec<u16>>) -> (GatedSource, Sender<()>) {
+        let (go, gate) = mpsc::channel();
+        let source = GatedSource {
+            go: gate,
+            frames: ScriptedSource::new(frames),
+            started: false,
+        };
+        (source, go)
+    }
+}
+
+impl AudioSource for GatedSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if !self.started {
+            let _ = self.go.recv();
+            self.started = true;
+        }
+        self.frames.read_frame(buf)
+    }
+}
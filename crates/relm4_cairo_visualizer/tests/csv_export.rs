@@ -0,0 +1,122 @@
+// Capturing frames to CSV, the exact text that comes out
+mod common;
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use common::GatedSource;
+use relm4_cairo_visualizer::visualizer::{
+    CaptureLimit, CsvSink, Frame, Visualizer, VisualizerConfig,
+};
+
+// A frame captured `ms` milliseconds after `start`
+fn frame(start: Instant, ms: u64, values: &[u16]) -> Frame {
+    Frame {
+        captured_at: start + Duration::from_millis(ms),
+        ..Frame::mono(values.to_vec())
+    }
+}
+
+fn capture(limit: CaptureLimit, frames: &[Frame]) -> String {
+    let mut sink = CsvSink::new(Vec::new(), limit);
+    for frame in frames {
+        if !sink.write_frame(frame).unwrap() {
+            break;
+        }
+    }
+    String::from_utf8(sink.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn one_row_per_frame() {
+    let start = Instant::now();
+    let frames = [
+        frame(start, 0, &[120, 3400, 65535]),
+        frame(start, 16, &[118, 3390, 60012]),
+        frame(start, 33, &[0, 0, 0]),
+    ];
+
+    assert_eq!(
+        capture(CaptureLimit::Frames(10), &frames),
+        "timestamp_ms,bar_0,bar_1,bar_2\n\
+         0,120,3400,65535\n\
+         16,118,3390,60012\n\
+         33,0,0,0\n"
+    );
+}
+
+#[test]
+fn a_new_bar_count_starts_a_new_section() {
+    let start = Instant::now();
+    let frames = [
+        frame(start, 0, &[1, 2]),
+        frame(start, 10, &[3, 4, 5]),
+        frame(start, 20, &[6, 7, 8]),
+        frame(start, 30, &[9, 10]),
+    ];
+
+    assert_eq!(
+        capture(CaptureLimit::Frames(10), &frames),
+        "timestamp_ms,bar_0,bar_1\n\
+         0,1,2\n\
+         \n\
+         timestamp_ms,bar_0,bar_1,bar_2\n\
+         10,3,4,5\n\
+         20,6,7,8\n\
+         \n\
+         timestamp_ms,bar_0,bar_1\n\
+         30,9,10\n"
+    );
+}
+
+#[test]
+fn the_capture_stops_at_the_limit() {
+    let start = Instant::now();
+    let frames: Vec<_> = (0..5).map(|i| frame(start, i * 100, &[i as u16])).collect();
+
+    let by_frames = capture(CaptureLimit::Frames(2), &frames);
+    assert_eq!(by_frames, "timestamp_ms,bar_0\n0,0\n100,1\n");
+    // The frame right at the duration still goes in
+    let by_duration = capture(CaptureLimit::Duration(Duration::from_millis(200)), &frames);
+    assert_eq!(by_duration, "timestamp_ms,bar_0\n0,0\n100,1\n200,2\n");
+}
+
+#[test]
+fn export_csv_records_the_scripted_frames() {
+    let path = std::env::temp_dir().join(format!("visualizer-export-{}.csv", std::process::id()));
+    let (source, go) = GatedSource::new(vec![vec![10; 2], vec![20; 2], vec![30; 2]]);
+    let visualizer = Visualizer::from_source(Box::new(source), VisualizerConfig::new(2)).unwrap();
+
+    let capture = visualizer
+        .export_csv(&path, Duration::from_secs(60))
+        .unwrap();
+    go.send(()).unwrap();
+    // The source runs out, which ends the capture before the minute is up
+    assert_eq!(capture.join().unwrap().unwrap(), 3);
+
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    // The timestamps depend on how fast the frames went through, leave them out
+    let without_timestamps: Vec<_> = text
+        .lines()
+        .map(|line| line.split_once(',').unwrap().1)
+        .collect();
+    assert_eq!(
+        without_timestamps,
+        ["bar_0,bar_1", "10,10", "20,20", "30,30"]
+    );
+}
+
+#[test]
+fn export_csv_fails_on_a_bad_path() {
+    let visualizer = Visualizer::from_source(
+        Box::new(GatedSource::new(vec![]).0),
+        VisualizerConfig::new(2),
+    )
+    .unwrap();
+    assert!(visualizer
+        .export_csv("/nonexistent/dir/frames.csv", Duration::from_secs(1))
+        .is_err());
+}
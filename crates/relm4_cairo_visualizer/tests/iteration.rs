@@ -0,0 +1,61 @@
+// The ways of getting the frames out of the handle: iter, next_timeout and for loops
+mod common;
+
+use std::{sync::mpsc::RecvTimeoutError, time::Duration};
+
+use common::GatedSource;
+use relm4_cairo_visualizer::visualizer::{ScriptedSource, Visualizer, VisualizerConfig};
+
+fn scripted(frames: usize) -> Visualizer {
+    let frames = (0..frames).map(|i| vec![i as u16 * 10; 2]).collect();
+    let source = ScriptedSource::new(frames);
+    Visualizer::from_source(Box::new(source), VisualizerConfig::new(2)).unwrap()
+}
+
+#[test]
+fn iter_ends_when_the_pipeline_shuts_down() {
+    let visualizer = scripted(5);
+
+    let seqs: Vec<_> = visualizer.iter().map(|frame| frame.seq).collect();
+    assert_eq!(seqs, vec![0, 1, 2, 3, 4]);
+    // And stays ended, without blocking
+    assert!(visualizer.iter().next().is_none());
+}
+
+#[test]
+fn a_for_loop_takes_the_handle_or_borrows_it() {
+    let visualizer = scripted(3);
+    let mut values = Vec::new();
+    for frame in &visualizer {
+        values.push(frame.values[0]);
+    }
+    assert_eq!(values, vec![0, 10, 20]);
+    // Still usable after the loop
+    assert_eq!(visualizer.stats().frames_received(), 3);
+
+    let mut count = 0;
+    for _frame in scripted(4) {
+        count += 1;
+    }
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn next_timeout_tells_a_slow_source_from_a_stopped_one() {
+    let (source, go) = GatedSource::new(vec![vec![7; 2]]);
+    let visualizer = Visualizer::from_source(Box::new(source), VisualizerConfig::new(2)).unwrap();
+
+    // Nothing yet: the source holds its frame until told to go
+    assert!(matches!(
+        visualizer.next_timeout(Duration::from_millis(50)),
+        Err(RecvTimeoutError::Timeout)
+    ));
+    go.send(()).unwrap();
+    let frame = visualizer.next_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(frame.values, vec![7, 7]);
+    // The source is done, the pipeline with it
+    assert!(matches!(
+        visualizer.next_timeout(Duration::from_secs(5)),
+        Err(RecvTimeoutError::Disconnected)
+    ));
+}
@@ -0,0 +1,126 @@
+// Drive the whole pipeline (decoding, stages, delivery) from frames written by hand,
+// no cava or sound card needed
+mod common;
+
+use std::{io, time::Duration};
+
+use common::GatedSource;
+use relm4_cairo_visualizer::visualizer::{
+    AudioSource, ChannelLayout, Ordering, ScriptedSource, Visualizer, VisualizerConfig,
+    VisualizerError,
+};
+
+// Without a smoothing time constant the frames aren't blended together,
+// so every frame can be checked on its own
+fn config(bars: usize) -> VisualizerConfig {
+    VisualizerConfig::new(bars)
+}
+
+fn scripted(frames: Vec<Vec<u16>>) -> Box<dyn AudioSource + Send> {
+    Box::new(ScriptedSource::new(frames))
+}
+
+#[test]
+fn frames_go_through_the_whole_pipeline() {
+    let frames = vec![vec![0, 300, 0, 300], vec![600; 4]];
+    let visualizer = Visualizer::from_source(scripted(frames), config(4)).unwrap();
+
+    let received: Vec<_> = visualizer.into_iter().collect();
+
+    assert_eq!(received.len(), 2);
+    // Every bar is averaged with its neighbours
+    assert_eq!(received[0].values, vec![150, 100, 200, 150]);
+    assert_eq!(received[1].values, vec![600; 4]);
+    for (seq, frame) in received.iter().enumerate() {
+        assert_eq!(frame.seq, seq as u64);
+        assert_eq!(frame.bars, 4);
+        assert_eq!(frame.layout, ChannelLayout::Mono);
+    }
+}
+
+#[test]
+fn the_bars_are_reordered_after_the_smoothing() {
+    let frames = vec![vec![0, 300, 600, 900, 1200]];
+    let config = config(5).ordering(Ordering::CenterOut);
+    let visualizer = Visualizer::from_source(scripted(frames), config).unwrap();
+
+    let frame = visualizer.iter().next().unwrap();
+    // Smoothed in cava's order ([150, 300, 600, 900, 1050]), then fanned out
+    assert_eq!(frame.values, vec![900, 300, 150, 600, 1050]);
+}
+
+#[test]
+fn a_smoothing_time_constant_blends_the_frames() {
+    // 50ms are 3 frames at the default 60 fps
+    let config = config(2).smoothing_time_constant(Duration::from_millis(50));
+    let frames = vec![vec![0; 2], vec![300; 2], vec![600; 2], vec![900; 2]];
+    let visualizer = Visualizer::from_source(scripted(frames), config).unwrap();
+
+    let values: Vec<_> = visualizer.iter().map(|frame| frame.values[0]).collect();
+    assert_eq!(values, vec![0, 150, 300, 600]);
+}
+
+#[test]
+fn every_subscriber_gets_every_frame() {
+    let (source, go) = GatedSource::new(vec![vec![10; 3], vec![20; 3], vec![30; 3]]);
+    let visualizer = Visualizer::from_source(Box::new(source), config(3)).unwrap();
+    let first = visualizer.subscribe();
+    let second = visualizer.subscribe();
+    go.send(()).unwrap();
+
+    for frames in [visualizer.iter(), first.iter(), second.iter()] {
+        let values: Vec<_> = frames.map(|frame| frame.values.clone()).collect();
+        assert_eq!(values, vec![vec![10; 3], vec![20; 3], vec![30; 3]]);
+    }
+    assert_eq!(visualizer.dropped_per_subscriber(), vec![0, 0, 0]);
+}
+
+#[test]
+fn the_stream_ends_with_the_source() {
+    let frames = vec![vec![1, 2], vec![3, 4], vec![5, 6]];
+    let visualizer = Visualizer::from_source(scripted(frames), config(2)).unwrap();
+
+    assert_eq!(visualizer.iter().count(), 3);
+    assert_eq!(visualizer.stats().frames_received(), 3);
+    let seqs: Vec<_> = visualizer.history().iter().map(|frame| frame.seq).collect();
+    assert_eq!(seqs, vec![0, 1, 2]);
+    // Running out of frames is not an error
+    assert!(visualizer.last_error().is_none());
+}
+
+#[test]
+fn a_frame_of_the_wrong_size_stops_the_pipeline() {
+    let frames = vec![vec![7; 4], vec![7; 3], vec![7; 4]];
+    let visualizer = Visualizer::from_source(scripted(frames), config(4)).unwrap();
+
+    assert_eq!(visualizer.iter().count(), 1);
+    assert!(matches!(
+        visualizer.last_error(),
+        Some(VisualizerError::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+    ));
+}
+
+#[test]
+fn an_invalid_config_is_rejected_before_starting() {
+    for config in [
+        config(4).framerate(0),
+        config(0),
+        config(u16::MAX as usize + 1),
+    ] {
+        let result = Visualizer::from_source(scripted(vec![]), config);
+        assert!(matches!(
+            result.err(),
+            Some(VisualizerError::InvalidConfig(_))
+        ));
+    }
+}
+
+#[test]
+fn only_cava_can_be_nudged() {
+    // There is no cava to restart with another sensitivity
+    let visualizer = Visualizer::from_source(scripted(vec![vec![7; 4]]), config(4)).unwrap();
+    assert!(matches!(
+        visualizer.nudge_sensitivity(10),
+        Err(VisualizerError::NotRestartable)
+    ));
+}
@@ -0,0 +1,23 @@
+// `--source synthetic` goes through the same checks and pipeline as cava
+use relm4_cairo_visualizer::visualizer::{Visualizer, VisualizerConfig, VisualizerError, Waveform};
+
+#[test]
+fn a_framerate_of_zero_is_rejected() {
+    let config = VisualizerConfig::new(8).framerate(0);
+    let result = Visualizer::synthetic(config, Waveform::SineSweep, 0);
+    assert!(matches!(
+        result.err(),
+        Some(VisualizerError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn frames_keep_coming_at_the_framerate() {
+    let config = VisualizerConfig::new(8).framerate(200);
+    let visualizer = Visualizer::synthetic(config, Waveform::Pulse { bpm: 120 }, 3).unwrap();
+
+    let frames: Vec<_> = visualizer.iter().take(5).collect();
+    let seqs: Vec<_> = frames.iter().map(|frame| frame.seq).collect();
+    assert_eq!(seqs, vec![0, 1, 2, 3, 4]);
+    assert!(frames.iter().all(|frame| frame.values.len() == 8));
+}
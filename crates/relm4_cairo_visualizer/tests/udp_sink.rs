@@ -0,0 +1,81 @@
+// Send a few frames over the loopback interface and decode them on the other side
+mod common;
+
+use std::{net::UdpSocket, time::Duration};
+
+use common::GatedSource;
+use relm4_cairo_visualizer::visualizer::{
+    udp::OSC_ADDRESS, PacketFormat, UdpSink, Visualizer, VisualizerConfig,
+};
+
+const FRAMES: [[u16; 3]; 3] = [[0, 100, 200], [65535, 0, 1], [7, 7, 7]];
+
+// Start a visualizer playing FRAMES into a sink sending to a fresh socket
+// Returns everything that must stay alive until the packets are read
+fn send_frames(format: PacketFormat) -> (UdpSocket, UdpSink, Visualizer) {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let (source, go) = GatedSource::new(FRAMES.iter().map(|frame| frame.to_vec()).collect());
+    let visualizer = Visualizer::from_source(Box::new(source), VisualizerConfig::new(3)).unwrap();
+    let sink = UdpSink::attach(&visualizer, receiver.local_addr().unwrap(), format).unwrap();
+    go.send(()).unwrap();
+
+    (receiver, sink, visualizer)
+}
+
+fn receive(socket: &UdpSocket) -> Vec<u8> {
+    let mut buf = [0_u8; 1024];
+    let len = socket.recv(&mut buf).expect("no packet within 5s");
+    buf[..len].to_vec()
+}
+
+// The values go through the smoother before they are sent
+fn smoothed(frame: &[u16]) -> Vec<u16> {
+    relm4_cairo_visualizer::visualizer::pipeline::smooth(frame, 3)
+}
+
+#[test]
+fn binary_packets_carry_the_seq_and_the_values() {
+    let (receiver, sink, _visualizer) = send_frames(PacketFormat::Binary);
+
+    for (seq, frame) in FRAMES.iter().enumerate() {
+        let packet = receive(&receiver);
+        assert_eq!(packet.len(), 8 + 2 * 3);
+        assert_eq!(
+            u64::from_le_bytes(packet[..8].try_into().unwrap()),
+            seq as u64
+        );
+        let values: Vec<u16> = packet[8..]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(values, smoothed(frame));
+    }
+    assert_eq!(sink.send_errors(), 0);
+}
+
+#[test]
+fn osc_packets_carry_one_float_per_bar() {
+    let (receiver, _sink, _visualizer) = send_frames(PacketFormat::Osc);
+
+    for frame in FRAMES {
+        let packet = receive(&receiver);
+        // "/cava/bars" is 10 bytes, padded to 12, ",fff" to 8 (there is always a 0 byte)
+        assert_eq!(&packet[..10], OSC_ADDRESS.as_bytes());
+        assert_eq!(&packet[10..12], [0, 0]);
+        assert_eq!(&packet[12..20], b",fff\0\0\0\0");
+
+        let floats: Vec<f32> = packet[20..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_be_bytes(bytes.try_into().unwrap()))
+            .collect();
+        let expected: Vec<f32> = smoothed(&frame)
+            .iter()
+            .map(|&value| value as f32 / u16::MAX as f32)
+            .collect();
+        assert_eq!(floats, expected);
+    }
+}
@@ -0,0 +1,48 @@
+// A WebSocket client reading the frames the way a browser would
+#![cfg(feature = "ws")]
+
+mod common;
+
+use common::GatedSource;
+use relm4_cairo_visualizer::visualizer::{Visualizer, VisualizerConfig};
+use tungstenite::Message;
+
+// The values of a frame, checking it's a flat JSON array of integers on the way
+fn parse_frame(json: &str) -> Vec<u16> {
+    let inner = json
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or_else(|| panic!("not a JSON array: {}", json));
+    inner
+        .split(',')
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("not a number in {}", json))
+        })
+        .collect()
+}
+
+#[test]
+fn a_client_gets_the_frames_as_json_arrays() {
+    let frames = vec![vec![100; 4], vec![200; 4], vec![300; 4]];
+    let (source, go) = GatedSource::new(frames);
+    let visualizer = Visualizer::from_source(Box::new(source), VisualizerConfig::new(4)).unwrap();
+    // No rate limit, every frame goes out
+    let server = visualizer.ws_server("127.0.0.1:0", u32::MAX).unwrap();
+    let url = format!("ws://{}", server.local_addr());
+    let (mut client, _) = tungstenite::connect(url).unwrap();
+    go.send(()).unwrap();
+
+    let mut received = Vec::new();
+    while let Ok(message) = client.read() {
+        match message {
+            Message::Text(json) => received.push(parse_frame(&json)),
+            Message::Close(_) => break,
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    // And the server said goodbye once the source was done
+    assert_eq!(received, [[100; 4], [200; 4], [300; 4]]);
+}
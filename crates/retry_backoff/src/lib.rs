@@ -0,0 +1,285 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// When a call to another service fails it is often a temporary problem (a timeout,
+// a restarting server...), so trying again a bit later is worth it.
+// But retrying right away, and all clients at the same moment, is exactly what keeps an
+// overloaded server down. Exponential backoff waits longer after every failure
+// (100ms, 200ms, 400ms...) and jitter spreads the clients over time so they don't
+// all come back in the same millisecond.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // How many times we call the function in total (the first call included)
+    pub max_attempts: u32,
+    // The wait after the first failure, it doubles after every other failure
+    pub base_delay: Duration,
+    // The wait never grows beyond this
+    pub max_delay: Duration,
+    // Randomly add or remove up to 20% of every wait
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    pub fn builder() -> RetryPolicyBuilder {
+        RetryPolicyBuilder::default()
+    }
+
+    // The wait after the failed attempt number `attempt` (starting at 0), without jitter:
+    // base_delay * 2^attempt, capped at max_delay
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        // 2^attempt overflows quickly, once it would we are way past max_delay anyway
+        let factor = 2_u32.checked_pow(attempt).unwrap_or(u32::MAX);
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+// The default policy: 3 attempts, 100ms then 200ms of wait, at most 10s, with jitter
+//
+//   let policy = RetryPolicy::builder()
+//       .max_attempts(5)
+//       .base_delay(Duration::from_millis(50))
+//       .build();
+pub struct RetryPolicyBuilder {
+    policy: RetryPolicy,
+}
+
+impl Default for RetryPolicyBuilder {
+    fn default() -> Self {
+        RetryPolicyBuilder {
+            policy: RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(100),
+                max_delay: Duration::from_secs(10),
+                jitter: true,
+            },
+        }
+    }
+}
+
+impl RetryPolicyBuilder {
+    pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicyBuilder {
+        self.policy.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> RetryPolicyBuilder {
+        self.policy.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> RetryPolicyBuilder {
+        self.policy.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicyBuilder {
+        self.policy.jitter = jitter;
+        self
+    }
+
+    pub fn build(self) -> RetryPolicy {
+        self.policy
+    }
+}
+
+// Call `f` until it succeeds or we run out of attempts
+// `f` returns a new future every time because a future can only be awaited once,
+// so retrying means building the request again.
+// When every attempt failed we return the error of the last one
+pub async fn retry<F, Fut, T, E>(policy: &RetryPolicy, f: F) -> Result<T, E>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut rng = seed();
+    let mut attempt = 0;
+
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                attempt += 1;
+                // max_attempts(0) behaves like 1, we always try at least once
+                if attempt >= policy.max_attempts {
+                    return Err(e);
+                }
+
+                let mut delay = policy.delay_for(attempt - 1);
+                if policy.jitter {
+                    // A factor between 0.8 and 1.2
+                    delay = delay.mul_f64(0.8 + 0.4 * next_random(&mut rng));
+                }
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+// A tiny xorshift pseudo random generator, the jitter doesn't need anything
+// better than that and we avoid pulling in the `rand` crate
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    // xorshift gets stuck on 0, so make sure at least one bit is set
+    nanos | 1
+}
+
+// A number between 0.0 and 1.0
+fn next_random(state: &mut u64) -> f64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    (*state >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+// Pretends to be an HTTP call to a flaky server that only answers on the third try
+async fn fetch_status(calls: &AtomicU32) -> Result<&'static str, String> {
+    let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+    if call <= 2 {
+        println!("  call #{}: 503 Service Unavailable", call);
+        Err(format!("call #{} failed with 503", call))
+    } else {
+        println!("  call #{}: 200 OK", call);
+        Ok("200 OK")
+    }
+}
+
+pub fn run() {
+    let policy = RetryPolicy::builder()
+        .max_attempts(5)
+        .base_delay(Duration::from_millis(50))
+        .max_delay(Duration::from_secs(1))
+        .build();
+
+    let delays: Vec<Duration> = (0..policy.max_attempts - 1)
+        .map(|attempt| policy.delay_for(attempt))
+        .collect();
+    println!("delays between attempts (before jitter): {:?}", delays);
+
+    // The rest of the playground is synchronous, so we start a runtime just for this demo
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("Failed to start the tokio runtime");
+
+    let calls = AtomicU32::new(0);
+    let start = Instant::now();
+    let result = runtime.block_on(retry(&policy, || fetch_status(&calls)));
+    println!(
+        "got {:?} after {} calls in {:?}",
+        result,
+        calls.load(Ordering::SeqCst),
+        start.elapsed()
+    );
+    assert_eq!(result, Ok("200 OK"));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // With only 2 attempts the server never gets to answer, we get the last error back
+    let calls = AtomicU32::new(0);
+    let short_policy = RetryPolicy {
+        max_attempts: 2,
+        ..policy
+    };
+    let result = runtime.block_on(retry(&short_policy, || fetch_status(&calls)));
+    println!("with 2 attempts: {:?}", result);
+    assert!(result.is_err());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fails `failures` times, then answers with the number of the call
+    async fn flaky(calls: &AtomicU32, failures: u32) -> Result<u32, String> {
+        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if call <= failures {
+            Err(format!("call #{}", call))
+        } else {
+            Ok(call)
+        }
+    }
+
+    fn policy(max_attempts: u32, jitter: bool) -> RetryPolicy {
+        RetryPolicy::builder()
+            .max_attempts(max_attempts)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(jitter)
+            .build()
+    }
+
+    #[test]
+    fn the_delay_doubles_up_to_the_maximum() {
+        let delays: Vec<_> = (0..6)
+            .map(|attempt| policy(7, false).delay_for(attempt))
+            .collect();
+        let ms = |ms| Duration::from_millis(ms);
+        assert_eq!(
+            delays,
+            [ms(100), ms(200), ms(400), ms(800), ms(1000), ms(1000)]
+        );
+        // Way past what 2^attempt can hold
+        assert_eq!(policy(7, false).delay_for(40), ms(1000));
+        assert_eq!(policy(7, false).delay_for(u32::MAX), ms(1000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_it_succeeds() {
+        let calls = AtomicU32::new(0);
+        let start = tokio::time::Instant::now();
+        let result = retry(&policy(5, false), || flaky(&calls, 2)).await;
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        // 100ms after the first failure, 200ms after the second
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_last_error_comes_back_after_max_attempts() {
+        let calls = AtomicU32::new(0);
+        let start = tokio::time::Instant::now();
+        let result = retry(&policy(4, false), || flaky(&calls, 10)).await;
+
+        assert_eq!(result, Err("call #4".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        // No wait after the last attempt
+        assert_eq!(start.elapsed(), Duration::from_millis(100 + 200 + 400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn at_least_one_attempt() {
+        for max_attempts in [0, 1] {
+            let calls = AtomicU32::new(0);
+            let result = retry(&policy(max_attempts, false), || flaky(&calls, 10)).await;
+            assert_eq!(result, Err("call #1".to_string()));
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    // Every wait is within 20% of the one without jitter
+    #[tokio::test(start_paused = true)]
+    async fn jitter_stays_within_20_percent() {
+        for _ in 0..20 {
+            let calls = AtomicU32::new(0);
+            let start = tokio::time::Instant::now();
+            let _ = retry(&policy(2, true), || flaky(&calls, 10)).await;
+            let waited = start.elapsed();
+            assert!(waited >= Duration::from_millis(80) && waited <= Duration::from_millis(120));
+        }
+    }
+
+    #[test]
+    fn next_random_is_between_0_and_1() {
+        let mut state = seed();
+        assert!((0..1000)
+            .map(|_| next_random(&mut state))
+            .all(|x| (0.0..1.0).contains(&x)));
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// A fixed-capacity queue for exactly one producer thread and one consumer thread (SPSC).
+// It never allocates after creation and never takes a lock: the two threads
+// coordinate through two atomic counters only.
+//
+// - `tail` is only written by the producer: it's the number of items pushed so far
+// - `head` is only written by the consumer: it's the number of items popped so far
+// - `tail - head` is how many items are in the buffer, and `index % N` is the slot
+//   an index maps to
+//
+//   slots:   [ _ | x | x | x | _ ]
+//                  ^           ^
+//                 head        tail
+//
+// The counters only ever grow (wrapping around after usize::MAX), which is what
+// lets us tell a full buffer (tail - head == N) from an empty one (tail == head).
+//
+// The algorithm is only correct when a single thread pushes and a single (other or
+// same) thread pops: two producers could both read the same `tail` and write the same
+// slot. So the buffer itself can't push or pop, `split` turns it into a `Producer`
+// and a `Consumer` half. There is only one of each, they can't be cloned, and pushing
+// or popping takes `&mut self`, so the compiler makes sure there is never a second
+// producer or consumer, even through a shared reference.
+pub struct RingBuffer<T, const N: usize> {
+    // MaybeUninit because most slots are empty most of the time, and UnsafeCell
+    // because we write to the slots through a shared `&self`
+    slots: UnsafeCell<[MaybeUninit<T>; N]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// SAFETY: the buffer moves values of T between threads, so T must be Send.
+// The two halves share the buffer through an Arc. That's fine because a slot is only
+// ever accessed by one of them at a time: the producer writes slots in
+// [tail, head + N) and the consumer reads slots in [head, tail). The Release/Acquire
+// pairs on the counters publish a slot to the other side only after it is fully
+// written (or fully read out). Anybody else holding a `&RingBuffer` can only read
+// the counters, `push` and `pop` are private to the halves.
+unsafe impl<T: Send, const N: usize> Send for RingBuffer<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    pub fn new() -> RingBuffer<T, N> {
+        assert!(N > 0, "a ring buffer needs at least one slot");
+
+        RingBuffer {
+            slots: UnsafeCell::new([const { MaybeUninit::uninit() }; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    // Hand out the two ends of the queue, each one can be moved to its own thread
+    pub fn split(self) -> (Producer<T, N>, Consumer<T, N>) {
+        let buffer = Arc::new(self);
+        let producer = Producer {
+            buffer: buffer.clone(),
+        };
+        (producer, Consumer { buffer })
+    }
+
+    // Only called by the (single) Producer
+    // Gives the item back when the buffer is full, so the caller can retry later
+    fn push(&self, item: T) -> Result<(), T> {
+        // Our own counter, nobody else writes it, so Relaxed is enough
+        let tail = self.tail.load(Ordering::Relaxed);
+        // Acquire pairs with the Release in `pop`: once we see the consumer's new head,
+        // we also see that it finished reading the slot it freed
+        let head = self.head.load(Ordering::Acquire);
+
+        if tail.wrapping_sub(head) == N {
+            return Err(item);
+        }
+
+        // SAFETY: the slot at `tail` is free (the consumer is done with it, see above)
+        // and only the producer writes free slots, so nobody else touches it right now
+        unsafe {
+            let slots = &mut *self.slots.get();
+            slots[tail % N].write(item);
+        }
+
+        // Release publishes the write above: a consumer that sees the new tail
+        // is guaranteed to see the item in the slot
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    // Only called by the (single) Consumer, or by `drop` once both halves are gone
+    fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        // Acquire pairs with the Release in `push`
+        let tail = self.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        // SAFETY: the slot at `head` was initialized by `push` (we saw its Release store)
+        // and the producer won't touch it until we move `head` forward below.
+        // `assume_init_read` moves the value out, the slot is logically empty again
+        let item = unsafe {
+            let slots = &*self.slots.get();
+            slots[head % N].assume_init_read()
+        };
+
+        // Release tells the producer it can reuse the slot
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+
+    // A snapshot: by the time the caller looks at it the other thread may have moved on
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> RingBuffer<T, N> {
+        RingBuffer::new()
+    }
+}
+
+// The pushing end of a ring buffer, see `RingBuffer::split`
+pub struct Producer<T, const N: usize> {
+    buffer: Arc<RingBuffer<T, N>>,
+}
+
+impl<T, const N: usize> Producer<T, N> {
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        self.buffer.push(item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.buffer.len() == N
+    }
+}
+
+// The popping end of a ring buffer, see `RingBuffer::split`
+pub struct Consumer<T, const N: usize> {
+    buffer: Arc<RingBuffer<T, N>>,
+}
+
+impl<T, const N: usize> Consumer<T, N> {
+    pub fn pop(&mut self) -> Option<T> {
+        self.buffer.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+// MaybeUninit never drops its contents, so items still in the buffer
+// would leak if we didn't drop them here
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        // `&mut self` means no other thread can be pushing or popping anymore
+        while self.pop().is_some() {}
+    }
+}
+
+pub fn run() {
+    const COUNT: u64 = 10_000;
+
+    let (mut producer, mut consumer) = RingBuffer::<u64, 128>::new().split();
+
+    let producer = std::thread::spawn(move || {
+        let mut sum = 0;
+        for value in 1..=COUNT {
+            // Spin until the consumer makes room
+            let mut item = value;
+            while let Err(rejected) = producer.push(item) {
+                item = rejected;
+                std::hint::spin_loop();
+            }
+            sum += value;
+        }
+        sum
+    });
+
+    let consumer = std::thread::spawn(move || {
+        let mut sum = 0;
+        let mut received = 0;
+        while received < COUNT {
+            match consumer.pop() {
+                Some(value) => {
+                    sum += value;
+                    received += 1;
+                }
+                None => std::hint::spin_loop(),
+            }
+        }
+        (sum, consumer)
+    });
+
+    let sent = producer.join().unwrap();
+    let (received, consumer) = consumer.join().unwrap();
+    println!("sent sum: {}, received sum: {}", sent, received);
+    assert_eq!(sent, received);
+    assert!(consumer.is_empty());
+
+    // Items left in the buffer are dropped with it, once both halves are gone
+    let (mut strings, _consumer) = RingBuffer::<String, 4>::new().split();
+    strings.push("left".to_string()).unwrap();
+    strings.push("behind".to_string()).unwrap();
+    println!("{} strings will be dropped with the buffer", strings.len());
+}
+
+// These go through every unsafe block (writing a slot, reading it out, dropping what's
+// left), so they are also meant to be run under Miri to catch reads of uninitialized
+// slots, double drops and leaks:
+//
+//   cargo +nightly miri test -p ring_buffer
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts how many times it was dropped, to catch leaks and double drops
+    #[derive(Debug)]
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn items_come_out_in_order() {
+        let (mut producer, mut consumer) = RingBuffer::<u32, 4>::new().split();
+        for value in 0..4 {
+            producer.push(value).unwrap();
+        }
+        assert_eq!(consumer.len(), 4);
+        for value in 0..4 {
+            assert_eq!(consumer.pop(), Some(value));
+        }
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn a_full_buffer_gives_the_item_back() {
+        let (mut producer, mut consumer) = RingBuffer::<String, 2>::new().split();
+        producer.push("a".to_string()).unwrap();
+        producer.push("b".to_string()).unwrap();
+        assert!(producer.is_full());
+        assert_eq!(producer.push("c".to_string()), Err("c".to_string()));
+
+        assert_eq!(consumer.pop().as_deref(), Some("a"));
+        producer.push("c".to_string()).unwrap();
+        assert_eq!(consumer.pop().as_deref(), Some("b"));
+        assert_eq!(consumer.pop().as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn the_slots_are_reused_around_the_ring() {
+        let (mut producer, mut consumer) = RingBuffer::<Box<u64>, 3>::new().split();
+        for value in 0..20 {
+            producer.push(Box::new(value)).unwrap();
+            producer.push(Box::new(value + 100)).unwrap();
+            assert_eq!(consumer.pop().map(|item| *item), Some(value));
+            assert_eq!(consumer.pop().map(|item| *item), Some(value + 100));
+        }
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn the_counters_can_wrap_around() {
+        let buffer = RingBuffer::<u8, 2>::new();
+        buffer.head.store(usize::MAX, Ordering::Relaxed);
+        buffer.tail.store(usize::MAX, Ordering::Relaxed);
+        let (mut producer, mut consumer) = buffer.split();
+
+        producer.push(1).unwrap();
+        producer.push(2).unwrap();
+        assert!(producer.is_full());
+        assert_eq!(producer.push(3), Err(3));
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn popped_items_are_dropped_once() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut producer, mut consumer) = RingBuffer::<Tracked, 4>::new().split();
+        producer.push(Tracked(drops.clone())).unwrap();
+        producer.push(Tracked(drops.clone())).unwrap();
+
+        drop(consumer.pop());
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        drop(consumer.pop());
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        drop((producer, consumer));
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn leftover_items_are_dropped_with_the_buffer() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut producer, mut consumer) = RingBuffer::<Tracked, 4>::new().split();
+        for _ in 0..3 {
+            producer.push(Tracked(drops.clone())).unwrap();
+        }
+        drop(consumer.pop());
+
+        // The buffer lives until both halves are gone
+        drop(producer);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        drop(consumer);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn a_full_buffer_doesnt_drop_the_rejected_item() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut producer, _consumer) = RingBuffer::<Tracked, 1>::new().split();
+        producer.push(Tracked(drops.clone())).unwrap();
+
+        let rejected = producer.push(Tracked(drops.clone())).unwrap_err();
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+        drop(rejected);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn two_threads_exchange_everything() {
+        // Small enough to stay quick under Miri
+        const COUNT: u64 = 200;
+        let (mut producer, mut consumer) = RingBuffer::<u64, 8>::new().split();
+
+        let sender = std::thread::spawn(move || {
+            for value in 0..COUNT {
+                let mut item = value;
+                while let Err(rejected) = producer.push(item) {
+                    item = rejected;
+                    std::thread::yield_now();
+                }
+            }
+        });
+
+        let mut received = Vec::new();
+        while received.len() < COUNT as usize {
+            match consumer.pop() {
+                Some(value) => received.push(value),
+                None => std::thread::yield_now(),
+            }
+        }
+        sender.join().unwrap();
+        assert_eq!(received, (0..COUNT).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,351 @@
+use std::{collections::BTreeSet, marker::PhantomData, ptr};
+
+use rand::Rng;
+
+// A skip list is a sorted linked list with "express lanes".
+// Every node is on the bottom level, about half of them are also on level 1,
+// a quarter on level 2 and so on. A search starts on the highest level, moves right
+// while the next value is smaller than the one we look for, then drops down a level.
+// Each level skips about half of the nodes of the level below, so like a binary
+// search it takes O(log n) steps (on average, the levels are random).
+//
+//   level 2: head ----------------------------> 30 -------------------> null
+//   level 1: head ----------> 10 -------------> 30 -------> 50 -------> null
+//   level 0: head --> 5 ----> 10 ----> 20 ----> 30 ----> 40 -> 50 ----> null
+//
+// Unlike a balanced tree, there's no rebalancing at all: inserting a node only
+// rewires the pointers right before it.
+
+// How many levels a skip list has, enough for about 2^16 = 65536 values
+// before searches start getting slower than O(log n)
+pub const DEFAULT_MAX_LEVEL: usize = 16;
+
+struct Node<T> {
+    // None only for the head, which sits before the first value
+    value: Option<T>,
+    // The next node on each level this node is part of (null = end of the level)
+    forward: Vec<*mut Node<T>>,
+}
+
+pub struct SkipList<T: Ord> {
+    // The head is part of every level
+    head: Box<Node<T>>,
+    len: usize,
+    max_level: usize,
+}
+
+// SAFETY (for every `unsafe` block in this file):
+// - every node except the head is allocated with `Box::new` and turned into a pointer
+//   with `Box::into_raw`, so the non-null pointers are aligned and point to a live node
+// - a node is only freed (with `Box::from_raw`) after it was unlinked from every level
+//   in `remove`, or in `Drop`, so no level ever points to a freed node
+// - the nodes are only modified through `&mut self` methods and only read through
+//   `&self` ones, so the borrow checker still makes sure nobody reads while we rewire
+// - `Iter` borrows the list, so the nodes it walks can't be freed while it is alive
+impl<T: Ord> SkipList<T> {
+    pub fn new() -> SkipList<T> {
+        SkipList::with_max_level(DEFAULT_MAX_LEVEL)
+    }
+
+    pub fn with_max_level(max_level: usize) -> SkipList<T> {
+        assert!(max_level > 0, "a skip list needs at least one level");
+        SkipList {
+            head: Box::new(Node {
+                value: None,
+                forward: vec![ptr::null_mut(); max_level],
+            }),
+            len: 0,
+            max_level,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Add a value, a value that is already in the list is ignored (it's a set)
+    pub fn insert(&mut self, value: T) {
+        let update = self.predecessors(&value);
+
+        unsafe {
+            let next = next_node(update[0], 0);
+            if !next.is_null() && (*next).value.as_ref() == Some(&value) {
+                return;
+            }
+
+            let level = self.random_level();
+            let node = Box::into_raw(Box::new(Node {
+                value: Some(value),
+                forward: vec![ptr::null_mut(); level],
+            }));
+
+            // On every level of the new node, insert it right after its predecessor
+            for (i, &predecessor) in update.iter().enumerate().take(level) {
+                set_next_node(node, i, next_node(predecessor, i));
+                set_next_node(predecessor, i, node);
+            }
+        }
+
+        self.len += 1;
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let mut current: *const Node<T> = &*self.head;
+
+        unsafe {
+            for level in (0..self.max_level).rev() {
+                current = advance(current, level, value);
+            }
+            let next = next_node(current, 0);
+            !next.is_null() && (*next).value.as_ref() == Some(value)
+        }
+    }
+
+    // Returns false if the value wasn't in the list
+    pub fn remove(&mut self, value: &T) -> bool {
+        let update = self.predecessors(value);
+
+        unsafe {
+            let target = next_node(update[0], 0);
+            if target.is_null() || (*target).value.as_ref() != Some(value) {
+                return false;
+            }
+
+            // Skip over the node on every level it is part of
+            // `update[i]` is the node right before `target` on level i
+            let levels = (*target).forward.len();
+            for (i, &predecessor) in update.iter().enumerate().take(levels) {
+                set_next_node(predecessor, i, next_node(target, i));
+            }
+
+            drop(Box::from_raw(target));
+        }
+
+        self.len -= 1;
+        true
+    }
+
+    // The values in order, by walking the bottom level
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.forward[0],
+            marker: PhantomData,
+        }
+    }
+
+    // For each level, the last node whose value is smaller than `value`
+    // (the head if there is none). Those are the nodes to rewire on insert/remove
+    fn predecessors(&mut self, value: &T) -> Vec<*mut Node<T>> {
+        let mut update = vec![ptr::null_mut(); self.max_level];
+        let mut current: *mut Node<T> = &mut *self.head;
+
+        for level in (0..self.max_level).rev() {
+            current = unsafe { advance(current, level, value) as *mut Node<T> };
+            update[level] = current;
+        }
+        update
+    }
+
+    // 1 level with probability 1/2, 2 levels with probability 1/4, 3 with 1/8...
+    // (a geometric distribution), capped at max_level
+    fn random_level(&self) -> usize {
+        let mut rng = rand::rng();
+        let mut level = 1;
+        while level < self.max_level && rng.random_bool(0.5) {
+            level += 1;
+        }
+        level
+    }
+}
+
+// Move right on `level` while the next value is smaller than `value`
+unsafe fn advance<T: Ord>(mut current: *const Node<T>, level: usize, value: &T) -> *const Node<T> {
+    loop {
+        let next = next_node(current, level);
+        if next.is_null() || (*next).value.as_ref().is_none_or(|v| v >= value) {
+            return current;
+        }
+        current = next;
+    }
+}
+
+// The pointer to the next node on `level`
+// The `&` is explicit: it's a short-lived reference to the `forward` Vec of a live node
+unsafe fn next_node<T>(node: *const Node<T>, level: usize) -> *mut Node<T> {
+    (&(*node).forward)[level]
+}
+
+unsafe fn set_next_node<T>(node: *mut Node<T>, level: usize, next: *mut Node<T>) {
+    (&mut (*node).forward)[level] = next;
+}
+
+impl<T: Ord> Default for SkipList<T> {
+    fn default() -> Self {
+        SkipList::new()
+    }
+}
+
+impl<T: Ord> Drop for SkipList<T> {
+    fn drop(&mut self) {
+        // Every node is on the bottom level, so walking it frees all of them
+        let mut current = self.head.forward[0];
+        while !current.is_null() {
+            let node = unsafe { Box::from_raw(current) };
+            current = node.forward[0];
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    next: *const Node<T>,
+    // We hand out `&'a T`, so the iterator must not outlive the list
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.next.is_null() {
+            return None;
+        }
+
+        let node: &'a Node<T> = unsafe { &*self.next };
+        self.next = node.forward[0];
+        node.value.as_ref()
+    }
+}
+
+pub fn run() {
+    let mut rng = rand::rng();
+
+    // 500 different random values, in the order we generated them
+    let mut unique = BTreeSet::new();
+    let mut values = Vec::new();
+    while values.len() < 500 {
+        let value: u32 = rng.random_range(0..100_000);
+        if unique.insert(value) {
+            values.push(value);
+        }
+    }
+
+    let mut list = SkipList::new();
+    for &value in &values {
+        list.insert(value);
+    }
+    assert_eq!(list.len(), 500);
+
+    // Remove the first 100 we inserted
+    for value in &values[..100] {
+        assert!(list.remove(value));
+        unique.remove(value);
+    }
+    assert!(!list.contains(&values[0]));
+    assert!(list.contains(&values[100]));
+
+    // A BTreeSet iterates in order too, so both must yield the same values
+    let remaining: Vec<u32> = list.iter().copied().collect();
+    let expected: Vec<u32> = unique.into_iter().collect();
+    assert_eq!(remaining.len(), 400);
+    assert_eq!(remaining, expected);
+
+    println!(
+        "{} values left, the smallest ones: {:?}",
+        list.len(),
+        &remaining[..10]
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn values(list: &SkipList<u32>) -> Vec<u32> {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn iter_is_sorted_like_a_btree_set() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut list = SkipList::new();
+        let mut set = BTreeSet::new();
+        for _ in 0..1000 {
+            let value = rng.random_range(0..500);
+            list.insert(value);
+            set.insert(value);
+        }
+        assert_eq!(list.len(), set.len());
+        assert!(list.iter().eq(set.iter()));
+    }
+
+    // Random inserts, removes and lookups, the answers must match the BTreeSet's
+    #[test]
+    fn random_operations_match_a_btree_set() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut list = SkipList::new();
+        let mut set = BTreeSet::new();
+        for _ in 0..5000 {
+            let value = rng.random_range(0..200);
+            match rng.random_range(0..3) {
+                0 => {
+                    list.insert(value);
+                    set.insert(value);
+                }
+                1 => assert_eq!(list.remove(&value), set.remove(&value)),
+                _ => assert_eq!(list.contains(&value), set.contains(&value)),
+            }
+            assert_eq!(list.len(), set.len());
+        }
+        assert_eq!(values(&list), set.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn a_value_is_only_stored_once() {
+        let mut list = SkipList::new();
+        for value in [3, 1, 3, 2, 1] {
+            list.insert(value);
+        }
+        assert_eq!(values(&list), [1, 2, 3]);
+        assert!(list.remove(&3));
+        assert!(!list.remove(&3));
+        assert_eq!(values(&list), [1, 2]);
+    }
+
+    #[test]
+    fn an_empty_list() {
+        let mut list: SkipList<u32> = SkipList::default();
+        assert!(list.is_empty());
+        assert!(!list.contains(&1));
+        assert!(!list.remove(&1));
+        assert_eq!(list.iter().next(), None);
+    }
+
+    // With a single level it's a sorted linked list, still correct
+    #[test]
+    fn a_single_level() {
+        let mut list = SkipList::with_max_level(1);
+        for value in [5, 1, 4, 2, 3] {
+            list.insert(value);
+        }
+        assert!(list.remove(&4));
+        assert_eq!(values(&list), [1, 2, 3, 5]);
+    }
+
+    // Dropping frees every node: under Miri (`cargo +nightly miri test -p skip_list`)
+    // a node leaked or freed twice shows up
+    #[test]
+    fn values_that_own_memory_are_dropped() {
+        let mut list = SkipList::new();
+        for word in ["pear", "apple", "fig"] {
+            list.insert(word.to_string());
+        }
+        assert!(list.remove(&"apple".to_string()));
+        assert_eq!(list.iter().collect::<Vec<_>>(), ["fig", "pear"]);
+    }
+}
@@ -0,0 +1,259 @@
+use std::ops::{Index, IndexMut};
+
+// A slab stores values in a Vec and hands out their index as a key.
+// Removing a value doesn't shift the others (that would change their keys), it just
+// marks the slot as free and remembers it, so the next insert reuses it.
+// That gives us:
+// - stable keys: a key keeps pointing to the same value until it is removed
+// - O(1) insert, remove and lookup
+// - values stored next to each other in memory, with no allocation per value
+//
+// It's a common way to build graphs or to keep track of connections in a server:
+// instead of references (and fights with the borrow checker) everything holds keys.
+//
+// The catch: once a key is removed and reused, an old copy of that key now points to
+// a different value. Slot maps fix that by storing a "generation" next to each slot
+// and in each key, we keep things simple here.
+#[derive(Debug)]
+enum Slot<T> {
+    Occupied(T),
+    Free,
+}
+
+#[derive(Debug)]
+pub struct Slab<T> {
+    slots: Vec<Slot<T>>,
+    // The free slots, the last one freed is reused first
+    free_list: Vec<usize>,
+    // How many slots are occupied
+    len: usize,
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Slab::new()
+    }
+}
+
+impl<T> Slab<T> {
+    pub fn new() -> Slab<T> {
+        Slab {
+            slots: Vec::new(),
+            free_list: Vec::new(),
+            len: 0,
+        }
+    }
+
+    // Store a value and return its key
+    pub fn insert(&mut self, value: T) -> usize {
+        self.len += 1;
+
+        // Reuse a free slot if there is one, otherwise grow the Vec
+        match self.free_list.pop() {
+            Some(key) => {
+                self.slots[key] = Slot::Occupied(value);
+                key
+            }
+            None => {
+                self.slots.push(Slot::Occupied(value));
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    // Take a value out, None if the key is free or was never handed out
+    pub fn remove(&mut self, key: usize) -> Option<T> {
+        let slot = self.slots.get_mut(key)?;
+        if let Slot::Free = slot {
+            return None;
+        }
+
+        // `replace` moves the value out and leaves a Free slot behind in one step
+        let Slot::Occupied(value) = std::mem::replace(slot, Slot::Free) else {
+            unreachable!("we just checked that the slot is occupied");
+        };
+        self.free_list.push(key);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn get(&self, key: usize) -> Option<&T> {
+        match self.slots.get(key)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Free => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.slots.get_mut(key)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Free => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The occupied slots with their keys, in key order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Occupied(value) => Some((key, value)),
+                Slot::Free => None,
+            })
+    }
+}
+
+// `slab[key]` works like indexing a Vec: it panics when there is no value for the key
+// Use `get` when the key may be stale
+impl<T> Index<usize> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &T {
+        match self.get(key) {
+            Some(value) => value,
+            None => panic!("no value for key {} in the slab", key),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, key: usize) -> &mut T {
+        match self.get_mut(key) {
+            Some(value) => value,
+            None => panic!("no value for key {} in the slab", key),
+        }
+    }
+}
+
+pub fn run() {
+    let mut slab = Slab::new();
+
+    let keys: Vec<usize> = (0..100)
+        .map(|i| slab.insert(format!("value {}", i)))
+        .collect();
+    println!("inserted {} values, keys 0..={}", slab.len(), keys[99]);
+
+    // Remove every third value, the other keys don't move
+    for &key in keys.iter().step_by(3) {
+        slab.remove(key);
+    }
+    println!("after removing every third value: {} left", slab.len());
+    assert_eq!(slab[1], "value 1");
+    assert!(slab.get(3).is_none());
+
+    // The new values go into the freed slots before the Vec grows
+    let new_keys: Vec<usize> = (0..30)
+        .map(|i| slab.insert(format!("new value {}", i)))
+        .collect();
+    println!("the 30 new values got the keys {:?}", new_keys);
+    assert!(new_keys.iter().all(|&key| key < 100));
+
+    slab[1].push_str(" (edited)");
+
+    for (key, value) in slab.iter().take(10) {
+        println!("  {}: {}", key, value);
+    }
+    println!("  ... {} values in total", slab.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_handed_out_in_order() {
+        let mut slab = Slab::new();
+        assert_eq!(
+            [slab.insert('a'), slab.insert('b'), slab.insert('c')],
+            [0, 1, 2]
+        );
+        assert_eq!((slab[0], slab[2]), ('a', 'c'));
+        assert_eq!(slab.len(), 3);
+    }
+
+    #[test]
+    fn a_removed_key_is_reused() {
+        let mut slab = Slab::new();
+        for value in ['a', 'b', 'c'] {
+            slab.insert(value);
+        }
+        assert_eq!(slab.remove(1), Some('b'));
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.insert('d'), 1);
+        assert_eq!(slab[1], 'd');
+        // Full again, the Vec grows
+        assert_eq!(slab.insert('e'), 3);
+    }
+
+    // The last one freed is reused first
+    #[test]
+    fn free_slots_are_reused_last_in_first_out() {
+        let mut slab = Slab::new();
+        for value in 0..5 {
+            slab.insert(value);
+        }
+        slab.remove(1);
+        slab.remove(3);
+        assert_eq!(
+            [slab.insert(10), slab.insert(11), slab.insert(12)],
+            [3, 1, 5]
+        );
+    }
+
+    #[test]
+    fn removing_twice_or_out_of_range_does_nothing() {
+        let mut slab = Slab::new();
+        let key = slab.insert("x");
+        assert_eq!(slab.remove(key), Some("x"));
+        assert_eq!(slab.remove(key), None);
+        assert_eq!(slab.remove(42), None);
+        assert!(slab.is_empty());
+    }
+
+    #[test]
+    fn iter_skips_the_free_slots() {
+        let mut slab = Slab::new();
+        for value in ["a", "b", "c", "d"] {
+            slab.insert(value);
+        }
+        slab.remove(0);
+        slab.remove(2);
+        let items: Vec<_> = slab.iter().collect();
+        assert_eq!(items, [(1, &"b"), (3, &"d")]);
+    }
+
+    #[test]
+    fn get_mut_and_index_mut() {
+        let mut slab = Slab::new();
+        let key = slab.insert(String::from("value"));
+        slab.get_mut(key).unwrap().push('!');
+        slab[key].push('?');
+        assert_eq!(slab[key], "value!?");
+        assert!(slab.get_mut(7).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "no value for key 5 in the slab")]
+    fn indexing_out_of_range_panics() {
+        let mut slab = Slab::new();
+        slab.insert(1);
+        let _ = slab[5];
+    }
+
+    #[test]
+    #[should_panic(expected = "no value for key 0 in the slab")]
+    fn indexing_a_removed_key_panics() {
+        let mut slab = Slab::new();
+        let key = slab.insert(1);
+        slab.remove(key);
+        slab[key] += 1;
+    }
+}
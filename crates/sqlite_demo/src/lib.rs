@@ -0,0 +1,257 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+
+// A todo list in SQLite, with rusqlite
+//
+// The tables are made by migrations: SQL scripts built into the program, each one run
+// once and in order. The database remembers how many of them it has seen (in the
+// schema_version table), so a database from an older version only gets the new ones,
+// and opening it again runs nothing at all.
+//
+// The values always go in as parameters (`?1`, `?2`... and `params![]`), never pasted
+// into the SQL: a title like `'); DROP TABLE todos; --` is just a title then.
+
+// Never change or remove one that was released, databases out there have run it
+// already. Add a new one instead
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS todos (
+         id INTEGER PRIMARY KEY,
+         title TEXT NOT NULL
+     );",
+    // SQLite has no booleans, `completed` is 0 or 1
+    // Unlike the one above this one can't run twice, the column would exist already:
+    // that's what the schema version is for
+    "ALTER TABLE todos ADD COLUMN completed INTEGER NOT NULL DEFAULT 0;",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Todo {
+    pub id: i64,
+    pub title: String,
+    pub completed: bool,
+}
+
+impl Todo {
+    // A row of `SELECT id, title, completed`, rusqlite converts each column to the
+    // type we ask for (and fails if it can't)
+    fn from_row(row: &Row) -> Result<Todo> {
+        Ok(Todo {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            completed: row.get(2)?,
+        })
+    }
+}
+
+// Open (or create) the database at `path` and bring its tables up to date
+// ":memory:" is a database that only lives as long as the connection
+pub fn open_db(path: &str) -> Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+// How many migrations the database has run
+pub fn schema_version(conn: &Connection) -> Result<usize> {
+    let version: Option<i64> = conn
+        .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+        .optional()?;
+    Ok(version.unwrap_or(0) as usize)
+}
+
+// Run the migrations the database hasn't run yet, returns how many that was
+// All of them or none: they are in one transaction, a failing one leaves the database
+// as it was before the first. A database from a newer version (it has run migrations
+// we don't know) is left alone
+fn migrate(conn: &mut Connection) -> Result<usize> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        [],
+    )?;
+    let version = schema_version(&tx)?;
+    let pending = MIGRATIONS.get(version..).unwrap_or_default();
+    for sql in pending {
+        tx.execute_batch(sql)?;
+    }
+    if !pending.is_empty() {
+        tx.execute("DELETE FROM schema_version", [])?;
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            params![MIGRATIONS.len() as i64],
+        )?;
+    }
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+// Add a todo, not completed yet, and return its id
+pub fn insert_todo(conn: &Connection, title: &str) -> Result<i64> {
+    conn.execute("INSERT INTO todos (title) VALUES (?1)", params![title])?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn list_todos(conn: &Connection) -> Result<Vec<Todo>> {
+    let mut statement = conn.prepare("SELECT id, title, completed FROM todos ORDER BY id")?;
+    let todos = statement.query_map([], Todo::from_row)?;
+    todos.collect()
+}
+
+// Mark a todo as completed, false if there is no todo with that id
+pub fn complete_todo(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute("UPDATE todos SET completed = 1 WHERE id = ?1", params![id])?;
+    Ok(changed == 1)
+}
+
+// Write `todo` as it is: a new row if there is none with its id, the existing one
+// replaced otherwise. Writing the same todo twice is the same as writing it once
+pub fn upsert_todo(conn: &Connection, todo: &Todo) -> Result<()> {
+    conn.execute(
+        "INSERT INTO todos (id, title, completed) VALUES (?1, ?2, ?3)
+         ON CONFLICT (id) DO UPDATE SET title = excluded.title, completed = excluded.completed",
+        params![todo.id, todo.title, todo.completed],
+    )?;
+    Ok(())
+}
+
+pub fn run() {
+    let conn = open_db(":memory:").unwrap();
+    println!("schema version {}", schema_version(&conn).unwrap());
+
+    let titles = [
+        "buy milk",
+        "write tests",
+        "call mom",
+        "fix the bike",
+        "read",
+    ];
+    let ids: Vec<i64> = titles
+        .iter()
+        .map(|title| insert_todo(&conn, title).unwrap())
+        .collect();
+    complete_todo(&conn, ids[1]).unwrap();
+    complete_todo(&conn, ids[3]).unwrap();
+    println!(
+        "complete todo 1000: {}",
+        complete_todo(&conn, 1000).unwrap()
+    );
+    print_todos(&conn);
+
+    // Upserting the same todo again changes nothing, a new id is a new todo
+    let mut todo = Todo {
+        id: ids[0],
+        title: "buy oat milk".to_string(),
+        completed: true,
+    };
+    upsert_todo(&conn, &todo).unwrap();
+    upsert_todo(&conn, &todo).unwrap();
+    todo.id = 100;
+    upsert_todo(&conn, &todo).unwrap();
+    upsert_todo(&conn, &todo).unwrap();
+    println!("after the upserts:");
+    print_todos(&conn);
+}
+
+fn print_todos(conn: &Connection) {
+    for todo in list_todos(conn).unwrap() {
+        let check = if todo.completed { "x" } else { " " };
+        println!("[{}] {} {}", check, todo.id, todo.title);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: i64, title: &str, completed: bool) -> Todo {
+        Todo {
+            id,
+            title: title.to_string(),
+            completed,
+        }
+    }
+
+    #[test]
+    fn a_new_database_runs_every_migration() {
+        let mut conn = open_db(":memory:").unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(migrate(&mut conn).unwrap(), 0);
+        assert!(list_todos(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn completed_todos() {
+        let conn = open_db(":memory:").unwrap();
+        let titles = [
+            "buy milk",
+            "write tests",
+            "call mom",
+            "fix the bike",
+            "read",
+        ];
+        let ids: Vec<i64> = titles
+            .iter()
+            .map(|title| insert_todo(&conn, title).unwrap())
+            .collect();
+        assert!(complete_todo(&conn, ids[1]).unwrap());
+        assert!(complete_todo(&conn, ids[3]).unwrap());
+        // Again is fine, it was found
+        assert!(complete_todo(&conn, ids[3]).unwrap());
+        assert!(!complete_todo(&conn, 1000).unwrap());
+
+        let todos = list_todos(&conn).unwrap();
+        let completed: Vec<bool> = todos.iter().map(|todo| todo.completed).collect();
+        assert_eq!(completed, [false, true, false, true, false]);
+        let listed: Vec<&str> = todos.iter().map(|todo| todo.title.as_str()).collect();
+        assert_eq!(listed, titles);
+    }
+
+    #[test]
+    fn titles_are_never_read_as_sql() {
+        let conn = open_db(":memory:").unwrap();
+        let title = "'); DROP TABLE todos; --";
+        let id = insert_todo(&conn, title).unwrap();
+        assert_eq!(list_todos(&conn).unwrap(), [todo(id, title, false)]);
+    }
+
+    #[test]
+    fn upsert_twice_updates_one_row() {
+        let conn = open_db(":memory:").unwrap();
+        let id = insert_todo(&conn, "buy milk").unwrap();
+        insert_todo(&conn, "read").unwrap();
+        let updated = todo(id, "buy oat milk", true);
+        upsert_todo(&conn, &updated).unwrap();
+        let once = list_todos(&conn).unwrap();
+        upsert_todo(&conn, &updated).unwrap();
+        assert_eq!(list_todos(&conn).unwrap(), once);
+        assert_eq!(once.len(), 2);
+        assert_eq!(once[0], updated);
+    }
+
+    #[test]
+    fn upsert_twice_inserts_one_row() {
+        let conn = open_db(":memory:").unwrap();
+        insert_todo(&conn, "buy milk").unwrap();
+        let new = todo(100, "read", false);
+        upsert_todo(&conn, &new).unwrap();
+        upsert_todo(&conn, &new).unwrap();
+        let todos = list_todos(&conn).unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos.last(), Some(&new));
+    }
+
+    #[test]
+    fn reopening_runs_no_migration_twice() {
+        // The ALTER TABLE would fail if it ran again
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todos.db");
+        let path = path.to_str().unwrap();
+        let mut conn = open_db(path).unwrap();
+        insert_todo(&conn, "survive a restart").unwrap();
+        assert_eq!(migrate(&mut conn).unwrap(), 0);
+        drop(conn);
+        let conn = open_db(path).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len());
+        let todos = list_todos(&conn).unwrap();
+        assert_eq!(todos, [todo(1, "survive a restart", false)]);
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+// A (simplified) TCP connection seen from the server side, modelled as a state machine.
+// Enums are a great fit: a connection is in exactly one state at a time, and the
+// compiler makes sure we handle every state wherever we match on it.
+//
+//   Closed --Open--> Listen --SynReceived--> SynReceived --Ack--> Established
+//                                                                     |
+//   Closed <--Ack-- LastAck <--LastAck-- CloseWait <-------Close------+
+
+/// The states a server-side TCP connection goes through.
+///
+/// Every `match` on a state must handle all of them, forgetting one is a compile error:
+///
+/// ```compile_fail
+/// use state_machine::TcpState;
+///
+/// fn describe(state: TcpState) -> &'static str {
+///     match state {
+///         TcpState::Closed => "closed",
+///         TcpState::Listen => "listening",
+///         TcpState::SynReceived => "handshaking",
+///         TcpState::Established => "connected",
+///         TcpState::CloseWait => "closing",
+///         // LastAck is missing: error[E0004]: non-exhaustive patterns
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynReceived,
+    Established,
+    CloseWait,
+    LastAck,
+}
+
+// Things that can happen to the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpEvent {
+    // The application starts listening
+    Open,
+    // A client sent a SYN
+    SynReceived,
+    // We answered with SYN+ACK
+    SynAckSent,
+    // The other side acknowledged our last segment
+    Ack,
+    // The client sent a FIN (or the application closed a listening socket)
+    Close,
+    // We sent our own FIN and wait for the last ACK
+    LastAck,
+}
+
+// An event that makes no sense in the current state
+// We keep both so the error message can say exactly what went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionError {
+    pub state: TcpState,
+    pub event: TcpEvent,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid event {:?} in state {:?}",
+            self.event, self.state
+        )
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+// The transition table
+// The outer match is on the state and has no wildcard arm on purpose: if we add a new
+// state to TcpState the compiler will refuse to build until we decide here how that
+// state reacts to events. The inner matches do use `_` because for a given state
+// "every other event is invalid" is exactly what we mean.
+pub fn transition(state: TcpState, event: TcpEvent) -> Result<TcpState, TransitionError> {
+    let invalid = Err(TransitionError { state, event });
+
+    match state {
+        TcpState::Closed => match event {
+            TcpEvent::Open => Ok(TcpState::Listen),
+            _ => invalid,
+        },
+        TcpState::Listen => match event {
+            TcpEvent::SynReceived => Ok(TcpState::SynReceived),
+            TcpEvent::Close => Ok(TcpState::Closed),
+            _ => invalid,
+        },
+        TcpState::SynReceived => match event {
+            // Sending the SYN+ACK doesn't change the state, we still wait for the ACK
+            TcpEvent::SynAckSent => Ok(TcpState::SynReceived),
+            TcpEvent::Ack => Ok(TcpState::Established),
+            _ => invalid,
+        },
+        TcpState::Established => match event {
+            TcpEvent::Close => Ok(TcpState::CloseWait),
+            _ => invalid,
+        },
+        TcpState::CloseWait => match event {
+            TcpEvent::LastAck => Ok(TcpState::LastAck),
+            _ => invalid,
+        },
+        TcpState::LastAck => match event {
+            TcpEvent::Ack => Ok(TcpState::Closed),
+            _ => invalid,
+        },
+    }
+}
+
+// A connection that keeps its current state and moves it forward one event at a time
+pub struct TcpConnectionSim {
+    state: TcpState,
+}
+
+impl TcpConnectionSim {
+    pub fn new() -> TcpConnectionSim {
+        TcpConnectionSim {
+            state: TcpState::Closed,
+        }
+    }
+
+    pub fn state(&self) -> TcpState {
+        self.state
+    }
+
+    // On error the connection stays in the state it was in
+    pub fn apply(&mut self, event: TcpEvent) -> Result<(), TransitionError> {
+        self.state = transition(self.state, event)?;
+        Ok(())
+    }
+}
+
+impl Default for TcpConnectionSim {
+    fn default() -> TcpConnectionSim {
+        TcpConnectionSim::new()
+    }
+}
+
+pub fn run() {
+    let mut connection = TcpConnectionSim::new();
+
+    // A complete lifetime: open, three-way handshake, and the passive close
+    let events = [
+        TcpEvent::Open,
+        TcpEvent::SynReceived,
+        TcpEvent::SynAckSent,
+        TcpEvent::Ack,
+        TcpEvent::Close,
+        TcpEvent::LastAck,
+        TcpEvent::Ack,
+    ];
+
+    for event in events {
+        let before = connection.state();
+        connection.apply(event).expect("valid handshake");
+        println!("{:?} --{:?}--> {:?}", before, event, connection.state());
+    }
+    assert_eq!(connection.state(), TcpState::Closed);
+
+    // Sending data before the handshake is done is rejected
+    let mut connection = TcpConnectionSim::new();
+    connection.apply(TcpEvent::Open).unwrap();
+    match connection.apply(TcpEvent::Ack) {
+        Ok(()) => unreachable!("an ACK while listening is not valid"),
+        Err(e) => println!("rejected: {}", e),
+    }
+    assert_eq!(connection.state(), TcpState::Listen);
+}
@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+
+// The strategy pattern: we have one job (sorting) and several algorithms that can do it.
+// Each algorithm is a "strategy" implementing the same trait, and the code using it
+// only talks to the trait, so we can pick the algorithm at runtime
+pub trait SortStrategy<T: Ord> {
+    fn sort(&self, data: &mut Vec<T>);
+}
+
+// Repeatedly swaps neighbours that are in the wrong order, O(n²)
+pub struct BubbleSort;
+
+impl<T: Ord> SortStrategy<T> for BubbleSort {
+    fn sort(&self, data: &mut Vec<T>) {
+        let len = data.len();
+        for pass in 0..len {
+            let mut swapped = false;
+            // After each pass the biggest remaining element is at the end,
+            // so we don't need to look at the last `pass` elements again
+            for i in 0..len.saturating_sub(pass + 1) {
+                if data[i] > data[i + 1] {
+                    data.swap(i, i + 1);
+                    swapped = true;
+                }
+            }
+            // Nothing moved, the vector is already sorted
+            if !swapped {
+                break;
+            }
+        }
+    }
+}
+
+// Grows a sorted prefix, inserting each new element at its place, O(n²)
+// but very fast for small or almost sorted inputs
+pub struct InsertionSort;
+
+impl<T: Ord> SortStrategy<T> for InsertionSort {
+    fn sort(&self, data: &mut Vec<T>) {
+        for i in 1..data.len() {
+            let mut j = i;
+            while j > 0 && data[j - 1] > data[j] {
+                data.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    }
+}
+
+// The "fast" strategy, O(n log n)
+// The standard library's unstable sort is a pattern-defeating quicksort,
+// no need to write our own
+pub struct QuickSort;
+
+impl<T: Ord> SortStrategy<T> for QuickSort {
+    fn sort(&self, data: &mut Vec<T>) {
+        data.sort_unstable();
+    }
+}
+
+// The context: it holds a strategy and uses it without knowing which one it is
+pub struct Sorter<T: Ord> {
+    strategy: Box<dyn SortStrategy<T>>,
+}
+
+impl<T: Ord> Sorter<T> {
+    pub fn new(strategy: Box<dyn SortStrategy<T>>) -> Sorter<T> {
+        Sorter { strategy }
+    }
+
+    pub fn sort(&self, data: &mut Vec<T>) {
+        self.strategy.sort(data);
+    }
+}
+
+// Same idea as the weather service in `box_dyn_traits`: the match arms return
+// different types, so we need a Box<dyn SortStrategy> to make them compatible
+pub fn strategy_from_config(name: &str) -> Box<dyn SortStrategy<u32>> {
+    match name {
+        "bubble" => Box::new(BubbleSort),
+        "insertion" => Box::new(InsertionSort),
+        "quick" => Box::new(QuickSort),
+        _ => panic!("Unknown sort strategy: {}", name),
+    }
+}
+
+// A tiny xorshift pseudo random generator, good enough to shuffle test data
+// without pulling in the `rand` crate
+fn random_numbers(count: usize, mut seed: u32) -> Vec<u32> {
+    (0..count)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        })
+        .collect()
+}
+
+fn is_sorted(data: &[u32]) -> bool {
+    data.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+fn time_strategy(name: &str, data: &[u32]) -> Duration {
+    let sorter = Sorter::new(strategy_from_config(name));
+    let mut data = data.to_vec();
+
+    let start = Instant::now();
+    sorter.sort(&mut data);
+    let elapsed = start.elapsed();
+
+    assert!(is_sorted(&data), "{} sort didn't sort", name);
+    elapsed
+}
+
+pub fn run() {
+    // Lets pretend this value comes from a configuration file
+    let config_value = "insertion";
+    let sorter = Sorter::new(strategy_from_config(config_value));
+
+    let mut data = random_numbers(1000, 42);
+    sorter.sort(&mut data);
+    assert!(is_sorted(&data));
+    println!("sorted 1000 numbers with the {:?} strategy", config_value);
+
+    // The O(n²) strategies fall behind quickly as the input grows
+    for size in [100, 1_000, 10_000] {
+        let data = random_numbers(size, 7);
+        println!("{} elements:", size);
+        for name in ["bubble", "insertion", "quick"] {
+            println!("  {:>9}: {:?}", name, time_strategy(name, &data));
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{debug, info, info_span, warn};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Registry};
+
+// `tracing` separates *producing* logs from *writing* them:
+//
+// - the code emits events (`info!`, `warn!`...) with fields, and opens spans: a span is
+//   a piece of work with a start and an end ("this request", "this database lookup").
+//   Every event knows the spans it happened in, so its fields don't have to be repeated.
+// - a subscriber decides what happens to them: print them as text, as JSON, send them
+//   somewhere... Without a subscriber, nothing happens at all (and it costs next to
+//   nothing).
+//
+// Fields are kept as values, not formatted into the message: a JSON subscriber writes
+// `"user_id": 42`, which a log search can filter on, instead of "user 42 logged in".
+
+// `#[instrument]` opens a span named after the function for every call, with the
+// arguments as fields: every event inside carries request_id and user_id for free.
+// (Arguments need to be Debug; `skip(...)` leaves out the ones that aren't or are
+// too big, `fields(...)` adds more.)
+#[tracing::instrument]
+pub fn process_request(request_id: u64, user_id: u32) {
+    info!("processing request");
+
+    // A child span: it's inside the request span, and adds its own fields
+    // `user_id` alone is a shorthand for `user_id = user_id`
+    let span = info_span!("db_lookup", user_id, table = "users");
+    // Events are in the span while the guard lives
+    let _guard = span.enter();
+    debug!(rows = 1, "looking up the user");
+    // Odd user ids are "slow" in this demo, so we get a warning to look at
+    if user_id % 2 == 1 {
+        // `?value` records a field with its Debug output, `%value` with Display
+        let elapsed = std::time::Duration::from_millis(250);
+        warn!(?elapsed, threshold_ms = 100, "slow query");
+    }
+}
+
+// Logs written into memory instead of a terminal, so we can look at what was logged
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The subscribers ask for a new writer for every event
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+pub fn run() {
+    // JSON, one object per line
+    // `with_default` only uses the subscriber inside the closure (on this thread).
+    // A real program calls `.init()` once at startup instead, for the whole process.
+    println!("JSON:");
+    let json = tracing_subscriber::fmt()
+        .json()
+        // With the debug! events, the default level is INFO
+        .with_max_level(tracing::Level::DEBUG)
+        // Every span we're in, not just the closest one
+        .with_span_list(true)
+        .with_current_span(false)
+        .finish();
+    tracing::subscriber::with_default(json, || process_request(1, 7));
+
+    // Two layers on the same events: JSON (for a machine) and text (for a person)
+    // A Registry keeps track of the spans, the layers only format
+    println!("JSON and text at the same time:");
+    let captured = Captured::default();
+    let subscriber = Registry::default()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(captured.clone()),
+        )
+        .with(tracing_subscriber::fmt::layer().with_writer(io::stdout));
+    tracing::subscriber::with_default(subscriber, || {
+        process_request(2, 41);
+        process_request(3, 42);
+    });
+    let logged = captured.text();
+    println!("the JSON layer wrote {} lines", logged.lines().count());
+
+    // What was logged, with the fields of the arguments and of the spans
+    // There is no level filter here, so both layers got the DEBUG events too: 3 events
+    // for the odd user and 2 for the even one. A filter can go on the whole subscriber
+    // or on one layer only (see `tracing_subscriber::filter`)
+    assert_eq!(logged.lines().count(), 5);
+    assert!(logged.contains(r#""message":"slow query""#));
+    assert!(logged.contains(r#""threshold_ms":100"#));
+    assert!(logged.contains(r#""elapsed":"250ms""#));
+    assert!(logged.contains(r#""name":"process_request""#));
+    assert!(logged.contains(r#""request_id":2"#));
+    assert!(logged.contains(r#""name":"db_lookup""#));
+    // Only the odd user got a warning
+    let warnings: Vec<&str> = logged.lines().filter(|l| l.contains("WARN")).collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains(r#""user_id":41"#));
+
+    // No subscriber, nothing is logged
+    process_request(4, 43);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_test::traced_test;
+
+    // `traced_test` installs a subscriber that keeps the events of this test,
+    // `logs_contain` looks for a piece of text in what it formatted
+    #[traced_test]
+    #[test]
+    fn a_slow_query_is_logged_with_its_fields() {
+        process_request(10, 41);
+
+        assert!(logs_contain("processing request"));
+        assert!(logs_contain("request_id=10"));
+        assert!(logs_contain("slow query"));
+        assert!(logs_contain("threshold_ms=100"));
+        assert!(logs_contain("elapsed=250ms"));
+    }
+
+    #[traced_test]
+    #[test]
+    fn a_fast_query_has_no_warning() {
+        process_request(11, 42);
+
+        assert!(logs_contain("looking up the user"));
+        assert!(logs_contain("table=\"users\""));
+        assert!(!logs_contain("slow query"));
+    }
+
+    #[traced_test]
+    #[test]
+    fn events_carry_the_fields_of_their_spans() {
+        process_request(12, 43);
+
+        // The span names and fields are written before the message
+        logs_assert(|lines: &[&str]| {
+            let warning = lines
+                .iter()
+                .find(|line| line.contains("slow query"))
+                .ok_or("no warning")?;
+            for expected in ["WARN", "process_request", "db_lookup", "user_id=43"] {
+                if !warning.contains(expected) {
+                    return Err(format!("{:?} is missing {}", warning, expected));
+                }
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use std::{io, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_util::sync::CancellationToken;
+
+// The same echo server as `tcp_echo_server`, but async: instead of one thread per
+// connection every connection is a task. Tasks are cheap (a few hundred bytes, no OS
+// thread), and while one waits for its socket the runtime runs the others on the
+// same threads, so thousands of idle connections cost next to nothing.
+
+// Runs an echo server until the process exits
+pub async fn echo_server(addr: &str) -> io::Result<()> {
+    echo_server_until(addr, CancellationToken::new()).await
+}
+
+// Same as `echo_server`, but returns as soon as `shutdown` is cancelled
+// A CancellationToken is the async version of the AtomicBool stop flag: instead of
+// polling it we can `.await` it, so the server reacts right away without waking up
+// every few milliseconds to check
+pub async fn echo_server_until(addr: &str, shutdown: CancellationToken) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        // `select!` waits for whichever comes first: a new connection or the shutdown
+        // The other branch is simply dropped, which is fine for both of them
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.cancelled() => {
+                println!("[server] shutdown requested, no more connections");
+                return Ok(());
+            }
+        };
+        println!("[server] accepted connection from {}", peer);
+
+        // Every connection gets its own task, the loop goes right back to accepting
+        // The task gets a child token: cancelling the server's token cancels it too
+        let connection_shutdown = shutdown.child_token();
+        tokio::spawn(async move {
+            tokio::select! {
+                result = handle_connection(stream) => {
+                    if let Err(e) = result {
+                        eprintln!("[server] connection error: {}", e);
+                    }
+                }
+                _ = connection_shutdown.cancelled() => {}
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream) -> io::Result<()> {
+    // `into_split` gives us an owned read half and an owned write half, so we can
+    // read from one and write to the other at the same time
+    let (mut reader, mut writer) = stream.into_split();
+
+    // `copy` reads until the client closes its side and writes everything back
+    tokio::io::copy(&mut reader, &mut writer).await?;
+    Ok(())
+}
+
+// Send every message over the same connection and read its echo before sending the next
+pub async fn run_client(addr: &str, messages: &[&str]) -> io::Result<Vec<String>> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut echoes = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        stream.write_all(message.as_bytes()).await?;
+
+        // TCP is a stream of bytes, not of messages: the echo can arrive in several
+        // pieces, so we read exactly as many bytes as we sent
+        let mut echo = vec![0_u8; message.len()];
+        stream.read_exact(&mut echo).await?;
+        echoes.push(String::from_utf8_lossy(&echo).into_owned());
+    }
+
+    // Tell the server we are done, its `copy` ends and the connection closes
+    stream.shutdown().await?;
+    Ok(echoes)
+}
+
+pub fn run() {
+    let addr = "127.0.0.1:7879";
+    let messages = ["hello", "async", "echo echo echo", "ünïcödé", "bye!"];
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the tokio runtime");
+    runtime.block_on(async {
+        // Start the server in the background, it runs until we cancel the token
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(echo_server_until(addr, shutdown.clone()));
+
+        // Give the server a moment to bind the port
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let echoes = run_client(addr, &messages).await.expect("client failed");
+        for (message, echo) in messages.iter().zip(&echoes) {
+            println!("[client] sent {:?}, got back {:?}", message, echo);
+        }
+        assert_eq!(echoes, messages);
+
+        // Ask the server to stop and wait for it
+        shutdown.cancel();
+        server
+            .await
+            .expect("server task panicked")
+            .expect("server failed");
+    });
+}
@@ -0,0 +1,102 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+// Runs an echo server on localhost until the process exits
+pub fn run_server(port: u16) -> io::Result<()> {
+    run_server_until(port, Arc::new(AtomicBool::new(false)))
+}
+
+// Same as `run_server`, but returns as soon as `stop` is set to true
+// The flag is shared (Arc) so another thread can flip it while we are running
+pub fn run_server_until(port: u16, stop: Arc<AtomicBool>) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+
+    // By default `accept()` blocks until somebody connects, which means we would never
+    // get the chance to look at the stop flag if no client shows up.
+    // In non-blocking mode `accept()` returns a `WouldBlock` error right away instead,
+    // so we can check the flag, sleep a little and try again.
+    listener.set_nonblocking(true)?;
+
+    while !stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                println!("[server] accepted connection from {}", addr);
+                // The connection itself should block on reads, we have a whole thread for it
+                stream.set_nonblocking(false)?;
+
+                // One thread per connection: simple, and fine for a handful of clients
+                // Each thread owns its stream thanks to `move`
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream) {
+                        eprintln!("[server] connection error: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // Nobody is waiting to connect, give the CPU a break
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    println!("[server] stop flag set, shutting down");
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    let mut buf = [0_u8; 4096];
+    loop {
+        // `read` returns how many bytes were read, at most 4096 at a time
+        // 0 means the client closed its side of the connection
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        stream.write_all(&buf[..n])?;
+    }
+}
+
+pub fn run_client(port: u16, message: &str) -> io::Result<String> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+    stream.write_all(message.as_bytes())?;
+
+    // Closing the write half tells the server that we are done sending
+    // It will echo what is left and then its `read` returns 0, so it closes too
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut echo = String::new();
+    stream.read_to_string(&mut echo)?;
+    Ok(echo)
+}
+
+pub fn run() {
+    let port = 7878;
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Start the server in the background, it will keep accepting
+    // connections until we set the stop flag
+    let server_stop = stop.clone();
+    let server = thread::spawn(move || run_server_until(port, server_stop));
+
+    // Give the server a moment to bind the port
+    thread::sleep(Duration::from_millis(100));
+
+    for message in ["hello", "echo echo echo", "bye!"] {
+        let echo = run_client(port, message).expect("client failed");
+        println!("[client] sent {:?}, got back {:?}", message, echo);
+        assert_eq!(echo, message);
+    }
+
+    // Ask the server to stop and wait for it
+    stop.store(true, Ordering::SeqCst);
+    server.join().unwrap().expect("server failed");
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+// A trie (prefix tree) stores words one character per level, so all the words
+// starting with the same letters share the same path from the root:
+//
+//   root
+//    └─ c
+//       └─ a
+//          ├─ r (end)        "car"
+//          │  └─ e (end)     "care"
+//          └─ t (end)        "cat"
+//
+// Looking up a word (or a prefix) walks one node per character, so it costs
+// O(length of the word) no matter how many words are stored.
+// `is_end` marks the nodes where a word ends, "car" is a word but "ca" isn't.
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<char, TrieNode>,
+    is_end: bool,
+}
+
+#[derive(Default)]
+pub struct Trie {
+    root: TrieNode,
+}
+
+impl Trie {
+    pub fn new() -> Trie {
+        Trie::default()
+    }
+
+    pub fn insert(&mut self, word: &str) {
+        let mut node = &mut self.root;
+        for c in word.chars() {
+            // Create the missing nodes as we go
+            node = node.children.entry(c).or_default();
+        }
+        node.is_end = true;
+    }
+
+    pub fn search(&self, word: &str) -> bool {
+        self.find(word).is_some_and(|node| node.is_end)
+    }
+
+    // Any word starting with `prefix`, including `prefix` itself
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.find(prefix).is_some()
+    }
+
+    // Every word starting with `prefix`, in alphabetical order
+    pub fn words_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        if let Some(node) = self.find(prefix) {
+            let mut current = prefix.to_string();
+            collect(node, &mut current, &mut words);
+        }
+        words
+    }
+
+    // Returns false if the word wasn't there
+    pub fn remove(&mut self, word: &str) -> bool {
+        let chars: Vec<char> = word.chars().collect();
+        remove(&mut self.root, &chars)
+    }
+
+    // The node at the end of `prefix`, if the path exists
+    fn find(&self, prefix: &str) -> Option<&TrieNode> {
+        let mut node = &self.root;
+        for c in prefix.chars() {
+            node = node.children.get(&c)?;
+        }
+        Some(node)
+    }
+}
+
+// Depth first: a node's word comes before the words going through it ("car" before "care")
+// `current` is the path from the root to `node`, we push and pop one character per level
+fn collect(node: &TrieNode, current: &mut String, words: &mut Vec<String>) {
+    if node.is_end {
+        words.push(current.clone());
+    }
+
+    // A HashMap has no order, so we sort the children to get the words alphabetically
+    let mut children: Vec<(&char, &TrieNode)> = node.children.iter().collect();
+    children.sort_by_key(|(c, _)| **c);
+    for (c, child) in children {
+        current.push(*c);
+        collect(child, current, words);
+        current.pop();
+    }
+}
+
+// Unmarking the end of the word is enough for `search`, but the nodes would stay
+// around forever (and `starts_with` would still find them). So on the way back up
+// we also delete every node that doesn't lead to another word anymore.
+// The nodes shared with other words ("car" when removing "care") are kept
+fn remove(node: &mut TrieNode, chars: &[char]) -> bool {
+    let Some((first, rest)) = chars.split_first() else {
+        let was_word = node.is_end;
+        node.is_end = false;
+        return was_word;
+    };
+
+    let Some(child) = node.children.get_mut(first) else {
+        return false;
+    };
+
+    let removed = remove(child, rest);
+    if removed && !child.is_end && child.children.is_empty() {
+        node.children.remove(first);
+    }
+    removed
+}
+
+// This makes `let trie: Trie = words.into_iter().collect()` work
+impl<'a> FromIterator<&'a str> for Trie {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Trie {
+        let mut trie = Trie::new();
+        for word in iter {
+            trie.insert(word);
+        }
+        trie
+    }
+}
+
+// 1000 common English words, one per line
+const WORDS: &str = include_str!("words.txt");
+
+// `count` words that are the beginning of other words ("act" of "action")
+fn prefix_words<'a>(trie: &Trie, words: &[&'a str], count: usize) -> Vec<&'a str> {
+    words
+        .iter()
+        .copied()
+        .filter(|word| trie.words_with_prefix(word).len() > 1)
+        .take(count)
+        .collect()
+}
+
+pub fn run() {
+    let words: Vec<&str> = WORDS.lines().collect();
+    let mut trie: Trie = words.iter().copied().collect();
+    println!("{} words in the trie", words.len());
+
+    for prefix in ["act", "com", "pre", "xyz"] {
+        let matches = trie.words_with_prefix(prefix);
+        println!(
+            "{:>3}: {} words, starts_with: {}, {:?}",
+            prefix,
+            matches.len(),
+            trie.starts_with(prefix),
+            &matches[..matches.len().min(5)]
+        );
+    }
+
+    // Remove 100 words that are the beginning of other words, the longer ones stay
+    let removed = prefix_words(&trie, &words, 100);
+    for word in &removed {
+        trie.remove(word);
+    }
+    println!(
+        "removed {} words, e.g. {:?}, still there: {:?}",
+        removed.len(),
+        removed[1],
+        trie.words_with_prefix(removed[1])
+    );
+    let kept = words.iter().filter(|word| !removed.contains(word));
+    println!("{} words left", kept.count());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> Vec<&'static str> {
+        WORDS.lines().collect()
+    }
+
+    #[test]
+    fn every_inserted_word_is_found() {
+        let words = words();
+        let trie: Trie = words.iter().copied().collect();
+        assert!(words.iter().all(|word| trie.search(word)));
+        assert!(trie.starts_with("com") && !trie.starts_with("xyz"));
+    }
+
+    // A prefix isn't a word unless it was inserted
+    #[test]
+    fn a_prefix_only_is_not_a_word() {
+        let trie: Trie = words().into_iter().collect();
+        assert!(trie.starts_with("democ") && !trie.search("democ"));
+    }
+
+    // The longer words must survive the removal of their beginning
+    #[test]
+    fn removing_a_word_keeps_the_longer_ones() {
+        let words = words();
+        let mut trie: Trie = words.iter().copied().collect();
+        let removed = prefix_words(&trie, &words, 100);
+        assert_eq!(removed.len(), 100);
+
+        for word in &removed {
+            assert!(trie.remove(word));
+            // Removing it again does nothing
+            assert!(!trie.remove(word));
+        }
+        for word in &removed {
+            assert!(!trie.search(word));
+            let siblings = trie.words_with_prefix(word);
+            assert!(!siblings.is_empty());
+            assert!(siblings.iter().all(|sibling| trie.search(sibling)));
+        }
+        // The words we didn't remove are all still there
+        let mut kept = words.iter().filter(|word| !removed.contains(word));
+        assert!(kept.all(|word| trie.search(word)));
+    }
+
+    #[test]
+    fn removing_a_missing_word_does_nothing() {
+        let mut trie: Trie = ["and"].into_iter().collect();
+        assert!(!trie.remove("an"));
+        assert!(!trie.remove("android"));
+        assert!(trie.search("and"));
+    }
+
+    // The empty string is a word like any other, it ends at the root
+    #[test]
+    fn the_empty_string() {
+        let mut trie: Trie = ["", "a"].into_iter().collect();
+        assert!(trie.search(""));
+        assert_eq!(trie.words_with_prefix(""), ["", "a"]);
+        assert!(trie.remove(""));
+        assert!(!trie.search("") && trie.starts_with(""));
+        assert_eq!(trie.words_with_prefix(""), ["a"]);
+        assert!(!Trie::new().search(""));
+    }
+
+    // Removing a word that overlaps with another one only removes the part nobody
+    // else uses
+    #[test]
+    fn overlapping_words() {
+        let mut trie: Trie = ["a", "an", "and"].into_iter().collect();
+        assert!(trie.search("a") && !trie.search("n"));
+        assert!(trie.remove("an"));
+        assert!(!trie.search("an") && trie.search("and") && trie.starts_with("an"));
+        assert!(trie.remove("and"));
+        assert!(!trie.starts_with("an") && trie.search("a"));
+    }
+}
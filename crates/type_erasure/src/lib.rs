@@ -0,0 +1,261 @@
+use std::{hint::black_box, marker::PhantomData, time::Duration, time::Instant};
+
+// "Type erasure" means forgetting the concrete type of a value and only keeping
+// what we need to use it. Here we want a list of shapes we can draw, without the
+// list caring whether each one is a Circle or a Rect.
+//
+// We compare three ways of doing it:
+// 1. `Box<dyn Drawable>`: the compiler builds the erased value for us
+// 2. `ErasedDrawable`: the same idea written by hand with a raw pointer and a function pointer
+// 3. `DrawableKind`: no erasure at all, an enum that lists every possible shape
+
+pub trait Drawable {
+    // Drawing "uses ink", which we count so the work can't be optimized away
+    fn draw(&mut self);
+    fn ink(&self) -> f64;
+}
+
+pub struct Circle {
+    pub radius: f64,
+    pub ink: f64,
+}
+
+pub struct Rect {
+    pub width: f64,
+    pub height: f64,
+    pub ink: f64,
+}
+
+impl Drawable for Circle {
+    fn draw(&mut self) {
+        self.ink += std::f64::consts::PI * self.radius * self.radius;
+    }
+
+    fn ink(&self) -> f64 {
+        self.ink
+    }
+}
+
+impl Drawable for Rect {
+    fn draw(&mut self) {
+        self.ink += self.width * self.height;
+    }
+
+    fn ink(&self) -> f64 {
+        self.ink
+    }
+}
+
+// Approach 1: trait objects
+// A `Box<dyn Drawable>` is a "fat pointer": a pointer to the data plus a pointer to
+// a vtable, a table generated by the compiler with the address of `draw`, `ink`,
+// `drop`, the size and the alignment of the concrete type.
+// Calling `draw` means reading the function address from the vtable and jumping to it.
+
+// Approach 2: the same fat pointer written by hand
+// `ptr` points to the boxed shape, and the function pointers know which type is
+// really behind it. Each `ErasedDrawable::new::<T>` call creates functions specialized
+// for that `T`, which is exactly what the compiler does when it builds a vtable.
+pub struct ErasedDrawable<'a> {
+    ptr: *mut (),
+    draw_fn: fn(*mut ()),
+    ink_fn: fn(*mut ()) -> f64,
+    drop_fn: fn(*mut ()),
+    // We behave as if we owned a `Box<dyn Drawable + 'a>`, so the shape can't outlive
+    // anything it borrows even though the raw pointer doesn't carry a lifetime
+    _marker: PhantomData<Box<dyn Drawable + 'a>>,
+}
+
+impl<'a> ErasedDrawable<'a> {
+    pub fn new<T: Drawable + 'a>(shape: T) -> ErasedDrawable<'a> {
+        // `into_raw` gives up the Box without freeing it: from now on we are the
+        // owner of the allocation and must free it exactly once (see Drop below)
+        let ptr = Box::into_raw(Box::new(shape)) as *mut ();
+
+        // These functions are generic over T and don't capture anything, so they
+        // coerce to plain `fn` pointers. They turn the `*mut ()` back into the real type
+        fn draw<T: Drawable>(ptr: *mut ()) {
+            // SAFETY: `ptr` was created from a `Box<T>` in `new` with this very T and
+            // stays valid until `drop_fn` runs. The only way to call this is through
+            // `&mut self`, so this is the only reference to the shape while it runs
+            unsafe { (*(ptr as *mut T)).draw() }
+        }
+
+        fn ink<T: Drawable>(ptr: *mut ()) -> f64 {
+            // SAFETY: same as in `draw`, and we only create a shared reference
+            // from `&self`, so nobody can be mutating the shape at the same time
+            unsafe { (*(ptr as *const T)).ink() }
+        }
+
+        fn drop<T>(ptr: *mut ()) {
+            // SAFETY: `ptr` came from `Box::into_raw` with the same T, and Drop runs
+            // once, so the allocation is turned back into a Box and freed exactly once
+            unsafe { std::mem::drop(Box::from_raw(ptr as *mut T)) }
+        }
+
+        ErasedDrawable {
+            ptr,
+            draw_fn: draw::<T>,
+            ink_fn: ink::<T>,
+            drop_fn: drop::<T>,
+            _marker: PhantomData,
+        }
+    }
+
+    // Taking `&mut self` is what keeps the aliasing rules: while `draw` runs, nobody
+    // else can call `draw` or `ink` on the same shape
+    pub fn draw(&mut self) {
+        (self.draw_fn)(self.ptr)
+    }
+
+    pub fn ink(&self) -> f64 {
+        (self.ink_fn)(self.ptr)
+    }
+}
+
+impl Drop for ErasedDrawable<'_> {
+    fn drop(&mut self) {
+        (self.drop_fn)(self.ptr)
+    }
+}
+
+// Approach 3: a tagged union
+// No pointers and no function tables: the shapes are stored inline in the Vec and
+// `draw` is a plain match. The data sits contiguously in memory, which the CPU cache
+// likes, and the compiler can inline each arm.
+// The price is that the enum is closed: adding a Triangle means changing this enum
+// and every match on it, while approaches 1 and 2 accept any type implementing Drawable,
+// even from another crate.
+pub enum DrawableKind {
+    Circle(Circle),
+    Rect(Rect),
+}
+
+impl DrawableKind {
+    pub fn draw(&mut self) {
+        match self {
+            DrawableKind::Circle(circle) => circle.draw(),
+            DrawableKind::Rect(rect) => rect.draw(),
+        }
+    }
+
+    pub fn ink(&self) -> f64 {
+        match self {
+            DrawableKind::Circle(circle) => circle.ink(),
+            DrawableKind::Rect(rect) => rect.ink(),
+        }
+    }
+}
+
+fn circle(n: u32) -> Circle {
+    Circle {
+        radius: (n % 10) as f64 + 1.0,
+        ink: 0.0,
+    }
+}
+
+fn rect(n: u32) -> Rect {
+    Rect {
+        width: (n % 7) as f64 + 1.0,
+        height: (n % 5) as f64 + 1.0,
+        ink: 0.0,
+    }
+}
+
+// A tiny xorshift pseudo random generator, so every approach gets the same
+// "random" sequence of shapes without pulling in the `rand` crate
+fn random_numbers(count: usize, mut seed: u32) -> Vec<u32> {
+    (0..count)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        })
+        .collect()
+}
+
+// Draw every shape once and return how long it took and the total ink used
+fn bench<S>(shapes: &mut [S], draw: impl Fn(&mut S), ink: impl Fn(&S) -> f64) -> (Duration, f64) {
+    let start = Instant::now();
+    for shape in shapes.iter_mut() {
+        draw(black_box(shape));
+    }
+    let elapsed = start.elapsed();
+    (elapsed, shapes.iter().map(ink).sum())
+}
+
+pub fn run() {
+    const SHAPES: usize = 10_000;
+
+    // Even numbers become circles, odd ones rectangles
+    let numbers = random_numbers(SHAPES, 0x2545_f491);
+
+    let mut boxed: Vec<Box<dyn Drawable>> = numbers
+        .iter()
+        .map(|&n| -> Box<dyn Drawable> {
+            if n % 2 == 0 {
+                Box::new(circle(n))
+            } else {
+                Box::new(rect(n))
+            }
+        })
+        .collect();
+
+    let mut erased: Vec<ErasedDrawable> = numbers
+        .iter()
+        .map(|&n| {
+            if n % 2 == 0 {
+                ErasedDrawable::new(circle(n))
+            } else {
+                ErasedDrawable::new(rect(n))
+            }
+        })
+        .collect();
+
+    let mut tagged: Vec<DrawableKind> = numbers
+        .iter()
+        .map(|&n| {
+            if n % 2 == 0 {
+                DrawableKind::Circle(circle(n))
+            } else {
+                DrawableKind::Rect(rect(n))
+            }
+        })
+        .collect();
+
+    let results = [
+        (
+            "Box<dyn Drawable>",
+            bench(&mut boxed, |s| s.draw(), |s| s.ink()),
+        ),
+        (
+            "ErasedDrawable",
+            bench(&mut erased, |s| s.draw(), |s| s.ink()),
+        ),
+        (
+            "DrawableKind",
+            bench(&mut tagged, |s| s.draw(), |s| s.ink()),
+        ),
+    ];
+
+    // Every approach drew the same shapes, so they must have used the same amount of ink
+    let expected_ink = results[0].1 .1;
+    for (_, (_, ink)) in &results {
+        assert_eq!(*ink, expected_ink);
+    }
+
+    println!("{} draws of random shapes:", SHAPES);
+    println!(
+        "  {:<18} | {:>12} | {:>10}",
+        "approach", "total", "per draw"
+    );
+    for (name, (elapsed, _)) in &results {
+        println!(
+            "  {:<18} | {:>12?} | {:>10?}",
+            name,
+            elapsed,
+            *elapsed / SHAPES as u32
+        );
+    }
+}
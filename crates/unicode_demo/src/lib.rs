@@ -0,0 +1,161 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+// "How long is this string?" has three answers in Rust:
+//
+// - bytes: what `len()` returns. A `str` is UTF-8, where a character takes 1 to 4
+//   bytes ('a' is 1, 'é' is 2, '中' is 3, '🦀' is 4).
+// - chars: Unicode scalar values (code points). What `chars()` iterates over.
+// - graphemes: what a person would call a character. One grapheme can be several
+//   chars: a letter followed by combining accents, a flag (two "regional indicator"
+//   chars), or a family emoji (several people glued together with zero width joiners).
+//
+// Slicing a `str` works on byte offsets and panics if the offset falls inside a char,
+// but even a valid char boundary can be in the middle of a grapheme: cutting "é"
+// written as e + U+0301 after the e leaves a bare "e" and an orphan accent.
+
+pub fn byte_count(s: &str) -> usize {
+    s.len()
+}
+
+pub fn char_count(s: &str) -> usize {
+    s.chars().count()
+}
+
+// `true` asks for *extended* grapheme clusters, the ones Unicode recommends and that
+// keep emoji sequences together. The legacy ones would split 👨‍👩‍👧‍👦 apart.
+pub fn grapheme_count(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+// The first `n` graphemes of `s`, or all of `s` if it has fewer
+// We cut at the byte offset where grapheme number `n` starts, which is always both a
+// char boundary and a grapheme boundary, so the slice is valid and nothing is split.
+// It borrows from `s`, no allocation.
+pub fn truncate_to_graphemes(s: &str, n: usize) -> &str {
+    match s.grapheme_indices(true).nth(n) {
+        Some((end, _)) => &s[..end],
+        None => s,
+    }
+}
+
+// (bytes, chars, graphemes)
+fn counts(s: &str) -> (usize, usize, usize) {
+    (byte_count(s), char_count(s), grapheme_count(s))
+}
+
+// The columns won't line up perfectly: `{:<14}` pads by chars, but the terminal
+// shows some graphemes two columns wide (emoji, CJK) and combining marks take none
+fn print_counts(label: &str, s: &str) {
+    let (bytes, chars, graphemes) = counts(s);
+    println!(
+        "  {:<24} {:<14} | {:>5} | {:>5} | {:>9}",
+        label, s, bytes, chars, graphemes
+    );
+}
+
+pub fn run() {
+    // The same "é", written two ways:
+    // precomposed, a single char U+00E9 (LATIN SMALL LETTER E WITH ACUTE)
+    let precomposed = "\u{e9}";
+    // decomposed, 'e' followed by U+0301 (COMBINING ACUTE ACCENT)
+    let combining = "e\u{301}";
+    // A family: man, woman, girl and boy, joined by three U+200D (ZERO WIDTH JOINER)
+    let family = "👨‍👩‍👧‍👦";
+    // A flag is two regional indicators, U+1F1EF U+1F1F5 are J and P
+    let flag = "🇯🇵";
+    // A waving hand with a skin tone modifier
+    let wave = "👋🏽";
+    let cjk = "你好世界";
+    // Zalgo text, every letter buried under combining marks
+    let zalgo = "Z\u{351}\u{36b}\u{343}a\u{300}\u{301}l\u{35b}g\u{310}o\u{30f}";
+
+    println!(
+        "  {:<24} {:<14} | {:>5} | {:>5} | {:>9}",
+        "", "text", "bytes", "chars", "graphemes"
+    );
+    print_counts("é precomposed", precomposed);
+    print_counts("é combining", combining);
+    print_counts("family", family);
+    print_counts("flag", flag);
+    print_counts("skin tone", wave);
+    print_counts("CJK", cjk);
+    print_counts("zalgo", zalgo);
+    print_counts("ascii", "hello");
+
+    // Truncating
+    let text = "Cafe\u{301} 👨‍👩‍👧‍👦 🇯🇵 你好";
+    println!("truncating {:?}:", text);
+    for n in [4, 6, 8, 100] {
+        println!(
+            "  first {:>3} graphemes: {:?}",
+            n,
+            truncate_to_graphemes(text, n)
+        );
+    }
+    // Cutting by chars would leave the accent behind, and split the family
+    let by_chars: String = text.chars().take(4).collect();
+    println!("  first   4 chars:     {:?}", by_chars);
+
+    // The graphemes themselves
+    println!("graphemes of {:?}:", text);
+    println!("  {:?}", text.graphemes(true).collect::<Vec<_>>());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAMILY: &str = "👨‍👩‍👧‍👦";
+
+    #[test]
+    fn family_emoji() {
+        // 4 people of 4 bytes each + 3 joiners of 3 bytes each, all one grapheme
+        assert_eq!(byte_count(FAMILY), 25);
+        assert_eq!(char_count(FAMILY), 7);
+        assert_eq!(grapheme_count(FAMILY), 1);
+        assert_eq!(FAMILY.chars().filter(|&c| c == '\u{200d}').count(), 3);
+        // Two families are two graphemes, the joiners don't reach across
+        assert_eq!(grapheme_count(&FAMILY.repeat(2)), 2);
+    }
+
+    #[test]
+    fn precomposed_and_combining() {
+        let (precomposed, combining) = ("\u{e9}", "e\u{301}");
+        // They look the same, but they are different strings
+        // (comparing them as equal needs Unicode normalization, NFC or NFD)
+        assert_ne!(precomposed, combining);
+        assert_eq!(counts(precomposed), (2, 1, 1));
+        assert_eq!(counts(combining), (3, 2, 1));
+    }
+
+    #[test]
+    fn other_sequences() {
+        // Two regional indicators, J and P
+        assert_eq!(counts("🇯🇵"), (8, 2, 1));
+        // Three flags in a row pair up, not overlap
+        assert_eq!(grapheme_count("🇯🇵🇫🇷🇩🇪"), 3);
+        assert_eq!(counts("👋🏽"), (8, 2, 1));
+        assert_eq!(counts("你好世界"), (12, 4, 4));
+        let zalgo = "Z\u{351}\u{36b}\u{343}a\u{300}\u{301}l\u{35b}g\u{310}o\u{30f}";
+        assert_eq!(grapheme_count(zalgo), 5);
+        assert_eq!(counts("hello"), (5, 5, 5));
+        assert_eq!(counts(""), (0, 0, 0));
+        // A lone combining mark is a grapheme of its own
+        assert_eq!(counts("\u{301}"), (2, 1, 1));
+    }
+
+    #[test]
+    fn truncating_never_splits_a_grapheme() {
+        let text = "Cafe\u{301} 👨‍👩‍👧‍👦 🇯🇵 你好";
+        // Cutting by chars would leave the accent behind
+        let by_chars: String = text.chars().take(4).collect();
+        assert_eq!(by_chars, "Cafe");
+        assert_eq!(truncate_to_graphemes(text, 4), "Cafe\u{301}");
+        assert_eq!(truncate_to_graphemes(text, 6), "Cafe\u{301} 👨‍👩‍👧‍👦");
+        assert_eq!(truncate_to_graphemes(text, 100), text);
+        assert_eq!(truncate_to_graphemes(FAMILY, 1), FAMILY);
+        assert_eq!(truncate_to_graphemes(FAMILY, 0), "");
+        assert_eq!(truncate_to_graphemes("你好世界", 2), "你好");
+        assert_eq!(truncate_to_graphemes("", 3), "");
+    }
+}
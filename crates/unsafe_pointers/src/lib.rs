@@ -0,0 +1,247 @@
+use std::{hint::black_box, ptr, time::Instant};
+
+// Raw pointers (`*const T` and `*mut T`) are like references without the guarantees:
+// - they can be null or dangling
+// - they can point to memory that isn't initialized
+// - many `*mut T` can point to the same place at the same time
+// - the compiler doesn't track how long the memory they point to lives
+// Creating a raw pointer is safe, but reading or writing through one is `unsafe`,
+// because we (and not the borrow checker) have to prove that the access is valid.
+// Every unsafe function below lists those proofs in a `# Safety` section.
+
+/// Sums `len` floats starting at `ptr` by walking the pointer manually.
+///
+/// # Safety
+///
+/// - `ptr` must be non-null and aligned for `f32`
+/// - `ptr` must point to `len` consecutive, initialized `f32` values that belong
+///   to the same allocation (e.g. a slice), so `ptr.add(i)` never leaves it
+/// - nobody may write to those values while we are reading them
+pub unsafe fn sum_raw(ptr: *const f32, len: usize) -> f32 {
+    let mut sum = 0.0;
+    for i in 0..len {
+        // `add(i)` moves the pointer forward by `i * size_of::<f32>()` bytes
+        // It is only valid while we stay inside the allocation, which the caller promised
+        sum += *ptr.add(i);
+    }
+    sum
+}
+
+// The safe version: the slice carries its own length, so the compiler can
+// check every access for us (and often removes the checks entirely)
+pub fn sum_iter(values: &[f32]) -> f32 {
+    values.iter().sum()
+}
+
+/// Swaps the values behind `a` and `b`.
+///
+/// # Safety
+///
+/// - `a` and `b` must be non-null, aligned and point to initialized values of `T`
+/// - both must be valid for reads and writes
+/// - `a` and `b` may be equal (the swap is then a no-op), but they must not
+///   partially overlap, each pointer points to exactly one whole `T`
+pub unsafe fn swap_raw<T>(a: *mut T, b: *mut T) {
+    // `ptr::read` makes a bitwise copy of the value without moving out of the place,
+    // so for a moment there are two copies of `a`: the one in `tmp` and the original
+    // We must not drop either of them, otherwise a value owning memory (like a String)
+    // would be freed twice
+    let tmp = ptr::read(a);
+
+    // `ptr::write` overwrites the destination *without* dropping the old value
+    // That's exactly what we want: the old value of `a` now lives in `tmp`
+    // and the value of `b` is duplicated into `a` until we overwrite `b` below
+    ptr::write(a, ptr::read(b));
+    ptr::write(b, tmp);
+
+    // At this point every value has exactly one owner again: nothing leaked and nothing is duplicated
+}
+
+/// Copies `count` bytes from `src` to `dst`, one byte at a time.
+///
+/// # Safety
+///
+/// - `src` must be valid for reads of `count` bytes
+/// - `dst` must be valid for writes of `count` bytes
+/// - the two ranges must not overlap (like `ptr::copy_nonoverlapping`), otherwise we
+///   would read bytes that we already overwrote
+pub unsafe fn memcpy_raw(dst: *mut u8, src: *const u8, count: usize) {
+    for i in 0..count {
+        // u8 has an alignment of 1, so any pointer inside the ranges is aligned
+        *dst.add(i) = *src.add(i);
+    }
+}
+
+/// Splits a mutable slice in two at `mid`, like `<[T]>::split_at_mut`.
+///
+/// # Safety
+///
+/// - `mid` must be less than or equal to `slice.len()`
+///
+/// This is the only precondition we can't check without a branch, everything else
+/// is proven below.
+pub unsafe fn split_at_mut_raw<T>(slice: &mut [T], mid: usize) -> (&mut [T], &mut [T]) {
+    let len = slice.len();
+    let ptr = slice.as_mut_ptr();
+
+    // Why this can't be written with safe code:
+    // `(&mut slice[..mid], &mut slice[mid..])` borrows `slice` mutably twice and the
+    // borrow checker doesn't know that the two ranges are disjoint
+    //
+    // Why it is sound:
+    // - the left half covers the elements [0, mid) and the right half [mid, len)
+    // - the ranges don't share a single element, so the two `&mut` never alias
+    // - `ptr.add(mid)` stays inside the allocation (or one past the end when mid == len,
+    //   which is allowed) because the caller promised `mid <= len`
+    // - both returned slices borrow from `slice`, so the compiler still makes sure
+    //   they can't outlive the original slice (the lifetimes are tied by the signature)
+    (
+        std::slice::from_raw_parts_mut(ptr, mid),
+        std::slice::from_raw_parts_mut(ptr.add(mid), len - mid),
+    )
+}
+
+pub fn run() {
+    // Summing with raw pointers vs a safe iterator
+    let values: Vec<f32> = (0..1_000_000).map(|i| (i % 100) as f32 * 0.5).collect();
+
+    let start = Instant::now();
+    // SAFETY: the pointer and length come from the same Vec, which is alive and
+    // not mutated while we read it
+    let raw = unsafe { sum_raw(black_box(values.as_ptr()), values.len()) };
+    let raw_elapsed = start.elapsed();
+
+    let start = Instant::now();
+    let safe = sum_iter(black_box(&values));
+    let safe_elapsed = start.elapsed();
+
+    println!("sum_raw:  {} in {:?}", raw, raw_elapsed);
+    println!("sum_iter: {} in {:?}", safe, safe_elapsed);
+
+    // Swapping two elements of a slice through raw pointers
+    let mut names = vec![String::from("first"), String::from("second")];
+    let base = names.as_mut_ptr();
+    // SAFETY: both pointers point to distinct, initialized elements of `names`
+    unsafe { swap_raw(base, base.add(1)) };
+    println!("swapped: {:?}", names);
+
+    // Copying bytes by hand
+    let src = b"hello raw pointers";
+    let mut dst = [0_u8; 18];
+    // SAFETY: both buffers are 18 bytes long and they are different arrays, so they don't overlap
+    unsafe { memcpy_raw(dst.as_mut_ptr(), src.as_ptr(), src.len()) };
+    println!("copied: {}", String::from_utf8_lossy(&dst));
+
+    // Splitting a slice into two mutable halves
+    let mut numbers = [1, 2, 3, 4, 5];
+    // SAFETY: 2 <= numbers.len()
+    let (left, right) = unsafe { split_at_mut_raw(&mut numbers, 2) };
+    left[0] = 10;
+    right[0] = 30;
+    println!("split and modified: {:?}", numbers);
+}
+
+// Small inputs and no timing: these are meant to run under `cargo +nightly miri test`
+// too, which checks every read and write through the pointers below
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+
+    #[test]
+    fn sum_raw_matches_the_iterator() {
+        let values = [1.5, -2.0, 4.25, 0.0, 8.0];
+        // SAFETY: the pointer and length come from the same array
+        let raw = unsafe { sum_raw(values.as_ptr(), values.len()) };
+        assert_eq!(raw, sum_iter(&values));
+        assert_eq!(raw, 11.75);
+    }
+
+    #[test]
+    fn sum_raw_of_nothing() {
+        // Never read, a dangling (but aligned and non-null) pointer is fine
+        // SAFETY: 0 values to read
+        let sum = unsafe { sum_raw(NonNull::dangling().as_ptr(), 0) };
+        assert_eq!(sum, 0.0);
+    }
+
+    #[test]
+    fn swap_raw_moves_owned_values() {
+        let mut names = vec![String::from("first"), String::from("second")];
+        let base = names.as_mut_ptr();
+        // SAFETY: two distinct, initialized elements of `names`
+        unsafe { swap_raw(base, base.add(1)) };
+        assert_eq!(names, ["second", "first"]);
+    }
+
+    #[test]
+    fn swap_raw_neither_drops_nor_duplicates() {
+        let (a, b) = (Rc::new(1), Rc::new(2));
+        let mut pair = [a.clone(), b.clone()];
+        let base = pair.as_mut_ptr();
+        // SAFETY: two distinct, initialized elements of `pair`
+        unsafe { swap_raw(base, base.add(1)) };
+        assert_eq!((*pair[0], *pair[1]), (2, 1));
+        assert_eq!((Rc::strong_count(&a), Rc::strong_count(&b)), (2, 2));
+        drop(pair);
+        assert_eq!((Rc::strong_count(&a), Rc::strong_count(&b)), (1, 1));
+    }
+
+    #[test]
+    fn swap_raw_with_itself() {
+        let mut name = String::from("alone");
+        let ptr: *mut String = &mut name;
+        // SAFETY: the same valid pointer twice is allowed
+        unsafe { swap_raw(ptr, ptr) };
+        assert_eq!(name, "alone");
+    }
+
+    #[test]
+    fn memcpy_raw_copies_every_byte() {
+        let src = b"hello raw pointers";
+        let mut dst = [0_u8; 18];
+        // SAFETY: two different arrays of 18 bytes
+        unsafe { memcpy_raw(dst.as_mut_ptr(), src.as_ptr(), src.len()) };
+        assert_eq!(&dst, src);
+    }
+
+    #[test]
+    fn memcpy_raw_copies_only_count_bytes() {
+        let src = [1_u8, 2, 3, 4];
+        let mut dst = [0_u8; 4];
+        // SAFETY: two different arrays of 4 bytes, 2 of them copied
+        unsafe { memcpy_raw(dst.as_mut_ptr(), src.as_ptr(), 2) };
+        assert_eq!(dst, [1, 2, 0, 0]);
+        // SAFETY: nothing copied
+        unsafe { memcpy_raw(dst.as_mut_ptr(), src.as_ptr(), 0) };
+        assert_eq!(dst, [1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn split_at_mut_raw_halves_are_disjoint() {
+        let mut numbers = [1, 2, 3, 4, 5];
+        // SAFETY: 2 <= numbers.len()
+        let (left, right) = unsafe { split_at_mut_raw(&mut numbers, 2) };
+        assert_eq!((&*left, &*right), (&[1, 2][..], &[3, 4, 5][..]));
+        // Both halves written while both are alive
+        left[0] = 10;
+        right[0] = 30;
+        left[1] = 20;
+        assert_eq!(numbers, [10, 20, 30, 4, 5]);
+    }
+
+    #[test]
+    fn split_at_mut_raw_at_the_ends() {
+        let mut numbers = [1, 2, 3];
+        // SAFETY: 0 <= numbers.len()
+        let (left, right) = unsafe { split_at_mut_raw(&mut numbers, 0) };
+        assert_eq!((left.len(), right.len()), (0, 3));
+        // SAFETY: mid == len, the right half starts one past the end
+        let (left, right) = unsafe { split_at_mut_raw(&mut numbers, 3) };
+        assert_eq!((left.len(), right.len()), (3, 0));
+        // SAFETY: 0 <= 0
+        let (left, right) = unsafe { split_at_mut_raw::<u8>(&mut [], 0) };
+        assert!(left.is_empty() && right.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+// A tiny expression language: numbers, variables, additions and multiplications
+// The Box is needed because an Expr contains other Exprs, without it the
+// enum would have an infinite size
+#[derive(Debug)]
+pub enum Expr {
+    Num(f64),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Var(String),
+}
+
+// The visitor pattern separates the tree (Expr) from the operations we run on it.
+// Each operation is a struct implementing Visitor, with one method per kind of node.
+// Adding a new operation means writing a new visitor, without touching Expr at all.
+//
+// The associated type lets every visitor decide what it produces:
+// a number for the evaluator, a String for the printer...
+pub trait Visitor {
+    type Output;
+
+    fn visit_num(&mut self, n: f64) -> Self::Output;
+    fn visit_add(&mut self, l: &Expr, r: &Expr) -> Self::Output;
+    fn visit_mul(&mut self, l: &Expr, r: &Expr) -> Self::Output;
+    fn visit_var(&mut self, name: &str) -> Self::Output;
+}
+
+impl Expr {
+    // "Double dispatch": the expression knows which kind of node it is
+    // and calls the matching method on the visitor
+    pub fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        match self {
+            Expr::Num(n) => visitor.visit_num(*n),
+            Expr::Add(l, r) => visitor.visit_add(l, r),
+            Expr::Mul(l, r) => visitor.visit_mul(l, r),
+            Expr::Var(name) => visitor.visit_var(name),
+        }
+    }
+}
+
+// Small helpers so building trees doesn't need Box::new everywhere
+pub fn num(n: f64) -> Expr {
+    Expr::Num(n)
+}
+
+pub fn var(name: &str) -> Expr {
+    Expr::Var(name.to_string())
+}
+
+pub fn add(l: Expr, r: Expr) -> Expr {
+    Expr::Add(Box::new(l), Box::new(r))
+}
+
+pub fn mul(l: Expr, r: Expr) -> Expr {
+    Expr::Mul(Box::new(l), Box::new(r))
+}
+
+// Computes the value of the expression
+// Variables are looked up in `vars`, an unknown variable evaluates to NaN
+pub struct EvalVisitor {
+    pub vars: HashMap<String, f64>,
+}
+
+impl Visitor for EvalVisitor {
+    type Output = f64;
+
+    fn visit_num(&mut self, n: f64) -> f64 {
+        n
+    }
+
+    fn visit_add(&mut self, l: &Expr, r: &Expr) -> f64 {
+        l.accept(self) + r.accept(self)
+    }
+
+    fn visit_mul(&mut self, l: &Expr, r: &Expr) -> f64 {
+        l.accept(self) * r.accept(self)
+    }
+
+    fn visit_var(&mut self, name: &str) -> f64 {
+        self.vars.get(name).copied().unwrap_or(f64::NAN)
+    }
+}
+
+// Turns the expression back into text
+// Additions get parentheses so `(x + 2) * 3` doesn't come out as `x + 2 * 3`
+pub struct PrintVisitor;
+
+impl Visitor for PrintVisitor {
+    type Output = String;
+
+    fn visit_num(&mut self, n: f64) -> String {
+        n.to_string()
+    }
+
+    fn visit_add(&mut self, l: &Expr, r: &Expr) -> String {
+        format!("({} + {})", l.accept(self), r.accept(self))
+    }
+
+    fn visit_mul(&mut self, l: &Expr, r: &Expr) -> String {
+        format!("{} * {}", l.accept(self), r.accept(self))
+    }
+
+    fn visit_var(&mut self, name: &str) -> String {
+        name.to_string()
+    }
+}
+
+pub fn run() {
+    // (x + 2) * (x + 3)
+    let expr = mul(add(var("x"), num(2.0)), add(var("x"), num(3.0)));
+
+    let mut printer = PrintVisitor;
+    let text = expr.accept(&mut printer);
+    println!("expression: {}", text);
+    assert_eq!(text, "(x + 2) * (x + 3)");
+
+    let mut evaluator = EvalVisitor {
+        vars: HashMap::from([("x".to_string(), 5.0)]),
+    };
+    let value = expr.accept(&mut evaluator);
+    println!("with x = 5: {}", value);
+    assert_eq!(value, 56.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &Expr, vars: &[(&str, f64)]) -> f64 {
+        let mut evaluator = EvalVisitor {
+            vars: vars
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value))
+                .collect(),
+        };
+        expr.accept(&mut evaluator)
+    }
+
+    fn print(expr: &Expr) -> String {
+        expr.accept(&mut PrintVisitor)
+    }
+
+    #[test]
+    fn eval_visitor() {
+        let expr = mul(add(var("x"), num(2.0)), add(var("x"), num(3.0)));
+        assert_eq!(eval(&expr, &[("x", 5.0)]), 56.0);
+        assert_eq!(eval(&num(1.5), &[]), 1.5);
+        assert_eq!(
+            eval(&add(num(1.0), mul(num(2.0), var("y"))), &[("y", 4.0)]),
+            9.0
+        );
+    }
+
+    #[test]
+    fn eval_visitor_with_an_unknown_variable() {
+        assert!(eval(&var("nope"), &[]).is_nan());
+        assert!(eval(&add(var("nope"), num(1.0)), &[("x", 1.0)]).is_nan());
+    }
+
+    #[test]
+    fn print_visitor() {
+        let expr = mul(add(var("x"), num(2.0)), add(var("x"), num(3.0)));
+        assert_eq!(print(&expr), "(x + 2) * (x + 3)");
+        assert_eq!(print(&num(2.5)), "2.5");
+        // No parentheses needed around a multiplication
+        assert_eq!(
+            print(&add(mul(var("a"), var("b")), num(1.0))),
+            "(a * b + 1)"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    thread,
+    time::Duration,
+};
+
+use futures::task::{waker_ref, ArcWake};
+
+// An executor runs futures: it polls them until they are done. This one is about as
+// small as one can be, one thread, no timers, no IO.
+//
+// The poll/wake cycle:
+//
+// 1. `spawn` wraps the future in a Task and puts it on the ready queue.
+// 2. `run` takes a task off the queue and polls its future, with a Context holding a
+//    Waker made from the task itself.
+// 3. The future does what it can. If it's done, it returns Ready and the task is
+//    finished. If it has to wait for something (a timer, a socket, another thread),
+//    it keeps a clone of the Waker where that something will find it, and returns
+//    Pending.
+// 4. Nothing happens to a pending task until its Waker is called. The executor
+//    doesn't poll it again "just in case": that would be busy waiting.
+// 5. When what it waited for happens, whoever has the Waker calls `wake`, which for
+//    our tasks means: send the task back to the ready queue. That can happen on any
+//    thread, that's why the queue is fed through a channel.
+// 6. `run` gets to it again, back to 2. The future picks up where it left off.
+//
+// A future that returns Pending without arranging for a wake is never polled again,
+// it hangs forever. That's the one rule of `poll`.
+
+// How many wakes can be waiting in the channel
+// `wake` must never block (it may be called from the executor's own thread, which
+// would then wait for itself), so the channel has room for far more than we need
+const MAX_QUEUED_TASKS: usize = 10_000;
+
+pub struct Task {
+    // The future is only ever polled by the executor's thread, but the Task is shared
+    // with every Waker made from it, which can be on other threads: the Mutex makes
+    // the Task Sync
+    future: Mutex<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    waker_tx: SyncSender<Arc<Task>>,
+    // Set once the future returned Ready, a late wake mustn't poll it again
+    finished: AtomicBool,
+}
+
+// What a Waker made from a Task does when it's called: queue the task again
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        let task = arc_self.clone();
+        // Only fails when the executor is gone, then there is nobody to run it anyway
+        let _ = arc_self.waker_tx.send(task);
+    }
+}
+
+pub struct Executor {
+    ready_queue: VecDeque<Arc<Task>>,
+    waker_tx: SyncSender<Arc<Task>>,
+    // The other end of the channel the wakes arrive on
+    waker_rx: Receiver<Arc<Task>>,
+    // Spawned and not finished yet, `run` returns when that's down to 0
+    // (the executor has a sender itself, so the channel never tells us it's empty for good)
+    unfinished: usize,
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        let (waker_tx, waker_rx) = sync_channel(MAX_QUEUED_TASKS);
+        Executor {
+            ready_queue: VecDeque::new(),
+            waker_tx,
+            waker_rx,
+            unfinished: 0,
+        }
+    }
+
+    // The future doesn't run yet, only once `run` is called
+    // Going through the channel like a wake lets this take `&self`
+    pub fn spawn<F: Future<Output = ()> + Send + 'static>(&self, f: F) {
+        let task = Arc::new(Task {
+            future: Mutex::new(Box::pin(f)),
+            waker_tx: self.waker_tx.clone(),
+            finished: AtomicBool::new(false),
+        });
+        self.waker_tx
+            .send(task)
+            .expect("The executor holds the receiver");
+    }
+
+    // Run until every spawned future is done
+    pub fn run(&mut self) {
+        // The tasks spawned since the last run are in the channel
+        self.receive_wakes();
+        self.unfinished += self.ready_queue.len();
+
+        while self.unfinished > 0 {
+            let task = match self.ready_queue.pop_front() {
+                Some(task) => task,
+                // Nothing to do until a waker is called: wait for it on the channel
+                // instead of spinning. Some other thread has the Waker of the task we
+                // wait for, or it would never be woken.
+                None => self.waker_rx.recv().expect("The executor holds a sender"),
+            };
+            // A wake can still be on its way when the task finishes: its future is
+            // done and mustn't be polled again. (A task woken twice is polled twice,
+            // that's only a wasted poll.)
+            if task.finished.load(Ordering::Acquire) {
+                continue;
+            }
+
+            let waker = waker_ref(&task);
+            let mut cx = Context::from_waker(&waker);
+            let mut future = task.future.lock().unwrap();
+            if future.as_mut().poll(&mut cx).is_ready() {
+                task.finished.store(true, Ordering::Release);
+                self.unfinished -= 1;
+            }
+            drop(future);
+
+            // Whatever got woken while we polled goes to the back of the queue
+            self.receive_wakes();
+        }
+    }
+
+    fn receive_wakes(&mut self) {
+        while let Ok(task) = self.waker_rx.try_recv() {
+            self.ready_queue.push_back(task);
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Executor {
+        Executor::new()
+    }
+}
+
+// Returns Pending `times` times before it's Ready, waking itself up every time:
+// lets the other tasks run in between, like tokio::task::yield_now
+pub struct YieldNow {
+    times: u32,
+}
+
+pub fn yield_now(times: u32) -> YieldNow {
+    YieldNow { times }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.times == 0 {
+            return Poll::Ready(());
+        }
+        self.times -= 1;
+        // We are ready to go on at once, we just give the others their turn: wake
+        // ourselves right away, the task goes to the back of the queue
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+// Ready once another thread says so, after `delay`
+// The thread calls the Waker: a wake from outside the executor
+pub struct Signal {
+    fired: Arc<AtomicBool>,
+    delay: Duration,
+    started: bool,
+}
+
+pub fn signal_after(delay: Duration) -> Signal {
+    Signal {
+        fired: Arc::new(AtomicBool::new(false)),
+        delay,
+        started: false,
+    }
+}
+
+impl Future for Signal {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.fired.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        if !self.started {
+            self.started = true;
+            let (fired, delay, waker) = (self.fired.clone(), self.delay, cx.waker().clone());
+            thread::spawn(move || {
+                thread::sleep(delay);
+                // Set the flag before waking, the next poll has to see it
+                fired.store(true, Ordering::Release);
+                waker.wake();
+            });
+        }
+        Poll::Pending
+    }
+}
+
+pub fn run() {
+    let mut executor = Executor::new();
+
+    // Three tasks that yield 0, 2 and 5 times
+    for (name, yields) in [("slow", 5), ("none", 0), ("some", 2)] {
+        executor.spawn(async move {
+            yield_now(yields).await;
+            println!("{} finished after yielding {} times", name, yields);
+        });
+    }
+    executor.run();
+
+    // A task that waits for another thread: the queue is empty meanwhile, and the
+    // executor sleeps on the channel until the wake arrives
+    executor.spawn(async move {
+        signal_after(Duration::from_millis(50)).await;
+        yield_now(1).await;
+        println!("woken from another thread");
+    });
+    executor.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Spawns a task per number of yields, returns the indices of the tasks in the
+    // order they finished
+    fn finish_order(executor: &mut Executor, yields: &[u32]) -> Vec<usize> {
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        for (i, &times) in yields.iter().enumerate() {
+            let finished = finished.clone();
+            executor.spawn(async move {
+                yield_now(times).await;
+                finished.lock().unwrap().push(i);
+            });
+        }
+        executor.run();
+        let order = finished.lock().unwrap().clone();
+        order
+    }
+
+    #[test]
+    fn every_task_completes() {
+        let mut executor = Executor::new();
+        let order = finish_order(&mut executor, &[5, 0, 2]);
+        assert_eq!(order.len(), 3);
+    }
+
+    // The queue takes turns, so the task that yields least finishes first
+    #[test]
+    fn the_tasks_take_turns() {
+        let mut executor = Executor::new();
+        assert_eq!(finish_order(&mut executor, &[5, 0, 2]), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn many_tasks_yielding_many_times() {
+        let mut executor = Executor::new();
+        let yields: Vec<u32> = (0..100).map(|i| i % 7 * 10).collect();
+        let mut order = finish_order(&mut executor, &yields);
+        order.sort();
+        assert_eq!(order, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn running_without_tasks_returns() {
+        Executor::new().run();
+    }
+
+    #[test]
+    fn the_executor_can_run_again() {
+        let mut executor = Executor::new();
+        assert_eq!(finish_order(&mut executor, &[1]), vec![0]);
+        assert_eq!(finish_order(&mut executor, &[3, 1]), vec![1, 0]);
+    }
+
+    // The queue is empty while the task waits, the executor sleeps until the wake
+    // from the other thread arrives
+    #[test]
+    fn a_wake_from_another_thread() {
+        let mut executor = Executor::new();
+        let finished = Arc::new(AtomicBool::new(false));
+        let done = finished.clone();
+        executor.spawn(async move {
+            signal_after(Duration::from_millis(20)).await;
+            yield_now(1).await;
+            done.store(true, Ordering::Release);
+        });
+        executor.run();
+        assert!(finished.load(Ordering::Acquire));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    cell::Cell,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+// "Zero-cost abstractions" means that using a higher level construct (an iterator,
+// a closure...) costs nothing compared to the code we would have written by hand.
+// Here we check the claim: each pair computes the same thing twice, once with a
+// plain loop and once with iterators, and we time both.
+//
+// Numbers only mean something in a release build (`--release`).
+// In a debug build nothing is inlined and the iterator versions look a lot slower,
+// that's the cost of the abstraction before the optimizer removes it.
+//
+// About vectorization: in release (-O, which is like clang's -O2/-O3) LLVM typically
+// auto-vectorizes the iterator versions on x86-64 just like the loops, processing
+// 2 or 4 values per instruction with SSE2/AVX. The iterator adapters are inlined
+// away first, so LLVM sees the same loop either way. Iterators can even help: there
+// is no `values[i]` bounds check left in the loop body to get in the way.
+// One exception: LLVM won't vectorize a plain f64 sum (neither version), since that
+// would add the values in a different order and floats don't round the same way then.
+
+// Pair 1: add up every value
+// Clippy would rather we use an iterator here, which is the whole point of the comparison
+#[allow(clippy::needless_range_loop)]
+pub fn sum_loop(values: &[f64]) -> f64 {
+    let mut sum = 0.0;
+    for i in 0..values.len() {
+        sum += values[i];
+    }
+    sum
+}
+
+pub fn sum_iter(values: &[f64]) -> f64 {
+    values.iter().sum()
+}
+
+// Pair 2: is `target` in there?
+pub fn contains_loop(values: &[f64], target: f64) -> bool {
+    for &value in values {
+        if value == target {
+            return true;
+        }
+    }
+    false
+}
+
+// `values.contains(&target)` does the same, we want to see `any` itself
+#[allow(clippy::manual_contains)]
+pub fn contains_any(values: &[f64], target: f64) -> bool {
+    values.iter().any(|&value| value == target)
+}
+
+// Pair 3: keep the values above the threshold and double them
+pub fn filter_map_loop(values: &[f64], threshold: f64) -> Vec<f64> {
+    let mut result = Vec::new();
+    for &value in values {
+        if value > threshold {
+            result.push(value * 2.0);
+        }
+    }
+    result
+}
+
+pub fn filter_map_iter(values: &[f64], threshold: f64) -> Vec<f64> {
+    values
+        .iter()
+        .filter_map(|&value| (value > threshold).then_some(value * 2.0))
+        .collect()
+}
+
+// Run `f` once and return its result and how long it took
+// `black_box` hides the result from the optimizer, otherwise it could notice
+// we never use it and not compute it at all
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = black_box(f());
+    (result, start.elapsed())
+}
+
+fn print_pair(name: &str, manual: Duration, abstraction: Duration) {
+    println!(
+        "  {:<12} | {:>12?} | {:>12?} | {:>5.2}",
+        name,
+        manual,
+        abstraction,
+        abstraction.as_secs_f64() / manual.as_secs_f64()
+    );
+}
+
+// Values between 0 and 1, from a xorshift so every run uses the same ones
+fn random_values(count: usize, mut seed: u64) -> Vec<f64> {
+    (0..count)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed >> 11) as f64 / (1_u64 << 53) as f64
+        })
+        .collect()
+}
+
+// The function we call directly, and through a Box<dyn Fn()>
+fn tick(counter: &Cell<u64>) {
+    counter.set(counter.get() + 1);
+}
+
+pub fn run() {
+    const ELEMENTS: usize = 10_000_000;
+    const CALLS: u64 = 10_000_000;
+
+    let values = random_values(ELEMENTS, 0x9e37_79b9_7f4a_7c15);
+    // Not in the values (they are all below 1), so both versions have to look at all of them
+    let missing = 2.0;
+
+    println!("{} elements (ratio = abstraction / manual):", ELEMENTS);
+    println!(
+        "  {:<12} | {:>12} | {:>12} | {:>5}",
+        "pair", "manual", "abstraction", "ratio"
+    );
+
+    // `black_box` on the input too, so the optimizer can't compute anything ahead of time
+    let (manual, manual_time) = time(|| sum_loop(black_box(&values)));
+    let (iter, iter_time) = time(|| sum_iter(black_box(&values)));
+    // Same additions in the same order, so even the floats are exactly equal
+    assert_eq!(manual, iter);
+    print_pair("sum", manual_time, iter_time);
+
+    let (manual, manual_time) = time(|| contains_loop(black_box(&values), missing));
+    let (iter, iter_time) = time(|| contains_any(black_box(&values), missing));
+    assert_eq!(manual, iter);
+    assert!(contains_any(&values, values[ELEMENTS / 2]));
+    print_pair("contains", manual_time, iter_time);
+
+    let (manual, manual_time) = time(|| filter_map_loop(black_box(&values), 0.5));
+    let (iter, iter_time) = time(|| filter_map_iter(black_box(&values), 0.5));
+    assert_eq!(manual, iter);
+    print_pair("filter_map", manual_time, iter_time);
+
+    // A direct call can be inlined, the compiler sees exactly which function runs
+    // A call through Box<dyn Fn()> goes through the vtable: the address of the function
+    // is only known at runtime, so it can't be inlined (unless the optimizer manages to
+    // figure out which closure is in the box, `black_box` makes sure it can't)
+    let counter = Cell::new(0);
+    let ((), direct_time) = time(|| {
+        for _ in 0..CALLS {
+            tick(black_box(&counter));
+        }
+    });
+
+    let boxed: Box<dyn Fn() + '_> = Box::new(|| tick(&counter));
+    let ((), boxed_time) = time(|| {
+        for _ in 0..CALLS {
+            black_box(&boxed)();
+        }
+    });
+    assert_eq!(counter.get(), 2 * CALLS);
+
+    println!("{} calls:", CALLS);
+    print_pair("Box<dyn Fn>", direct_time, boxed_time);
+}